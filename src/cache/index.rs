@@ -0,0 +1,232 @@
+//! Index of the stage3 tarballs in the cache
+//!
+//! Tarballs keep their mirror name, `stage3-<arch>-<profile>-<timestamp>`
+//! followed by the archive extension, so the cache can be grouped by the
+//! [`SelectedProfile::get_stage3_pattern`] they were downloaded for. A
+//! verified download leaves a `<tarball>.sha256` sidecar next to it, in the
+//! `sha256sum` format.
+
+use crate::downloader::calculate_file_sha256;
+use crate::profile::selected::SelectedProfile;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Extension of the SHA256 sidecar files
+const SIDECAR_EXTENSION: &str = "sha256";
+
+/// Extension of interrupted downloads, see [`crate::downloader::partial_path`]
+const PARTIAL_EXTENSION: &str = "part";
+
+/// Profile and build timestamp parsed from a stage3 filename
+#[derive(Debug, Clone)]
+pub struct Stage3Name {
+    pub profile: SelectedProfile,
+    /// Build timestamp as published, e.g. `20250105T170325Z`
+    pub timestamp: String,
+}
+
+impl Stage3Name {
+    /// Parse `stage3-<arch>-<profile>-<timestamp>.tar.<ext>`
+    ///
+    /// The profile may contain dashes, the architecture and the timestamp
+    /// do not.
+    pub fn parse(filename: &str) -> Option<Self> {
+        let (stem, _extension) = filename.split_once(".tar")?;
+        let rest = stem.strip_prefix("stage3-")?;
+        let (rest, timestamp) = rest.rsplit_once('-')?;
+        let (architecture, profile) = rest.split_once('-')?;
+
+        let is_timestamp = timestamp.starts_with(|c: char| c.is_ascii_digit())
+            && timestamp.chars().all(|c| c.is_ascii_alphanumeric());
+        if !is_timestamp || architecture.is_empty() || profile.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            profile: SelectedProfile::new(architecture.to_string(), profile.to_string()),
+            timestamp: timestamp.to_string(),
+        })
+    }
+}
+
+/// A tarball of the stage3 cache
+#[derive(Debug, Clone)]
+pub struct CachedStage3 {
+    pub path: PathBuf,
+    pub filename: String,
+    /// `None` when the file does not follow the stage3 naming
+    pub name: Option<Stage3Name>,
+    pub size: u64,
+    /// When the file was written to the cache
+    pub downloaded: SystemTime,
+}
+
+/// Outcome of checking a tarball against its sidecar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SidecarStatus {
+    Valid,
+    Mismatch,
+    Missing,
+}
+
+impl CachedStage3 {
+    /// Stage3 pattern of the profile, `None` for files that do not parse
+    pub fn pattern(&self) -> Option<String> {
+        self.name.as_ref().map(|name| name.profile.get_stage3_pattern())
+    }
+
+    /// Hash the tarball and compare it with the SHA256 recorded in its sidecar
+    pub async fn verify_sidecar(&self) -> Result<SidecarStatus, Box<dyn std::error::Error>> {
+        let Some(expected) = read_sidecar(&self.path)? else {
+            return Ok(SidecarStatus::Missing);
+        };
+        let calculated = calculate_file_sha256(&self.path).await?;
+        if calculated.eq_ignore_ascii_case(&expected) {
+            Ok(SidecarStatus::Valid)
+        } else {
+            Ok(SidecarStatus::Mismatch)
+        }
+    }
+}
+
+/// Path of the SHA256 sidecar of a tarball
+pub fn sidecar_path(tarball: &Path) -> PathBuf {
+    let mut path = tarball.as_os_str().to_owned();
+    path.push(".");
+    path.push(SIDECAR_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Whether the path is a SHA256 sidecar rather than a tarball
+pub fn is_sidecar(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == SIDECAR_EXTENSION)
+}
+
+/// Whether the path is an interrupted download, kept to be resumed
+pub fn is_partial(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == PARTIAL_EXTENSION)
+}
+
+/// Record the verified SHA256 of a tarball next to it
+pub fn write_sidecar(tarball: &Path, sha256: &str) -> Result<(), io::Error> {
+    let filename = tarball.file_name().unwrap_or_default().to_string_lossy();
+    fs::write(sidecar_path(tarball), format!("{}  {filename}\n", sha256.to_lowercase()))
+}
+
+/// Read the SHA256 recorded for a tarball, `None` without a sidecar
+pub fn read_sidecar(tarball: &Path) -> Result<Option<String>, io::Error> {
+    match fs::read_to_string(sidecar_path(tarball)) {
+        Ok(content) => Ok(content.split_whitespace().next().map(str::to_string)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Delete a tarball and its sidecar, if any
+pub fn remove_with_sidecar(tarball: &Path) -> Result<(), io::Error> {
+    fs::remove_file(tarball)?;
+    match fs::remove_file(sidecar_path(tarball)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// List the tarballs of the cache directory
+///
+/// Sidecars, interrupted downloads and directories, such as the temporary
+/// directories of uncached downloads, are skipped.
+pub fn scan(cache_dir: &Path) -> Result<Vec<CachedStage3>, io::Error> {
+    let mut tarballs = Vec::new();
+
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let path = entry.path();
+        if !metadata.is_file() || is_sidecar(&path) || is_partial(&path) {
+            continue;
+        }
+
+        let filename = entry.file_name().to_string_lossy().into_owned();
+        tarballs.push(CachedStage3 {
+            name: Stage3Name::parse(&filename),
+            filename,
+            path,
+            size: metadata.len(),
+            downloaded: metadata.modified()?,
+        });
+    }
+
+    tarballs.sort_by(|a, b| a.filename.cmp(&b.filename));
+    Ok(tarballs)
+}
+
+/// List the interrupted downloads of the cache directory
+///
+/// Their name is parsed without the `.part` extension.
+pub fn scan_partials(cache_dir: &Path) -> Result<Vec<CachedStage3>, io::Error> {
+    let mut partials = Vec::new();
+
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let path = entry.path();
+        if !metadata.is_file() || !is_partial(&path) {
+            continue;
+        }
+
+        let filename = entry.file_name().to_string_lossy().into_owned();
+        partials.push(CachedStage3 {
+            name: path.file_stem().and_then(|stem| Stage3Name::parse(&stem.to_string_lossy())),
+            filename,
+            path,
+            size: metadata.len(),
+            downloaded: metadata.modified()?,
+        });
+    }
+
+    partials.sort_by(|a, b| a.filename.cmp(&b.filename));
+    Ok(partials)
+}
+
+/// Select the interrupted downloads of `pattern` that are not of `current`
+///
+/// Only the release being downloaded is resumed, the partial downloads of
+/// older builds would never complete.
+pub fn select_stale_partials<'a>(partials: &'a [CachedStage3], pattern: &str, current: &str) -> Vec<&'a CachedStage3> {
+    partials
+        .iter()
+        .filter(|partial| partial.pattern().as_deref() == Some(pattern))
+        .filter(|partial| partial.path.file_stem().is_some_and(|stem| stem != current))
+        .collect()
+}
+
+/// Group the tarballs by stage3 pattern, most recent build first
+///
+/// Files that do not follow the stage3 naming are left out.
+pub fn group_by_pattern(tarballs: &[CachedStage3]) -> BTreeMap<String, Vec<&CachedStage3>> {
+    let mut groups: BTreeMap<String, Vec<&CachedStage3>> = BTreeMap::new();
+    for tarball in tarballs {
+        if let Some(pattern) = tarball.pattern() {
+            groups.entry(pattern).or_default().push(tarball);
+        }
+    }
+
+    for group in groups.values_mut() {
+        group.sort_by(|a, b| {
+            let timestamp = |tarball: &CachedStage3| tarball.name.as_ref().map(|n| n.timestamp.clone());
+            timestamp(b).cmp(&timestamp(a))
+        });
+    }
+    groups
+}
+
+/// Select the tarballs to delete to keep the `keep` most recent of each pattern
+pub fn select_prune(tarballs: &[CachedStage3], keep: usize) -> Vec<&CachedStage3> {
+    group_by_pattern(tarballs)
+        .into_values()
+        .flat_map(|group| group.into_iter().skip(keep))
+        .collect()
+}
@@ -0,0 +1,197 @@
+//! Stage3 cache size management
+//!
+//! The eviction policy is a pure function over [`CacheEntry`] values, the
+//! filesystem side only scans the cache directory and removes what it selects.
+
+pub mod index;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A file of the stage3 cache
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Most recent of the access and modification times
+    pub last_used: SystemTime,
+    /// Protected entries are never evicted
+    pub protected: bool,
+}
+
+/// Parse a human-readable size such as "5GB", "512 MB" or "1073741824"
+///
+/// Units are binary multiples, matching how sizes are displayed.
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return None,
+    };
+
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Select the entries to remove so that the cache fits in the budget
+///
+/// Least recently used entries go first; protected entries are kept even
+/// if the budget cannot be met without them.
+pub fn select_evictions(entries: &[CacheEntry], budget: u64) -> Vec<CacheEntry> {
+    let mut total: u64 = entries.iter().map(|e| e.size).sum();
+    if total <= budget {
+        return Vec::new();
+    }
+
+    let mut candidates: Vec<&CacheEntry> = entries.iter().filter(|e| !e.protected).collect();
+    candidates.sort_by_key(|e| e.last_used);
+
+    let mut evicted = Vec::new();
+    for entry in candidates {
+        if total <= budget {
+            break;
+        }
+        total = total.saturating_sub(entry.size);
+        evicted.push(entry.clone());
+    }
+    evicted
+}
+
+/// List the files of the cache directory, protecting the given paths
+///
+/// SHA256 sidecars are not listed, they go with their tarball.
+pub fn scan_cache(cache_dir: &Path, protected: &[PathBuf]) -> Result<Vec<CacheEntry>, io::Error> {
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let path = entry.path();
+        if !metadata.is_file() || index::is_sidecar(&path) {
+            continue;
+        }
+
+        let modified = metadata.modified()?;
+        let last_used = metadata.accessed().map_or(modified, |accessed| accessed.max(modified));
+
+        entries.push(CacheEntry {
+            protected: protected.contains(&path),
+            path,
+            size: metadata.len(),
+            last_used,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Evict least recently used files until the cache fits in the budget
+///
+/// Returns the removed entries.
+pub fn enforce_budget(
+    cache_dir: &Path,
+    budget: u64,
+    protected: &[PathBuf],
+) -> Result<Vec<CacheEntry>, io::Error> {
+    let entries = scan_cache(cache_dir, protected)?;
+    let evicted = select_evictions(&entries, budget);
+
+    for entry in &evicted {
+        index::remove_with_sidecar(&entry.path)?;
+        log::info!("Evicted {} from the stage3 cache", entry.path.display());
+    }
+
+    Ok(evicted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{File, FileTimes};
+    use std::time::Duration;
+
+    fn entry(name: &str, size: u64, age_days: u64, protected: bool) -> CacheEntry {
+        CacheEntry {
+            path: PathBuf::from(name),
+            size,
+            last_used: SystemTime::UNIX_EPOCH + Duration::from_secs(100 * 86400 - age_days * 86400),
+            protected,
+        }
+    }
+
+    fn names(entries: &[CacheEntry]) -> Vec<String> {
+        entries.iter().map(|e| e.path.display().to_string()).collect()
+    }
+
+    #[test]
+    fn sizes_are_parsed_in_binary_units() {
+        assert_eq!(parse_size("5GB"), Some(5 << 30));
+        assert_eq!(parse_size("512 MB"), Some(512 << 20));
+        assert_eq!(parse_size("1.5k"), Some(1536));
+        assert_eq!(parse_size("1073741824"), Some(1 << 30));
+        assert_eq!(parse_size("2 TiB"), Some(2 << 40));
+        assert_eq!(parse_size("5 parsecs"), None);
+        assert_eq!(parse_size("GB"), None);
+    }
+
+    #[test]
+    fn the_least_recently_used_entries_go_first() {
+        let entries = [
+            entry("recent", 40, 1, false),
+            entry("oldest", 40, 30, false),
+            entry("old", 40, 10, false),
+        ];
+        assert!(select_evictions(&entries, 120).is_empty());
+        assert_eq!(names(&select_evictions(&entries, 80)), ["oldest"]);
+        assert_eq!(names(&select_evictions(&entries, 50)), ["oldest", "old"]);
+    }
+
+    #[test]
+    fn protected_entries_stay_over_the_budget() {
+        let entries = [
+            entry("just-downloaded", 100, 0, true),
+            entry("in-use", 100, 50, true),
+            entry("stale", 10, 20, false),
+        ];
+        assert_eq!(names(&select_evictions(&entries, 0)), ["stale"]);
+    }
+
+    #[test]
+    fn enforce_budget_removes_the_tarballs_with_their_sidecars() {
+        let cache = tempfile::tempdir().unwrap();
+        let file = |name: &str, age_days: u64| {
+            let path = cache.path().join(name);
+            fs::write(&path, vec![0u8; 100]).unwrap();
+            let time = SystemTime::now() - Duration::from_secs(age_days * 86400);
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_times(FileTimes::new().set_accessed(time).set_modified(time))
+                .unwrap();
+            path
+        };
+        let old = file("stage3-old.tar.xz", 30);
+        index::write_sidecar(&old, "hash").unwrap();
+        let protected = file("stage3-protected.tar.xz", 60);
+        let recent = file("stage3-recent.tar.xz", 1);
+
+        let evicted = enforce_budget(cache.path(), 250, std::slice::from_ref(&protected)).unwrap();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].path, old);
+        assert!(!old.exists());
+        assert!(!index::sidecar_path(&old).exists());
+        assert!(protected.exists() && recent.exists());
+    }
+}
@@ -0,0 +1,327 @@
+//! Extraction of tar archives into a chroot
+//!
+//! Stage3 archives are xz-compressed, except on some older architectures,
+//! and imported archives may also use zstd, gzip or bzip2. The compression
+//! is detected from the magic bytes, falling back to the file extension
+//! when the archive cannot be read, such as a stream.
+
+use crate::config::Config;
+use crate::error::ChrootError;
+use crate::signals;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use tokio::io::AsyncRead;
+
+/// Compression of a tar archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveCompression {
+    Xz,
+    Zstd,
+    Gzip,
+    Bzip2,
+    None,
+}
+
+impl ArchiveCompression {
+    /// Compression identified by the first bytes of the file
+    fn from_magic(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::Xz)
+        } else if header.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            Some(Self::Zstd)
+        } else if header.starts_with(&[0x1F, 0x8B]) {
+            Some(Self::Gzip)
+        } else if header.starts_with(b"BZh") {
+            Some(Self::Bzip2)
+        } else {
+            None
+        }
+    }
+
+    /// Compression implied by the file name
+    pub fn from_extension(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Self::Xz
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Self::Zstd
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Self::Gzip
+        } else if name.ends_with(".tar.bz2") || name.ends_with(".tbz2") {
+            Self::Bzip2
+        } else {
+            Self::None
+        }
+    }
+
+    /// Detect the compression of an archive
+    pub fn detect(path: &Path) -> Self {
+        let mut header = [0u8; 6];
+        let read = File::open(path).and_then(|mut file| file.read(&mut header));
+        match read {
+            Ok(count) => Self::from_magic(&header[..count]).unwrap_or(Self::None),
+            Err(e) => {
+                log::debug!("Unable to read {}, using its extension: {e}", path.display());
+                Self::from_extension(path)
+            }
+        }
+    }
+
+    /// Decompression option of GNU tar
+    fn tar_option(&self) -> Option<&'static str> {
+        match self {
+            Self::Xz => Some("--xz"),
+            Self::Zstd => Some("--zstd"),
+            Self::Gzip => Some("--gzip"),
+            Self::Bzip2 => Some("--bzip2"),
+            Self::None => None,
+        }
+    }
+}
+
+/// How tar restores ownership, extended attributes and leading directories
+///
+/// The defaults restore the archive as is. Without `preserve_owner` the
+/// files belong to the extracting user, which works on filesystems that
+/// reject chown but breaks setuid tools in the chroot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtractionOptions {
+    /// Restore the numeric owners recorded in the archive
+    pub preserve_owner: bool,
+    /// Restore the extended attributes, such as file capabilities
+    pub preserve_xattrs: bool,
+    /// Leading path components removed from the archive members
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub strip_components: u32,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+impl Default for ExtractionOptions {
+    fn default() -> Self {
+        Self {
+            preserve_owner: true,
+            preserve_xattrs: true,
+            strip_components: 0,
+        }
+    }
+}
+
+impl ExtractionOptions {
+    /// Options set in the configuration
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            preserve_owner: config.extract_preserve_owner,
+            preserve_xattrs: config.extract_preserve_xattrs,
+            ..Self::default()
+        }
+    }
+
+    /// GNU tar options implementing these settings
+    fn tar_options(&self) -> Vec<String> {
+        let mut options = Vec::new();
+        if self.preserve_owner {
+            options.extend(["--same-owner".to_string(), "--numeric-owner".to_string()]);
+        } else {
+            options.push("--no-same-owner".to_string());
+        }
+        if self.preserve_xattrs {
+            // No shell parses these, a quoted pattern would match no attribute
+            options.extend(["--xattrs".to_string(), "--xattrs-include=*.*".to_string()]);
+        } else {
+            options.push("--no-xattrs".to_string());
+        }
+        if self.strip_components > 0 {
+            options.push(format!("--strip-components={}", self.strip_components));
+        }
+        options
+    }
+}
+
+impl crate::chroot::core::ChrootUnit {
+    /// Extract a tar archive into the chroot directory, keeping modes
+    ///
+    /// Owners and xattrs are restored as set in `options`. `operation_desc`
+    /// names the extraction in logs and errors. The archive is read by this
+    /// process and piped into tar, see
+    /// [`extract_archive_stream`](Self::extract_archive_stream).
+    pub async fn extract_archive(
+        &self,
+        archive: &Path,
+        options: &ExtractionOptions,
+        operation_desc: &str,
+    ) -> Result<(), ChrootError> {
+        let compression = ArchiveCompression::detect(archive);
+        log::info!("Extracting {} ({compression:?})", archive.display());
+
+        let mut file = tokio::fs::File::open(archive).await?;
+        self.extract_archive_stream(&mut file, compression, options, operation_desc)
+            .await
+    }
+
+    /// Extract a tar archive read from `reader` into the chroot directory
+    ///
+    /// The archive is piped into an elevated tar as it is read. A read
+    /// error, including a failed integrity check of the reader, ends the
+    /// extraction with [`ChrootError::Stage3Stream`] once tar has exited,
+    /// whatever tar reports; the files already extracted are left in place.
+    /// A termination signal closes the pipe the same way, ending it with
+    /// [`ChrootError::Interrupted`].
+    pub async fn extract_archive_stream<R>(
+        &self,
+        reader: &mut R,
+        compression: ArchiveCompression,
+        options: &ExtractionOptions,
+        operation_desc: &str,
+    ) -> Result<(), ChrootError>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+    {
+        log::info!(
+            "Extracting a stream ({compression:?}, {options:?}) to {}",
+            self.chroot_path.display()
+        );
+
+        let chroot_path_str = self.chroot_path.to_string_lossy();
+        let tar_options = options.tar_options();
+        let mut tar_args = vec!["xpf", "-"];
+        tar_args.extend(compression.tar_option());
+        tar_args.extend(tar_options.iter().map(String::as_str));
+        tar_args.extend(["-C", &chroot_path_str]);
+
+        let mut child = self.elevated_piped_command("tar", &tar_args)?.spawn()?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| ChrootError::Command(format!("{operation_desc} failed: no pipe to tar")))?;
+        let operation = signals::start_operation();
+        let copied = tokio::select! {
+            copied = tokio::io::copy(reader, &mut stdin) => copied,
+            _ = operation.cancelled() => Err(std::io::ErrorKind::Interrupted.into()),
+        };
+        // Closing the pipe lets tar finish, or fail on a truncated archive
+        drop(stdin);
+        let output = child.wait_with_output().await?;
+        if operation.is_cancelled() {
+            log::warn!("{operation_desc} interrupted, tar said: {}", String::from_utf8_lossy(&output.stderr));
+            return Err(ChrootError::Interrupted);
+        }
+
+        // tar also fails on the truncated archive, the stream error is the cause
+        let bytes = match copied {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::error!(
+                    "Error during {operation_desc}, tar said: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                return Err(ChrootError::Stage3Stream(e));
+            }
+        };
+        if !output.status.success() {
+            let error_msg = String::from_utf8_lossy(&output.stderr);
+            log::error!("Error during {operation_desc}: {error_msg}");
+            return Err(ChrootError::Command(format!("{operation_desc} failed: {error_msg}")));
+        }
+        log::info!("{operation_desc} successful ({bytes} bytes)");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroot::ChrootUnit;
+
+    #[test]
+    fn every_combination_maps_to_its_tar_options() {
+        for preserve_owner in [true, false] {
+            for preserve_xattrs in [true, false] {
+                for strip_components in [0, 2] {
+                    let options = ExtractionOptions { preserve_owner, preserve_xattrs, strip_components };
+                    let mut expected: Vec<&str> = if preserve_owner {
+                        vec!["--same-owner", "--numeric-owner"]
+                    } else {
+                        vec!["--no-same-owner"]
+                    };
+                    if preserve_xattrs {
+                        expected.extend(["--xattrs", "--xattrs-include=*.*"]);
+                    } else {
+                        expected.push("--no-xattrs");
+                    }
+                    if strip_components > 0 {
+                        expected.push("--strip-components=2");
+                    }
+                    assert_eq!(options.tar_options(), expected, "{options:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_configuration_sets_owners_and_xattrs_but_never_strips() {
+        let config = Config { extract_preserve_owner: false, ..Config::default() };
+        let options = ExtractionOptions::from_config(&config);
+        assert_eq!(
+            options,
+            ExtractionOptions { preserve_owner: false, preserve_xattrs: true, strip_components: 0 }
+        );
+        assert_eq!(ExtractionOptions::from_config(&Config::default()), ExtractionOptions::default());
+    }
+
+    #[test]
+    fn options_round_trip_without_a_zero_strip() {
+        let options = ExtractionOptions { preserve_owner: false, preserve_xattrs: false, strip_components: 0 };
+        let written = toml::to_string(&options).unwrap();
+        assert!(!written.contains("strip_components"), "{written}");
+        assert_eq!(toml::from_str::<ExtractionOptions>(&written).unwrap(), options);
+
+        let options = ExtractionOptions { strip_components: 1, ..options };
+        assert_eq!(toml::from_str::<ExtractionOptions>(&toml::to_string(&options).unwrap()).unwrap(), options);
+    }
+
+    #[tokio::test]
+    async fn owners_are_restored_only_when_preserved() {
+        use std::os::unix::fs::MetadataExt;
+        // tar runs through sudo otherwise, which may ask for a password
+        if !nix::unistd::geteuid().is_root() {
+            return;
+        }
+        let work = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(work.path().join("tree/top/etc")).unwrap();
+        std::fs::write(work.path().join("tree/top/etc/os-release"), "NAME=Gentoo\n").unwrap();
+        let archive = work.path().join("stage3.tar");
+        let status = std::process::Command::new("tar")
+            .args(["--owner=1234", "--group=1234", "-cf"])
+            .arg(&archive)
+            .arg("-C")
+            .arg(work.path().join("tree"))
+            .arg("top")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        for (name, preserve_owner, uid) in [("owned", true, 1234), ("unowned", false, 0)] {
+            let unit = ChrootUnit {
+                name: name.to_string(),
+                chroot_path: work.path().join(name),
+                profile: None,
+                metadata: None,
+                shared_distfiles: None,
+            };
+            std::fs::create_dir(&unit.chroot_path).unwrap();
+            let options = ExtractionOptions { preserve_owner, preserve_xattrs: false, strip_components: 1 };
+            unit.extract_archive(&archive, &options, "Test extraction").await.unwrap();
+
+            let extracted = unit.chroot_path.join("etc/os-release");
+            assert_eq!(std::fs::metadata(&extracted).unwrap().uid(), uid, "{name}");
+        }
+    }
+}
@@ -1,25 +1,39 @@
-use crate::elevation::{SecureElevation, get_global_elevation};
+use crate::elevation::{self, shared_elevation};
 use crate::error::{ChrootError, ElevationError};
-use std::sync::{Arc, Mutex};
-
-// Shared global instance of the elevation system with cache
-pub static SHARED_ELEVATION: std::sync::LazyLock<Arc<Mutex<SecureElevation>>> =
-    std::sync::LazyLock::new(|| Arc::new(Mutex::new(SecureElevation::new())));
+use crate::platform;
 
 /// Authentication and elevation methods for ChrootUnit
 impl crate::chroot::core::ChrootUnit {
     /// Execute a command with shared cached elevation
+    ///
+    /// The elevation lock is only held to build the command and to check its
+    /// output, not while it runs: a long extraction must not keep the cleanup
+    /// run on a termination signal from unmounting or removing the chroot.
     pub fn execute_elevated(
         &self,
         command: &str,
         args: &[&str],
     ) -> Result<std::process::Output, ChrootError> {
-        let elevation = SHARED_ELEVATION.lock().unwrap();
-        elevation
-            .execute_command(command, args)
+        let mut elevated = shared_elevation().lock().unwrap().command(command, args)?;
+        let output = elevated.output()?;
+        shared_elevation()
+            .lock()
+            .unwrap()
+            .check_output(command, output)
             .map_err(ChrootError::from)
     }
 
+    /// Elevated command fed through its standard input, see `SecureElevation::piped_command`
+    pub fn elevated_piped_command(
+        &self,
+        command: &str,
+        args: &[&str],
+    ) -> Result<tokio::process::Command, ChrootError> {
+        let elevation = shared_elevation().lock().unwrap();
+        let command = elevation.piped_command(command, args).map_err(ChrootError::from)?;
+        Ok(tokio::process::Command::from(command))
+    }
+
     pub fn execute_command_with_logging(
         &self,
         command: &str,
@@ -29,6 +43,7 @@ impl crate::chroot::core::ChrootUnit {
         log::debug!("Executing {command} with cached elevation: {args:?}");
 
         let output = self.execute_elevated(command, args)?;
+        log::info!("Elevated {command} {} exited with {}", args.join(" "), output.status);
 
         if output.status.success() {
             log::info!("{operation_desc} successful");
@@ -49,7 +64,7 @@ impl crate::chroot::core::ChrootUnit {
     /// This method is important for security cleanup when privileges are no longer needed
     #[allow(dead_code)]
     pub fn invalidate_elevation_cache(&self) {
-        if let Ok(elevation) = SHARED_ELEVATION.lock() {
+        if let Ok(elevation) = shared_elevation().lock() {
             elevation.invalidate_cache();
             log::info!(
                 "Shared elevation cache invalidated by chroot: {}",
@@ -62,18 +77,15 @@ impl crate::chroot::core::ChrootUnit {
     /// This method is useful for determining if authentication is needed before operations
     #[allow(dead_code)]
     pub fn is_elevation_cached(&self) -> bool {
-        if let Ok(elevation) = SHARED_ELEVATION.lock() {
-            elevation.is_authenticated()
-        } else {
-            false
-        }
+        elevation::is_authenticated()
     }
 
     /// Pre-authenticate for upcoming privileged operations to avoid multiple password prompts
     /// This should be called before performing mount and chroot operations
     pub fn pre_authenticate_operations(&self) -> Result<(), ChrootError> {
-        let elevation = get_global_elevation();
-        let elevation_guard = elevation
+        platform::ensure_supported()?;
+
+        let elevation_guard = shared_elevation()
             .lock()
             .map_err(|_| ChrootError::Elevation(ElevationError::FailedToAcquireElevationLock))?;
 
@@ -87,18 +99,14 @@ impl crate::chroot::core::ChrootUnit {
 
     /// Check if we have cached authentication for privileged operations
     pub fn is_authenticated(&self) -> bool {
-        if let Ok(elevation) = get_global_elevation().lock() {
-            elevation.is_authenticated()
-        } else {
-            false
-        }
+        elevation::is_authenticated()
     }
 
     /// Invalidate the authentication cache (useful for cleanup or error recovery)
     /// This method is important for security cleanup after operations are complete
     #[allow(dead_code)]
     pub fn invalidate_authentication(&self) {
-        if let Ok(elevation) = get_global_elevation().lock() {
+        if let Ok(elevation) = shared_elevation().lock() {
             elevation.invalidate_cache();
             log::debug!("Authentication cache invalidated for chroot operations");
         }
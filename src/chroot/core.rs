@@ -1,14 +1,37 @@
+use crate::elevation::shared_elevation;
+use crate::chroot::metadata::{ChrootMetadata, LEGACY_PROFILE_FILE, METADATA_FILE};
+use crate::chroot::archive::{ArchiveCompression, ExtractionOptions};
 use crate::config::Config;
+use chrono::Local;
 use crate::error::ChrootError;
 use std::fs;
 use std::path::{Path, PathBuf};
 use crate::profile::selected::SelectedProfile;
+use crate::say;
+use crate::ui::symbols::Symbol;
+use tokio::io::AsyncRead;
+
+/// Entries that do not make a chroot directory non-empty
+const IGNORED_ENTRIES: &[&str] = &["lost+found"];
+
+/// Archive a stage3 is extracted from
+pub enum Stage3Source<'a> {
+    /// An archive on disk, such as a cached download
+    File(&'a Path),
+    /// An archive extracted as it is read, such as a download in progress
+    Stream(&'a mut (dyn AsyncRead + Unpin + Send), ArchiveCompression),
+}
 
 #[derive(Debug, Clone)]
 pub struct ChrootUnit {
     pub name: String,
     pub chroot_path: PathBuf,
     pub profile: Option<SelectedProfile>,
+    /// Metadata read from the chroot, `None` when it has none
+    pub metadata: Option<ChrootMetadata>,
+    /// Host directory bind-mounted to `/var/cache/distfiles`, see
+    /// [`Config::shared_distfiles_dir`]
+    pub shared_distfiles: Option<PathBuf>,
 }
 
 impl ChrootUnit {
@@ -17,46 +40,91 @@ impl ChrootUnit {
         profile: Option<&SelectedProfile>,
         config: &Config,
     ) -> Result<Self, ChrootError> {
+        Self::validate_name(&name)?;
         let chroot_path = Path::new(&config.chroot_base_dir).join(&name);
 
         Ok(Self {
+            metadata: profile.map(|profile| ChrootMetadata::new(&name, profile)),
             name,
             chroot_path,
             profile: profile.cloned(),
+            shared_distfiles: config.shared_distfiles_dir(),
         })
     }
 
+    /// Share the distfiles directory of the configuration, if any, when mounting
+    pub fn with_config_mounts(mut self, config: &Config) -> Self {
+        self.shared_distfiles = config.shared_distfiles_dir();
+        self
+    }
+
+    /// Check that a name can be used as a directory of the chroot base directory
+    pub fn validate_name(name: &str) -> Result<(), ChrootError> {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(ChrootError::InvalidName(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Unit for a directory outside of the chroot base directory, without metadata
+    pub fn with_path(name: String, chroot_path: PathBuf) -> Self {
+        Self {
+            name,
+            chroot_path,
+            profile: None,
+            metadata: None,
+            shared_distfiles: None,
+        }
+    }
+
+    /// Unit for an existing chroot directory
+    ///
+    /// The metadata is read with [`ChrootUnit::read_metadata`], from
+    /// `/etc/chrootmanager.toml` or else the legacy profile file. A chroot
+    /// without readable metadata is loaded without a profile.
     pub fn load(path: &Path) -> Result<ChrootUnit, ChrootError> {
-        let name = path.file_name().unwrap().to_str().unwrap();
+        let name = path.file_name().map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy());
         log::debug!("load name: {name}");
 
         let mut unit = Self {
             name: name.to_string(),
             chroot_path: path.to_path_buf(),
             profile: None,
+            metadata: None,
+            shared_distfiles: None,
         };
-        
-        // Try to read the profile info
-        match unit.read_arch_profile_info() {
-            Ok(profile_info) => {
-                // Profile info is in the format "architecture-profile"
-                if let Some(separator_pos) = profile_info.find('-') {
-                    let architecture = profile_info[..separator_pos].to_string();
-                    let profile = profile_info[separator_pos + 1..].to_string();
-                    unit.profile = Some(crate::profile::selected::SelectedProfile::new(architecture, profile));
-                }
-            },
-            Err(_) => {
+
+        match unit.read_metadata() {
+            Ok(metadata) => {
+                unit.profile = metadata.selected_profile();
+                unit.metadata = Some(metadata);
+            }
+            Err(e) => {
                 // Keep profile as None if we can't read it
-                log::debug!("Could not read profile info for chroot: {name}");
+                log::debug!("Could not read metadata for chroot {name}: {e}");
+                if let Some(target) = unit.make_profile() {
+                    log::debug!("Portage profile of chroot {name}: {}", target.display());
+                }
             }
         }
-        
+
         log::debug!("load unit: {unit:?}");
 
         Ok(unit)
     }
 
+    /// Profile shown in the chroot listings
+    ///
+    /// A chroot without metadata but with a Portage profile, e.g. one
+    /// changed with `eselect profile set`, is shown as a custom profile.
+    pub fn profile_label(&self) -> String {
+        match &self.profile {
+            Some(profile) => profile.to_string(),
+            None if self.make_profile().is_some() => "custom/unknown".to_string(),
+            None => "Undefined".to_string(),
+        }
+    }
+
     /// Prepare the chroot directory
     pub async fn prepare_chroot_directory(&self) -> Result<(), ChrootError> {
         log::info!(
@@ -74,88 +142,442 @@ impl ChrootUnit {
         Ok(())
     }
 
-    /// Extract stage3 into the chroot directory
-    pub async fn extract_stage3(&self, cached_stage3_path: &Path) -> Result<(), ChrootError> {
-        log::info!(
-            "Extracting from stage3: {} to {}",
-            cached_stage3_path.display(),
-            self.chroot_path.display()
-        );
+    /// List the entries of the chroot directory that an extraction would overlay
+    ///
+    /// `lost+found` is ignored, as it is present on any freshly created filesystem.
+    pub fn existing_entries(&self) -> Result<Vec<String>, ChrootError> {
+        if !self.chroot_path.exists() {
+            return Ok(Vec::new());
+        }
 
-        let cached_stage3_path_str = cached_stage3_path.to_str().unwrap();
-        let chroot_path_str = self.chroot_path.to_str().unwrap();
+        let mut entries = fs::read_dir(&self.chroot_path)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| !IGNORED_ENTRIES.contains(&name.as_str()))
+            .collect::<Vec<_>>();
+        entries.sort();
+        Ok(entries)
+    }
 
-        let tar_args = vec![
-            "xpvf",
-            cached_stage3_path_str,
-            "--xattrs-include=*.*",
-            "--numeric-owner",
-            "-C",
-            chroot_path_str,
-        ];
+    /// Refuse to extract a stage3 on top of an existing tree
+    pub fn ensure_empty_for_extraction(&self) -> Result<(), ChrootError> {
+        let entries = self.existing_entries()?;
+        match entries.first() {
+            None => Ok(()),
+            Some(first) => Err(ChrootError::DirectoryNotEmpty {
+                path: self.chroot_path.clone(),
+                count: entries.len(),
+                first: first.clone(),
+            }),
+        }
+    }
 
-        self.execute_command_with_logging("tar", &tar_args, "Stage3 extraction")?;
+    /// Extract stage3 into the chroot directory
+    pub async fn extract_stage3(
+        &self,
+        source: Stage3Source<'_>,
+        options: &ExtractionOptions,
+    ) -> Result<(), ChrootError> {
+        match source {
+            Stage3Source::File(cached_stage3_path) => {
+                log::info!(
+                    "Extracting from stage3: {} to {}",
+                    cached_stage3_path.display(),
+                    self.chroot_path.display()
+                );
+                self.extract_archive(cached_stage3_path, options, "Stage3 extraction")
+                    .await?;
+            }
+            Stage3Source::Stream(reader, compression) => {
+                log::info!("Extracting a streamed stage3 to {}", self.chroot_path.display());
+                self.extract_archive_stream(reader, compression, options, "Stage3 extraction")
+                    .await?;
+            }
+        }
         log::info!("Stage3 successfully extracted");
         Ok(())
     }
 
-    /// Write Profile info
-    pub fn write_arch_profile_info(&self) -> Result<(), ChrootError> {
-        if let Some(profile) = &self.profile {
-            let profile_info = format!("{}-{}", profile.architecture, profile.profile);
-            let profile_path = self.chroot_path.join("etc/arch-chroot-profile");
+    /// Write the metadata, along with the legacy profile file read by older versions
+    pub fn write_metadata(
+        &self,
+        stage3: Option<&str>,
+        stage3_sha256: Option<&str>,
+        release: Option<&str>,
+        mirror: Option<&str>,
+        extraction: &ExtractionOptions,
+    ) -> Result<(), ChrootError> {
+        let Some(metadata) = &self.metadata else {
+            return Err(ChrootError::NoProfile);
+        };
+        let metadata = ChrootMetadata {
+            stage3: stage3.map(str::to_string),
+            stage3_sha256: stage3_sha256.map(str::to_lowercase),
+            release: release.map(str::to_string),
+            mirror: mirror.map(str::to_string),
+            extraction: Some(*extraction),
+            ..metadata.clone()
+        };
+        self.save_metadata(&metadata)
+    }
 
-            let temp_file = format!("/tmp/arch-chroot-profile-{}", std::process::id());
+    /// Record in the metadata that the Portage tree was just synced
+    pub fn record_portage_sync(&self) -> Result<(), ChrootError> {
+        let metadata = ChrootMetadata {
+            portage_synced_at: Some(Local::now()),
+            ..self.read_metadata()?
+        };
+        self.save_metadata(&metadata)
+    }
 
-            fs::write(&temp_file, &profile_info).map_err(ChrootError::Io)?;
+    /// Save the given metadata into this chroot
+    pub(crate) fn save_metadata(&self, metadata: &ChrootMetadata) -> Result<(), ChrootError> {
+        let elevation = shared_elevation().lock().unwrap();
+        metadata.save(&self.chroot_path, &elevation)
+    }
 
-            self.execute_elevated("mv", &[&temp_file, &profile_path.to_string_lossy()])?;
+    /// Write the metadata of an imported tree
+    ///
+    /// The metadata shipped in the tree is upgraded to the current layout;
+    /// without any, the profile is guessed from the Portage profile symlink.
+    /// Returns false when neither is available. The options of the import
+    /// replace the recorded extraction.
+    pub(crate) fn adopt_imported_metadata(
+        &mut self,
+        extraction: &ExtractionOptions,
+    ) -> Result<bool, ChrootError> {
+        let metadata = match self.read_metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                log::debug!("No metadata in the imported tree: {e}");
+                match self.make_profile().and_then(|target| ChrootMetadata::from_make_profile(&target)) {
+                    Some(metadata) => metadata,
+                    None => return Ok(false),
+                }
+            }
+        };
 
-            log::debug!("Profile info written to {}", profile_path.display());
-            Ok(())
-        } else {
-            Err(ChrootError::NoProfile)
+        // The layout of a newer version is unknown, leave it as shipped
+        if metadata.is_unsupported() {
+            self.profile = metadata.selected_profile();
+            self.metadata = Some(metadata);
+            return Ok(true);
         }
+
+        let metadata = ChrootMetadata {
+            name: Some(self.name.clone()),
+            extraction: Some(*extraction),
+            ..metadata.migrate()
+        };
+        self.save_metadata(&metadata)?;
+        self.profile = metadata.selected_profile();
+        self.metadata = Some(metadata);
+        Ok(true)
     }
 
-    pub fn read_arch_profile_info(&self) -> Result<String, ChrootError> {
-        let profile_path = self.chroot_path.join("etc/arch-chroot-profile");
+    /// Record the current name of the chroot in its metadata, after a rename or a copy
+    ///
+    /// Legacy and unsupported metadata are left alone. A failure is only
+    /// reported: the name is informative, the directory name is authoritative.
+    pub(crate) fn record_name_in_metadata(&mut self) {
+        let Some(metadata) = &self.metadata else {
+            return;
+        };
+        if metadata.is_outdated() || metadata.is_unsupported() || metadata.name.as_deref() == Some(&self.name) {
+            return;
+        }
 
+        let updated = ChrootMetadata {
+            name: Some(self.name.clone()),
+            ..metadata.clone()
+        };
+        match self.save_metadata(&updated) {
+            Ok(()) => self.metadata = Some(updated),
+            Err(e) => say!("{} Unable to record the new name in the metadata: {e}", Symbol::Warning),
+        }
+    }
+
+    /// Read the metadata, falling back to the legacy profile file
+    pub fn read_metadata(&self) -> Result<ChrootMetadata, ChrootError> {
+        let metadata_path = self.chroot_path.join(METADATA_FILE);
+        if metadata_path.exists() {
+            let content = fs::read_to_string(&metadata_path)?;
+            let (metadata, dropped) = ChrootMetadata::from_toml_lenient(&content)
+                .map_err(|e| ChrootError::InvalidMetadata(format!("{}: {e}", metadata_path.display())))?;
+            if !dropped.is_empty() {
+                say!(
+                    "{} Ignoring invalid fields of {}: {}",
+                    Symbol::Warning,
+                    metadata_path.display(),
+                    dropped.join(", ")
+                );
+            }
+            return Ok(metadata);
+        }
+
+        let profile_path = self.chroot_path.join(LEGACY_PROFILE_FILE);
         if !profile_path.exists() {
             log::debug!("Profile file doesn't exist: {}", profile_path.display());
             return Err(ChrootError::NoProfile);
         }
 
-        match fs::read_to_string(&profile_path) {
-            Ok(content) => Ok(content.trim().to_string()),
-            Err(e) => {
-                log::debug!("Failed to read a profile file: {e}");
-                Err(ChrootError::Io(e))
+        let content = fs::read_to_string(&profile_path)?;
+        ChrootMetadata::from_legacy(&content).ok_or_else(|| {
+            ChrootError::InvalidMetadata(format!("{}: '{}'", profile_path.display(), content.trim()))
+        })
+    }
+
+    /// Fail unless the metadata has the current layout
+    ///
+    /// Used by operations that rely on fields missing from older layouts.
+    pub fn ensure_current_metadata(&self) -> Result<(), ChrootError> {
+        self.ensure_supported_metadata()?;
+        match &self.metadata {
+            Some(metadata) if metadata.is_outdated() => Err(ChrootError::MetadataTooOld {
+                name: self.name.clone(),
+                version: metadata.metadata_version,
+            }),
+            Some(_) => Ok(()),
+            None => Err(ChrootError::NoProfile),
+        }
+    }
+
+    /// Fail if the metadata was written by a newer chrootmanager
+    pub fn ensure_supported_metadata(&self) -> Result<(), ChrootError> {
+        match &self.metadata {
+            Some(metadata) if metadata.is_unsupported() => Err(ChrootError::MetadataTooNew {
+                name: self.name.clone(),
+                version: metadata.metadata_version,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Upgrade the metadata to the current layout in place
+    ///
+    /// Returns false when it already was current.
+    pub fn migrate_metadata(&mut self) -> Result<bool, ChrootError> {
+        self.ensure_supported_metadata()?;
+        let Some(metadata) = self.metadata.take() else {
+            return Err(ChrootError::NoProfile);
+        };
+        if !metadata.is_outdated() {
+            self.metadata = Some(metadata);
+            return Ok(false);
+        }
+
+        let from = metadata.metadata_version;
+        let migrated = metadata.clone().migrate();
+        if let Err(e) = self.save_metadata(&migrated) {
+            self.metadata = Some(metadata);
+            return Err(e);
+        }
+        log::info!(
+            "Metadata of chroot {} migrated from version {from} to {}",
+            self.name,
+            migrated.metadata_version
+        );
+        self.metadata = Some(migrated);
+        Ok(true)
+    }
+
+    /// Whether a directory of the chroot base directory holds a chroot
+    ///
+    /// Hidden directories and directories without an `etc/` are left out.
+    /// Fails when the directory cannot be read.
+    pub fn looks_like_chroot(path: &Path) -> Result<bool, std::io::Error> {
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            return Ok(false);
+        }
+        path.join("etc").try_exists()
+    }
+
+    /// Chroot directories of `base_dir`, see [`ChrootUnit::looks_like_chroot`]
+    ///
+    /// Entries that cannot be read are skipped with a warning in the log.
+    pub fn chroot_dirs(base_dir: &Path) -> Result<Vec<PathBuf>, ChrootError> {
+        let mut dirs = Vec::new();
+        for entry in fs::read_dir(base_dir)? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    log::warn!("Skipping an unreadable entry of {}: {e}", base_dir.display());
+                    continue;
+                }
+            };
+            if !path.is_dir() {
+                continue;
+            }
+            match Self::looks_like_chroot(&path) {
+                Ok(true) => dirs.push(path),
+                Ok(false) => log::debug!("Not a chroot, skipped: {}", path.display()),
+                Err(e) => log::warn!("Skipping {}: {e}", path.display()),
             }
         }
+        Ok(dirs)
     }
 
     /// Find all chroot units in the configured directory
     /// This function is intended for bulk operations and GUI integration
-    #[allow(dead_code)]
     pub fn find_units(config: &Config) -> Result<Vec<ChrootUnit>, ChrootError> {
-        let rd = fs::read_dir(&config.chroot_base_dir);
+        let units = Self::chroot_dirs(&config.chroot_base_dir)?
+            .iter()
+            .map(|p| ChrootUnit::load(p).map(|unit| unit.with_config_mounts(config)))
+            .collect::<Result<Vec<ChrootUnit>, ChrootError>>()?;
 
-        if let Err(e) = rd {
-            return Err(ChrootError::from(e));
+        Ok(units)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroot::metadata::METADATA_VERSION;
+
+    /// Chroot directory with an `etc/` and the given files, relative to its root
+    fn chroot_dir(base_dir: &Path, name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = base_dir.join(name);
+        fs::create_dir_all(path.join("etc")).unwrap();
+        for (file, content) in files {
+            fs::write(path.join(file), content).unwrap();
         }
+        path
+    }
 
-        let dirs = rd?
-            .filter_map(|e| e.ok())
-            .map(|e| e.path())
-            .filter(|e| e.is_dir())
-            .collect::<Vec<_>>();
+    fn find_units_in(base_dir: &Path) -> Vec<ChrootUnit> {
+        let config = Config { chroot_base_dir: base_dir.to_path_buf(), ..Config::default() };
+        let mut units = ChrootUnit::find_units(&config).unwrap();
+        units.sort_by(|a, b| a.name.cmp(&b.name));
+        units
+    }
 
-        let units = dirs
-            .iter()
-            .map(|p| ChrootUnit::load(p))
-            .collect::<Result<Vec<ChrootUnit>, ChrootError>>()?;
+    #[test]
+    fn find_units_reads_the_metadata_then_the_legacy_file() {
+        let base_dir = tempfile::tempdir().unwrap();
+        let metadata =
+            format!("metadata_version = {METADATA_VERSION}\narchitecture = \"amd64\"\nprofile = \"systemd\"\n");
+        chroot_dir(
+            base_dir.path(),
+            "current",
+            &[(METADATA_FILE, &metadata), (LEGACY_PROFILE_FILE, "x86-openrc")],
+        );
+        chroot_dir(base_dir.path(), "legacy", &[(LEGACY_PROFILE_FILE, "arm64-openrc\n")]);
+        chroot_dir(base_dir.path(), "none", &[]);
 
-        Ok(units)
+        let units = find_units_in(base_dir.path());
+        let names: Vec<&str> = units.iter().map(|unit| unit.name.as_str()).collect();
+        assert_eq!(names, ["current", "legacy", "none"]);
+
+        // The metadata file takes precedence over the legacy file
+        assert_eq!(units[0].profile_label(), "amd64-systemd");
+        assert_eq!(units[0].metadata.as_ref().unwrap().metadata_version, METADATA_VERSION);
+
+        assert_eq!(units[1].profile_label(), "arm64-openrc");
+        assert_eq!(units[1].metadata.as_ref().unwrap().metadata_version, 0);
+
+        assert_eq!(units[2].profile_label(), "Undefined");
+        assert!(units[2].metadata.is_none());
+        assert_eq!(units[2].chroot_path, base_dir.path().join("none"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn find_units_fails_on_a_missing_base_directory() {
+        let base_dir = tempfile::tempdir().unwrap();
+        let config = Config { chroot_base_dir: base_dir.path().join("missing"), ..Config::default() };
+        assert!(ChrootUnit::find_units(&config).is_err());
+    }
+
+    #[test]
+    fn find_units_keeps_chroots_with_an_unrecognized_profile() {
+        let base_dir = tempfile::tempdir().unwrap();
+        let eselected = chroot_dir(base_dir.path(), "eselected", &[]);
+        fs::create_dir(eselected.join("etc/portage")).unwrap();
+        std::os::unix::fs::symlink(
+            "../../var/db/repos/gentoo/profiles/default/linux/amd64/23.0/no-multilib/hardened",
+            eselected.join("etc/portage/make.profile"),
+        )
+        .unwrap();
+        chroot_dir(
+            base_dir.path(),
+            "garbled",
+            &[(METADATA_FILE, "not = [toml"), (LEGACY_PROFILE_FILE, "amd64")],
+        );
+
+        let units = find_units_in(base_dir.path());
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[0].name, "eselected");
+        assert_eq!(units[0].profile_label(), "custom/unknown");
+        assert_eq!(units[1].name, "garbled");
+        assert!(units[1].metadata.is_none());
+        assert_eq!(units[1].profile_label(), "Undefined");
+    }
+
+    #[test]
+    fn find_units_skips_hidden_and_non_chroot_directories() {
+        let base_dir = tempfile::tempdir().unwrap();
+        chroot_dir(base_dir.path(), "gentoo", &[]);
+        chroot_dir(base_dir.path(), ".trash", &[]);
+        fs::create_dir(base_dir.path().join("empty")).unwrap();
+        fs::write(base_dir.path().join("notes.txt"), "not a chroot").unwrap();
+
+        let names: Vec<String> = find_units_in(base_dir.path()).into_iter().map(|unit| unit.name).collect();
+        assert_eq!(names, ["gentoo"]);
+    }
+
+    #[test]
+    fn looks_like_chroot_fails_on_an_unreadable_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let base_dir = tempfile::tempdir().unwrap();
+        assert!(ChrootUnit::looks_like_chroot(&chroot_dir(base_dir.path(), "gentoo", &[])).unwrap());
+        assert!(!ChrootUnit::looks_like_chroot(&chroot_dir(base_dir.path(), ".trash", &[])).unwrap());
+        fs::create_dir(base_dir.path().join("empty")).unwrap();
+        assert!(!ChrootUnit::looks_like_chroot(&base_dir.path().join("empty")).unwrap());
+
+        // Root reads any directory
+        if nix::unistd::geteuid().is_root() {
+            return;
+        }
+        let locked = chroot_dir(base_dir.path(), "locked", &[]);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        let result = ChrootUnit::looks_like_chroot(&locked);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(result.is_err());
+        // The unreadable directory is skipped, the other chroots are still found
+        let names: Vec<String> = find_units_in(base_dir.path()).into_iter().map(|unit| unit.name).collect();
+        assert_eq!(names, ["gentoo"]);
+    }
+
+    #[test]
+    fn extraction_is_refused_over_anything_but_lost_and_found() {
+        let base_dir = tempfile::tempdir().unwrap();
+        let unit = ChrootUnit {
+            name: "gentoo".to_string(),
+            chroot_path: base_dir.path().join("gentoo"),
+            profile: None,
+            metadata: None,
+            shared_distfiles: None,
+        };
+        // Missing, then empty
+        unit.ensure_empty_for_extraction().unwrap();
+        fs::create_dir(&unit.chroot_path).unwrap();
+        unit.ensure_empty_for_extraction().unwrap();
+
+        fs::create_dir(unit.chroot_path.join("lost+found")).unwrap();
+        assert!(unit.existing_entries().unwrap().is_empty());
+        unit.ensure_empty_for_extraction().unwrap();
+
+        fs::create_dir(unit.chroot_path.join("usr")).unwrap();
+        fs::write(unit.chroot_path.join("etc"), "").unwrap();
+        assert_eq!(unit.existing_entries().unwrap(), ["etc", "usr"]);
+        match unit.ensure_empty_for_extraction() {
+            Err(ChrootError::DirectoryNotEmpty { count, first, .. }) => {
+                assert_eq!(count, 2);
+                assert_eq!(first, "etc");
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
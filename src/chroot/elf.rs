@@ -0,0 +1,228 @@
+//! Architecture check of an extracted chroot
+//!
+//! Only the `e_machine` field of the ELF header of a well-known binary is
+//! read, enough to catch a stage3 built for another architecture before it
+//! shows up as exec format errors.
+
+use crate::chroot::core::ChrootUnit;
+use crate::error::ChrootError;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Binaries probed, relative to the chroot root, in order
+const PROBED_BINARIES: &[&str] = &["bin/busybox", "bin/bash", "usr/bin/bash"];
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+
+/// Read the `e_machine` field of an ELF file, `None` if it is not one
+pub(crate) fn read_elf_machine(path: &Path) -> Result<Option<u16>, io::Error> {
+    // e_ident (16 bytes), e_type (2), e_machine (2)
+    let mut header = [0u8; 20];
+    let mut file = File::open(path)?;
+    if let Err(e) = file.read_exact(&mut header) {
+        return match e.kind() {
+            io::ErrorKind::UnexpectedEof => Ok(None),
+            _ => Err(e),
+        };
+    }
+    if &header[..4] != ELF_MAGIC {
+        return Ok(None);
+    }
+
+    let machine = [header[18], header[19]];
+    match header[5] {
+        1 => Ok(Some(u16::from_le_bytes(machine))),
+        2 => Ok(Some(u16::from_be_bytes(machine))),
+        _ => Ok(None),
+    }
+}
+
+/// Whether an ELF file requests a program interpreter, i.e. is dynamically linked
+///
+/// `None` when the file is not a readable ELF file.
+pub(crate) fn is_dynamically_linked(path: &Path) -> Result<Option<bool>, io::Error> {
+    let data = std::fs::read(path)?;
+    if data.len() < 52 || &data[..4] != ELF_MAGIC {
+        return Ok(None);
+    }
+
+    let little_endian = match data[5] {
+        1 => true,
+        2 => false,
+        _ => return Ok(None),
+    };
+    let read = |offset: usize, size: usize| -> Option<u64> {
+        let bytes = data.get(offset..offset + size)?;
+        let mut value = [0u8; 8];
+        if little_endian {
+            value[..size].copy_from_slice(bytes);
+            Some(u64::from_le_bytes(value))
+        } else {
+            value[8 - size..].copy_from_slice(bytes);
+            Some(u64::from_be_bytes(value))
+        }
+    };
+
+    // e_phoff, e_phentsize and e_phnum depend on the ELF class
+    let (phoff, phentsize, phnum) = match data[4] {
+        1 => (read(28, 4), read(42, 2), read(44, 2)),
+        2 => (read(32, 8), read(54, 2), read(56, 2)),
+        _ => return Ok(None),
+    };
+    let (Some(phoff), Some(phentsize), Some(phnum)) = (phoff, phentsize, phnum) else {
+        return Ok(None);
+    };
+
+    const PT_INTERP: u64 = 3;
+    for index in 0..phnum {
+        let Some(p_type) = read((phoff + index * phentsize) as usize, 4) else {
+            return Ok(None);
+        };
+        if p_type == PT_INTERP {
+            return Ok(Some(true));
+        }
+    }
+    Ok(Some(false))
+}
+
+/// Gentoo architecture name of an ELF machine
+pub(crate) fn machine_architecture(machine: u16) -> Option<&'static str> {
+    let architecture = match machine {
+        0x03 => "x86",
+        0x04 => "m68k",
+        0x08 => "mips",
+        0x0F => "hppa",
+        0x14 => "ppc",
+        0x15 => "ppc64",
+        0x16 => "s390",
+        0x28 => "arm",
+        0x2B => "sparc",
+        0x32 => "ia64",
+        0x3E => "amd64",
+        0xB7 => "arm64",
+        0xF3 => "riscv",
+        0x102 => "loong",
+        0x9026 => "alpha",
+        _ => return None,
+    };
+    Some(architecture)
+}
+
+impl ChrootUnit {
+    /// Check that the binaries of the chroot are built for its architecture
+    ///
+    /// The check is skipped when the chroot has no profile or none of the
+    /// probed binaries is a readable ELF file. Symlinks are not followed,
+    /// their target could be on the host.
+    pub fn verify_architecture(&self) -> Result<(), ChrootError> {
+        let Some(profile) = &self.profile else {
+            return Ok(());
+        };
+
+        for binary in PROBED_BINARIES {
+            let path = self.chroot_path.join(binary);
+            match path.symlink_metadata() {
+                Ok(metadata) if metadata.is_file() => {}
+                _ => continue,
+            }
+
+            let machine = match read_elf_machine(&path) {
+                Ok(Some(machine)) => machine,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::debug!("Unable to read {}: {e}", path.display());
+                    continue;
+                }
+            };
+
+            let found = machine_architecture(machine)
+                .map_or_else(|| format!("unknown ELF machine {machine:#x}"), str::to_string);
+            log::debug!("{} is built for {found}", path.display());
+            if found != profile.arch() {
+                return Err(ChrootError::ArchitectureMismatch {
+                    expected: profile.arch().to_string(),
+                    found,
+                    binary: path,
+                });
+            }
+            return Ok(());
+        }
+
+        log::warn!(
+            "No binary to check the architecture of {} against",
+            self.chroot_path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian 64-bit ELF file with a single program header of type `p_type`
+    fn elf64(machine: u16, p_type: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 64 + 56];
+        data[..4].copy_from_slice(ELF_MAGIC);
+        data[4] = 2; // ELFCLASS64
+        data[5] = 1; // ELFDATA2LSB
+        data[18..20].copy_from_slice(&machine.to_le_bytes());
+        data[32..40].copy_from_slice(&64u64.to_le_bytes()); // e_phoff
+        data[54..56].copy_from_slice(&56u16.to_le_bytes()); // e_phentsize
+        data[56..58].copy_from_slice(&1u16.to_le_bytes()); // e_phnum
+        data[64..68].copy_from_slice(&p_type.to_le_bytes());
+        data
+    }
+
+    fn write(dir: &Path, name: &str, data: &[u8]) -> std::path::PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_the_machine_and_interpreter_of_elf_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let static_arm64 = write(dir.path(), "qemu-aarch64", &elf64(0xB7, 1));
+        let dynamic_amd64 = write(dir.path(), "bash", &elf64(0x3E, 3));
+
+        assert_eq!(read_elf_machine(&static_arm64).unwrap(), Some(0xB7));
+        assert_eq!(is_dynamically_linked(&static_arm64).unwrap(), Some(false));
+        assert_eq!(read_elf_machine(&dynamic_amd64).unwrap(), Some(0x3E));
+        assert_eq!(is_dynamically_linked(&dynamic_amd64).unwrap(), Some(true));
+
+        let mut big_endian = elf64(0x15, 1);
+        big_endian[5] = 2;
+        big_endian[18..20].copy_from_slice(&0x15u16.to_be_bytes());
+        let ppc64 = write(dir.path(), "ppc64", &big_endian);
+        assert_eq!(read_elf_machine(&ppc64).unwrap(), Some(0x15));
+    }
+
+    #[test]
+    fn other_files_are_not_elf() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write(dir.path(), "script", b"#!/bin/sh\nexec qemu-aarch64 \"$@\"\n");
+        let truncated = write(dir.path(), "truncated", &elf64(0xB7, 1)[..10]);
+
+        assert_eq!(read_elf_machine(&script).unwrap(), None);
+        assert_eq!(is_dynamically_linked(&script).unwrap(), None);
+        assert_eq!(read_elf_machine(&truncated).unwrap(), None);
+        assert_eq!(is_dynamically_linked(&truncated).unwrap(), None);
+        assert!(read_elf_machine(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn machines_of_the_gentoo_architectures() {
+        for (machine, architecture) in [
+            (0x3E, "amd64"),
+            (0x03, "x86"),
+            (0xB7, "arm64"),
+            (0xF3, "riscv"),
+            (0x15, "ppc64"),
+        ] {
+            assert_eq!(machine_architecture(machine), Some(architecture), "{machine:#x}");
+        }
+        assert_eq!(machine_architecture(0), None);
+    }
+}
@@ -0,0 +1,103 @@
+//! Foreign-architecture chroots
+//!
+//! Binaries of another architecture run through qemu-user, which the kernel
+//! starts by itself once a binfmt_misc handler is registered for them. The
+//! emulator runs inside the chroot, so it has to be statically linked and,
+//! unless the handler was registered with the `F` flag, present at the
+//! same path in the chroot.
+
+use crate::chroot::core::ChrootUnit;
+use crate::chroot::elf::is_dynamically_linked;
+use crate::error::ChrootError;
+use crate::platform::{self, BinfmtHandler};
+use std::path::{Path, PathBuf};
+
+impl ChrootUnit {
+    /// Whether the binaries of the chroot need qemu-user to run on this host
+    ///
+    /// A chroot without a profile is assumed to be native.
+    pub fn needs_emulation(&self) -> bool {
+        self.profile
+            .as_ref()
+            .is_some_and(|profile| !platform::runs_natively(profile.arch()))
+    }
+
+    /// Enabled binfmt_misc handler for the architecture of the chroot
+    ///
+    /// Fails with a message naming what is missing on the host.
+    fn emulation_handler(&self) -> Result<BinfmtHandler, ChrootError> {
+        let arch = self.profile.as_ref().ok_or(ChrootError::NoProfile)?.arch();
+        let unavailable = |reason: String| ChrootError::EmulationUnavailable {
+            arch: arch.to_string(),
+            reason,
+        };
+
+        let target = platform::qemu_target(arch)
+            .ok_or_else(|| unavailable("qemu-user has no target for this architecture".to_string()))?;
+        let handler = platform::binfmt_handler(target).ok_or_else(|| {
+            unavailable(format!(
+                "no binfmt_misc handler is enabled for qemu-{target}, install qemu with its static user targets and enable its binfmt service"
+            ))
+        })?;
+
+        match is_dynamically_linked(&handler.interpreter) {
+            Ok(Some(true)) => Err(unavailable(format!(
+                "the emulator {} is dynamically linked, a static build is needed inside a chroot",
+                handler.interpreter.display()
+            ))),
+            Ok(_) => Ok(handler),
+            Err(e) => Err(unavailable(format!("unable to read the emulator {}: {e}", handler.interpreter.display()))),
+        }
+    }
+
+    /// Path of the emulator inside the chroot
+    fn emulator_path(&self, interpreter: &Path) -> PathBuf {
+        self.chroot_path
+            .join(interpreter.strip_prefix("/").unwrap_or(interpreter))
+    }
+
+    /// Copy the qemu-user emulator into the chroot, at the path the kernel runs it from
+    ///
+    /// Nothing is copied for a native chroot, or when the binfmt_misc
+    /// handler keeps the emulator open.
+    pub fn install_emulator(&self) -> Result<(), ChrootError> {
+        if !self.needs_emulation() {
+            return Ok(());
+        }
+
+        let handler = self.emulation_handler()?;
+        if handler.fix_binary {
+            log::debug!(
+                "The binfmt_misc handler keeps {} open, no copy needed",
+                handler.interpreter.display()
+            );
+            return Ok(());
+        }
+
+        let destination = self.emulator_path(&handler.interpreter);
+        let source = handler.interpreter.to_string_lossy();
+        let destination_str = destination.to_string_lossy();
+        if let Some(parent) = destination.parent().filter(|parent| !parent.exists()) {
+            self.execute_command_with_logging("mkdir", &["-p", &parent.to_string_lossy()], "Emulator directory creation")?;
+        }
+        self.execute_command_with_logging("cp", &["-f", &source, &destination_str], "Emulator copy")?;
+        log::info!("{source} copied to {destination_str}");
+        Ok(())
+    }
+
+    /// Make sure the binaries of the chroot can run before entering it
+    ///
+    /// The emulator is copied again when it is missing from the chroot, for
+    /// chroots created before qemu-user was set up on the host.
+    pub fn ensure_emulation(&self) -> Result<(), ChrootError> {
+        if !self.needs_emulation() {
+            return Ok(());
+        }
+
+        let handler = self.emulation_handler()?;
+        if !handler.fix_binary && !self.emulator_path(&handler.interpreter).exists() {
+            self.install_emulator()?;
+        }
+        Ok(())
+    }
+}
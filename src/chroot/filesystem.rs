@@ -1,13 +1,157 @@
+use crate::config::Config;
 use crate::error::{ChrootError, ElevationError};
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
 
-use super::auth::SHARED_ELEVATION;
+use crate::elevation::shared_elevation;
+use super::mounts::{self, MountEntry, MountState};
+use crate::chroot::core::ChrootUnit;
+use crate::say;
+use crate::signals::{self, CleanupGuard};
+use crate::ui::symbols::Symbol;
+
+/// Host files copied into the chroot for name resolution
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+const HOSTS_FILE: &str = "/etc/hosts";
+
+/// Runtime directory of systemd-resolved, relative to the root
+const RESOLVED_RUNTIME_DIR: &str = "run/systemd/resolve";
+
+/// CA bundle of the host, copied when `copy_host_ca_certs` is set
+const HOST_CA_BUNDLE: &str = "/etc/ssl/certs/ca-certificates.crt";
+
+/// resolv.conf to copy from the host whose root directory is `root`
+///
+/// With systemd-resolved, `/etc/resolv.conf` links to its stub, which
+/// points at 127.0.0.53 where nothing listens inside the chroot. The file
+/// listing the upstream servers of systemd-resolved is used instead, or
+/// the stub with a warning when it is missing. `None` when the host has no
+/// resolv.conf.
+fn host_resolv_conf(root: &Path) -> Option<PathBuf> {
+    let resolv_conf = root.join(RESOLV_CONF.trim_start_matches('/'));
+    let Ok(target) = fs::read_link(&resolv_conf) else {
+        return resolv_conf.exists().then_some(resolv_conf);
+    };
+    if !target.to_string_lossy().contains(&format!("{RESOLVED_RUNTIME_DIR}/")) {
+        return Some(resolv_conf);
+    }
+
+    let upstream = root.join(RESOLVED_RUNTIME_DIR).join("resolv.conf");
+    if upstream.exists() {
+        log::info!("{} links to systemd-resolved, copying {} instead", resolv_conf.display(), upstream.display());
+        return Some(upstream);
+    }
+    say!(
+        "{} {} of systemd-resolved is missing, the chroot gets the stub resolver 127.0.0.53, which does not answer inside it",
+        Symbol::Warning,
+        upstream.display()
+    );
+    Some(resolv_conf)
+}
+
+/// Times the mount table is checked after unmounting
+const UNMOUNT_ATTEMPTS: u32 = 3;
+
+/// Delay before unmounting what remains again
+const UNMOUNT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Filesystems mounted for chroot operation: the mount point, relative to
+/// the chroot, the `mount` arguments before it, and whether it is made a
+/// slave so that unmounting it leaves the host alone
+const CHROOT_MOUNTS: &[(&str, &[&str], bool)] = &[
+    ("proc", &["-t", "proc", "/proc"], false),
+    ("sys", &["--rbind", "/sys"], true),
+    ("dev", &["--rbind", "/dev"], true),
+    ("dev/pts", &["--rbind", "/dev/pts"], false),
+    ("dev/shm", &["--rbind", "/dev/shm"], false),
+];
+
+/// Where a shared distfiles directory is mounted, relative to the chroot
+const DISTFILES_MOUNT_POINT: &str = "var/cache/distfiles";
+
+/// Line marking the distfiles hint added to make.conf
+const DISTFILES_HINT_MARKER: &str = "# chrootmanager: shared distfiles";
+
+/// Filesystems mounted by [`ChrootUnit::mount_filesystems`], unmounted when dropped
+///
+/// Dropping the guard unmounts on a best-effort basis and only logs the
+/// failures, [`MountGuard::unmount`] reports them. While the guard lives,
+/// a termination signal unmounts too.
+#[must_use = "the filesystems are unmounted when the guard is dropped"]
+pub struct MountGuard<'a> {
+    unit: &'a ChrootUnit,
+    armed: bool,
+    _termination: CleanupGuard,
+}
+
+impl MountGuard<'_> {
+    /// Unmount the filesystems now, reporting a failure
+    pub fn unmount(mut self) -> Result<(), ChrootError> {
+        self.armed = false;
+        self.unit.unmount_filesystems().map(|_| ())
+    }
+
+    /// Leave the filesystems mounted, for a session that outlives the guard
+    ///
+    /// They are then unmounted with [`ChrootUnit::unmount_filesystems`].
+    #[allow(dead_code)]
+    pub fn leave_mounted(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for MountGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            if let Err(e) = self.unit.unmount_filesystems() {
+                log::warn!("Failed to unmount the filesystems of {}: {e}", self.unit.name);
+            }
+        }
+    }
+}
 
 /// Filesystem operations for ChrootUnit
-impl crate::chroot::core::ChrootUnit {
+impl ChrootUnit {
     /// Mount the necessary filesystems for chroot operation
-    pub fn mount_filesystems(&self) -> Result<&Self, ChrootError> {
+    ///
+    /// Mount points that are already mounted, e.g. after an interrupted
+    /// session, are reused rather than mounted over. When a mount fails,
+    /// what was mounted is unmounted again, as when a termination signal is
+    /// received meanwhile. The filesystems stay mounted as long as the
+    /// returned guard lives.
+    pub fn mount_filesystems(&self) -> Result<MountGuard<'_>, ChrootError> {
+        // The unmount on termination is only registered once everything is mounted
+        let operation = signals::start_operation();
+        let mounted = self.mount_all().and_then(|()| {
+            if operation.is_cancelled() {
+                Err(ChrootError::Interrupted)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(e) = mounted {
+            // Partial mounts may remain
+            let _ = self.unmount_filesystems();
+            return Err(e);
+        }
+
+        let unit = self.clone();
+        let termination = signals::on_termination(format!("unmount the filesystems of {}", self.name), move || {
+            if let Err(e) = unit.unmount_filesystems() {
+                log::warn!("Failed to unmount filesystems: {e}");
+            }
+        });
+        Ok(MountGuard {
+            unit: self,
+            armed: true,
+            _termination: termination,
+        })
+    }
+
+    fn mount_all(&self) -> Result<(), ChrootError> {
         if !self.is_authenticated() {
             return Err(ChrootError::Elevation(
                 ElevationError::AuthenticationRequired,
@@ -16,40 +160,49 @@ impl crate::chroot::core::ChrootUnit {
 
         log::info!("Mounting filesystems for chroot: {}", self.name);
 
+        if let Some(distfiles) = self.shared_distfiles.as_ref().filter(|distfiles| !distfiles.is_dir()) {
+            say!(
+                "{} The distfiles directory {} does not exist, it is not shared",
+                Symbol::Warning,
+                distfiles.display()
+            );
+        }
+
+        let active = self.active_mounts()?;
+        let mut targets = Vec::new();
+        for (mount_point, args, slave) in self.planned_mounts() {
+            let target = self.chroot_path.join(&mount_point);
+            if active.iter().any(|entry| entry.mount_point == target) {
+                log::info!("Reusing the filesystem already mounted at {}", target.display());
+                continue;
+            }
+            if mount_point == DISTFILES_MOUNT_POINT && !target.is_dir() {
+                let target = target.to_string_lossy();
+                self.execute_command_with_logging("mkdir", &["-p", &target], "Distfiles directory creation")?;
+            }
+            targets.push((target.to_string_lossy().into_owned(), args, slave));
+        }
+        if targets.is_empty() {
+            log::info!("All filesystems of chroot {} are already mounted", self.name);
+            return Ok(());
+        }
+
         // Prepare all mount commands to execute in batch
-        let proc_path = self.chroot_path.join("proc");
-        let sys_path = self.chroot_path.join("sys");
-        let dev_path = self.chroot_path.join("dev");
-        let dev_pts_path = self.chroot_path.join("dev/pts");
-        let dev_shm_path = self.chroot_path.join("dev/shm");
-
-        let mount_commands = vec![
-            // Mount proc
-            (
-                "mount",
-                vec!["-t", "proc", "/proc", proc_path.to_str().unwrap()],
-            ),
-            // Mount sys with rbind
-            ("mount", vec!["--rbind", "/sys", sys_path.to_str().unwrap()]),
-            // Mount dev with rbind
-            ("mount", vec!["--rbind", "/dev", dev_path.to_str().unwrap()]),
-            // Mount dev/pts with rbind
-            (
-                "mount",
-                vec!["--rbind", "/dev/pts", dev_pts_path.to_str().unwrap()],
-            ),
-            // Mount dev/shm with rbind
-            (
-                "mount",
-                vec!["--rbind", "/dev/shm", dev_shm_path.to_str().unwrap()],
-            ),
-            // Make sys slave
-            ("mount", vec!["--make-slave", sys_path.to_str().unwrap()]),
-            // Make dev slave
-            ("mount", vec!["--make-slave", dev_path.to_str().unwrap()]),
-        ];
+        let mut mount_commands: Vec<(&str, Vec<&str>)> = targets
+            .iter()
+            .map(|(target, args, _)| {
+                let args = args.iter().map(String::as_str).chain([target.as_str()]).collect();
+                ("mount", args)
+            })
+            .collect();
+        mount_commands.extend(
+            targets
+                .iter()
+                .filter(|(_, _, slave)| *slave)
+                .map(|(target, _, _)| ("mount", vec!["--make-slave", target.as_str()])),
+        );
 
-        let elevation = SHARED_ELEVATION.lock().unwrap();
+        let elevation = shared_elevation().lock().unwrap();
         let results = elevation
             .execute_batch_commands(mount_commands)
             .map_err(ChrootError::from)?;
@@ -65,34 +218,128 @@ impl crate::chroot::core::ChrootUnit {
             }
         }
 
+        drop(elevation);
+        if self.shared_distfiles.is_some() {
+            self.add_distfiles_hint();
+        }
+
         log::info!(
             "Successfully mounted all filesystems for chroot: {}",
             self.name
         );
-        Ok(self)
+        Ok(())
     }
 
-    /// Copies DNS resolution files
-    pub fn copy_dns_info(&self) -> Result<(), ChrootError> {
-        log::info!("Copy DNS information with cached elevation");
+    /// Filesystems to mount: the mount point relative to the chroot, the
+    /// `mount` arguments before it and whether it is made a slave
+    ///
+    /// The shared distfiles directory comes last, when it exists on the host.
+    fn planned_mounts(&self) -> Vec<(String, Vec<String>, bool)> {
+        let mut mounts: Vec<(String, Vec<String>, bool)> = CHROOT_MOUNTS
+            .iter()
+            .map(|(mount_point, args, slave)| {
+                (mount_point.to_string(), args.iter().map(|arg| arg.to_string()).collect(), *slave)
+            })
+            .collect();
 
-        let resolv_conf_src = Path::new("/etc/resolv.conf");
-        let resolv_conf_dst = self.chroot_path.join("etc/resolv.conf");
+        match &self.shared_distfiles {
+            Some(distfiles) if distfiles.is_dir() => mounts.push((
+                DISTFILES_MOUNT_POINT.to_string(),
+                vec!["--bind".to_string(), distfiles.to_string_lossy().into_owned()],
+                false,
+            )),
+            Some(distfiles) => log::debug!("The distfiles directory {} does not exist", distfiles.display()),
+            None => {}
+        }
+        mounts
+    }
 
-        if resolv_conf_src.exists() {
-            let resolv_conf_src_str = resolv_conf_src.to_str().unwrap();
-            let resolv_conf_dst_str = resolv_conf_dst.to_str().unwrap();
+    /// Point Portage at the shared distfiles in make.conf, once
+    ///
+    /// A make.conf that is missing, a directory, or already carries the
+    /// hint is left alone. A failure is only logged.
+    fn add_distfiles_hint(&self) {
+        let make_conf = self.chroot_path.join("etc/portage/make.conf");
+        let Ok(content) = fs::read_to_string(&make_conf) else {
+            log::debug!("No make.conf file to add the distfiles hint to: {}", make_conf.display());
+            return;
+        };
+        if content.contains(DISTFILES_HINT_MARKER) {
+            return;
+        }
 
-            let cp_args = vec![resolv_conf_src_str, resolv_conf_dst_str];
+        let source = self.shared_distfiles.as_deref().unwrap_or(Path::new("")).display();
+        let hint = format!(
+            "\n{DISTFILES_HINT_MARKER}, mounted from {source} on the host\nDISTDIR=\"/{DISTFILES_MOUNT_POINT}\"\n"
+        );
+        let make_conf = make_conf.to_string_lossy();
+        let appended = shared_elevation()
+            .lock()
+            .unwrap()
+            .piped_command("tee", &["-a", &make_conf])
+            .map_err(|e| e.to_string())
+            .and_then(|mut command| command.spawn().map_err(|e| e.to_string()))
+            .and_then(|mut child| {
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(hint.as_bytes()).map_err(|e| e.to_string())?;
+                }
+                let output = child.wait_with_output().map_err(|e| e.to_string())?;
+                if output.status.success() {
+                    Ok(())
+                } else {
+                    Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+                }
+            });
+        match appended {
+            Ok(()) => log::info!("Distfiles hint added to {make_conf}"),
+            Err(e) => log::warn!("Unable to add the distfiles hint to {make_conf}: {e}"),
+        }
+    }
 
-            self.execute_command_with_logging("cp", &cp_args, "DNS info copy")?;
-            log::info!("resolv.conf copied with cached elevation");
+    /// Copies the resolv.conf and hosts files of the host, and its CA bundle on request
+    ///
+    /// Files missing on the host are skipped with a warning. The resolv.conf
+    /// copied is the one [`host_resolv_conf`] picks.
+    pub fn copy_network_info(&self, copy_ca_certs: bool) -> Result<(), ChrootError> {
+        log::info!("Copy network information with cached elevation");
+
+        let mut files = vec![(host_resolv_conf(Path::new("/")), RESOLV_CONF)];
+        files.push((Some(Path::new(HOSTS_FILE).to_path_buf()), HOSTS_FILE));
+        if copy_ca_certs {
+            files.push((Some(Path::new(HOST_CA_BUNDLE).to_path_buf()), HOST_CA_BUNDLE));
+        }
+
+        for (source, file) in files {
+            // A dangling symlink does not exist either
+            let Some(source) = source.filter(|source| source.exists()) else {
+                say!("{} {file} does not exist on the host, it is not copied into the chroot", Symbol::Warning);
+                continue;
+            };
+            let source = source.to_string_lossy();
+            let destination = self.chroot_path.join(file.trim_start_matches('/'));
+            if let Some(parent) = destination.parent().filter(|parent| !parent.exists()) {
+                self.execute_command_with_logging("mkdir", &["-p", &parent.to_string_lossy()], "Directory creation")?;
+            }
+
+            // The source is dereferenced, such as the stub of systemd-resolved,
+            // and a symlink in the chroot is replaced instead of followed
+            self.execute_command_with_logging(
+                "cp",
+                &["-L", "--remove-destination", &source, &destination.to_string_lossy()],
+                &format!("{file} copy"),
+            )?;
+            log::info!("{source} copied to {file} with cached elevation");
         }
 
         Ok(())
     }
 
-    /// Unmount without blocking
+    /// Unmount the filesystems of the chroot, deepest first
+    ///
+    /// Nothing is unmounted lazily: a busy filesystem stays mounted, and
+    /// after a few attempts the processes keeping it busy are reported and
+    /// [`ChrootError::StillMounted`] lists what remains. See
+    /// [`ChrootUnit::detach_filesystems`] to detach them anyway.
     pub fn unmount_filesystems(&self) -> Result<&Self, ChrootError> {
         if !self.is_authenticated() {
             return Err(ChrootError::Elevation(
@@ -102,63 +349,468 @@ impl crate::chroot::core::ChrootUnit {
 
         log::info!("Cleaning up mount points for chroot: {}", self.name);
 
-        let elevation = SHARED_ELEVATION.lock().unwrap();
-        let chroot_path_str = self.chroot_path.to_string_lossy();
-
-        // Use a single command to unmount everything recursively with a lazy option
-        let unmount_result = elevation.execute_command(
-            "umount",
-            &[
-                "-l", // lazy unmount
-                "-R", // recursive
-                &chroot_path_str,
-            ],
-        );
-
-        match unmount_result {
-            Ok(output) => {
-                if output.status.success() {
-                    log::info!("✓ Successfully unmounted all filesystems");
-                } else {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    if stderr.contains("not mounted") {
-                        log::debug!("No filesystems were mounted");
-                    } else {
-                        log::warn!("Unmount warning: {stderr}");
+        for attempt in 1..=UNMOUNT_ATTEMPTS {
+            let remaining = self.active_mounts()?;
+            if remaining.is_empty() {
+                log::info!("Mount point cleanup completed");
+                return Ok(self);
+            }
+            if attempt > 1 {
+                log::debug!(
+                    "{} filesystem(s) still mounted in {}, retrying ({attempt}/{UNMOUNT_ATTEMPTS})",
+                    remaining.len(),
+                    self.chroot_path.display()
+                );
+                thread::sleep(UNMOUNT_RETRY_DELAY);
+            }
+            for mount in &remaining {
+                let mount_point = mount.mount_point.to_string_lossy();
+                match self.execute_elevated("umount", &[&mount_point]) {
+                    Ok(output) if !output.status.success() => {
+                        log::debug!("umount {mount_point}: {}", String::from_utf8_lossy(&output.stderr).trim());
                     }
+                    Ok(_) => {}
+                    Err(e) => log::debug!("umount {mount_point} failed: {e}"),
                 }
             }
-            Err(e) => {
-                log::warn!("Unmount command failed: {e}");
-            }
         }
 
-        // Additional cleanup with individual mount points if needed
-        let mount_points = ["dev/shm", "dev/pts", "dev", "sys", "proc"];
+        let remaining: Vec<PathBuf> = self.active_mounts()?.into_iter().map(|mount| mount.mount_point).collect();
+        if remaining.is_empty() {
+            log::info!("Mount point cleanup completed");
+            return Ok(self);
+        }
+        for process in self.busy_processes(&remaining) {
+            say!(
+                "{} Process {} ({}) keeps the chroot '{}' busy",
+                Symbol::Warning,
+                process.pid,
+                process.command,
+                self.name
+            );
+        }
+        Err(ChrootError::StillMounted(remaining))
+    }
 
-        for mount_point in mount_points {
-            let full_path = self.chroot_path.join(mount_point);
-            if full_path.exists() {
-                let path_str = full_path.to_string_lossy();
-                let _ = elevation.execute_command("umount", &["-l", &path_str]);
-            }
+    /// Unmount the filesystems, detaching the busy ones lazily
+    ///
+    /// A detached filesystem leaves the chroot at once but is only released
+    /// when the last process using it exits.
+    pub fn detach_filesystems(&self) -> Result<&Self, ChrootError> {
+        let remaining = match self.unmount_filesystems() {
+            Err(ChrootError::StillMounted(remaining)) => remaining,
+            result => return result,
+        };
+        for mount_point in &remaining {
+            say!("{} Detaching {} lazily", Symbol::Warning, mount_point.display());
+            let _ = self.execute_elevated("umount", &["-l", &mount_point.to_string_lossy()]);
         }
 
-        log::info!("Mount point cleanup completed");
-        Ok(self)
+        let remaining: Vec<PathBuf> = self.active_mounts()?.into_iter().map(|mount| mount.mount_point).collect();
+        if remaining.is_empty() {
+            Ok(self)
+        } else {
+            Err(ChrootError::StillMounted(remaining))
+        }
+    }
+
+    /// Check that the chroot directory lies strictly inside `base_dir`
+    ///
+    /// Both paths are canonicalized when they exist, so that a symbolic
+    /// link cannot point the check elsewhere.
+    pub fn ensure_inside(&self, base_dir: &Path) -> Result<(), ChrootError> {
+        let path = fs::canonicalize(&self.chroot_path).unwrap_or_else(|_| self.chroot_path.clone());
+        let base = fs::canonicalize(base_dir).unwrap_or_else(|_| base_dir.to_path_buf());
+        if path != base && path.starts_with(&base) {
+            Ok(())
+        } else {
+            Err(ChrootError::OutsideBaseDir {
+                path,
+                base_dir: base,
+            })
+        }
+    }
+
+    /// Check that nothing at or below the chroot directory is in the mount `table`
+    fn ensure_unmounted(&self, table: &[MountEntry]) -> Result<(), ChrootError> {
+        let remaining = mounts::mounts_under(table, &self.chroot_path);
+        if remaining.is_empty() {
+            return Ok(());
+        }
+        Err(ChrootError::StillMounted(
+            remaining.into_iter().map(|mount| mount.mount_point).collect(),
+        ))
     }
 
     /// Cleans the chroot (unmounts and optionally deletes)
-    pub fn cleanup(&self, remove_directory: bool) -> Result<(), ChrootError> {
+    ///
+    /// With `remove_from`, the chroot directory is deleted afterwards, only
+    /// when it lies inside that base directory and nothing below it is
+    /// still mounted.
+    pub fn cleanup(&self, remove_from: Option<&Path>) -> Result<(), ChrootError> {
         log::info!("Cleaning the chroot");
 
+        if let Some(base_dir) = remove_from {
+            self.ensure_inside(base_dir)?;
+        }
         self.unmount_filesystems()?;
 
-        if remove_directory && self.chroot_path.exists() {
+        if remove_from.is_some() && self.chroot_path.exists() {
+            // Never delete through a filesystem that is still mounted
+            self.ensure_unmounted(&mounts::read_mount_table()?)?;
             fs::remove_dir_all(&self.chroot_path)?;
             log::info!("Deleted chroot directory: {:?}", self.chroot_path);
         }
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Check that the chroot can be renamed, returning its new path
+    ///
+    /// Both names must be valid chroot names, and the chroot directory must
+    /// lie inside the chroot base directory.
+    pub fn check_rename(&self, new_name: &str, config: &Config) -> Result<PathBuf, ChrootError> {
+        Self::validate_name(&self.name)?;
+        Self::validate_name(new_name)?;
+        self.ensure_inside(&config.chroot_base_dir)?;
+
+        if let Some(mount) = self.active_mounts()?.first() {
+            return Err(ChrootError::MountedDuringRename(mount.mount_point.clone()));
+        }
+
+        let new_path = config.chroot_base_dir.join(new_name);
+        if new_path.exists() {
+            return Err(ChrootError::NameTaken(new_name.to_string()));
+        }
+        Ok(new_path)
+    }
+
+    /// Rename the chroot directory, returning the unit under its new name
+    ///
+    /// The stage3 tree is root-owned, so the directory is moved with elevated
+    /// privileges. The name recorded in the metadata is updated.
+    pub fn rename(&self, new_name: &str, config: &Config) -> Result<Self, ChrootError> {
+        let new_path = self.check_rename(new_name, config)?;
+
+        let source = self.chroot_path.to_string_lossy();
+        let destination = new_path.to_string_lossy();
+        // -T: never move the chroot into a directory created meanwhile
+        self.execute_command_with_logging("mv", &["-T", &source, &destination], "Chroot rename")?;
+        log::info!("Renamed chroot {} to {new_name}", self.name);
+
+        let mut renamed = Self {
+            name: new_name.to_string(),
+            chroot_path: new_path,
+            ..self.clone()
+        };
+        renamed.record_name_in_metadata();
+        Ok(renamed)
+    }
+
+    /// Check that the chroot can be copied to `dest`, returning the path of the copy
+    pub fn check_clone(&self, dest: &str, config: &Config) -> Result<PathBuf, ChrootError> {
+        Self::validate_name(dest)?;
+
+        if let Some(mount) = self.active_mounts()?.first() {
+            return Err(ChrootError::MountedDuringClone(mount.mount_point.clone()));
+        }
+
+        let dest_path = config.chroot_base_dir.join(dest);
+        if dest_path.exists() {
+            return Err(ChrootError::NameTaken(dest.to_string()));
+        }
+        Ok(dest_path)
+    }
+
+    /// Copy the chroot to `dest` in the chroot base directory
+    ///
+    /// The copy keeps ownership and modes, and is a reflink copy on
+    /// filesystems that support it (btrfs, xfs). The metadata files are part
+    /// of the tree, so the clone reports the same profile; its recorded name
+    /// is updated. A failed copy is removed.
+    pub fn clone_to(&self, dest: &str, config: &Config) -> Result<Self, ChrootError> {
+        let dest_path = self.check_clone(dest, config)?;
+
+        let source = self.chroot_path.to_string_lossy();
+        let destination = dest_path.to_string_lossy();
+        let copied = self.execute_command_with_logging(
+            "cp",
+            &["-a", "--reflink=auto", "-T", &source, &destination],
+            "Chroot copy",
+        );
+        if let Err(e) = copied {
+            if dest_path.exists() {
+                if let Err(cleanup) = self.execute_elevated("rm", &["-rf", &destination]) {
+                    log::warn!("Failed to remove the partial copy {destination}: {cleanup}");
+                }
+            }
+            return Err(e);
+        }
+        log::info!("Cloned chroot {} to {dest}", self.name);
+
+        let mut clone = Self {
+            name: dest.to_string(),
+            chroot_path: dest_path,
+            ..self.clone()
+        };
+        clone.record_name_in_metadata();
+        Ok(clone)
+    }
+
+    /// Whether the filesystems the chroot needs are mounted
+    ///
+    /// Reported as unmounted when the mount table cannot be read.
+    pub fn mount_state(&self) -> MountState {
+        let expected: Vec<PathBuf> = self
+            .planned_mounts()
+            .iter()
+            .map(|(mount_point, _, _)| self.chroot_path.join(mount_point))
+            .collect();
+        match self.active_mounts() {
+            Ok(active) => MountState::of(&expected, &active),
+            Err(e) => {
+                log::debug!("Unable to read the mount table: {e}");
+                MountState::Unmounted
+            }
+        }
+    }
+
+    /// List the filesystems currently mounted inside the chroot, deepest first
+    pub fn active_mounts(&self) -> Result<Vec<MountEntry>, ChrootError> {
+        let table = mounts::read_mount_table()?;
+        Ok(mounts::mounts_under(&table, &self.chroot_path))
+    }
+
+    /// Compute the apparent size of the chroot tree in bytes
+    ///
+    /// Mounted filesystems are not traversed, and entries that cannot be read
+    /// (root-only directories) are skipped, so the result is a lower bound
+    /// when running unprivileged.
+    pub fn disk_usage(&self) -> u64 {
+        fn walk(path: &Path, skipped: &[PathBuf]) -> u64 {
+            let Ok(entries) = fs::read_dir(path) else {
+                return 0;
+            };
+
+            entries
+                .filter_map(|e| e.ok())
+                .map(|entry| match entry.metadata() {
+                    Ok(metadata) if metadata.is_dir() => {
+                        let entry_path = entry.path();
+                        if skipped.contains(&entry_path) {
+                            0
+                        } else {
+                            walk(&entry_path, skipped)
+                        }
+                    }
+                    Ok(metadata) => metadata.len(),
+                    Err(_) => 0,
+                })
+                .sum()
+        }
+
+        let mount_points: Vec<PathBuf> = self
+            .active_mounts()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| entry.mount_point)
+            .collect();
+
+        walk(&self.chroot_path, &mount_points)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_stay_inside_the_base_directory() {
+        let base = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::create_dir_all(base.path().join("gentoo/etc")).unwrap();
+        fs::create_dir(base.path().join("taken")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), base.path().join("elsewhere")).unwrap();
+        let config = Config { chroot_base_dir: base.path().to_path_buf(), ..Config::default() };
+
+        let unit = ChrootUnit::load(&base.path().join("gentoo")).unwrap();
+        assert_eq!(unit.check_rename("dev", &config).unwrap(), base.path().join("dev"));
+        assert!(matches!(unit.check_rename("taken", &config), Err(ChrootError::NameTaken(_))));
+        for name in ["..", "/etc", "a/b"] {
+            assert!(matches!(unit.check_rename(name, &config), Err(ChrootError::InvalidName(_))), "{name}");
+        }
+
+        let escaping = ChrootUnit { name: "..".to_string(), chroot_path: base.path().join(".."), ..unit.clone() };
+        assert!(matches!(escaping.check_rename("dev", &config), Err(ChrootError::InvalidName(_))));
+
+        let linked = ChrootUnit::load(&base.path().join("elsewhere")).unwrap();
+        assert!(matches!(linked.check_rename("dev", &config), Err(ChrootError::OutsideBaseDir { .. })));
+    }
+
+    #[test]
+    fn deletion_stays_strictly_inside_the_base_directory() {
+        let base = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::create_dir_all(base.path().join("gentoo/etc")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), base.path().join("elsewhere")).unwrap();
+        let unit = |path: PathBuf| ChrootUnit {
+            name: "gentoo".to_string(),
+            chroot_path: path,
+            profile: None,
+            metadata: None,
+            shared_distfiles: None,
+        };
+
+        unit(base.path().join("gentoo")).ensure_inside(base.path()).unwrap();
+        // Not created yet
+        unit(base.path().join("new")).ensure_inside(base.path()).unwrap();
+        for path in [base.path().to_path_buf(), base.path().join("elsewhere"), base.path().join("gentoo/../..")] {
+            assert!(
+                matches!(unit(path.clone()).ensure_inside(base.path()), Err(ChrootError::OutsideBaseDir { .. })),
+                "{}",
+                path.display()
+            );
+        }
+
+        // Refused before anything is unmounted or deleted
+        let linked = unit(base.path().join("elsewhere"));
+        assert!(matches!(linked.cleanup(Some(base.path())), Err(ChrootError::OutsideBaseDir { .. })));
+        assert!(outside.path().exists());
+    }
+
+    #[test]
+    fn a_chroot_with_a_remaining_mount_is_not_deleted() {
+        let unit = ChrootUnit {
+            name: "gentoo".to_string(),
+            chroot_path: PathBuf::from("/var/lib/chroots/gentoo"),
+            profile: None,
+            metadata: None,
+            shared_distfiles: None,
+        };
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+30 22 0:5 / /var/lib/chroots/gentoo/dev rw,nosuid master:2 - devtmpfs devtmpfs rw
+31 30 0:20 / /var/lib/chroots/gentoo/dev/pts rw,nosuid master:3 - devpts devpts rw
+32 22 0:21 / /var/lib/chroots/gentoo-old/proc rw - proc proc rw
+";
+        let table = mounts::parse_mountinfo(mountinfo);
+        match unit.ensure_unmounted(&table) {
+            Err(ChrootError::StillMounted(remaining)) => assert_eq!(
+                remaining,
+                [
+                    PathBuf::from("/var/lib/chroots/gentoo/dev/pts"),
+                    PathBuf::from("/var/lib/chroots/gentoo/dev"),
+                ]
+            ),
+            other => panic!("{other:?}"),
+        }
+
+        // Only the root and the mounts of a sibling chroot are left
+        let mut table = table;
+        table.retain(|entry| !entry.mount_point.starts_with("/var/lib/chroots/gentoo/"));
+        unit.ensure_unmounted(&table).unwrap();
+    }
+
+    #[test]
+    fn an_unmounted_chroot_inside_the_base_directory_is_deleted() {
+        // Unmounting goes through sudo otherwise, which may ask for a password
+        if !nix::unistd::geteuid().is_root() {
+            return;
+        }
+        let base = tempfile::tempdir().unwrap();
+        fs::create_dir_all(base.path().join("gentoo/etc")).unwrap();
+        let unit = ChrootUnit::load(&base.path().join("gentoo")).unwrap();
+
+        // Without a base directory, only unmounted
+        unit.cleanup(None).unwrap();
+        assert!(unit.chroot_path.exists());
+        unit.cleanup(Some(base.path())).unwrap();
+        assert!(!unit.chroot_path.exists());
+        assert!(base.path().exists());
+    }
+
+    /// Chroot with a tmpfs mounted on its `proc`, `None` when mounting is not possible
+    ///
+    /// Only a harmless tmpfs is mounted, never the host filesystems that
+    /// [`ChrootUnit::mount_filesystems`] binds.
+    fn chroot_with_a_mount(base: &Path) -> Option<ChrootUnit> {
+        // Unmounting goes through sudo otherwise, which may ask for a password
+        if !nix::unistd::geteuid().is_root() {
+            return None;
+        }
+        let proc = base.join("gentoo/proc");
+        fs::create_dir_all(&proc).unwrap();
+        let mounted = std::process::Command::new("mount")
+            .args(["-t", "tmpfs", "chrootmanager-test"])
+            .arg(&proc)
+            .status()
+            .is_ok_and(|status| status.success());
+        mounted.then(|| ChrootUnit::load(&base.join("gentoo")).unwrap())
+    }
+
+    fn guard(unit: &ChrootUnit) -> MountGuard<'_> {
+        MountGuard {
+            unit,
+            armed: true,
+            _termination: signals::on_termination("test mounts", || {}),
+        }
+    }
+
+    #[test]
+    fn a_guard_dropped_by_a_panic_unmounts() {
+        let base = tempfile::tempdir().unwrap();
+        let Some(unit) = chroot_with_a_mount(base.path()) else {
+            return;
+        };
+        assert_eq!(unit.active_mounts().unwrap().len(), 1);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = guard(&unit);
+            panic!("simulated failure while the filesystems are mounted");
+        }));
+        assert!(result.is_err());
+        assert!(unit.active_mounts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_guard_unmounts_explicitly_or_leaves_the_mounts() {
+        let base = tempfile::tempdir().unwrap();
+        let Some(unit) = chroot_with_a_mount(base.path()) else {
+            return;
+        };
+
+        guard(&unit).leave_mounted();
+        assert_eq!(unit.active_mounts().unwrap().len(), 1);
+        guard(&unit).unmount().unwrap();
+        assert!(unit.active_mounts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn the_resolv_conf_of_the_host_is_picked_past_the_resolved_stub() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        fs::create_dir_all(root.join("etc")).unwrap();
+        let resolv_conf = root.join("etc/resolv.conf");
+
+        // Missing, then a regular file
+        assert_eq!(host_resolv_conf(root), None);
+        fs::write(&resolv_conf, "nameserver 192.0.2.1\n").unwrap();
+        assert_eq!(host_resolv_conf(root), Some(resolv_conf.clone()));
+
+        // A link elsewhere than systemd-resolved is kept
+        fs::create_dir_all(root.join("etc/resolvconf/run")).unwrap();
+        fs::rename(&resolv_conf, root.join("etc/resolvconf/run/resolv.conf")).unwrap();
+        std::os::unix::fs::symlink("resolvconf/run/resolv.conf", &resolv_conf).unwrap();
+        assert_eq!(host_resolv_conf(root), Some(resolv_conf.clone()));
+
+        // The stub of systemd-resolved, without then with its upstream file
+        let resolved = root.join(RESOLVED_RUNTIME_DIR);
+        fs::create_dir_all(&resolved).unwrap();
+        fs::write(resolved.join("stub-resolv.conf"), "nameserver 127.0.0.53\n").unwrap();
+        fs::remove_file(&resolv_conf).unwrap();
+        std::os::unix::fs::symlink("../run/systemd/resolve/stub-resolv.conf", &resolv_conf).unwrap();
+        assert_eq!(host_resolv_conf(root), Some(resolv_conf.clone()));
+
+        fs::write(resolved.join("resolv.conf"), "nameserver 192.0.2.1\n").unwrap();
+        assert_eq!(host_resolv_conf(root), Some(resolved.join("resolv.conf")));
+    }
+}
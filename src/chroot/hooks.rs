@@ -0,0 +1,53 @@
+//! Post-create provisioning hooks
+//!
+//! A hook is a script on the host. It is copied into `/tmp` of the chroot
+//! and run there as root, with the standard streams of chrootmanager, then
+//! removed. The shebang of the script picks its interpreter, `/bin/sh` runs
+//! scripts without one.
+
+use crate::chroot::core::ChrootUnit;
+use crate::elevation::shared_elevation;
+use crate::error::ChrootError;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+impl ChrootUnit {
+    /// Run a host script inside the chroot, whose filesystems must be mounted
+    ///
+    /// Fails with `HookFailed` when the script exits with a non-zero status.
+    pub fn run_hook(&self, script: &Path) -> Result<(), ChrootError> {
+        let content = fs::read(script).map_err(|_| ChrootError::HookNotFound(script.to_path_buf()))?;
+        let file_name = script
+            .file_name()
+            .map(|name| format!("chrootmanager-hook-{}", name.to_string_lossy()))
+            .ok_or_else(|| ChrootError::HookNotFound(script.to_path_buf()))?;
+
+        let host_path = self.prepare_chroot_temp_file(&file_name, &content)?;
+        let status = fs::set_permissions(&host_path, fs::Permissions::from_mode(0o755))
+            .map_err(ChrootError::Io)
+            .and_then(|()| {
+                log::info!("Running the hook {} in chroot: {}", script.display(), self.name);
+                let chroot_path = self.chroot_path.to_string_lossy();
+                let hook_path = format!("/tmp/{file_name}");
+                // The lock is released before the hook starts so that cleanup can still unmount
+                let command = shared_elevation()
+                    .lock()
+                    .unwrap()
+                    .interactive_command("chroot", &[&chroot_path, &hook_path]);
+                command
+                    .map_err(ChrootError::Elevation)
+                    .and_then(|mut command| command.status().map_err(ChrootError::Io))
+            });
+        self.cleanup_chroot_temp_file(&host_path);
+
+        let status = status?;
+        if !status.success() {
+            return Err(ChrootError::HookFailed {
+                hook: script.to_path_buf(),
+                status,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! Timezone, locale and hostname of new chroots
+//!
+//! A stage3 comes with UTC, the POSIX locale and no hostname. The values of
+//! the configuration are written into the usual Gentoo files:
+//!
+//! - timezone: `/etc/timezone`, and `/etc/localtime` linked to its zoneinfo
+//! - locale: appended to `/etc/locale.gen`, then `LANG` set in
+//!   `/etc/locale.conf` (systemd) or `/etc/env.d/02locale` (OpenRC)
+//! - hostname: `/etc/hostname`, from a scheme where `{{name}}` is the chroot name
+//!
+//! The locale still has to be generated with `locale-gen` inside the chroot.
+
+use crate::chroot::core::ChrootUnit;
+use crate::chroot::metadata::write_elevated;
+use crate::config::Config;
+use crate::elevation::shared_elevation;
+use crate::error::ChrootError;
+use std::fs;
+use std::path::Path;
+
+const TIMEZONE_FILE: &str = "etc/timezone";
+const LOCALTIME_LINK: &str = "etc/localtime";
+const ZONEINFO_DIR: &str = "usr/share/zoneinfo";
+const LOCALE_GEN_FILE: &str = "etc/locale.gen";
+const SYSTEMD_LOCALE_FILE: &str = "etc/locale.conf";
+const OPENRC_LOCALE_FILE: &str = "etc/env.d/02locale";
+const HOSTNAME_FILE: &str = "etc/hostname";
+
+/// Timezone, locale and hostname scheme applied to new chroots
+#[derive(Debug, Clone, PartialEq)]
+pub struct Localization {
+    /// Zoneinfo name, such as "Europe/Paris"
+    pub timezone: Option<String>,
+    /// Locale name with its charset, such as "en_US.UTF-8"
+    pub locale: Option<String>,
+    /// Hostname, where `{{name}}` stands for the chroot name
+    pub hostname: Option<String>,
+}
+
+impl Localization {
+    /// Values of the configuration, `None` when none is set
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let localization = Self {
+            timezone: config.chroot_timezone.clone(),
+            locale: config.chroot_locale.clone(),
+            hostname: config.chroot_hostname.clone(),
+        };
+        (localization.timezone.is_some() || localization.locale.is_some() || localization.hostname.is_some())
+            .then_some(localization)
+    }
+}
+
+/// Line of `/etc/locale.gen` for a locale such as "en_US.UTF-8"
+fn locale_gen_line(locale: &str) -> String {
+    let charset = locale.split_once('.').map_or("UTF-8", |(_, charset)| charset);
+    format!("{locale} {charset}")
+}
+
+/// Value of `LANG` in a locale file
+fn read_lang(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok()?.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("LANG=")?;
+        Some(value.trim_matches('"').to_string())
+    })
+}
+
+impl ChrootUnit {
+    /// Whether the chroot runs systemd rather than OpenRC
+    fn uses_systemd(&self) -> bool {
+        self.profile
+            .as_ref()
+            .is_some_and(|profile| profile.profile().contains("systemd"))
+    }
+
+    /// Write the timezone, locale and hostname into the chroot
+    pub fn localize(&self, localization: &Localization) -> Result<(), ChrootError> {
+        let elevation = shared_elevation().lock().unwrap();
+
+        if let Some(timezone) = &localization.timezone {
+            if timezone.is_empty() || timezone.starts_with('/') || timezone.split('/').any(|part| part == "..") {
+                return Err(ChrootError::InvalidTimezone(timezone.clone()));
+            }
+            if !self.chroot_path.join(ZONEINFO_DIR).join(timezone).exists() {
+                log::warn!("The chroot has no zoneinfo for {timezone}, /etc/localtime will be dangling");
+            }
+            write_elevated(&self.chroot_path, TIMEZONE_FILE, &format!("{timezone}\n"), &elevation)?;
+            let target = format!("../{ZONEINFO_DIR}/{timezone}");
+            let link = self.chroot_path.join(LOCALTIME_LINK);
+            let output = elevation.execute_command("ln", &["-sfn", &target, &link.to_string_lossy()])?;
+            if !output.status.success() {
+                return Err(ChrootError::Command(format!(
+                    "Linking {} failed: {}",
+                    link.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+
+        if let Some(locale) = &localization.locale {
+            let line = locale_gen_line(locale);
+            let mut locale_gen = fs::read_to_string(self.chroot_path.join(LOCALE_GEN_FILE)).unwrap_or_default();
+            if !locale_gen.lines().any(|existing| existing.trim() == line) {
+                if !locale_gen.is_empty() && !locale_gen.ends_with('\n') {
+                    locale_gen.push('\n');
+                }
+                locale_gen.push_str(&line);
+                locale_gen.push('\n');
+                write_elevated(&self.chroot_path, LOCALE_GEN_FILE, &locale_gen, &elevation)?;
+            }
+            let locale_file = if self.uses_systemd() { SYSTEMD_LOCALE_FILE } else { OPENRC_LOCALE_FILE };
+            write_elevated(&self.chroot_path, locale_file, &format!("LANG=\"{locale}\"\n"), &elevation)?;
+        }
+
+        if let Some(scheme) = &localization.hostname {
+            let hostname = scheme.replace("{{name}}", &self.name);
+            write_elevated(&self.chroot_path, HOSTNAME_FILE, &format!("{hostname}\n"), &elevation)?;
+        }
+
+        Ok(())
+    }
+
+    /// Timezone of the chroot, from `/etc/timezone` or the `/etc/localtime` link
+    pub fn timezone(&self) -> Option<String> {
+        if let Ok(content) = fs::read_to_string(self.chroot_path.join(TIMEZONE_FILE)) {
+            return Some(content.trim().to_string()).filter(|timezone| !timezone.is_empty());
+        }
+        let target = fs::read_link(self.chroot_path.join(LOCALTIME_LINK)).ok()?;
+        let target = target.to_string_lossy();
+        let (_, timezone) = target.split_once("zoneinfo/")?;
+        Some(timezone.to_string())
+    }
+
+    /// `LANG` of the chroot, unset for the POSIX locale
+    pub fn locale(&self) -> Option<String> {
+        read_lang(&self.chroot_path.join(SYSTEMD_LOCALE_FILE))
+            .or_else(|| read_lang(&self.chroot_path.join(OPENRC_LOCALE_FILE)))
+    }
+
+    /// Hostname written in `/etc/hostname`
+    pub fn hostname(&self) -> Option<String> {
+        let content = fs::read_to_string(self.chroot_path.join(HOSTNAME_FILE)).ok()?;
+        Some(content.trim().to_string()).filter(|hostname| !hostname.is_empty())
+    }
+}
@@ -0,0 +1,481 @@
+//! Metadata recorded inside each chroot
+//!
+//! Layouts, oldest first:
+//!
+//! - version 0: `/etc/arch-chroot-profile` holding a bare "arch-profile" string
+//! - version 1: `/etc/chrootmanager.toml`, which also records the version of
+//!   chrootmanager that created the chroot and the stage3 it was extracted from,
+//!   with its SHA256, build timestamp and mirror when known
+//!
+//! `/etc/chrootmanager.toml` is a stable interface for external tools. Every
+//! field except `metadata_version` is optional, fields are only ever added
+//! within a version, and keys unknown to chrootmanager (annotations of other
+//! tools) are kept when the file is rewritten. An incompatible change bumps
+//! [`METADATA_VERSION`] and comes with a migration.
+//!
+//! ```toml
+//! metadata_version = 1
+//! name = "devbox"
+//! created_by = "chrootmanager 0.1.0"
+//! created_at = "2025-01-31T10:12:00+01:00"
+//! architecture = "amd64"
+//! profile = "openrc"
+//! stage3 = "stage3-amd64-openrc-20250126T170321Z.tar.xz"
+//! stage3_sha256 = "4c5e4d1b0f0e8a2b6f4d3b2f1e0d9c8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d"
+//! release = "20250126T170321Z"
+//! mirror = "https://distfiles.gentoo.org"
+//! portage_synced_at = "2025-01-31T10:15:42+01:00"
+//!
+//! [extraction]
+//! preserve_owner = true
+//! preserve_xattrs = true
+//! ```
+//!
+//! The legacy file is left in place by migrations so that older versions of
+//! chrootmanager can still read the profile.
+
+use crate::chroot::archive::ExtractionOptions;
+use crate::elevation::SecureElevation;
+use crate::error::ChrootError;
+use crate::profile::selected::SelectedProfile;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Layout written by this version of chrootmanager
+pub const METADATA_VERSION: u32 = 1;
+
+/// Metadata file, relative to the chroot root
+pub const METADATA_FILE: &str = "etc/chrootmanager.toml";
+
+/// Metadata file of version 0, relative to the chroot root
+pub const LEGACY_PROFILE_FILE: &str = "etc/arch-chroot-profile";
+
+/// Key of the layout version, the only mandatory field
+const VERSION_KEY: &str = "metadata_version";
+
+/// Keys of the fields of [`ChrootMetadata`], any other key is kept in `extra`
+const KNOWN_KEYS: &[&str] = &[
+    VERSION_KEY,
+    "name",
+    "created_by",
+    "created_at",
+    "architecture",
+    "profile",
+    "stage3",
+    "stage3_sha256",
+    "release",
+    "mirror",
+    "extraction",
+    "portage_synced_at",
+];
+
+/// Content of the chroot metadata, whatever its version
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChrootMetadata {
+    pub metadata_version: u32,
+    /// Name of the chroot, which is also its directory name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Version of chrootmanager that created the chroot (e.g. "chrootmanager 0.1.0")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Filename of the stage3 archive the chroot was extracted from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage3: Option<String>,
+    /// SHA256 of the stage3 archive, unknown when it was only checked with another algorithm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage3_sha256: Option<String>,
+    /// Build timestamp of the stage3 (e.g. "20250126T170321Z")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<String>,
+    /// Mirror the stage3 was downloaded from, unknown when it came from the cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<String>,
+    /// How the tree was extracted, unknown for chroots created before it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extraction: Option<ExtractionOptions>,
+    /// Last successful sync of the Portage tree by chrootmanager
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub portage_synced_at: Option<DateTime<Local>>,
+    /// Keys unknown to this version, written back unchanged
+    #[serde(skip)]
+    pub extra: toml::Table,
+}
+
+impl ChrootMetadata {
+    /// Metadata of version `metadata_version` with every optional field unset
+    fn empty(metadata_version: u32) -> Self {
+        Self {
+            metadata_version,
+            name: None,
+            created_by: None,
+            created_at: None,
+            architecture: None,
+            profile: None,
+            stage3: None,
+            stage3_sha256: None,
+            release: None,
+            mirror: None,
+            extraction: None,
+            portage_synced_at: None,
+            extra: toml::Table::new(),
+        }
+    }
+
+    /// Metadata of a chroot created now with the given profile
+    pub fn new(name: &str, profile: &SelectedProfile) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            created_by: Some(current_creator()),
+            created_at: Some(Local::now()),
+            architecture: Some(profile.architecture.clone()),
+            profile: Some(profile.profile.clone()),
+            ..Self::empty(METADATA_VERSION)
+        }
+    }
+
+    /// Parse the content of a version 0 profile file ("arch-profile")
+    pub fn from_legacy(content: &str) -> Option<Self> {
+        let (architecture, profile) = content.trim().split_once('-')?;
+        Some(Self {
+            architecture: Some(architecture.to_string()),
+            profile: Some(profile.to_string()),
+            ..Self::empty(0)
+        })
+    }
+
+    /// Guess the metadata of a chroot from its Portage profile
+    ///
+    /// `make_profile` is the target of `/etc/portage/make.profile`, e.g.
+    /// `/var/db/repos/gentoo/profiles/default/linux/amd64/23.0/systemd`. The
+    /// sub-profiles below the release become the stage3 profile name, with
+    /// "openrc" implied when no init system is named.
+    pub fn from_make_profile(make_profile: &Path) -> Option<Self> {
+        let components: Vec<String> = make_profile
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let linux = components.iter().position(|component| component == "linux")?;
+        let architecture = components.get(linux + 1)?.clone();
+        // Skip the release directory (e.g. "23.0")
+        let mut parts: Vec<String> = components.iter().skip(linux + 3).cloned().collect();
+        if !parts.iter().any(|part| part == "systemd" || part == "openrc") {
+            parts.push("openrc".to_string());
+        }
+
+        Some(Self {
+            architecture: Some(architecture),
+            profile: Some(parts.join("-")),
+            ..Self::empty(METADATA_VERSION)
+        })
+    }
+
+    /// Parse the content of the metadata file, failing on any invalid field
+    ///
+    /// chrootmanager itself reads with [`Self::from_toml_lenient`]; this is
+    /// meant for external tools using the library.
+    #[allow(dead_code)]
+    pub fn from_toml(content: &str) -> Result<Self, ChrootError> {
+        let (known, extra) = split_known_keys(parse_table(content)?);
+        let metadata: Self = known
+            .try_into()
+            .map_err(|e| ChrootError::InvalidMetadata(e.to_string()))?;
+        Ok(Self { extra, ..metadata })
+    }
+
+    /// Parse the content of the metadata file, dropping the invalid fields
+    ///
+    /// Returns the metadata along with the keys that were dropped. Only a file
+    /// that is not TOML or has no valid `metadata_version` is an error.
+    pub fn from_toml_lenient(content: &str) -> Result<(Self, Vec<String>), ChrootError> {
+        let (known, extra) = split_known_keys(parse_table(content)?);
+        if let Ok(metadata) = known.clone().try_into::<Self>() {
+            return Ok((Self { extra, ..metadata }, Vec::new()));
+        }
+
+        let version = known
+            .get(VERSION_KEY)
+            .ok_or_else(|| ChrootError::InvalidMetadata(format!("missing {VERSION_KEY}")))?;
+        let mut accepted = toml::Table::new();
+        accepted.insert(VERSION_KEY.to_string(), version.clone());
+        let mut metadata: Self = accepted
+            .clone()
+            .try_into()
+            .map_err(|e| ChrootError::InvalidMetadata(format!("{VERSION_KEY}: {e}")))?;
+
+        // Add the fields one at a time, dropping those that do not parse
+        let mut dropped = Vec::new();
+        for (key, value) in known.into_iter().filter(|(key, _)| key != VERSION_KEY) {
+            let mut candidate = accepted.clone();
+            candidate.insert(key.clone(), value);
+            match candidate.clone().try_into::<Self>() {
+                Ok(parsed) => {
+                    metadata = parsed;
+                    accepted = candidate;
+                }
+                Err(_) => dropped.push(key),
+            }
+        }
+        Ok((Self { extra, ..metadata }, dropped))
+    }
+
+    /// Serialize the metadata, followed by the keys unknown to this version
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        let mut table = toml::Table::try_from(self)?;
+        for (key, value) in &self.extra {
+            table.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        toml::to_string_pretty(&table)
+    }
+
+    /// Profile of the chroot, when both parts are known
+    pub fn selected_profile(&self) -> Option<SelectedProfile> {
+        match (&self.architecture, &self.profile) {
+            (Some(architecture), Some(profile)) => {
+                Some(SelectedProfile::new(architecture.clone(), profile.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the metadata predates the current layout
+    pub fn is_outdated(&self) -> bool {
+        self.metadata_version < METADATA_VERSION
+    }
+
+    /// Whether the metadata was written by a newer chrootmanager
+    pub fn is_unsupported(&self) -> bool {
+        self.metadata_version > METADATA_VERSION
+    }
+
+    /// Upgrade the metadata to the current layout, one version at a time
+    ///
+    /// Unsupported (newer) metadata is returned unchanged.
+    pub fn migrate(mut self) -> Self {
+        while self.metadata_version < METADATA_VERSION {
+            self = match self.metadata_version {
+                // Same fields, the creator of a version 0 chroot is unknown
+                0 => Self {
+                    metadata_version: 1,
+                    ..self
+                },
+                _ => unreachable!("no migration from metadata version {}", self.metadata_version),
+            };
+        }
+        self
+    }
+
+    /// Write the metadata file of the chroot at `chroot_path`
+    ///
+    /// The legacy profile file read by older versions is written as well when
+    /// the profile is known. Files of a stage3 tree are root-owned, so both are
+    /// moved in place with elevated privileges.
+    pub(crate) fn save(&self, chroot_path: &Path, elevation: &SecureElevation) -> Result<(), ChrootError> {
+        if let (Some(architecture), Some(profile)) = (&self.architecture, &self.profile) {
+            write_elevated(chroot_path, LEGACY_PROFILE_FILE, &format!("{architecture}-{profile}"), elevation)?;
+        }
+        let content = self
+            .to_toml()
+            .map_err(|e| ChrootError::InvalidMetadata(e.to_string()))?;
+        write_elevated(chroot_path, METADATA_FILE, &content, elevation)
+    }
+}
+
+fn parse_table(content: &str) -> Result<toml::Table, ChrootError> {
+    toml::from_str(content).map_err(|e| ChrootError::InvalidMetadata(e.to_string()))
+}
+
+/// Separate the keys of the metadata from those added by other tools
+fn split_known_keys(table: toml::Table) -> (toml::Table, toml::Table) {
+    table
+        .into_iter()
+        .partition(|(key, _)| KNOWN_KEYS.contains(&key.as_str()))
+}
+
+/// Write a root-owned file inside the chroot through a temporary file
+pub(crate) fn write_elevated(
+    chroot_path: &Path,
+    relative_path: &str,
+    content: &str,
+    elevation: &SecureElevation,
+) -> Result<(), ChrootError> {
+    let target = chroot_path.join(relative_path);
+    let file_name = Path::new(relative_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp_file = format!("/tmp/{file_name}-{}", std::process::id());
+
+    fs::write(&temp_file, content).map_err(ChrootError::Io)?;
+    let output = elevation.execute_command("mv", &[&temp_file, &target.to_string_lossy()])?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&temp_file);
+        return Err(ChrootError::Command(format!(
+            "Writing {} failed: {}",
+            target.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    log::debug!("{} written", target.display());
+    Ok(())
+}
+
+/// Value of `created_by` for chroots created by this binary
+pub fn current_creator() -> String {
+    format!("chrootmanager {}", env!("CARGO_PKG_VERSION"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroot::ChrootUnit;
+
+    #[test]
+    fn legacy_profile_file_migrates_to_the_current_layout() {
+        let legacy = ChrootMetadata::from_legacy("amd64-openrc-splitusr\n").unwrap();
+        assert_eq!(legacy.metadata_version, 0);
+        assert!(legacy.is_outdated());
+
+        let migrated = legacy.migrate();
+        assert_eq!(migrated.metadata_version, METADATA_VERSION);
+        assert!(!migrated.is_outdated());
+        assert_eq!(migrated.architecture.as_deref(), Some("amd64"));
+        assert_eq!(migrated.profile.as_deref(), Some("openrc-splitusr"));
+        // The creator of a legacy chroot stays unknown
+        assert_eq!(migrated.created_by, None);
+
+        let (reread, dropped) = ChrootMetadata::from_toml_lenient(&migrated.to_toml().unwrap()).unwrap();
+        assert!(dropped.is_empty());
+        assert_eq!(reread, migrated);
+    }
+
+    #[test]
+    fn chroot_with_only_the_legacy_file_loads_its_profile() {
+        let chroot = tempfile::tempdir().unwrap();
+        fs::create_dir(chroot.path().join("etc")).unwrap();
+        fs::write(chroot.path().join(LEGACY_PROFILE_FILE), "arm64-systemd").unwrap();
+
+        let unit = ChrootUnit::load(chroot.path()).unwrap();
+        let metadata = unit.metadata.as_ref().unwrap();
+        assert_eq!(metadata.metadata_version, 0);
+        assert_eq!(unit.profile.as_ref().map(|p| p.to_string()), Some("arm64-systemd".to_string()));
+        assert!(unit.ensure_current_metadata().is_err());
+    }
+
+    #[test]
+    fn lenient_parse_round_trips_and_keeps_unknown_keys() {
+        let content = r#"
+metadata_version = 1
+name = "devbox"
+created_by = "chrootmanager 0.1.0"
+created_at = "2025-01-31T10:12:00+01:00"
+architecture = "amd64"
+profile = "openrc"
+stage3 = "stage3-amd64-openrc-20250126T170321Z.tar.xz"
+release = "20250126T170321Z"
+mirror = "https://distfiles.gentoo.org"
+owner = "ci"
+
+[extraction]
+preserve_owner = true
+preserve_xattrs = true
+
+[other_tool]
+checked = true
+"#;
+        let (metadata, dropped) = ChrootMetadata::from_toml_lenient(content).unwrap();
+        assert!(dropped.is_empty());
+        assert_eq!(metadata.name.as_deref(), Some("devbox"));
+        assert_eq!(metadata.release.as_deref(), Some("20250126T170321Z"));
+        assert_eq!(metadata.extra.len(), 2);
+
+        let written = metadata.to_toml().unwrap();
+        assert!(written.contains("owner = \"ci\""), "{written}");
+        let (reread, dropped) = ChrootMetadata::from_toml_lenient(&written).unwrap();
+        assert!(dropped.is_empty());
+        assert_eq!(reread, metadata);
+    }
+
+    #[test]
+    fn lenient_parse_drops_invalid_fields_only() {
+        let content = "metadata_version = 1\nprofile = \"openrc\"\ncreated_at = \"yesterday\"\npinned = 3\n";
+        let (metadata, dropped) = ChrootMetadata::from_toml_lenient(content).unwrap();
+        assert_eq!(dropped, ["created_at"]);
+        assert_eq!(metadata.profile.as_deref(), Some("openrc"));
+        // No longer a field, kept as an annotation of another tool
+        assert!(metadata.extra.contains_key("pinned"));
+
+        assert!(ChrootMetadata::from_toml_lenient("profile = \"openrc\"").is_err());
+        assert!(ChrootMetadata::from_toml_lenient("not toml").is_err());
+    }
+
+    #[test]
+    fn future_versions_are_read_but_not_migrated() {
+        let content = "metadata_version = 7\nprofile = \"openrc\"\nsnapshot_policy = \"weekly\"\n";
+        let (metadata, dropped) = ChrootMetadata::from_toml_lenient(content).unwrap();
+        assert!(dropped.is_empty());
+        assert!(metadata.is_unsupported());
+
+        let migrated = metadata.clone().migrate();
+        assert_eq!(migrated, metadata);
+        assert!(migrated.to_toml().unwrap().contains("snapshot_policy"));
+    }
+
+    #[test]
+    fn malformed_legacy_content_is_rejected() {
+        assert_eq!(ChrootMetadata::from_legacy("openrc"), None);
+    }
+
+    #[test]
+    fn profiles_are_guessed_from_real_world_profile_links() {
+        for (link, architecture, profile) in [
+            ("default/linux/amd64/23.0", "amd64", "openrc"),
+            ("default/linux/amd64/23.0/systemd", "amd64", "systemd"),
+            (
+                "default/linux/amd64/23.0/desktop/gnome/systemd",
+                "amd64",
+                "desktop-gnome-systemd",
+            ),
+            (
+                "default/linux/amd64/23.0/desktop/plasma",
+                "amd64",
+                "desktop-plasma-openrc",
+            ),
+            ("default/linux/amd64/23.0/split-usr", "amd64", "split-usr-openrc"),
+            ("default/linux/arm64/23.0/musl", "arm64", "musl-openrc"),
+            (
+                "default/linux/amd64/23.0/no-multilib/hardened",
+                "amd64",
+                "no-multilib-hardened-openrc",
+            ),
+            ("default/linux/x86/17.0", "x86", "openrc"),
+        ] {
+            let link = Path::new("/var/db/repos/gentoo/profiles").join(link);
+            let metadata = ChrootMetadata::from_make_profile(&link).unwrap();
+            assert_eq!(
+                metadata.architecture.as_deref(),
+                Some(architecture),
+                "{}",
+                link.display()
+            );
+            assert_eq!(metadata.profile.as_deref(), Some(profile), "{}", link.display());
+        }
+
+        // Profiles outside of default/linux are not recognized
+        for link in [
+            "/var/db/repos/gentoo/profiles/prefix/darwin/macos/10.15",
+            "/usr/portage/profiles/default/linux",
+        ] {
+            assert_eq!(ChrootMetadata::from_make_profile(Path::new(link)), None, "{link}");
+        }
+    }
+}
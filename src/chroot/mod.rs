@@ -1,6 +1,20 @@
+pub mod archive;
 mod auth;
 mod core;
+mod elf;
+mod emulation;
 mod filesystem;
+mod hooks;
+pub mod localize;
+pub mod metadata;
+pub mod mounts;
+pub mod processes;
+mod status;
+mod template;
 mod terminal;
 
-pub use core::ChrootUnit;
\ No newline at end of file
+pub use core::{ChrootUnit, Stage3Source};
+#[allow(unused_imports)]
+pub use filesystem::MountGuard;
+pub use status::ChrootStatus;
+pub use terminal::SessionOptions;
\ No newline at end of file
@@ -0,0 +1,348 @@
+//! Mount table inspection for chroot directories
+//!
+//! Reads `/proc/self/mountinfo` to find out which filesystems are currently
+//! mounted inside a chroot, without requiring elevated privileges.
+
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const MOUNTINFO_PATH: &str = "/proc/self/mountinfo";
+
+/// Mount options that break device nodes, setuid programs or executables
+pub const RESTRICTIVE_OPTIONS: &[&str] = &["nodev", "nosuid", "noexec"];
+
+/// A single entry of the mount table
+#[derive(Debug, Clone, PartialEq)]
+pub struct MountEntry {
+    /// Absolute path where the filesystem is mounted
+    pub mount_point: PathBuf,
+    /// Filesystem type (e.g., "proc", "sysfs", "tmpfs")
+    pub fstype: String,
+    /// Mount source (device or pseudo filesystem name)
+    pub source: String,
+    /// Per-mount options (e.g., "rw", "nosuid", "nodev")
+    pub options: Vec<String>,
+}
+
+impl MountEntry {
+    /// Options of [`RESTRICTIVE_OPTIONS`] the filesystem is mounted with
+    pub fn restrictive_options(&self) -> Vec<&str> {
+        RESTRICTIVE_OPTIONS
+            .iter()
+            .copied()
+            .filter(|option| self.options.iter().any(|o| o == option))
+            .collect()
+    }
+}
+
+/// How many of the filesystems a chroot needs are mounted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MountState {
+    Unmounted,
+    /// Some are mounted, e.g. after an interrupted session
+    Partial,
+    Mounted,
+}
+
+impl MountState {
+    /// State of the `expected` mount points, given the mounts under the chroot
+    pub fn of(expected: &[PathBuf], mounts: &[MountEntry]) -> Self {
+        let mounted = expected
+            .iter()
+            .filter(|path| mounts.iter().any(|entry| &entry.mount_point == *path))
+            .count();
+        if mounted == expected.len() && mounted > 0 {
+            MountState::Mounted
+        } else if mounted > 0 {
+            MountState::Partial
+        } else {
+            MountState::Unmounted
+        }
+    }
+}
+
+impl fmt::Display for MountState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MountState::Unmounted => write!(f, "unmounted"),
+            MountState::Partial => write!(f, "partial"),
+            MountState::Mounted => write!(f, "mounted"),
+        }
+    }
+}
+
+/// Read and parse the mount table of the current process
+pub fn read_mount_table() -> Result<Vec<MountEntry>, io::Error> {
+    let content = fs::read_to_string(MOUNTINFO_PATH)?;
+    Ok(parse_mountinfo(&content))
+}
+
+/// Parse the content of a mountinfo file
+///
+/// Lines follow the format described in proc(5):
+/// `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue`
+pub fn parse_mountinfo(content: &str) -> Vec<MountEntry> {
+    content.lines().filter_map(parse_mountinfo_line).collect()
+}
+
+fn parse_mountinfo_line(line: &str) -> Option<MountEntry> {
+    let (before, after) = line.split_once(" - ")?;
+
+    let fields: Vec<&str> = before.split_whitespace().collect();
+    if fields.len() < 6 {
+        log::debug!("Ignoring malformed mountinfo line: {line}");
+        return None;
+    }
+
+    let mut after_fields = after.split_whitespace();
+    let fstype = after_fields.next()?.to_string();
+    let source = unescape_mount_field(after_fields.next().unwrap_or("none"));
+
+    Some(MountEntry {
+        mount_point: PathBuf::from(unescape_mount_field(fields[4])),
+        fstype,
+        source,
+        options: parse_mount_options(fields[5]),
+    })
+}
+
+/// Split a comma-separated option field of the mount table
+pub fn parse_mount_options(field: &str) -> Vec<String> {
+    field
+        .split(',')
+        .filter(|option| !option.is_empty())
+        .map(|option| option.to_string())
+        .collect()
+}
+
+/// Decode the octal escapes (`\040` for space, ...) used by the kernel in mount fields
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && i + 4 <= bytes.len()
+            && bytes[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b))
+        {
+            let value = bytes[i + 1..i + 4]
+                .iter()
+                .fold(0u32, |acc, b| acc * 8 + u32::from(b - b'0'));
+            decoded.push(value as u8);
+            i += 4;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Return the entries mounted at or below the given path, deepest first
+pub fn mounts_under(entries: &[MountEntry], path: &Path) -> Vec<MountEntry> {
+    let mut mounts: Vec<MountEntry> = entries
+        .iter()
+        .filter(|entry| entry.mount_point.starts_with(path))
+        .cloned()
+        .collect();
+
+    mounts.sort_by(|a, b| {
+        b.mount_point
+            .components()
+            .count()
+            .cmp(&a.mount_point.components().count())
+    });
+    mounts.dedup_by(|a, b| a.mount_point == b.mount_point);
+    mounts
+}
+
+/// Entry of the filesystem holding `path`: the deepest mount point above it
+///
+/// The path should be canonical, symbolic links are not resolved. When a
+/// mount point is mounted over, the last entry of the table wins.
+pub fn mount_containing<'a>(entries: &'a [MountEntry], path: &Path) -> Option<&'a MountEntry> {
+    entries
+        .iter()
+        .filter(|entry| path.starts_with(&entry.mount_point))
+        .fold(None, |deepest: Option<&MountEntry>, entry| match deepest {
+            Some(current)
+                if current.mount_point.components().count()
+                    > entry.mount_point.components().count() =>
+            {
+                Some(current)
+            }
+            _ => Some(entry),
+        })
+}
+
+/// Entry of the filesystem `path` is on, or would be created on
+///
+/// The closest existing directory above a missing `path` is looked up, with
+/// its symbolic links resolved.
+pub fn filesystem_of(path: &Path) -> Result<Option<MountEntry>, io::Error> {
+    let Some(existing) = path.ancestors().find(|ancestor| ancestor.is_dir()) else {
+        return Ok(None);
+    };
+    let existing = fs::canonicalize(existing)?;
+    let entries = read_mount_table()?;
+    Ok(mount_containing(&entries, &existing).cloned())
+}
+
+/// Whether a filesystem type keeps its content in memory
+pub fn is_memory_backed(fstype: &str) -> bool {
+    matches!(fstype, "tmpfs" | "ramfs")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mount table of a host with the chroot "dev box" entered, bind mounts included
+    const MOUNTINFO: &str = r"22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw
+23 22 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:12 - proc proc rw
+25 22 0:5 / /dev rw,nosuid shared:2 - devtmpfs devtmpfs rw,size=8000000k,nr_inodes=2000000,mode=755
+30 22 0:26 / /tmp rw,nosuid,nodev shared:14 - tmpfs tmpfs rw
+31 22 8:3 / /home rw,nodev,relatime shared:30 - ext4 /dev/sda3 rw
+40 22 8:3 /me/chroots /var/lib/chroots rw,nodev,relatime shared:30 master:4 propagate_from:1 - ext4 /dev/sda3 rw
+51 31 0:21 / /home/me/chroots/dev\040box/proc rw,relatime - proc proc rw
+53 31 0:5 / /home/me/chroots/dev\040box/dev rw,nosuid master:2 - devtmpfs devtmpfs rw
+54 53 0:23 / /home/me/chroots/dev\040box/dev/pts rw,nosuid,noexec,relatime master:9 - devpts devpts rw,gid=5,mode=620
+55 31 8:4 /gentoo/distfiles /home/me/chroots/dev\040box/var/cache/distfiles rw,relatime - ext4 /dev/disk/by-label/data\040disk rw
+malformed line
+";
+
+    const CHROOT: &str = "/home/me/chroots/dev box";
+
+    fn entry<'a>(entries: &'a [MountEntry], mount_point: &str) -> &'a MountEntry {
+        entries
+            .iter()
+            .find(|entry| entry.mount_point == Path::new(mount_point))
+            .unwrap_or_else(|| panic!("{mount_point} not parsed"))
+    }
+
+    #[test]
+    fn parses_escapes_optional_fields_and_bind_mounts() {
+        let entries = parse_mountinfo(MOUNTINFO);
+        assert_eq!(entries.len(), 10);
+
+        let proc = entry(&entries, "/home/me/chroots/dev box/proc");
+        assert_eq!((proc.fstype.as_str(), proc.source.as_str()), ("proc", "proc"));
+        assert_eq!(proc.options, ["rw", "relatime"]);
+
+        // Several optional fields before the separator
+        let bind = entry(&entries, "/var/lib/chroots");
+        assert_eq!((bind.fstype.as_str(), bind.source.as_str()), ("ext4", "/dev/sda3"));
+
+        let distfiles = entry(&entries, "/home/me/chroots/dev box/var/cache/distfiles");
+        assert_eq!(distfiles.source, "/dev/disk/by-label/data disk");
+    }
+
+    #[test]
+    fn mounts_under_a_chroot_deepest_first() {
+        let entries = parse_mountinfo(MOUNTINFO);
+        let mounts = mounts_under(&entries, Path::new(CHROOT));
+        let mount_points: Vec<&Path> = mounts.iter().map(|entry| entry.mount_point.as_path()).collect();
+        assert_eq!(mount_points.len(), 4);
+        assert_eq!(
+            mount_points[..2],
+            [
+                Path::new("/home/me/chroots/dev box/var/cache/distfiles"),
+                Path::new("/home/me/chroots/dev box/dev/pts"),
+            ]
+        );
+        // A sibling whose name starts with the chroot name is not under it
+        assert!(mounts_under(&entries, Path::new("/home/me/chroots/dev")).is_empty());
+    }
+
+    #[test]
+    fn mount_state_of_the_chroot() {
+        let entries = parse_mountinfo(MOUNTINFO);
+        let mounts = mounts_under(&entries, Path::new(CHROOT));
+        let expected =
+            |names: &[&str]| -> Vec<PathBuf> { names.iter().map(|name| Path::new(CHROOT).join(name)).collect() };
+
+        assert_eq!(
+            MountState::of(&expected(&["proc", "dev", "dev/pts"]), &mounts),
+            MountState::Mounted
+        );
+        assert_eq!(
+            MountState::of(&expected(&["proc", "sys", "dev"]), &mounts),
+            MountState::Partial
+        );
+        assert_eq!(MountState::of(&expected(&["sys"]), &mounts), MountState::Unmounted);
+        assert_eq!(MountState::of(&[], &mounts), MountState::Unmounted);
+    }
+
+    #[test]
+    fn mount_containing_picks_the_deepest_mount_point() {
+        let entries = parse_mountinfo(MOUNTINFO);
+        let containing =
+            |path: &str| mount_containing(&entries, Path::new(path)).map(|entry| entry.mount_point.as_path());
+
+        assert_eq!(containing("/tmp/chroots/dev"), Some(Path::new("/tmp")));
+        assert_eq!(containing("/home/me/chroots"), Some(Path::new("/home")));
+        assert_eq!(containing("/var/lib/chroots/dev"), Some(Path::new("/var/lib/chroots")));
+        assert_eq!(containing("/var/lib"), Some(Path::new("/")));
+        // Not a prefix match on the name
+        assert_eq!(containing("/tmpdir"), Some(Path::new("/")));
+    }
+
+    #[test]
+    fn mount_containing_prefers_the_last_mount_over_a_mount_point() {
+        let content = format!("{MOUNTINFO}60 30 0:40 / /tmp rw,relatime - ext4 /dev/sdb1 rw\n");
+        let entries = parse_mountinfo(&content);
+        let tmp = mount_containing(&entries, Path::new("/tmp/chroots")).unwrap();
+        assert_eq!(tmp.fstype, "ext4");
+    }
+
+    #[test]
+    fn memory_backed_filesystems() {
+        let entries = parse_mountinfo(MOUNTINFO);
+        let fstype = |path: &str| mount_containing(&entries, Path::new(path)).unwrap().fstype.clone();
+
+        assert!(is_memory_backed(&fstype("/tmp/chroots")));
+        assert!(!is_memory_backed(&fstype("/home/me/chroots")));
+        assert!(is_memory_backed("ramfs"));
+        assert!(!is_memory_backed("devtmpfs"));
+    }
+
+    #[test]
+    fn restrictive_options_of_the_filesystem_holding_a_path() {
+        let entries = parse_mountinfo(MOUNTINFO);
+        let restrictive = |path: &str| {
+            mount_containing(&entries, Path::new(path))
+                .unwrap()
+                .restrictive_options()
+        };
+
+        assert_eq!(restrictive("/tmp/chroots"), ["nodev", "nosuid"]);
+        assert_eq!(restrictive("/home/me/chroots"), ["nodev"]);
+        assert_eq!(restrictive("/srv/chroots"), Vec::<&str>::new());
+        // In the order of RESTRICTIVE_OPTIONS, not of the mount table
+        assert_eq!(restrictive("/home/me/chroots/dev box/dev/pts/0"), ["nosuid", "noexec"]);
+    }
+
+    #[test]
+    fn parses_mount_options() {
+        assert_eq!(
+            parse_mount_options("rw,nosuid,nodev,noexec"),
+            ["rw", "nosuid", "nodev", "noexec"]
+        );
+        assert_eq!(parse_mount_options("rw,,relatime,"), ["rw", "relatime"]);
+        assert!(parse_mount_options("").is_empty());
+        // Only whole options count, not prefixes
+        let entry = MountEntry {
+            mount_point: PathBuf::from("/mnt"),
+            fstype: "ext4".to_string(),
+            source: "/dev/sdb1".to_string(),
+            options: parse_mount_options("rw,nodevices,noexec_custom"),
+        };
+        assert!(entry.restrictive_options().is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+//! Processes keeping the filesystems of a chroot busy
+//!
+//! `fuser -m` is asked first, with elevated privileges, since the processes
+//! of a chroot usually belong to root. Without it, `/proc` is scanned, which
+//! only finds the processes the user may inspect. Either way, only the
+//! processes whose root, working directory or executable is inside the
+//! chroot are kept: `/dev` and `/sys` are bind mounts of the host ones, and
+//! `fuser -m` also lists every host process using them.
+
+use crate::chroot::core::ChrootUnit;
+use crate::error::ChrootError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A process using a chroot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChrootProcess {
+    pub pid: u32,
+    /// Command name from `/proc/<pid>/comm`, empty when unreadable
+    pub command: String,
+}
+
+impl ChrootProcess {
+    fn new(pid: u32) -> Self {
+        let command = fs::read_to_string(format!("/proc/{pid}/comm"))
+            .map(|comm| comm.trim().to_string())
+            .unwrap_or_default();
+        Self { pid, command }
+    }
+}
+
+/// PIDs printed by `fuser -m`, which may suffix them with access letters
+fn parse_fuser_pids(stdout: &str) -> Vec<u32> {
+    stdout
+        .split_whitespace()
+        .filter_map(|field| field.trim_end_matches(|c: char| c.is_ascii_alphabetic()).parse().ok())
+        .collect()
+}
+
+/// Whether a process is rooted, working or running from below `path`
+///
+/// False when its links cannot be read, e.g. once it has exited.
+fn is_inside(pid: u32, path: &Path) -> bool {
+    ["root", "cwd", "exe"].iter().any(|link| {
+        fs::read_link(format!("/proc/{pid}/{link}")).is_ok_and(|target| target.starts_with(path))
+    })
+}
+
+/// PIDs of the processes rooted, working or running from below `path`
+fn scan_proc(path: &Path) -> Vec<u32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| is_inside(*pid, path))
+        .collect()
+}
+
+impl ChrootUnit {
+    /// Processes keeping the given mount points of the chroot busy
+    pub fn busy_processes(&self, mount_points: &[PathBuf]) -> Vec<ChrootProcess> {
+        let mut args = vec!["-m"];
+        let paths: Vec<String> = mount_points.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+        args.extend(paths.iter().map(String::as_str));
+
+        let mut pids = match self.execute_elevated("fuser", &args) {
+            Ok(output) => parse_fuser_pids(&String::from_utf8_lossy(&output.stdout)),
+            Err(e) => {
+                log::debug!("fuser failed: {e}");
+                Vec::new()
+            }
+        };
+        pids.retain(|pid| is_inside(*pid, &self.chroot_path));
+        if pids.is_empty() {
+            pids = scan_proc(&self.chroot_path);
+        }
+
+        pids.sort_unstable();
+        pids.dedup();
+        // Never report ourselves, fuser may see the process reading the mount table
+        pids.retain(|pid| *pid != std::process::id());
+        pids.into_iter().map(ChrootProcess::new).collect()
+    }
+
+    /// Send SIGTERM to the given processes with elevated privileges
+    pub fn terminate_processes(&self, processes: &[ChrootProcess]) -> Result<(), ChrootError> {
+        if processes.is_empty() {
+            return Ok(());
+        }
+        let pids: Vec<String> = processes.iter().map(|process| process.pid.to_string()).collect();
+        let args: Vec<&str> = std::iter::once("-TERM").chain(pids.iter().map(String::as_str)).collect();
+        self.execute_command_with_logging("kill", &args, "Termination of the chroot processes")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_pids_printed_by_fuser() {
+        assert_eq!(parse_fuser_pids(" 1234c  1240e 1301rm\n"), [1234, 1240, 1301]);
+        assert_eq!(parse_fuser_pids("   987\n"), [987]);
+        assert!(parse_fuser_pids("").is_empty());
+    }
+
+    #[test]
+    fn host_processes_are_not_inside_a_chroot() {
+        let chroot = tempfile::tempdir().unwrap();
+        let pid = std::process::id();
+        assert!(is_inside(pid, Path::new("/")));
+        assert!(!is_inside(pid, chroot.path()));
+        assert!(!scan_proc(chroot.path()).contains(&pid));
+        // No such process
+        assert!(!is_inside(u32::MAX, Path::new("/")));
+    }
+}
@@ -0,0 +1,197 @@
+//! Point-in-time description of a chroot, shared by `info` and frontends
+
+use crate::cache::index::Stage3Name;
+use crate::chroot::core::ChrootUnit;
+use crate::chroot::mounts::MountState;
+use crate::state::{SessionRecord, UserState};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Portage profile symlink, relative to the chroot root
+const MAKE_PROFILE_LINK: &str = "etc/portage/make.profile";
+
+/// Everything known about a single chroot
+#[derive(Debug, Clone, Serialize)]
+pub struct ChrootStatus {
+    pub name: String,
+    pub path: PathBuf,
+    pub architecture: Option<String>,
+    pub profile: Option<String>,
+    /// Not recorded for chroots created before metadata version 1
+    pub stage3: Option<String>,
+    /// SHA256 of the stage3 archive, when recorded
+    pub stage3_sha256: Option<String>,
+    /// Autobuilds snapshot of the stage3, the build timestamp in its filename
+    pub snapshot: Option<String>,
+    /// Mirror the stage3 was downloaded from, when recorded
+    pub mirror: Option<String>,
+    pub created_at: Option<DateTime<Local>>,
+    /// Version of chrootmanager that created the chroot
+    pub created_by: Option<String>,
+    /// Zoneinfo name, UTC when unset
+    pub timezone: Option<String>,
+    /// `LANG`, the POSIX locale when unset
+    pub locale: Option<String>,
+    pub hostname: Option<String>,
+    /// Last sync of the Portage tree, unknown when done by hand in the chroot
+    pub portage_synced_at: Option<DateTime<Local>>,
+    /// Start of the last session, shell or command, run through chrootmanager
+    pub last_entered_at: Option<DateTime<Local>>,
+    /// Recent sessions, oldest first
+    pub sessions: Vec<SessionRecord>,
+    pub metadata_version: Option<u32>,
+    /// Reason why the metadata needs attention, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_issue: Option<String>,
+    /// Target of `/etc/portage/make.profile`, as an absolute path inside the chroot
+    pub make_profile: Option<PathBuf>,
+    pub mounted: bool,
+    /// Whether the filesystems the chroot needs are all mounted
+    pub mount_state: MountState,
+    pub mount_points: Vec<PathBuf>,
+    /// Lower bound when running unprivileged, see [`ChrootUnit::disk_usage`]
+    pub disk_usage_bytes: u64,
+}
+
+/// Resolve a symlink target lexically, relative to the directory of the link
+///
+/// The target is never followed on the host: an absolute target is a path
+/// inside the chroot.
+fn resolve_link_target(link_dir: &Path, target: &Path) -> PathBuf {
+    let mut resolved = if target.is_absolute() {
+        PathBuf::from("/")
+    } else {
+        link_dir.to_path_buf()
+    };
+    for component in target.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(part) => resolved.push(part),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    resolved
+}
+
+impl ChrootUnit {
+    /// Target of the Portage profile symlink, if the chroot has one
+    pub fn make_profile(&self) -> Option<PathBuf> {
+        let target = fs::read_link(self.chroot_path.join(MAKE_PROFILE_LINK)).ok()?;
+        Some(resolve_link_target(Path::new("/etc/portage"), &target))
+    }
+
+    /// Collect the status of the chroot
+    ///
+    /// The whole tree is walked for the disk usage, which can take a while.
+    pub fn status(&self) -> ChrootStatus {
+        // Chroots created before the date was recorded fall back to the directory
+        let created_at = self
+            .metadata
+            .as_ref()
+            .and_then(|m| m.created_at)
+            .or_else(|| {
+                fs::metadata(&self.chroot_path)
+                    .and_then(|metadata| metadata.created())
+                    .ok()
+                    .map(DateTime::<Local>::from)
+            });
+
+        let mount_points: Vec<PathBuf> = match self.active_mounts() {
+            Ok(mounts) => mounts.into_iter().map(|m| m.mount_point).collect(),
+            Err(e) => {
+                log::warn!("Unable to read the mount table: {e}");
+                Vec::new()
+            }
+        };
+
+        let sessions = match UserState::load(&UserState::path()) {
+            Ok(state) => state.sessions(&self.name).to_vec(),
+            Err(e) => {
+                log::warn!("Unable to read the session history: {e}");
+                Vec::new()
+            }
+        };
+
+        let stage3 = self.metadata.as_ref().and_then(|m| m.stage3.clone());
+        ChrootStatus {
+            name: self.name.clone(),
+            path: self.chroot_path.clone(),
+            architecture: self.profile.as_ref().map(|p| p.arch().to_string()),
+            profile: self.profile.as_ref().map(|p| p.profile().to_string()),
+            stage3: stage3.clone(),
+            stage3_sha256: self.metadata.as_ref().and_then(|m| m.stage3_sha256.clone()),
+            snapshot: self
+                .metadata
+                .as_ref()
+                .and_then(|m| m.release.clone())
+                .or_else(|| stage3.as_deref().and_then(Stage3Name::parse).map(|name| name.timestamp)),
+            mirror: self.metadata.as_ref().and_then(|m| m.mirror.clone()),
+            created_at,
+            created_by: self.metadata.as_ref().and_then(|m| m.created_by.clone()),
+            timezone: self.timezone(),
+            locale: self.locale(),
+            hostname: self.hostname(),
+            portage_synced_at: self.metadata.as_ref().and_then(|m| m.portage_synced_at),
+            last_entered_at: sessions.last().map(|session| session.started_at),
+            sessions,
+            metadata_version: self.metadata.as_ref().map(|m| m.metadata_version),
+            metadata_issue: self.ensure_current_metadata().err().map(|e| e.to_string()),
+            make_profile: self.make_profile(),
+            mounted: !mount_points.is_empty(),
+            mount_state: self.mount_state(),
+            mount_points,
+            disk_usage_bytes: self.disk_usage(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    const PROFILE: &str = "/var/db/repos/gentoo/profiles/default/linux/amd64/23.0/systemd";
+
+    /// Portage profile of a chroot whose `make.profile` points to `target`
+    fn make_profile_to(target: &str) -> Option<PathBuf> {
+        let chroot = tempfile::tempdir().unwrap();
+        fs::create_dir_all(chroot.path().join("etc/portage")).unwrap();
+        symlink(target, chroot.path().join(MAKE_PROFILE_LINK)).unwrap();
+        ChrootUnit::with_path("test".to_string(), chroot.path().to_path_buf()).make_profile()
+    }
+
+    #[test]
+    fn make_profile_symlink_shapes() {
+        for target in [
+            PROFILE,
+            "../../var/db/repos/gentoo/profiles/default/linux/amd64/23.0/systemd",
+            "../../var/db/repos/gentoo/profiles/default/linux/amd64/23.0/systemd/",
+            "./../../var/db/repos/gentoo/profiles/../profiles/default/linux/amd64/23.0/systemd",
+            // More parents than directories stop at the root, as in the kernel
+            "../../../../var/db/repos/gentoo/profiles/default/linux/amd64/23.0/systemd",
+            "//var/db/repos/gentoo/profiles/default/linux/amd64/23.0/systemd",
+        ] {
+            assert_eq!(make_profile_to(target), Some(PathBuf::from(PROFILE)), "{target}");
+        }
+        // Portage still uses the legacy location on older installs
+        assert_eq!(
+            make_profile_to("../../usr/portage/profiles/default/linux/arm64/17.0"),
+            Some(PathBuf::from("/usr/portage/profiles/default/linux/arm64/17.0"))
+        );
+    }
+
+    #[test]
+    fn make_profile_without_a_symlink() {
+        let chroot = tempfile::tempdir().unwrap();
+        let unit = ChrootUnit::with_path("test".to_string(), chroot.path().to_path_buf());
+        assert_eq!(unit.make_profile(), None);
+
+        // A directory in place of the symlink is not a profile link
+        fs::create_dir_all(chroot.path().join(MAKE_PROFILE_LINK)).unwrap();
+        assert_eq!(unit.make_profile(), None);
+    }
+}
@@ -0,0 +1,102 @@
+//! Portage configuration template of new chroots
+//!
+//! The files of the template directory (make.conf, package.use/,
+//! repos.conf/...) are copied into `/etc/portage` of the chroot, keeping
+//! their layout. Text files can use placeholders, replaced on the host
+//! before copying:
+//!
+//! - `{{nproc}}`: number of CPUs of the host
+//! - `{{chroot_name}}`: name of the chroot
+//! - `{{arch}}`: architecture of the chroot
+//!
+//! A file of the stage3 replaced by the template is kept next to it with a
+//! `.stage3` suffix.
+
+use crate::chroot::core::ChrootUnit;
+use crate::error::ChrootError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Portage configuration directory, relative to the chroot root
+const PORTAGE_CONFIG_DIR: &str = "etc/portage";
+
+/// Suffix of the stage3 files replaced by the template
+const STAGE3_BACKUP_SUFFIX: &str = ".stage3";
+
+/// Files of the template directory, relative to it, in a stable order
+fn template_files(template_dir: &Path) -> Result<Vec<PathBuf>, ChrootError> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        for entry in fs::read_dir(template_dir.join(&relative))? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            if entry.path().is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+impl ChrootUnit {
+    /// Replace the placeholders of a template file
+    fn render_template(&self, content: &str) -> String {
+        let nproc = std::thread::available_parallelism().map_or(1, |count| count.get());
+        let arch = self.profile.as_ref().map(|profile| profile.arch()).unwrap_or_default();
+        content
+            .replace("{{nproc}}", &nproc.to_string())
+            .replace("{{chroot_name}}", &self.name)
+            .replace("{{arch}}", arch)
+    }
+
+    /// Copy the Portage configuration template into the chroot
+    ///
+    /// Files that are not UTF-8 are copied unchanged. Returns the number of
+    /// files copied.
+    pub fn apply_portage_template(&self, template_dir: &Path) -> Result<usize, ChrootError> {
+        log::info!("Applying the Portage template {} to {}", template_dir.display(), self.name);
+        let files = template_files(template_dir)?;
+        let staging = tempfile::Builder::new().prefix("chrootmanager-template-").tempdir()?;
+        let portage_dir = self.chroot_path.join(PORTAGE_CONFIG_DIR);
+
+        for (index, relative) in files.iter().enumerate() {
+            let source = template_dir.join(relative);
+            let content = fs::read(&source)?;
+            let rendered = match String::from_utf8(content) {
+                Ok(text) => self.render_template(&text).into_bytes(),
+                Err(binary) => binary.into_bytes(),
+            };
+            let staged = staging.path().join(index.to_string());
+            fs::write(&staged, rendered)?;
+
+            let target = portage_dir.join(relative);
+            if let Some(parent) = target.parent() {
+                self.execute_command_with_logging("mkdir", &["-p", &parent.to_string_lossy()], "Portage directory creation")?;
+            }
+            if target.is_file() {
+                let mut backup = target.clone().into_os_string();
+                backup.push(STAGE3_BACKUP_SUFFIX);
+                let backup = PathBuf::from(backup);
+                if !backup.exists() {
+                    self.execute_command_with_logging(
+                        "cp",
+                        &["-a", &target.to_string_lossy(), &backup.to_string_lossy()],
+                        "Stage3 file backup",
+                    )?;
+                }
+            }
+            self.execute_command_with_logging(
+                "cp",
+                &[&staged.to_string_lossy(), &target.to_string_lossy()],
+                "Portage template copy",
+            )?;
+            log::debug!("Copied {} to {}", source.display(), target.display());
+        }
+
+        Ok(files.len())
+    }
+}
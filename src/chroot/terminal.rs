@@ -1,13 +1,83 @@
 use crate::error::{ChrootError, ElevationError};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output};
+use std::time::SystemTime;
 
-use super::auth::SHARED_ELEVATION;
+use crate::elevation::shared_elevation;
+use crate::elevation::elevation_program;
+use crate::say;
+use crate::ui::symbols::Symbol;
+
+/// Environment file written by the rc script of a session, relative to the chroot root
+const SESSION_ENV_FILE: &str = "tmp/chroot_env.sh";
+
+/// Settings of a session sourced by its environment file, in `/tmp` of the chroot
+///
+/// It holds the values given on the command line, which are written as is
+/// rather than through the rc script where a line could end its here-document.
+const SESSION_SETUP_FILE: &str = "chroot_session.sh";
+
+/// Environment and working directory of a session in the chroot
+///
+/// They only apply inside the chroot: the elevated `chroot` call itself
+/// runs with the environment of chrootmanager.
+#[derive(Debug, Clone, Default)]
+pub struct SessionOptions {
+    /// Variables set in the session, after those of `/etc/profile`
+    pub env: Vec<(String, String)>,
+    /// Directory the session starts in, `/` when unset
+    pub workdir: Option<PathBuf>,
+}
+
+impl SessionOptions {
+    /// Shell lines exporting the variables and entering the working directory
+    fn shell_setup(&self) -> String {
+        let mut lines: Vec<String> = self
+            .env
+            .iter()
+            .map(|(key, value)| format!("export {key}={}", shell_quote(value)))
+            .collect();
+        if let Some(workdir) = &self.workdir {
+            lines.push(format!("cd -- {}", shell_quote(&workdir.to_string_lossy())));
+        }
+        lines.join("\n")
+    }
+
+    /// Command line running `argv` with the session settings
+    ///
+    /// The variables are passed through `env` and the directory to `sh` as
+    /// arguments, so that no value goes through a shell parser.
+    fn wrap_command(&self, argv: &[String]) -> Vec<String> {
+        let mut wrapped = Vec::new();
+        if !self.env.is_empty() {
+            wrapped.push("env".to_string());
+            wrapped.extend(self.env.iter().map(|(key, value)| format!("{key}={value}")));
+        }
+        if let Some(workdir) = &self.workdir {
+            wrapped.extend(["sh", "-c", "cd -- \"$1\" && shift && exec \"$@\"", "sh"].map(String::from));
+            wrapped.push(workdir.to_string_lossy().into_owned());
+        }
+        wrapped.extend(argv.iter().cloned());
+        wrapped
+    }
+}
+
+/// Quote a value for a POSIX shell, in single quotes
+///
+/// A single quote inside the value closes the quoting, is escaped and
+/// reopens it: `it's` becomes `'it'\''s'`.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
 
 /// Terminal and interactive operations for ChrootUnit
 impl crate::chroot::core::ChrootUnit {
     /// Enter chroot environment interactively
-    pub fn enter_chroot_interactive(&self) -> Result<(), ChrootError> {
+    ///
+    /// A shell exiting with a non-zero status, such as that of the last
+    /// command run in it, is not an error. Failing to start the shell is.
+    pub fn enter_chroot_interactive(&self, session: &SessionOptions) -> Result<(), ChrootError> {
         if !self.is_authenticated() {
             return Err(ChrootError::Elevation(
                 ElevationError::AuthenticationRequired,
@@ -18,41 +88,115 @@ impl crate::chroot::core::ChrootUnit {
 
         let chroot_path_str = self.chroot_path.to_str().unwrap();
 
-        println!("🚀 Entering chroot environment '{}'...", self.name);
-        println!("💡 Type 'exit' to quit the chroot environment");
+        say!("{} Entering chroot environment '{}'...", Symbol::Rocket, self.name);
+        say!("{} Type 'exit' to quit the chroot environment", Symbol::Hint);
 
         // Use shared business logic
-        let bashrc_path = self.prepare_chroot_bashrc()?;
+        let bashrc_path = self.prepare_session_bashrc(session)?;
 
-        // Use the cached elevation system instead of direct pkexec
-        let elevation = SHARED_ELEVATION.lock().unwrap();
-        let output = elevation
-            .execute_command_interactive(
-                "chroot",
-                &[
-                    chroot_path_str,
-                    "/bin/bash",
-                    "--rcfile",
-                    "/tmp/chroot_bashrc",
-                    "-i",
-                ],
-            )
-            .map_err(|e| ChrootError::ElevationError(format!("Failed to enter chroot: {e}")))?;
+        // Use the cached elevation system instead of direct pkexec. The lock is
+        // released before the session starts so that cleanup can still unmount.
+        let command = shared_elevation().lock().unwrap().interactive_command(
+            "chroot",
+            &[
+                chroot_path_str,
+                "/bin/bash",
+                "--rcfile",
+                "/tmp/chroot_bashrc",
+                "-i",
+            ],
+        );
+        let started_at = SystemTime::now();
+        let status = command
+            .map_err(|e| ChrootError::ElevationError(format!("Failed to enter chroot: {e}")))
+            .and_then(|mut command| {
+                command
+                    .status()
+                    .map_err(|e| ChrootError::ElevationError(format!("Failed to enter chroot: {e}")))
+            });
 
         // Cleanup using shared logic
         self.cleanup_chroot_bashrc(&bashrc_path);
 
-        if !output.status.success() {
-            return Err(ChrootError::ElevationError(
-                "Chroot execution failed".to_string(),
-            ));
+        let status = status?;
+        if !status.success() {
+            if !self.shell_started_since(started_at) {
+                return Err(ChrootError::ShellNotStarted(status));
+            }
+            log::info!("The shell of chroot {} exited with {status}", self.name);
         }
 
-        println!("✅ Exited chroot '{}'", self.name);
+        say!("{} Exited chroot '{}'", Symbol::Success, self.name);
         log::info!("Successfully exited chroot environment: {}", self.name);
         Ok(())
     }
 
+    /// Run a single command inside the chroot, with the standard streams inherited
+    ///
+    /// The filesystems are mounted for the duration of the command and
+    /// unmounted afterwards, whatever its exit status.
+    pub fn exec_command(&self, argv: &[String], session: &SessionOptions) -> Result<ExitStatus, ChrootError> {
+        if !self.is_authenticated() {
+            return Err(ChrootError::Elevation(
+                ElevationError::AuthenticationRequired,
+            ));
+        }
+
+        log::info!("Running {argv:?} in chroot: {}", self.name);
+
+        let mounts = self
+            .mount_filesystems()
+            .map_err(|e| ChrootError::MountFailed(e.to_string()))?;
+
+        let chroot_path_str = self.chroot_path.to_string_lossy();
+        let argv = session.wrap_command(argv);
+        let args: Vec<&str> = std::iter::once(chroot_path_str.as_ref())
+            .chain(argv.iter().map(String::as_str))
+            .collect();
+
+        // The lock is released before the command starts so that cleanup can still unmount
+        let command = shared_elevation().lock().unwrap().interactive_command("chroot", &args);
+        let status = command
+            .map_err(ChrootError::Elevation)
+            .and_then(|mut command| command.status().map_err(ChrootError::Io));
+
+        mounts
+            .unmount()
+            .map_err(|e| ChrootError::UnmountFailed(e.to_string()))?;
+
+        status
+    }
+
+    /// Run a single command inside the chroot, with its output captured
+    ///
+    /// As [`exec_command`](Self::exec_command), for commands run side by side
+    /// in several chroots, whose output would otherwise mix.
+    pub fn run_command(&self, argv: &[String]) -> Result<Output, ChrootError> {
+        if !self.is_authenticated() {
+            return Err(ChrootError::Elevation(
+                ElevationError::AuthenticationRequired,
+            ));
+        }
+
+        log::info!("Running {argv:?} in chroot: {}", self.name);
+
+        let mounts = self
+            .mount_filesystems()
+            .map_err(|e| ChrootError::MountFailed(e.to_string()))?;
+
+        let chroot_path_str = self.chroot_path.to_string_lossy();
+        let args: Vec<&str> = std::iter::once(chroot_path_str.as_ref())
+            .chain(argv.iter().map(String::as_str))
+            .collect();
+        let output = self.execute_elevated("chroot", &args);
+
+        mounts
+            .unmount()
+            .map_err(|e| ChrootError::UnmountFailed(e.to_string()))?;
+
+        output
+    }
+
     /// Generate chroot command for external terminal (for GUI)
     /// This method is intended for future GUI integration
     #[allow(dead_code)]
@@ -62,7 +206,7 @@ impl crate::chroot::core::ChrootUnit {
         let chroot_args = self.get_chroot_command_args(&bashrc_path);
         
         // Build the complete command string for the external terminal
-        let chroot_command = format!("sudo chroot {}", chroot_args.join(" "));
+        let chroot_command = format!("{} chroot {}", elevation_program(), chroot_args.join(" "));
         
         Ok((chroot_command, bashrc_path))
     }
@@ -86,33 +230,62 @@ impl crate::chroot::core::ChrootUnit {
         ]
     }
 
+    /// Whether the rc script of a session ran since `started_at`
+    ///
+    /// The script writes the environment file of the shell as it starts, so
+    /// an older file means that the shell could not be run at all.
+    fn shell_started_since(&self, started_at: SystemTime) -> bool {
+        fs::metadata(self.chroot_path.join(SESSION_ENV_FILE))
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified >= started_at)
+    }
+
     /// SHARED BUSINESS LOGIC: Cleanup temporary bashrc
     pub fn cleanup_chroot_bashrc(&self, bashrc_path: &Path) {
-        if let Err(e) = fs::remove_file(bashrc_path) {
-            let path_display = bashrc_path.display();
-            log::debug!("Failed to clean up a bashrc file {path_display}: {e}");
+        self.cleanup_chroot_temp_file(bashrc_path);
+        self.cleanup_chroot_temp_file(&self.chroot_path.join("tmp").join(SESSION_SETUP_FILE));
+    }
+
+    /// Remove a file written by `prepare_chroot_temp_file`
+    pub fn cleanup_chroot_temp_file(&self, path: &Path) {
+        if let Err(e) = fs::remove_file(path) {
+            let path_display = path.display();
+            log::debug!("Failed to clean up a temporary file {path_display}: {e}");
         } else {
-            let path_display = bashrc_path.display();
-            log::debug!("Cleaned up a bashrc file: {path_display}");
+            let path_display = path.display();
+            log::debug!("Cleaned up a temporary file: {path_display}");
         }
     }
 
-    /// Prepare bashrc and return the path to it
-    pub fn prepare_chroot_bashrc(&self) -> Result<PathBuf, ChrootError> {
-        // Create a temporary bashrc file inside the chroot
-        let bashrc_path = self.chroot_path.join("tmp/chroot_bashrc");
-
+    /// Write a file into `/tmp` of the chroot and return its path on the host
+    pub fn prepare_chroot_temp_file(&self, file_name: &str, content: &[u8]) -> Result<PathBuf, ChrootError> {
         // Ensure the tmp directory exists
         let tmp_dir = self.chroot_path.join("tmp");
         if !tmp_dir.exists() {
             fs::create_dir_all(&tmp_dir)?;
         }
 
+        let path = tmp_dir.join(file_name);
+        fs::write(&path, content).map_err(ChrootError::Io)?;
+        Ok(path)
+    }
+
+    /// Prepare bashrc and return the path to it
+    pub fn prepare_chroot_bashrc(&self) -> Result<PathBuf, ChrootError> {
+        self.prepare_session_bashrc(&SessionOptions::default())
+    }
+
+    /// Prepare the bashrc of a session with its variables and working directory
+    pub fn prepare_session_bashrc(&self, session: &SessionOptions) -> Result<PathBuf, ChrootError> {
+        let prompt = format!(r"\[\e[1;32m\](chroot) \[\e[01;31m\]{}\[\e[01;34m\] \w \$\[\e[00m\] ", self.name);
+        let setup = format!("export PS1={}\n{}\n", shell_quote(&prompt), session.shell_setup());
+        self.prepare_chroot_temp_file(SESSION_SETUP_FILE, setup.as_bytes())?;
+
         // Bashrc content (no shebang, no exec, just configuration)
         let bashrc_content = format!(
             r#"#!/bin/bash
 export ENV="/tmp/chroot_env.sh"
-cat > /tmp/chroot_env.sh << 'EOF'
+cat > /tmp/chroot_env.sh << 'CHROOTMANAGER_EOF'
 source /etc/profile 2>/dev/null || true
 export TERM=xterm-256color
 eval "$(dircolors -b 2>/dev/null || true)"
@@ -120,15 +293,61 @@ alias ls='ls --color=auto'
 alias ll='ls -l --color=auto'
 alias la='ls -la --color=auto'
 alias grep='grep --color=auto'
-export PS1='\[\e[1;32m\](chroot) \[\e[01;31m\]{}\[\e[01;34m\] \w \$\[\e[00m\] '
-EOF
+. /tmp/{SESSION_SETUP_FILE}
+CHROOTMANAGER_EOF
 exec bash --posix -i
-"#,
-            self.name
+"#
         );
 
-        // Write the bashrc file
-        fs::write(&bashrc_path, bashrc_content).map_err(ChrootError::Io)?;
-        Ok(bashrc_path)
+        // Create a temporary bashrc file inside the chroot
+        self.prepare_chroot_temp_file("chroot_bashrc", bashrc_content.as_bytes())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    /// Output of `script` run by `sh`
+    fn run_sh(script: &str) -> String {
+        let output = Command::new("sh").arg("-c").arg(script).output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn shell_quote_survives_the_shell() {
+        for value in ["plain", "it's", "$HOME `id` \"x\"", "a\nCHROOTMANAGER_EOF\nb", "", "'"] {
+            assert_eq!(run_sh(&format!("printf %s {}", shell_quote(value))), value);
+        }
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn shell_setup_exports_the_values_unchanged() {
+        let value = "x'\nCHROOTMANAGER_EOF\necho injected";
+        let session = SessionOptions {
+            env: vec![("VALUE".to_string(), value.to_string())],
+            workdir: Some(PathBuf::from("/")),
+        };
+        let script = format!("{}\nprintf '%s|%s' \"$VALUE\" \"$PWD\"", session.shell_setup());
+        assert_eq!(run_sh(&script), format!("{value}|/"));
+    }
+
+    #[test]
+    fn wrap_command_passes_values_as_arguments() {
+        let argv = vec!["printenv".to_string(), "VALUE".to_string()];
+        assert_eq!(SessionOptions::default().wrap_command(&argv), argv);
+
+        let session = SessionOptions {
+            env: vec![("VALUE".to_string(), "$(id) 'quoted'".to_string())],
+            workdir: Some(PathBuf::from("/tmp")),
+        };
+        let wrapped = session.wrap_command(&argv);
+        assert_eq!(wrapped[..2], ["env", "VALUE=$(id) 'quoted'"]);
+        assert_eq!(wrapped[wrapped.len() - 3..], ["/tmp", "printenv", "VALUE"]);
+
+        let output = Command::new(&wrapped[0]).args(&wrapped[1..]).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "$(id) 'quoted'\n");
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,130 @@
+//! `chrootmanager cache`: inspect and clean the stage3 cache
+
+use crate::cache::index::{self, CachedStage3, SidecarStatus};
+use crate::cli::command::CacheAction;
+use crate::cli::common::confirm;
+use crate::cli::download::format_bytes;
+use crate::cli::error::ChrootManagerError;
+use crate::cli::load_config;
+use crate::say;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+use chrono::{DateTime, Local};
+use colored::Colorize;
+
+fn total_size(tarballs: &[&CachedStage3]) -> u64 {
+    tarballs.iter().map(|tarball| tarball.size).sum()
+}
+
+/// Delete the tarballs and their sidecars, returning the space freed
+fn remove_tarballs(tarballs: &[&CachedStage3]) -> Result<u64, ChrootManagerError> {
+    let mut freed = 0;
+    for tarball in tarballs {
+        index::remove_with_sidecar(&tarball.path).map_err(ChrootManagerError::Io)?;
+        say!("   {} Removed {} ({})", Symbol::Cleanup, tarball.filename, format_bytes(tarball.size));
+        freed += tarball.size;
+    }
+    Ok(freed)
+}
+
+/// Show each tarball with its size, download date and sidecar status
+///
+/// Every tarball is hashed, which takes a while on a large cache.
+async fn list_cache(tarballs: &[CachedStage3]) {
+    if output::is_quiet() {
+        for tarball in tarballs {
+            println!("{}", tarball.filename);
+        }
+        return;
+    }
+
+    if tarballs.is_empty() {
+        say!("   The stage3 cache is empty");
+        return;
+    }
+
+    say!("{} Verifying the cached tarballs against their SHA256 sidecars...", Symbol::Search);
+    println!("   {:<50} {:>10}  {:<16}  SHA256", "FILE", "SIZE", "DOWNLOADED");
+    println!("   {}", Symbol::Separator.as_str().repeat(90));
+    for tarball in tarballs {
+        let downloaded = DateTime::<Local>::from(tarball.downloaded).format("%Y-%m-%d %H:%M");
+        let sha256 = match tarball.verify_sidecar().await {
+            Ok(SidecarStatus::Valid) => "valid".green(),
+            Ok(SidecarStatus::Mismatch) => "MISMATCH".red().bold(),
+            Ok(SidecarStatus::Missing) => "no sidecar".dimmed(),
+            Err(e) => format!("unreadable ({e})").red(),
+        };
+        println!(
+            "   {:<50} {:>10}  {:<16}  {}",
+            tarball.filename,
+            format_bytes(tarball.size),
+            downloaded,
+            sha256
+        );
+    }
+
+    let all: Vec<&CachedStage3> = tarballs.iter().collect();
+    say!("\n   {} tarball(s), {}", tarballs.len(), format_bytes(total_size(&all)));
+}
+
+/// Delete every tarball, and the interrupted downloads, after confirmation
+fn clean_cache(tarballs: &[CachedStage3], partials: &[CachedStage3]) -> Result<(), ChrootManagerError> {
+    if tarballs.is_empty() && partials.is_empty() {
+        say!("{} The stage3 cache is already empty", Symbol::Info);
+        return Ok(());
+    }
+
+    let all: Vec<&CachedStage3> = tarballs.iter().chain(partials).collect();
+    let confirmed = confirm(
+        &format!(
+            "Delete the {} cached tarball(s) and {} partial download(s) ({})?",
+            tarballs.len(),
+            partials.len(),
+            format_bytes(total_size(&all))
+        ),
+        false,
+    )?;
+    if !confirmed {
+        say!("{} Cache cleaning cancelled", Symbol::Info);
+        return Ok(());
+    }
+
+    let freed = remove_tarballs(&all)?;
+    println!("{} Stage3 cache cleaned, {} freed", Symbol::Success, format_bytes(freed));
+    Ok(())
+}
+
+/// Keep the `keep` most recent tarballs of each architecture and profile
+///
+/// Files that do not follow the stage3 naming are left alone.
+fn prune_cache(tarballs: &[CachedStage3], keep: usize) -> Result<(), ChrootManagerError> {
+    let pruned = index::select_prune(tarballs, keep);
+    if pruned.is_empty() {
+        say!("{} Nothing to prune", Symbol::Info);
+        return Ok(());
+    }
+
+    let freed = remove_tarballs(&pruned)?;
+    println!(
+        "{} {} tarball(s) pruned, {} freed",
+        Symbol::Success,
+        pruned.len(),
+        format_bytes(freed)
+    );
+    Ok(())
+}
+
+/// Runs a cache subcommand
+pub async fn run_cache_command(action: CacheAction) -> Result<(), ChrootManagerError> {
+    let config = load_config().await?;
+    let tarballs = index::scan(&config.stage3_cache_dir).map_err(ChrootManagerError::Io)?;
+    let partials = index::scan_partials(&config.stage3_cache_dir).map_err(ChrootManagerError::Io)?;
+
+    match action {
+        CacheAction::List => list_cache(&tarballs).await,
+        CacheAction::Clean => clean_cache(&tarballs, &partials)?,
+        CacheAction::Prune { keep } => prune_cache(&tarballs, keep)?,
+    }
+
+    Ok(())
+}
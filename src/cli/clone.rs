@@ -0,0 +1,52 @@
+use crate::cli::common::find_chroot_unit;
+use crate::cli::download::format_bytes;
+use crate::cli::error::ChrootManagerError;
+use crate::cli::load_config;
+use crate::say;
+use crate::space;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+use colored::Colorize;
+
+/// Copies an existing chroot under a new name
+pub async fn clone_chroot(source: String, dest: String) -> Result<(), ChrootManagerError> {
+    let config = load_config().await?;
+    let unit = find_chroot_unit(&source).await?;
+    // Fail before asking for a password
+    unit.check_clone(&dest, &config)?;
+
+    // Root-only directories are skipped, so this is a lower bound
+    let size = unit.disk_usage();
+    say!("{} Size of '{source}': at least {}", Symbol::Stats, format_bytes(size));
+    if let Some(available) = space::available_space(&config.chroot_base_dir) {
+        if available < size {
+            say!(
+                "{}",
+                format!(
+                    "{} Only {} free on the chroot filesystem, the copy may fail unless it is a reflink copy",
+                    Symbol::Warning,
+                    format_bytes(available)
+                )
+                .yellow()
+            );
+        }
+    }
+
+    say!("{} Authenticating for privileged operations...", Symbol::Lock);
+    unit.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)?;
+
+    say!("{} Copying '{source}' to '{dest}'...", Symbol::Package);
+    let clone = unit.clone_to(&dest, &config)?;
+
+    if output::is_quiet() {
+        println!("{}", clone.chroot_path.display());
+    } else {
+        println!(
+            "{}",
+            format!("{} Chroot '{source}' cloned to '{dest}' ({})", Symbol::Success, clone.chroot_path.display())
+                .green()
+                .bold()
+        );
+    }
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use clap::{Parser, Subcommand};
+use crate::cli::completions::CompletionShell;
+use crate::cli::describe::DescribeFormat;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(
@@ -7,6 +10,21 @@ use clap::{Parser, Subcommand};
     version
 )]
 pub struct Cli {
+    /// Only print the result of the command (and prompts)
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Print progress details and informational log messages
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+    /// Work from the local caches only, without any network access
+    #[arg(long, global = true)]
+    pub offline: bool,
+    /// Append the log to this file too, instead of the `log_file` of the configuration
+    #[arg(long, global = true, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+    /// Answer yes to every confirmation, as does setting CHROOTMANAGER_ASSUME_YES
+    #[arg(short, long, global = true)]
+    pub yes: bool,
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -23,23 +41,489 @@ pub enum Commands {
         /// Profile
         #[arg(short, long)]
         profile: Option<String>,
-        /// Interactive mode
+        /// Interactive mode: prompt for the architecture and profile when not given
         #[arg(short, long)]
         interactive: bool,
+        /// Stream the stage3 into tar as it downloads, without writing it to the cache
+        #[arg(long)]
+        no_cache: bool,
+        /// Do not trim the stage3 cache to its budget after downloading
+        #[arg(long)]
+        no_evict: bool,
+        /// Extract the stage3 even if the chroot directory is not empty
+        #[arg(long)]
+        force_extract: bool,
+        /// Fail when the latest stage3 is not on the mirrors yet, instead of using the previous one
+        #[arg(long)]
+        strict_latest: bool,
+        /// Create the chroot even if the cache or chroot directory is on a tmpfs
+        #[arg(long)]
+        allow_tmpfs: bool,
+        /// Create the chroot even if its filesystem is mounted nodev, nosuid or noexec
+        #[arg(long)]
+        ignore_fs_checks: bool,
+        /// Extract the files as owned by root, for filesystems that reject chown
+        #[arg(long)]
+        no_same_owner: bool,
+        /// Skip the OpenPGP signature check of the stage3, for mirrors without signatures
+        #[arg(long)]
+        no_gpg: bool,
+        /// Download and extract even when the free space looks insufficient
+        #[arg(long)]
+        force: bool,
+        /// Install the stage3 of a past snapshot, a timestamp such as 20240301T164822Z
+        /// or a date such as 2024-03-01 for the last build of that day
+        #[arg(long, visible_alias = "date", value_name = "TIMESTAMP|DATE")]
+        release: Option<String>,
+        /// Discover the architectures and profiles on the mirrors even if the cached ones are recent
+        #[arg(long)]
+        refresh_profiles: bool,
+        /// Only print a single key=value line describing the result
+        #[arg(long)]
+        summary_only: bool,
+        /// Do not copy the portage_template_dir of the configuration into the new chroot
+        #[arg(long)]
+        no_template: bool,
+        /// Do not set the chroot_timezone, chroot_locale and chroot_hostname of the configuration
+        #[arg(long)]
+        no_localize: bool,
+        /// Sync the Portage tree of the new chroot, which a stage3 does not include
+        #[arg(long)]
+        sync: bool,
+        /// How the Portage tree is synced by --sync or sync_on_create
+        #[arg(long, value_enum, default_value_t = SyncMethod::Webrsync)]
+        sync_method: SyncMethod,
+        /// Script run inside the new chroot after the post_create_hooks of the configuration (repeatable)
+        #[arg(long = "hook", value_name = "SCRIPT")]
+        hooks: Vec<PathBuf>,
+        /// Keep the chroot when a hook fails, instead of deleting it
+        #[arg(long)]
+        keep_on_hook_failure: bool,
+    },
+    /// Download and verify a stage3 without creating a chroot
+    Download {
+        /// Architecture
+        #[arg(short, long)]
+        arch: Option<String>,
+        /// Profile
+        #[arg(short, long)]
+        profile: Option<String>,
+        /// Move the stage3 into this directory instead of keeping it in the cache
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+        /// Keep the stage3 in the cache too when --output is used
+        #[arg(long, requires = "output")]
+        keep_download: bool,
+        /// Fail when the latest stage3 is not on the mirrors yet, instead of using the previous one
+        #[arg(long)]
+        strict_latest: bool,
+        /// Skip the OpenPGP signature check of the stage3, for mirrors without signatures
+        #[arg(long)]
+        no_gpg: bool,
+        /// Download even when the free space looks insufficient
+        #[arg(long)]
+        force: bool,
+        /// Download the stage3 of a past snapshot, a timestamp such as 20240301T164822Z
+        /// or a date such as 2024-03-01 for the last build of that day
+        #[arg(long, visible_alias = "date", value_name = "TIMESTAMP|DATE")]
+        release: Option<String>,
     },
     /// List all chroots
     List {
         /// Interactive mode
         #[arg(short, long, default_value_t = false)]
         interactive: bool,
+        /// Do not warn about low disk space when leaving a chroot
+        #[arg(long)]
+        no_space_warning: bool,
+        /// Only list the chroots of this project
+        #[arg(long, conflicts_with = "interactive")]
+        project: Option<String>,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ListFormat::Text, conflicts_with = "interactive")]
+        format: ListFormat,
+        /// Add the disk usage of each chroot to the JSON and plain formats, walking their whole tree
+        #[arg(long, conflicts_with = "interactive")]
+        size: bool,
+    },
+    /// Enter a chroot by name
+    Enter {
+        /// Chroot name
+        name: String,
+        /// Do not warn about low disk space when leaving the chroot
+        #[arg(long)]
+        no_space_warning: bool,
+        /// Variable set in the shell (repeatable)
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+        env: Vec<(String, String)>,
+        /// Directory of the chroot the shell starts in
+        #[arg(long)]
+        workdir: Option<PathBuf>,
+    },
+    /// Run a command inside a chroot, without an interactive shell
+    Exec {
+        /// Chroot name
+        name: String,
+        /// Variable set for the command (repeatable)
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+        env: Vec<(String, String)>,
+        /// Directory of the chroot the command runs in
+        #[arg(long)]
+        workdir: Option<PathBuf>,
+        /// Command and arguments, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
     },
     /// Configure mirrors
+    #[command(args_conflicts_with_subcommands = true)]
     Mirror {
+        #[command(subcommand)]
+        action: Option<MirrorAction>,
         /// Mirror URL (optional in interactive mode)
         #[arg(index = 1)]
         new_mirror: Option<String>,
         /// Interactive mode
         #[arg(short, long, default_value_t = false)]
         interactive: bool,
+        /// Show the configured mirrors
+        #[arg(long, conflicts_with_all = ["new_mirror", "interactive"])]
+        show: bool,
+        /// Add the mirrors of a region from the Gentoo mirror list, without prompting
+        #[arg(long, conflicts_with_all = ["new_mirror", "interactive", "show"])]
+        region: Option<String>,
+        /// Add the mirrors of a country from the Gentoo mirror list, without prompting
+        #[arg(long, conflicts_with_all = ["new_mirror", "interactive", "show"])]
+        country: Option<String>,
+        /// Protocol of the mirrors added with --region or --country
+        #[arg(long, default_value = "https")]
+        protocol: String,
+        /// Add the first mirror when several match --region and --country
+        #[arg(long, conflicts_with = "all")]
+        first: bool,
+        /// Add every mirror matching --region and --country
+        #[arg(long)]
+        all: bool,
+        /// Download the Gentoo mirror list again, even if the cached copy is recent
+        #[arg(long, conflicts_with_all = ["new_mirror", "show"])]
+        refresh: bool,
+        /// Also list the mirrors marked partial, which may not carry the stage3 archives
+        #[arg(long, conflicts_with_all = ["new_mirror", "show"])]
+        include_partial: bool,
+        /// Only list the mirrors reachable over IPv6, the default on hosts without IPv4 route
+        #[arg(long, conflicts_with_all = ["new_mirror", "show"])]
+        ipv6_only: bool,
+        /// Prompt for the protocol of each mirror selected with -i, instead of preferring https
+        #[arg(long, requires = "interactive")]
+        ask_protocol: bool,
+        /// Also check that the mirror URL carries the autobuilds of this architecture
+        #[arg(long, requires = "new_mirror")]
+        arch: Option<String>,
+    },
+    /// List the architectures and profiles available for create
+    Profiles {
+        /// Only list the profiles of this architecture
+        #[arg(short, long)]
+        arch: Option<String>,
+        /// Crawl the mirrors even if the cached profiles are recent
+        #[arg(long)]
+        refresh: bool,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Show the details of a chroot
+    Info {
+        /// Chroot name
+        name: String,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Show what happened during the last failed command
+    WhyFailed {
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Create a chroot from a tar archive of a chroot tree (.tar.xz, .tar.zst, .tar.gz)
+    Import {
+        /// Chroot name
+        name: String,
+        /// Archive to extract
+        archive: PathBuf,
+        /// Extract the files as owned by root, for filesystems that reject chown
+        #[arg(long)]
+        no_same_owner: bool,
+        /// Remove this many leading directories from the archive members
+        #[arg(long, default_value_t = 0)]
+        strip_components: u32,
+    },
+    /// Rename a chroot
+    Rename {
+        /// Current chroot name
+        old: String,
+        /// New chroot name
+        new: String,
+    },
+    /// Copy a chroot under a new name
+    Clone {
+        /// Chroot to copy
+        source: String,
+        /// Name of the copy
+        dest: String,
+    },
+    /// Manage projects, named groups of chroots
+    Project {
+        #[command(subcommand)]
+        command: ProjectCommand,
+    },
+    /// Inspect and clean the stage3 cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Show or change the settings of the configuration file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Unmount the filesystems left mounted in a chroot, without entering it
+    Unmount {
+        /// Chroot name
+        #[arg(required_unless_present = "all")]
+        name: Option<String>,
+        /// Unmount every chroot that has mounted filesystems
+        #[arg(long, conflicts_with = "name")]
+        all: bool,
+        /// Send SIGTERM to the processes keeping a chroot busy, then unmount again
+        #[arg(long)]
+        kill: bool,
+        /// Detach lazily the filesystems that are still busy
+        #[arg(long)]
+        lazy: bool,
+    },
+    /// Upgrade the metadata of a chroot to the current format
+    Migrate {
+        /// Chroot name
+        name: String,
+        /// Only show the migration, without writing the metadata
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check mounting, unmounting and chroot execution in a scratch directory
+    Selftest,
+    /// Check that the host has what chrootmanager needs, with hints to fix it
+    Doctor,
+    /// Describe the subcommands and flags for wrappers
+    #[command(name = "__describe", hide = true)]
+    Describe {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Json)]
+        format: DescribeFormat,
+    },
+    /// Print the completion script of a shell
+    Completions {
+        /// Shell to complete for
+        #[arg(value_enum)]
+        shell: CompletionShell,
+    },
+    /// List the chroot names or profiles for the completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[command(subcommand)]
+        target: CompleteTarget,
+    },
+    /// Serve the session bus API used by graphical frontends
+    #[cfg(feature = "dbus")]
+    Daemon,
+}
+
+impl Commands {
+    /// Whether the command mounts, extracts or enters chroots
+    pub fn needs_chroot_operations(&self) -> bool {
+        match self {
+            Commands::Create { .. }
+            | Commands::Enter { .. }
+            | Commands::Exec { .. }
+            | Commands::Rename { .. }
+            | Commands::Clone { .. }
+            | Commands::Import { .. }
+            | Commands::Unmount { .. }
+            | Commands::Migrate { .. }
+            | Commands::Selftest
+            | Commands::Project { command: ProjectCommand::Unmount { .. } | ProjectCommand::Update { .. } } => true,
+            Commands::List { interactive, .. } => *interactive,
+            #[cfg(feature = "dbus")]
+            Commands::Daemon => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ProjectCommand {
+    /// Create an empty project
+    Create {
+        /// Project name
+        name: String,
+    },
+    /// Delete a project, keeping its chroots
+    Delete {
+        /// Project name
+        name: String,
+    },
+    /// Add a chroot to a project
+    Add {
+        /// Project name
+        project: String,
+        /// Chroot name
+        chroot: String,
+    },
+    /// Remove a chroot from a project, keeping the chroot
+    Remove {
+        /// Project name
+        project: String,
+        /// Chroot name
+        chroot: String,
+    },
+    /// List the projects and their members
+    List,
+    /// Show the mount status of the members of a project
+    Status {
+        /// Project name
+        name: String,
+    },
+    /// Unmount the filesystems of every member of a project
+    Unmount {
+        /// Project name
+        name: String,
+        /// Send SIGTERM to the processes keeping a member busy, then unmount again
+        #[arg(long)]
+        kill: bool,
+        /// Detach lazily the filesystems that are still busy
+        #[arg(long)]
+        lazy: bool,
+    },
+    /// Sync the Portage tree of every member of a project
+    Update {
+        /// Project name
+        name: String,
+        /// How the Portage trees are synced
+        #[arg(long, value_enum, default_value_t = SyncMethod::Webrsync)]
+        sync_method: SyncMethod,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MirrorAction {
+    /// List the configured mirrors and check that they answer
+    List,
+    /// Remove a mirror from the configuration
+    Remove {
+        /// Mirror URL, or its index in `mirror list`
+        target: String,
+    },
+    /// Rank the configured mirrors by latency and throughput
+    Bench {
+        /// Save the mirrors in the ranked order, clearing their priorities
+        #[arg(long)]
+        apply: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Show the effective settings, and whether each comes from the file or the defaults
+    Show,
+    /// Print the path of the configuration file
+    Path,
+    /// Change a setting, paths being absolute
+    Set {
+        /// Setting name, e.g. chroot_base_dir
+        key: String,
+        /// New value
+        value: String,
+    },
+    /// Reset a setting to its default
+    Unset {
+        /// Setting name
+        key: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CompleteTarget {
+    /// Names of the chroots of the base directory
+    Chroots,
+    /// Profiles of the profile cache
+    Profiles {
+        /// Only list the profiles of this architecture, instead of the default one or all
+        #[arg(short, long)]
+        arch: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    /// List the cached tarballs and check them against their SHA256
+    List,
+    /// Delete every cached tarball and interrupted download, after confirmation
+    Clean,
+    /// Keep only the most recent tarballs of each architecture and profile
+    Prune {
+        /// Number of tarballs to keep per architecture and profile
+        #[arg(long)]
+        keep: usize,
     },
-}
\ No newline at end of file
+}
+
+/// Parse a `KEY=VALUE` variable, the key being a shell variable name
+fn parse_env_var(value: &str) -> Result<(String, String), String> {
+    let (key, value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("'{value}' is not of the form KEY=VALUE"))?;
+    let valid_key = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_key {
+        return Err(format!("'{key}' is not a valid variable name"));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Command syncing the Portage tree of a new chroot
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum SyncMethod {
+    /// emerge-webrsync, the latest daily snapshot over HTTP
+    Webrsync,
+    /// emerge --sync, with the repository settings of the chroot (git once configured)
+    Git,
+}
+
+impl SyncMethod {
+    /// Command line run inside the chroot
+    pub fn command(&self) -> Vec<String> {
+        let argv: &[&str] = match self {
+            SyncMethod::Webrsync => &["emerge-webrsync"],
+            SyncMethod::Git => &["emerge", "--sync"],
+        };
+        argv.iter().map(|arg| arg.to_string()).collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable output
+    Text,
+    /// JSON output for scripts
+    Json,
+}
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ListFormat {
+    /// Human-readable table
+    Text,
+    /// JSON array of chroots
+    Json,
+    /// One tab-separated line per chroot: name, path, architecture, profile, mount state,
+    /// creation date, then the disk usage in bytes with --size
+    Plain,
+}
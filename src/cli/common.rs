@@ -1,101 +1,1251 @@
-use crate::chroot::ChrootUnit;
+use crate::chroot::archive::{ArchiveCompression, ExtractionOptions};
+use crate::chroot::localize::Localization;
+use crate::chroot::mounts;
+use crate::chroot::{ChrootUnit, SessionOptions, Stage3Source};
+use crate::cli::download::{
+    download_stage3_with_cache, format_bytes, open_stage3_stream, Stage3Info, Stage3Stream,
+};
+use crate::cli::command::SyncMethod;
 use crate::cli::error::ChrootManagerError;
+use crate::cli::timing::{CreatePhase, PhaseTimings};
+use crate::config::Config;
+use crate::diagnostics;
 use crate::error::ChrootError;
+use crate::profile::selected::SelectedProfile;
+use crate::signals;
+use crate::space::{self, LowSpaceThreshold, SpaceEstimate};
+use crate::state::{SessionRecord, UserState};
+use chrono::{DateTime, Local};
 use colored::Colorize;
+use inquire::{Confirm, InquireError};
 use std::fs;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use crate::say;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+
+/// Parameters of a chroot creation, once the architecture and profile are resolved
+#[derive(Debug, Clone)]
+pub struct CreateRequest {
+    pub name: String,
+    pub profile: SelectedProfile,
+    pub options: CreateOptions,
+}
+
+/// Flags of the create command that alter the creation sequence
+#[derive(Debug, Clone)]
+pub struct CreateOptions {
+    /// Download the stage3 through the cache, otherwise stream it into tar
+    pub use_cache: bool,
+    /// Trim the stage3 cache to its budget after a download
+    pub evict_cache: bool,
+    /// Extract even if the chroot directory is not empty
+    pub force_extract: bool,
+    /// Fail instead of using the previous snapshot when the latest stage3 is missing
+    pub strict_latest: bool,
+    /// Write the stage3 and the chroot to a tmpfs or ramfs without asking
+    pub allow_tmpfs: bool,
+    /// Create the chroot on a filesystem mounted nodev, nosuid or noexec
+    pub ignore_fs_checks: bool,
+    /// Extract the files as owned by root, whatever the configuration says
+    pub no_same_owner: bool,
+    /// Check the OpenPGP signature of the stage3 on top of its SHA256
+    pub verify_signature: bool,
+    /// Fail before downloading or extracting when the free space is insufficient
+    pub check_space: bool,
+    /// Snapshot timestamp or date to install instead of the latest stage3
+    pub release: Option<String>,
+    /// Crawl the mirrors for the profiles even if the profile cache is fresh
+    pub refresh_profiles: bool,
+    /// Copy the Portage template of the configuration into the chroot
+    pub apply_template: bool,
+    /// Set the timezone, locale and hostname of the configuration in the chroot
+    pub localize: bool,
+    /// Sync the Portage tree once extracted, also done when `sync_on_create` is set
+    pub sync: bool,
+    /// Command syncing the tree
+    pub sync_method: SyncMethod,
+    /// Scripts run inside the chroot after the post-create hooks of the configuration
+    pub hooks: Vec<PathBuf>,
+    /// Leave the chroot in place when a hook fails
+    pub keep_on_hook_failure: bool,
+}
+
+/// Configuration applied to a chroot once extracted
+#[derive(Debug, Clone, Default)]
+pub struct ChrootSetup {
+    /// Directory copied into `/etc/portage`
+    pub template_dir: Option<PathBuf>,
+    pub localization: Option<Localization>,
+    /// Copy the CA bundle of the host along with resolv.conf and hosts
+    pub copy_host_ca_certs: bool,
+}
+
+impl ChrootSetup {
+    /// Setup from the configuration, without the parts the options turn off
+    pub fn from_config(config: &Config, apply_template: bool, localize: bool) -> Self {
+        Self {
+            template_dir: config.portage_template_dir.clone().filter(|_| apply_template),
+            localization: Localization::from_config(config).filter(|_| localize),
+            copy_host_ca_certs: config.copy_host_ca_certs,
+        }
+    }
+}
+
+/// Identity of a created chroot
+#[derive(Debug, Clone)]
+pub struct ChrootInfo {
+    pub name: String,
+    pub path: PathBuf,
+    pub profile: SelectedProfile,
+}
+
+/// Result of a successful chroot creation
+#[derive(Debug, Clone)]
+pub struct CreateOutcome {
+    pub chroot: ChrootInfo,
+    pub stage3: Stage3Info,
+    /// Whether the stage3 archive was served from the cache
+    pub cache_hit: bool,
+    pub duration: Duration,
+    /// Time spent in each phase of the creation
+    pub timings: PhaseTimings,
+    /// Estimated uncompressed size of the stage3, when known
+    pub estimated_size: Option<u64>,
+}
 
 /// Loads and validates chroot units from the base directory
 pub async fn load_chroot_units() -> Result<Vec<ChrootUnit>, ChrootManagerError> {
     let config = crate::cli::load_config().await?;
     let base_dir_display = config.chroot_base_dir.display();
-    println!("   📂 Chroot Directory: {base_dir_display}");
+    say!("   {} Chroot Directory: {base_dir_display}", Symbol::Folder);
 
     if !config.chroot_base_dir.exists() {
-        println!("   ❌ Chroot directory not found");
-        println!("   The directory will be created when the first chroot is created");
-        println!("   Make sure you have permissions to create chroots");
+        say!("   {} Chroot directory not found", Symbol::Error);
+        say!("   The directory will be created when the first chroot is created");
+        say!("   Make sure you have permissions to create chroots");
         return Ok(Vec::new());
     }
 
     let rd = fs::read_dir(&config.chroot_base_dir);
 
     if let Err(e) = rd {
-        println!("   ❌ Directory access error: {e}");
+        say!("   {} Directory access error: {e}", Symbol::Error);
         let base_dir_display = config.chroot_base_dir.display();
-        println!("   💡 Check permissions for: {base_dir_display}");
+        say!("   {} Check permissions for: {base_dir_display}", Symbol::Hint);
         return Ok(Vec::new());
     }
 
-    let dirs = rd
-        .unwrap()
-        .filter_map(|e| e.ok())
-        .map(|e| e.path())
-        .filter(|e| e.is_dir())
-        .collect::<Vec<_>>();
+    let mut dirs = Vec::new();
+    for entry in rd.unwrap() {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                say!("   {} Skipping an unreadable entry of {base_dir_display}: {e}", Symbol::Warning);
+                continue;
+            }
+        };
+        if !path.is_dir() {
+            continue;
+        }
+        match ChrootUnit::looks_like_chroot(&path) {
+            Ok(true) => dirs.push(path),
+            Ok(false) => log::debug!("Not a chroot, skipped: {}", path.display()),
+            Err(e) => say!("   {} Skipping {}: {e}", Symbol::Warning, path.display()),
+        }
+    }
 
-    match dirs
+    // A chroot that fails to load is skipped, the others are still listed
+    Ok(dirs
         .iter()
-        .map(|p| ChrootUnit::load(p))
-        .collect::<Result<Vec<ChrootUnit>, ChrootError>>()
-    {
-        Ok(units) => Ok(units),
+        .filter_map(|p| match ChrootUnit::load(p) {
+            Ok(unit) => Some(unit.with_config_mounts(&config)),
+            Err(e) => {
+                say!("   {} Skipping {}: {e}", Symbol::Warning, p.display());
+                None
+            }
+        })
+        .collect())
+}
+
+/// Names of the chroots of `base_dir`, sorted, none when it cannot be read
+pub(crate) fn chroot_names(base_dir: &Path) -> Vec<String> {
+    let Ok(dirs) = ChrootUnit::chroot_dirs(base_dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = dirs
+        .into_iter()
+        .filter_map(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Chroot directory `name` of `base_dir`, `None` when there is none
+///
+/// The name must be a single directory name, and the directory, symbolic
+/// links resolved, must lie inside `base_dir`.
+fn chroot_unit_in(base_dir: &Path, name: &str) -> Result<Option<ChrootUnit>, ChrootError> {
+    ChrootUnit::validate_name(name)?;
+    let chroot_path = base_dir.join(name);
+    if !chroot_path.is_dir() {
+        return Ok(None);
+    }
+    let unit = ChrootUnit::load(&chroot_path)?;
+    unit.ensure_inside(base_dir)?;
+    Ok(Some(unit))
+}
+
+/// Loads a single chroot unit by name
+///
+/// When no chroot with this name exists, the available chroot names are listed
+/// before returning an error.
+pub async fn find_chroot_unit(name: &str) -> Result<ChrootUnit, ChrootManagerError> {
+    let config = crate::cli::load_config().await?;
+    if let Some(unit) = chroot_unit_in(&config.chroot_base_dir, name)? {
+        return Ok(unit.with_config_mounts(&config));
+    }
+
+    say!("{}", format!("{} The chroot '{name}' does not exist.", Symbol::Warning).yellow().bold());
+    let available = ChrootUnit::find_units(&config).unwrap_or_default();
+    if available.is_empty() {
+        say!("   No chroots available");
+    } else {
+        say!("   Available chroots:");
+        for unit in &available {
+            say!("   {} {}", Symbol::Bullet, unit.name);
+        }
+    }
+
+    Err(ChrootManagerError::ChrootNotFound(name.to_string()))
+}
+
+/// Portage build directory, where a failed or huge emerge leaves its files
+const PORTAGE_TMPDIR: &str = "var/tmp/portage";
+
+/// Warn when the filesystem holding the chroot is running out of space
+///
+/// The largest package build directories are listed as the likely culprits.
+fn warn_low_space(chroot_unit: &ChrootUnit, threshold: &LowSpaceThreshold) {
+    let Some(fs_space) = space::filesystem_space(&chroot_unit.chroot_path) else {
+        log::debug!("Unable to query the free space of {}", chroot_unit.chroot_path.display());
+        return;
+    };
+    if !threshold.is_low(&fs_space) {
+        return;
+    }
+
+    println!(
+        "{}",
+        format!(
+            "{} Low disk space: {} free ({:.1}%) on the filesystem of '{}'",
+            Symbol::Warning,
+            format_bytes(fs_space.available),
+            fs_space.available_percent(),
+            chroot_unit.name
+        )
+        .yellow()
+        .bold()
+    );
+
+    let portage_tmpdir = chroot_unit.chroot_path.join(PORTAGE_TMPDIR);
+    let largest = space::largest_subdirectories(&portage_tmpdir, 2, 3);
+    if !largest.is_empty() {
+        println!("   Largest build directories in /{PORTAGE_TMPDIR}:");
+        for (path, size) in largest {
+            let relative = path.strip_prefix(&portage_tmpdir).unwrap_or(&path);
+            println!("   {} {} ({})", Symbol::Bullet, relative.display(), format_bytes(size));
+        }
+    }
+}
+
+/// Migrate outdated metadata while privileges are available
+///
+/// Failures are only reported, they do not prevent entering the chroot.
+pub fn upgrade_metadata(chroot_unit: &mut ChrootUnit) {
+    if chroot_unit.metadata.as_ref().is_none_or(|metadata| !metadata.is_outdated()) {
+        return;
+    }
+
+    match chroot_unit.migrate_metadata() {
+        Ok(_) => say!("{} Chroot metadata upgraded to the current format", Symbol::Refresh),
+        Err(e) => say!("{}", format!("{} Unable to upgrade the chroot metadata: {e}", Symbol::Warning).yellow()),
+    }
+}
+
+/// Enters a chroot environment interactively using a ChrootUnit
+///
+/// This function handles mounting, entering, and cleaning up the chroot,
+/// for both the interactive list and the enter command.
+/// When a threshold is given, the free space is checked on exit.
+pub fn enter_chroot_with_unit(
+    chroot_unit: &ChrootUnit,
+    space_threshold: Option<&LowSpaceThreshold>,
+    session: &SessionOptions,
+) -> Result<(), ChrootManagerError> {
+    // Show chroot info
+    say!("{} Found chroot: {}", Symbol::Success, chroot_unit.chroot_path.display());
+
+    if let Some(profile) = &chroot_unit.profile {
+        say!("{} Profile: {}", Symbol::Info, profile.to_string().cyan());
+    }
+    let extraction = chroot_unit.metadata.as_ref().and_then(|metadata| metadata.extraction);
+    if extraction.is_some_and(|extraction| !extraction.preserve_owner) {
+        say!(
+            "{}",
+            format!(
+                "{} This chroot was extracted without its file owners, setuid tools such as su, sudo or ping will not work",
+                Symbol::Warning
+            )
+            .yellow()
+        );
+    }
+
+    // Pre-authenticate
+    say!("{} Authenticating for privileged operations...", Symbol::Lock);
+    chroot_unit.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)?;
+    chroot_unit.ensure_emulation().map_err(ChrootManagerError::Chroot)?;
+
+    // Mount filesystems
+    say!("{} Mounting filesystems...", Symbol::Mount);
+    let mounts = chroot_unit.mount_filesystems().map_err(ChrootManagerError::Chroot)?;
+
+    let started_at = Local::now();
+    let result = {
+        let _shield = signals::shield_interrupts();
+        chroot_unit.enter_chroot_interactive(session)
+    };
+    if result.is_ok() {
+        record_session(&chroot_unit.name, started_at, None, None);
+    }
+
+    if let Some(threshold) = space_threshold {
+        warn_low_space(chroot_unit, threshold);
+    }
+
+    // Always try to unmount, even if chroot failed
+    say!("{} Cleaning up filesystems...", Symbol::Cleanup);
+    if let Err(e) = mounts.unmount() {
+        say!("{}", format!("{} Warning: Failed to unmount filesystems: {e}", Symbol::Warning).yellow());
+    } else {
+        say!("{}", format!("{} Filesystems unmounted successfully", Symbol::Success).green());
+    }
+
+    // Handle chroot result
+    match result {
+        Ok(()) => {
+            say!("{}", format!("{} Successfully exited chroot '{}'", Symbol::Success, chroot_unit.name).green());
+            Ok(())
+        }
         Err(e) => Err(ChrootManagerError::Chroot(e)),
     }
 }
 
+/// Add a session to the history of the chroot, only warning when the state cannot be saved
+pub(crate) fn record_session(
+    chroot_name: &str,
+    started_at: DateTime<Local>,
+    command: Option<&[String]>,
+    exit_code: Option<i32>,
+) {
+    let session = SessionRecord {
+        started_at,
+        duration_secs: u64::try_from((Local::now() - started_at).num_seconds()).unwrap_or(0),
+        command: command.map(|argv| argv.join(" ")),
+        exit_code,
+    };
+    let path = UserState::path();
+    let recorded = UserState::load(&path).and_then(|mut state| {
+        state.record_session(chroot_name, session);
+        state.save(&path)
+    });
+    if let Err(e) = recorded {
+        log::warn!("Unable to record the session of '{chroot_name}': {e}");
+    }
+}
+
+/// Variable answering yes to every confirmation, as `--yes` does
+pub const ASSUME_YES_ENV: &str = "CHROOTMANAGER_ASSUME_YES";
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+pub fn set_assume_yes(assume_yes: bool) {
+    ASSUME_YES.store(assume_yes, Ordering::Relaxed);
+}
+
+/// Whether `--yes` or [`ASSUME_YES_ENV`] skips the confirmations
+pub fn assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::Relaxed)
+}
+
+/// Whether [`ASSUME_YES_ENV`] is set to anything but empty, "0" or "false"
+pub fn assume_yes_from_env() -> bool {
+    std::env::var(ASSUME_YES_ENV)
+        .is_ok_and(|value| !matches!(value.trim().to_lowercase().as_str(), "" | "0" | "false" | "no"))
+}
+
+/// Fail instead of waiting for an answer that cannot come
+fn ensure_interactive() -> Result<(), ChrootManagerError> {
+    if !std::io::stdin().is_terminal() {
+        return Err(ChrootManagerError::Custom(
+            "Refusing to prompt in non-interactive mode, pass --yes".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Ask a yes/no question, `default` being the answer to a bare Enter
+///
+/// With `--yes` the answer is yes without asking. Escape answers no.
+pub(crate) fn confirm(question: &str, default: bool) -> Result<bool, ChrootManagerError> {
+    if assume_yes() {
+        return Ok(true);
+    }
+    ensure_interactive()?;
+    match Confirm::new(question).with_default(default).prompt() {
+        Ok(answer) => Ok(answer),
+        Err(InquireError::OperationCanceled) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Print a question and read the answer from stdin
+pub(crate) fn ask(question: &str) -> Result<String, ChrootManagerError> {
+    ensure_interactive()?;
+    print!("{question}");
+    std::io::stdout().flush().map_err(ChrootManagerError::Io)?;
+
+    let mut input = String::new();
+    std::io::stdin()
+        .read_line(&mut input)
+        .map_err(ChrootManagerError::Io)?;
+    Ok(input.trim().to_string())
+}
+
+/// Unmount the filesystems left mounted in a chroot, and make sure none remains
+fn force_unmount(chroot_unit: &ChrootUnit) -> Result<(), ChrootManagerError> {
+    say!("{}", format!("{} Unmounting the stale filesystems...", Symbol::Tool).yellow().bold());
+    chroot_unit.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)?;
+    match chroot_unit.unmount_filesystems() {
+        Ok(_) => Ok(()),
+        Err(ChrootError::StillMounted(remaining)) => {
+            say!("{} Filesystems still mounted:", Symbol::Error);
+            for mount_point in &remaining {
+                say!("   {} {}", Symbol::Bullet, mount_point.display());
+            }
+            Err(ChrootManagerError::Custom(format!(
+                "The chroot '{}' still has {} mounted filesystem(s); it was not deleted.",
+                chroot_unit.name,
+                remaining.len()
+            )))
+        }
+        Err(e) => Err(ChrootManagerError::Chroot(e)),
+    }
+}
+
+/// What deleting an existing chroot takes once the user agreed
+#[derive(Debug, PartialEq)]
+struct DeletionPlan {
+    /// Unmount the stale filesystems first, giving up if any remains
+    unmount: bool,
+    /// Have the user type the chroot name
+    confirm_name: bool,
+}
+
+impl DeletionPlan {
+    /// Plan from the filesystems mounted in the chroot
+    ///
+    /// `assume_yes` skips typing the name, not the unmounting.
+    fn for_mounts(mounts: &[mounts::MountEntry], assume_yes: bool) -> Self {
+        Self {
+            unmount: !mounts.is_empty(),
+            confirm_name: !mounts.is_empty() && !assume_yes,
+        }
+    }
+}
+
 /// Checks if a chroot already exists and handles the case
-pub fn handle_existing_chroot(chroot_unit: &ChrootUnit) -> Result<bool, ChrootManagerError> {
-    if chroot_unit.chroot_path.exists() {
-        let chroot_name = &chroot_unit.name;
+///
+/// A chroot with filesystems still mounted (left over by a crash) is only
+/// deleted once they are all unmounted and the user typed its name. A
+/// directory outside the chroot base directory is never deleted.
+pub fn handle_existing_chroot(chroot_unit: &ChrootUnit, config: &Config) -> Result<bool, ChrootManagerError> {
+    if !chroot_unit.chroot_path.exists() {
+        return Ok(false);
+    }
+    chroot_unit
+        .ensure_inside(&config.chroot_base_dir)
+        .map_err(ChrootManagerError::Chroot)?;
+
+    let chroot_name = &chroot_unit.name;
+    println!(
+        "{}",
+        format!("{} The chroot '{chroot_name}' already exists.", Symbol::Warning)
+            .yellow()
+            .bold()
+    );
+
+    let mounts = chroot_unit.active_mounts().map_err(ChrootManagerError::Chroot)?;
+    if !mounts.is_empty() {
         println!(
             "{}",
-            format!("⚠️ The chroot '{chroot_name}' already exists.")
-                .yellow()
-                .bold()
+            format!("{} It still has {} active mount(s):", Symbol::Warning, mounts.len()).yellow()
         );
-        print!("Do you want to delete and recreate it? (y/N): ");
-        std::io::stdout().flush().map_err(ChrootManagerError::Io)?;
-
-        let mut input = String::new();
-        std::io::stdin()
-            .read_line(&mut input)
-            .map_err(ChrootManagerError::Io)?;
-
-        if input.trim().to_lowercase().starts_with('y') {
-            println!("{}", "🗑️ Removing the old chroot...".red().bold());
-            chroot_unit.cleanup(true).map_err(ChrootManagerError::Chroot)?;
-            println!("✅ Old chroot deleted");
-            Ok(true)
-        } else {
-            let chroot_name = &chroot_unit.name;
-            Err(ChrootManagerError::Custom(format!(
-                "The chroot '{chroot_name}' already exists. Use another name or delete it first."
-            )))
+        for mount in &mounts {
+            println!("   {} {} ({})", Symbol::Bullet, mount.mount_point.display(), mount.fstype);
         }
-    } else {
-        Ok(false)
+    }
+
+    // The layout of a chroot created by a newer version is unknown
+    ChrootUnit::load(&chroot_unit.chroot_path)
+        .and_then(|existing| existing.ensure_supported_metadata())
+        .map_err(ChrootManagerError::Chroot)?;
+
+    if !confirm("Do you want to delete and recreate it?", false)? {
+        return Err(ChrootManagerError::Custom(format!(
+            "The chroot '{chroot_name}' already exists. Use another name or delete it first."
+        )));
+    }
+
+    let plan = DeletionPlan::for_mounts(&mounts, assume_yes());
+    if plan.unmount {
+        force_unmount(chroot_unit)?;
+    }
+    if plan.confirm_name {
+        let typed = ask(&format!(
+            "Type the chroot name to confirm deletion of a chroot with active mounts ({chroot_name}): "
+        ))?;
+        if typed != *chroot_name {
+            return Err(ChrootManagerError::Custom(format!(
+                "Deletion of the chroot '{chroot_name}' cancelled."
+            )));
+        }
+    }
+
+    say!("{}", format!("{} Removing the old chroot...", Symbol::Trash).red().bold());
+    chroot_unit
+        .cleanup(Some(&config.chroot_base_dir))
+        .map_err(ChrootManagerError::Chroot)?;
+    say!("{} Old chroot deleted", Symbol::Success);
+
+    let path = UserState::path();
+    let forgotten = UserState::load(&path).and_then(|mut state| {
+        if state.forget_sessions(chroot_name) {
+            state.save(&path)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = forgotten {
+        log::warn!("Unable to forget the sessions of '{chroot_name}': {e}");
+    }
+    Ok(true)
+}
+
+/// Remove the chroot directory after a failed or interrupted extraction
+///
+/// Filesystems mounted below it are not crossed.
+fn remove_partial_chroot(chroot_unit: &ChrootUnit) {
+    let path = chroot_unit.chroot_path.to_string_lossy();
+    match chroot_unit.execute_elevated("rm", &["-rf", "--one-file-system", &path]) {
+        Ok(_) => say!("{} Removed the partially extracted chroot {path}", Symbol::Cleanup),
+        Err(e) => log::warn!("Unable to remove the partially extracted chroot {path}: {e}"),
     }
 }
 
 /// Finalizes chroot creation with common steps
+///
+/// The stage3 is only extracted into an empty directory unless `force_extract`
+/// is set. The extraction options are recorded in the metadata, then the
+/// setup is applied. Returns the time spent extracting and finalizing.
 pub async fn finalize_chroot_creation(
     chroot_unit: &ChrootUnit,
-    cached_path: &std::path::Path,
-) -> Result<(), ChrootManagerError> {
+    source: Stage3Source<'_>,
+    stage3: Option<&Stage3Info>,
+    force_extract: bool,
+    extraction: &ExtractionOptions,
+    setup: &ChrootSetup,
+) -> Result<PhaseTimings, ChrootManagerError> {
+    let mut timings = PhaseTimings::default();
+
+    diagnostics::set_phase(CreatePhase::Extract.label());
+    let started = Instant::now();
     chroot_unit.prepare_chroot_directory().await.map_err(ChrootManagerError::Chroot)?;
-    chroot_unit.extract_stage3(cached_path).await.map_err(ChrootManagerError::Chroot)?;
-    chroot_unit.copy_dns_info().map_err(ChrootManagerError::Chroot)?;
-    chroot_unit.write_arch_profile_info().map_err(ChrootManagerError::Chroot)?;
+    if force_extract {
+        log::warn!("Extracting over the existing content of {}", chroot_unit.chroot_path.display());
+    } else {
+        chroot_unit.ensure_empty_for_extraction().map_err(ChrootManagerError::Chroot)?;
+    }
+    // A termination signal stops the extraction, whose failure removes what it extracted
+    let operation = signals::start_operation();
+    // The directory was empty, an interrupted or failed creation only leaves what it extracted
+    let _partial_guard = (!force_extract).then(|| {
+        let unit = chroot_unit.clone();
+        let description = format!("remove the partially extracted chroot {}", unit.chroot_path.display());
+        signals::on_termination(description, move || remove_partial_chroot(&unit))
+    });
+    if let Err(e) = chroot_unit.extract_stage3(source, extraction).await {
+        if force_extract {
+            log::warn!("Leaving {}, it was extracted over existing content", chroot_unit.chroot_path.display());
+        } else {
+            remove_partial_chroot(chroot_unit);
+        }
+        return Err(ChrootManagerError::Chroot(e));
+    }
+    drop(operation);
+    timings.record(CreatePhase::Extract, started.elapsed());
+
+    diagnostics::set_phase(CreatePhase::Finalize.label());
+    let started = Instant::now();
+    chroot_unit.verify_architecture().map_err(ChrootManagerError::Chroot)?;
+    chroot_unit
+        .copy_network_info(setup.copy_host_ca_certs)
+        .map_err(ChrootManagerError::Chroot)?;
+    if let Some(template_dir) = &setup.template_dir {
+        let copied = chroot_unit
+            .apply_portage_template(template_dir)
+            .map_err(ChrootManagerError::Chroot)?;
+        say!("{} Portage template applied ({copied} file(s) from {})", Symbol::Success, template_dir.display());
+    }
+    if let Some(localization) = &setup.localization {
+        chroot_unit.localize(localization).map_err(ChrootManagerError::Chroot)?;
+        say!("{} Timezone, locale and hostname set", Symbol::Success);
+    }
+    if let Err(e) = chroot_unit.install_emulator() {
+        // The emulator is copied again on entering, once the host is set up
+        say!("{}", format!("{} {e}", Symbol::Warning).yellow());
+    }
+    chroot_unit
+        .write_metadata(
+            stage3.map(|stage3| stage3.filename.as_str()),
+            stage3.and_then(|stage3| stage3.sha256.as_deref()),
+            stage3.and_then(|stage3| stage3.release.as_deref()),
+            stage3.and_then(|stage3| stage3.mirror.as_deref()),
+            extraction,
+        )
+        .map_err(ChrootManagerError::Chroot)?;
+    timings.record(CreatePhase::Finalize, started.elapsed());
+
+    Ok(timings)
+}
+
+/// Syncs the Portage tree of the new chroot, with the output of the command shown
+///
+/// A failure is only reported: the chroot is usable without a tree, which
+/// can be synced later from inside it.
+fn sync_portage_tree(chroot_unit: &ChrootUnit, method: SyncMethod) {
+    let argv = method.command();
+    say!("{} Syncing the Portage tree ({})...", Symbol::Refresh, argv.join(" "));
+    let result = chroot_unit
+        .pre_authenticate_operations()
+        .and_then(|()| chroot_unit.ensure_emulation())
+        .and_then(|()| {
+            // Interrupts are meant for the sync, which then fails
+            let _shield = signals::shield_interrupts();
+            chroot_unit.exec_command(&argv, &SessionOptions::default())
+        });
+
+    match result {
+        Ok(status) if status.success() => {
+            if let Err(e) = chroot_unit.record_portage_sync() {
+                log::warn!("Unable to record the Portage tree sync in the metadata: {e}");
+            }
+            say!("{} Portage tree synced", Symbol::Success);
+        }
+        Ok(status) => say!(
+            "{}",
+            format!("{} {} failed ({status}), sync the tree from inside the chroot later", Symbol::Warning, argv.join(" "))
+                .yellow()
+        ),
+        Err(e) => say!(
+            "{}",
+            format!("{} Unable to sync the Portage tree: {e}", Symbol::Warning).yellow()
+        ),
+    }
+}
+
+/// Runs the post-create hooks in the new chroot, with its filesystems mounted
+///
+/// Stops at the first failing hook. Unless `keep_on_failure` is set, the
+/// chroot is then deleted.
+fn run_post_create_hooks(
+    chroot_unit: &ChrootUnit,
+    hooks: &[PathBuf],
+    keep_on_failure: bool,
+    config: &Config,
+) -> Result<(), ChrootManagerError> {
+    let result = (|| -> Result<(), ChrootError> {
+        chroot_unit.pre_authenticate_operations()?;
+        chroot_unit.ensure_emulation()?;
+        say!("{} Mounting filesystems...", Symbol::Mount);
+        let mounts = chroot_unit.mount_filesystems()?;
+
+        let result = hooks.iter().try_for_each(|hook| {
+            say!("{} Running the hook {}...", Symbol::Tool, hook.display());
+            // Interrupts are meant for the hook, which then fails
+            let _shield = signals::shield_interrupts();
+            chroot_unit.run_hook(hook)
+        });
+
+        let unmounted = mounts.unmount();
+        result.and(unmounted)
+    })();
+
+    let Err(e) = result else {
+        say!("{} {} hook(s) run successfully", Symbol::Success, hooks.len());
+        return Ok(());
+    };
+    say!("{} {e}", Symbol::Error);
+    if keep_on_failure {
+        say!(
+            "{}",
+            format!("{} The chroot was kept for inspection: {}", Symbol::Warning, chroot_unit.chroot_path.display())
+                .yellow()
+        );
+    } else if let Err(cleanup_error) = chroot_unit.cleanup(Some(&config.chroot_base_dir)) {
+        log::warn!("Unable to remove the chroot after the hook failure: {cleanup_error}");
+    } else {
+        say!("{} Removed the chroot {}", Symbol::Cleanup, chroot_unit.chroot_path.display());
+    }
+    Err(ChrootManagerError::Chroot(e))
+}
+
+/// Extract a streamed stage3 and finalize the chroot
+///
+/// The download and the extraction overlap, both are timed as the
+/// extraction. When the stream fails, a hash mismatch included, the
+/// partially extracted chroot is removed by [`finalize_chroot_creation`],
+/// unless it was extracted over existing content.
+async fn extract_stage3_stream(
+    chroot_unit: &ChrootUnit,
+    mut stream: Stage3Stream,
+    stage3: &Stage3Info,
+    force_extract: bool,
+    extraction: &ExtractionOptions,
+    setup: &ChrootSetup,
+) -> Result<PhaseTimings, ChrootManagerError> {
+    let mut timings = stream.timings;
+    let compression = ArchiveCompression::from_extension(std::path::Path::new(&stream.filename));
+    let result = finalize_chroot_creation(
+        chroot_unit,
+        Stage3Source::Stream(&mut *stream.reader, compression),
+        Some(stage3),
+        force_extract,
+        extraction,
+        setup,
+    )
+    .await;
+    say!(); // New line after the progress bar
+
+    match result {
+        Ok(finalize_timings) => {
+            if let Some(algorithm) = stream.algorithm {
+                say!("{} {algorithm} verification of the stream successful", Symbol::Success);
+            }
+            timings.extend(finalize_timings);
+            Ok(timings)
+        }
+        Err(error @ ChrootManagerError::Chroot(ChrootError::Stage3Stream(_))) => {
+            say!("{} {error}", Symbol::Error);
+            Err(error)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Check that the extraction fits in the free space, warn when it exceeds the quota
+///
+/// With `enforce` unset, a lack of free space is only a warning and the
+/// extraction is attempted anyway.
+fn check_space(
+    config: &Config,
+    stage3_path: &std::path::Path,
+    enforce: bool,
+) -> Result<SpaceEstimate, ChrootManagerError> {
+    let estimate = space::estimate(stage3_path, &config.chroot_base_dir);
+
+    let Some(required) = estimate.required else {
+        log::debug!("Uncompressed size of {} unknown", stage3_path.display());
+        return Ok(estimate);
+    };
+    say!("{} Estimated uncompressed size: {}", Symbol::Stats, format_bytes(required));
+
+    if estimate.exceeds_available() {
+        let available = estimate.available.unwrap_or_default();
+        if enforce {
+            return Err(ChrootManagerError::Chroot(ChrootError::InsufficientSpace {
+                path: config.chroot_base_dir.clone(),
+                needed: required,
+                available,
+            }));
+        }
+        say!(
+            "{}",
+            format!(
+                "{} Only {} free on the chroot filesystem, the extraction may fail",
+                Symbol::Warning,
+                format_bytes(available)
+            )
+            .yellow()
+        );
+    }
+
+    if let Some(quota) = config.chroot_fs_quota() {
+        let used: u64 = ChrootUnit::find_units(config)
+            .map(|units| units.iter().map(|unit| unit.disk_usage()).sum())
+            .unwrap_or_default();
+        if estimate.exceeds_quota(used, quota) {
+            say!(
+                "{}",
+                format!(
+                    "{} Chroots use {}, the new one would exceed the {} quota",
+                    Symbol::Warning,
+                    format_bytes(used),
+                    format_bytes(quota)
+                )
+                .yellow()
+            );
+        }
+    }
+
+    Ok(estimate)
+}
+
+/// Refuse to create the chroot on a filesystem mounted nodev, nosuid or noexec
+///
+/// Such a chroot extracts fine but its device nodes, setuid programs or
+/// binaries then fail in confusing ways. With `ignore`, only a warning is
+/// printed. Nothing is checked when the mount table cannot be read.
+fn check_chroot_filesystem(config: &Config, ignore: bool) -> Result<(), ChrootManagerError> {
+    let entry = match mounts::filesystem_of(&config.chroot_base_dir) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            log::debug!("Unable to find the filesystem of {}: {e}", config.chroot_base_dir.display());
+            return Ok(());
+        }
+    };
+    let options = entry.restrictive_options();
+    if options.is_empty() {
+        return Ok(());
+    }
+
+    if ignore {
+        println!(
+            "{}",
+            format!(
+                "{} {} is mounted {}, the chroot may not work properly",
+                Symbol::Warning,
+                entry.mount_point.display(),
+                options.join(",")
+            )
+            .yellow()
+        );
+        return Ok(());
+    }
+    Err(ChrootManagerError::Chroot(ChrootError::RestrictiveMount {
+        path: config.chroot_base_dir.clone(),
+        mount_point: entry.mount_point.clone(),
+        options: options.join(","),
+    }))
+}
+
+/// Memory-backed filesystem holding a directory used by the creation
+struct MemoryBackedDir<'a> {
+    role: &'a str,
+    path: &'a std::path::Path,
+    fstype: String,
+    mount_point: PathBuf,
+}
+
+/// Refuse to write the stage3 or the chroot to RAM unless allowed
+///
+/// A stage3 and its extracted tree take a few GB, enough to exhaust memory
+/// on a live system where the home directory is a tmpfs. Without
+/// `allow_tmpfs`, a terminal user is asked to confirm, as is `--yes`;
+/// otherwise it is an error.
+fn check_memory_backed_dirs(config: &Config, allow_tmpfs: bool) -> Result<(), ChrootManagerError> {
+    let table = match mounts::read_mount_table() {
+        Ok(table) => table,
+        Err(e) => {
+            log::debug!("Unable to read the mount table: {e}");
+            return Ok(());
+        }
+    };
+
+    let dirs = [
+        ("Stage3 cache", config.stage3_cache_dir.as_path()),
+        ("Chroot directory", config.chroot_base_dir.as_path()),
+    ];
+    let memory_backed: Vec<MemoryBackedDir> = dirs
+        .into_iter()
+        .filter_map(|(role, path)| {
+            let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            let entry = mounts::mount_containing(&table, &canonical)?;
+            mounts::is_memory_backed(&entry.fstype).then(|| MemoryBackedDir {
+                role,
+                path,
+                fstype: entry.fstype.clone(),
+                mount_point: entry.mount_point.clone(),
+            })
+        })
+        .collect();
+    if memory_backed.is_empty() {
+        return Ok(());
+    }
 
     println!(
         "{}",
-        format!("✅ Chroot '{}' created successfully!", chroot_unit.name)
+        format!("{} The stage3 and the chroot would be written to memory:", Symbol::Warning)
+            .yellow()
+            .bold()
+    );
+    for dir in &memory_backed {
+        let size = match space::filesystem_space(&dir.mount_point) {
+            Some(fs_space) if fs_space.total > 0 => format_bytes(fs_space.total),
+            _ => "no size limit".to_string(),
+        };
+        println!(
+            "   {} {}: {} is on a {} mounted at {} ({size})",
+            Symbol::Bullet,
+            dir.role,
+            dir.path.display(),
+            dir.fstype,
+            dir.mount_point.display()
+        );
+    }
+    println!("   A chroot takes a few GB and may exhaust the memory of this machine.");
+
+    if allow_tmpfs {
+        return Ok(());
+    }
+    // Without a terminal, the error below names --allow-tmpfs
+    if (std::io::stdin().is_terminal() || assume_yes()) && confirm("Do you want to continue anyway?", false)? {
+        return Ok(());
+    }
+    Err(ChrootManagerError::Custom(
+        "Refusing to create a chroot in memory. Configure other directories or use --allow-tmpfs".to_string(),
+    ))
+}
+
+/// Runs the whole creation sequence shared by every create front-end
+///
+/// The preflight checks, the stage3 download, the existing chroot check and
+/// the extraction are performed in order, so that an existing chroot is not
+/// deleted for a creation bound to fail. The outcome is returned for
+/// rendering.
+pub async fn perform_create(
+    config: &Config,
+    request: &CreateRequest,
+) -> Result<CreateOutcome, ChrootManagerError> {
+    let start = Instant::now();
+
+    let chroot_unit = ChrootUnit::new(request.name.clone(), Some(&request.profile), config)
+        .await
+        .map_err(ChrootManagerError::Chroot)?;
+
+    log::debug!("chroot path: {:?}", chroot_unit.chroot_path);
+
+    // A missing hook is found before anything is downloaded
+    let hooks: Vec<PathBuf> = config
+        .post_create_hooks
+        .iter()
+        .chain(&request.options.hooks)
+        .cloned()
+        .collect();
+    if let Some(missing) = hooks.iter().find(|hook| !hook.is_file()) {
+        return Err(ChrootManagerError::Chroot(ChrootError::HookNotFound(missing.clone())));
+    }
+    let setup = ChrootSetup::from_config(config, request.options.apply_template, request.options.localize);
+    if let Some(template_dir) = &setup.template_dir {
+        if !template_dir.is_dir() {
+            return Err(ChrootManagerError::Custom(format!(
+                "The Portage template directory {} does not exist (portage_template_dir), use --no-template to skip it",
+                template_dir.display()
+            )));
+        }
+    }
+
+    // Refused before anything is downloaded, or an existing chroot deleted
+    check_memory_backed_dirs(config, request.options.allow_tmpfs)?;
+    check_chroot_filesystem(config, request.options.ignore_fs_checks)?;
+
+    let mut extraction = ExtractionOptions::from_config(config);
+    if request.options.no_same_owner {
+        extraction.preserve_owner = false;
+    }
+
+
+    let (stage3, cache_hit, mut timings, estimated_size) = if request.options.use_cache {
+        let download = download_stage3_with_cache(&request.profile, config, &request.options).await?;
+        let mut timings = download.timings;
+        let estimate = check_space(config, &download.path, request.options.check_space)?;
+        // An existing chroot is deleted only once every check has passed
+        handle_existing_chroot(&chroot_unit, config)?;
+        let stage3 = Stage3Info {
+            filename: download.filename,
+            path: Some(download.path.clone()),
+            sha256: download.sha256,
+            release: download.release,
+            mirror: download.mirror,
+        };
+        timings.extend(
+            finalize_chroot_creation(
+                &chroot_unit,
+                Stage3Source::File(&download.path),
+                Some(&stage3),
+                request.options.force_extract,
+                &extraction,
+                &setup,
+            )
+            .await?,
+        );
+        (stage3, download.cache_hit, timings, estimate.required)
+    } else {
+        let stream = open_stage3_stream(&request.profile, config, &request.options).await?;
+        handle_existing_chroot(&chroot_unit, config)?;
+        let stage3 = Stage3Info {
+            filename: stream.filename.clone(),
+            path: None,
+            sha256: stream.sha256.clone(),
+            release: stream.release.clone(),
+            mirror: stream.mirror.clone(),
+        };
+        let timings =
+            extract_stage3_stream(
+                &chroot_unit,
+                stream,
+                &stage3,
+                request.options.force_extract,
+                &extraction,
+                &setup,
+            )
+            .await?;
+        (stage3, false, timings, None)
+    };
+
+    if request.options.sync || config.sync_on_create {
+        diagnostics::set_phase(CreatePhase::Sync.label());
+        let started = Instant::now();
+        sync_portage_tree(&chroot_unit, request.options.sync_method);
+        timings.record(CreatePhase::Sync, started.elapsed());
+    }
+
+    if !hooks.is_empty() {
+        diagnostics::set_phase(CreatePhase::Hooks.label());
+        let started = Instant::now();
+        run_post_create_hooks(&chroot_unit, &hooks, request.options.keep_on_hook_failure, config)?;
+        timings.record(CreatePhase::Hooks, started.elapsed());
+    }
+
+    Ok(CreateOutcome {
+        chroot: ChrootInfo {
+            name: chroot_unit.name,
+            path: chroot_unit.chroot_path,
+            profile: request.profile.clone(),
+        },
+        stage3,
+        cache_hit,
+        duration: start.elapsed(),
+        timings,
+        estimated_size,
+    })
+}
+
+/// Format a duration compactly (e.g., "45s", "3m12s", "1h02m")
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{secs}s")
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+/// Displays the result of a chroot creation
+pub fn display_create_outcome(outcome: &CreateOutcome) {
+    if output::is_quiet() {
+        println!("{}", outcome.chroot.path.display());
+        return;
+    }
+    say!(
+        "{}",
+        format!("{} Chroot '{}' created successfully!", Symbol::Success, outcome.chroot.name)
             .green()
             .bold()
     );
-    let chroot_path_display = chroot_unit.chroot_path.display();
-    println!("📍 Path: {chroot_path_display}");
+    let chroot_path_display = outcome.chroot.path.display();
+    say!("{} Path: {chroot_path_display}", Symbol::Location);
+    say!("{} Profile: {}", Symbol::Info, outcome.chroot.profile);
 
-    Ok(())
-}
\ No newline at end of file
+    let source = match (&outcome.stage3.path, outcome.cache_hit) {
+        (_, true) => "cache",
+        (Some(_), false) => "download",
+        (None, false) => "stream",
+    };
+    say!(
+        "{} Created in {} from {} ({source})",
+        Symbol::Timer,
+        format_duration(outcome.duration),
+        outcome.stage3.filename
+    );
+    if let Some(size) = outcome.estimated_size {
+        say!("{} Uncompressed size: ~{}", Symbol::Stats, format_bytes(size));
+    }
+    say!("   {}", outcome.timings.summary().dimmed());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    /// Base directory with a chroot skeleton and the usual strays next to it
+    fn base_dir_with_strays() -> tempfile::TempDir {
+        let base_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(base_dir.path().join("gentoo/etc")).unwrap();
+        fs::create_dir_all(base_dir.path().join(OsStr::from_bytes(b"bad\xffname/etc"))).unwrap();
+        fs::create_dir_all(base_dir.path().join(".trash/etc")).unwrap();
+        fs::create_dir(base_dir.path().join("empty")).unwrap();
+        fs::write(base_dir.path().join("notes.txt"), "not a chroot").unwrap();
+        base_dir
+    }
+
+    #[test]
+    fn chroot_names_skip_hidden_and_non_chroot_directories() {
+        let base_dir = base_dir_with_strays();
+        assert_eq!(chroot_names(base_dir.path()), ["bad\u{fffd}name", "gentoo"]);
+        assert!(chroot_names(&base_dir.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn find_units_loads_non_utf8_names_lossily() {
+        let base_dir = base_dir_with_strays();
+        let config = Config { chroot_base_dir: base_dir.path().to_path_buf(), ..Config::default() };
+        let units = ChrootUnit::find_units(&config).unwrap();
+        let unit = units.iter().find(|unit| unit.name == "bad\u{fffd}name").unwrap();
+        assert_eq!(unit.chroot_path.file_name().unwrap().as_bytes(), b"bad\xffname");
+        assert_eq!(unit.profile_label(), "Undefined");
+    }
+
+    #[test]
+    fn chroot_names_cannot_escape_the_base_directory() {
+        let base_dir = base_dir_with_strays();
+        for name in ["..", ".", "", "/etc", "a/../../x", "gentoo/etc"] {
+            assert!(
+                matches!(chroot_unit_in(base_dir.path(), name), Err(ChrootError::InvalidName(_))),
+                "{name}"
+            );
+        }
+
+        std::os::unix::fs::symlink("/etc", base_dir.path().join("elsewhere")).unwrap();
+        assert!(matches!(
+            chroot_unit_in(base_dir.path(), "elsewhere"),
+            Err(ChrootError::OutsideBaseDir { .. })
+        ));
+
+        assert_eq!(chroot_unit_in(base_dir.path(), "gentoo").unwrap().unwrap().name, "gentoo");
+        assert!(chroot_unit_in(base_dir.path(), "missing").unwrap().is_none());
+    }
+
+    /// Request creating `gentoo`, with the checks of a plain `create` run
+    fn create_request(allow_tmpfs: bool) -> CreateRequest {
+        let options = CreateOptions {
+            use_cache: true,
+            evict_cache: false,
+            force_extract: false,
+            strict_latest: false,
+            allow_tmpfs,
+            ignore_fs_checks: false,
+            no_same_owner: false,
+            verify_signature: true,
+            check_space: true,
+            release: None,
+            refresh_profiles: false,
+            apply_template: false,
+            localize: false,
+            sync: false,
+            sync_method: SyncMethod::Webrsync,
+            hooks: Vec::new(),
+            keep_on_hook_failure: false,
+        };
+        let profile = SelectedProfile::new("amd64".to_string(), "openrc".to_string());
+        CreateRequest { name: "gentoo".to_string(), profile, options }
+    }
+
+    #[test]
+    fn an_existing_chroot_survives_the_refusal_of_a_tmpfs() {
+        let shm = Path::new("/dev/shm");
+        let memory_backed = mounts::filesystem_of(shm)
+            .ok()
+            .flatten()
+            .is_some_and(|entry| mounts::is_memory_backed(&entry.fstype));
+        // Without a terminal the refusal is not prompted for
+        if !memory_backed || std::io::stdin().is_terminal() {
+            return;
+        }
+        let base_dir = tempfile::tempdir_in(shm).unwrap();
+        fs::create_dir_all(base_dir.path().join("gentoo/etc")).unwrap();
+        let config = Config {
+            chroot_base_dir: base_dir.path().to_path_buf(),
+            stage3_cache_dir: base_dir.path().join(".cache"),
+            ..Config::default()
+        };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(perform_create(&config, &create_request(false)));
+        assert!(matches!(result, Err(ChrootManagerError::Custom(message)) if message.contains("--allow-tmpfs")));
+        assert!(base_dir.path().join("gentoo/etc").is_dir());
+    }
+
+    #[test]
+    fn an_existing_chroot_survives_the_refusal_of_a_restrictive_mount() {
+        // /dev/shm and /run are usually mounted nosuid,nodev
+        let restrictive = ["/dev/shm", "/run", "/tmp"].into_iter().map(Path::new).find(|dir| {
+            mounts::filesystem_of(dir).ok().flatten().is_some_and(|entry| !entry.restrictive_options().is_empty())
+        });
+        let Some(dir) = restrictive else {
+            return;
+        };
+        let Ok(base_dir) = tempfile::tempdir_in(dir) else {
+            return;
+        };
+        fs::create_dir_all(base_dir.path().join("gentoo/etc")).unwrap();
+        let config = Config {
+            chroot_base_dir: base_dir.path().to_path_buf(),
+            stage3_cache_dir: base_dir.path().join(".cache"),
+            ..Config::default()
+        };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(perform_create(&config, &create_request(true)));
+        assert!(matches!(result, Err(ChrootManagerError::Chroot(ChrootError::RestrictiveMount { .. }))));
+        assert!(base_dir.path().join("gentoo/etc").is_dir());
+    }
+
+    #[test]
+    fn deleting_a_chroot_with_stale_mounts_unmounts_then_asks_for_its_name() {
+        let table = mounts::parse_mountinfo(
+            r"22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw
+51 22 0:21 / /srv/chroots/gentoo/proc rw,relatime - proc proc rw
+53 22 0:5 / /srv/chroots/gentoo/dev rw,nosuid master:2 - devtmpfs devtmpfs rw
+60 22 0:21 / /srv/chroots/gentoo-old/proc rw,relatime - proc proc rw
+",
+        );
+
+        let stale = mounts::mounts_under(&table, Path::new("/srv/chroots/gentoo"));
+        assert_eq!(stale.len(), 2);
+        let plan = DeletionPlan::for_mounts(&stale, false);
+        assert_eq!(plan, DeletionPlan { unmount: true, confirm_name: true });
+        // --yes does not skip the unmounting
+        assert_eq!(DeletionPlan::for_mounts(&stale, true), DeletionPlan { unmount: true, confirm_name: false });
+
+        let clean = mounts::mounts_under(&table, Path::new("/srv/chroots/other"));
+        assert_eq!(DeletionPlan::for_mounts(&clean, false), DeletionPlan { unmount: false, confirm_name: false });
+    }
+}
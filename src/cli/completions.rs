@@ -0,0 +1,216 @@
+//! `chrootmanager completions <shell>`: shell completion scripts
+//!
+//! The flags and subcommands are completed by the script clap_complete
+//! generates from the clap definition. Chroot names and profiles depend on
+//! the host, the script gets them at runtime from the hidden `__complete`
+//! command, which only reads the configuration, the chroot base directory
+//! and the profile cache: no network, no prompt, and nothing but the
+//! candidates on stdout, even on error.
+
+use crate::cli::command::{Cli, CompleteTarget};
+use crate::cli::common::chroot_names;
+use crate::cli::error::ChrootManagerError;
+use crate::config::Config;
+use crate::profile::parser::ProfileCache;
+use clap::{CommandFactory, ValueEnum};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Write};
+
+/// Name of the program the scripts complete
+const BIN_NAME: &str = "chrootmanager";
+
+/// Subcommands whose first argument is the name of an existing chroot
+const CHROOT_SUBCOMMANDS: &[&str] = &["enter", "exec", "info", "rename", "clone", "unmount", "migrate"];
+
+/// Shells with completion scripts
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Completion of chroot names and profiles, appended to the bash script
+const BASH_DYNAMIC: &str = r#"
+_chrootmanager_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local i word subcommand="" positionals=0 arch=""
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word="${COMP_WORDS[i]}"
+        case "$word" in
+            -a|--arch) arch="${COMP_WORDS[i+1]}" ;;
+            --arch=*) arch="${word#--arch=}" ;;
+            -*) ;;
+            *) if [[ -z "$subcommand" ]]; then subcommand="$word"; else ((positionals++)); fi ;;
+        esac
+    done
+    if [[ "$subcommand" == "create" && ( "$prev" == "-p" || "$prev" == "--profile" ) ]]; then
+        COMPREPLY=($(compgen -W "$(chrootmanager __complete profiles ${arch:+--arch "$arch"} 2>/dev/null)" -- "$cur"))
+        return 0
+    fi
+    case "$subcommand" in
+        @CHROOT_SUBCOMMANDS@)
+            if [[ "$cur" != -* && $positionals -eq 0 ]]; then
+                COMPREPLY=($(compgen -W "$(chrootmanager __complete chroots 2>/dev/null)" -- "$cur"))
+                return 0
+            fi
+            ;;
+    esac
+    _chrootmanager "$@"
+}
+
+if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+    complete -F _chrootmanager_dynamic -o nosort -o bashdefault -o default chrootmanager
+else
+    complete -F _chrootmanager_dynamic -o bashdefault -o default chrootmanager
+fi
+"#;
+
+/// Completion of chroot names and profiles, appended to the zsh script
+const ZSH_DYNAMIC: &str = r#"
+_chrootmanager_dynamic() {
+    local subcommand="" arch="" word
+    local -i i positionals=0
+    for ((i = 2; i < CURRENT; i++)); do
+        word="${words[i]}"
+        case "$word" in
+            -a|--arch) arch="${words[i+1]}" ;;
+            --arch=*) arch="${word#--arch=}" ;;
+            -*) ;;
+            *) if [[ -z "$subcommand" ]]; then subcommand="$word"; else ((positionals++)); fi ;;
+        esac
+    done
+    local -a candidates
+    if [[ "$subcommand" == "create" && ( "${words[CURRENT-1]}" == "-p" || "${words[CURRENT-1]}" == "--profile" ) ]]; then
+        candidates=(${(f)"$(chrootmanager __complete profiles ${arch:+--arch "$arch"} 2>/dev/null)"})
+        compadd -a candidates
+        return
+    fi
+    case "$subcommand" in
+        @CHROOT_SUBCOMMANDS@)
+            if [[ "${words[CURRENT]}" != -* && $positionals -eq 0 ]]; then
+                candidates=(${(f)"$(chrootmanager __complete chroots 2>/dev/null)"})
+                compadd -a candidates
+                return
+            fi
+            ;;
+    esac
+    _chrootmanager "$@"
+}
+
+"#;
+
+/// First line of the block registering the zsh completion function
+const ZSH_REGISTRATION: &str = "if [ \"$funcstack[1]\" = \"_chrootmanager\" ]; then";
+
+/// Completion of chroot names and profiles, appended to the fish script
+const FISH_DYNAMIC: &str = r#"
+function __chrootmanager_profiles
+    set -l tokens (commandline -opc)
+    set -l arch
+    for i in (seq (count $tokens))
+        switch $tokens[$i]
+            case -a --arch
+                set arch $tokens[(math $i + 1)]
+            case '--arch=*'
+                set arch (string replace -- --arch= '' $tokens[$i])
+        end
+    end
+    if test -n "$arch"
+        chrootmanager __complete profiles --arch $arch 2>/dev/null
+    else
+        chrootmanager __complete profiles 2>/dev/null
+    end
+end
+
+function __chrootmanager_wants_chroot
+    set -l tokens (commandline -opc)
+    set -l positionals (string match -v -- '-*' $tokens)
+    test (count $positionals) -eq 2; and contains -- $positionals[2] @CHROOT_SUBCOMMANDS@
+end
+
+complete -c chrootmanager -n __chrootmanager_wants_chroot -f -a "(chrootmanager __complete chroots 2>/dev/null)"
+complete -c chrootmanager -n "__fish_seen_subcommand_from create" -s p -l profile -x -a "(__chrootmanager_profiles)"
+"#;
+
+/// Completion script of `shell`
+fn completion_script(shell: CompletionShell) -> String {
+    let mut command = Cli::command();
+    let (generator, dynamic, separator) = match shell {
+        CompletionShell::Bash => (clap_complete::Shell::Bash, BASH_DYNAMIC, "|"),
+        CompletionShell::Zsh => (clap_complete::Shell::Zsh, ZSH_DYNAMIC, "|"),
+        CompletionShell::Fish => (clap_complete::Shell::Fish, FISH_DYNAMIC, " "),
+    };
+    let mut generated = Vec::new();
+    clap_complete::generate(generator, &mut command, BIN_NAME, &mut generated);
+    let generated = String::from_utf8_lossy(&generated);
+    let dynamic = dynamic.replace("@CHROOT_SUBCOMMANDS@", &CHROOT_SUBCOMMANDS.join(separator));
+
+    match shell {
+        // Autoloaded zsh functions run the registration on first use, so the
+        // dynamic function must be defined before it and registered instead
+        CompletionShell::Zsh => match generated.find(ZSH_REGISTRATION) {
+            Some(index) => {
+                let (functions, registration) = generated.split_at(index);
+                let registration = registration
+                    .replace("_chrootmanager \"$@\"", "_chrootmanager_dynamic \"$@\"")
+                    .replace("compdef _chrootmanager ", "compdef _chrootmanager_dynamic ");
+                format!("{functions}{dynamic}{registration}")
+            }
+            None => format!("{generated}{dynamic}compdef _chrootmanager_dynamic chrootmanager\n"),
+        },
+        // The later `complete` of bash and the extra `complete` of fish take precedence
+        CompletionShell::Bash | CompletionShell::Fish => format!("{generated}{dynamic}"),
+    }
+}
+
+/// Prints the completion script of `shell` on stdout
+pub fn print_completions(shell: CompletionShell) -> Result<(), ChrootManagerError> {
+    io::stdout().write_all(completion_script(shell).as_bytes())?;
+    Ok(())
+}
+
+/// Reads the configuration as saved, the defaults when there is none
+///
+/// Unlike [`load_config`](crate::cli::load_config), it never prompts,
+/// migrates nor writes anything.
+fn read_config() -> Option<Config> {
+    let path = Config::default_config_path();
+    if !path.exists() {
+        return Some(Config::default());
+    }
+    Config::try_parse_config(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Cached profiles of `arch`, or of the default architecture, or of all
+fn cached_profiles(config: &Config, arch: Option<String>) -> Vec<String> {
+    let Some(cache) = ProfileCache::load(&config.profile_cache_path()) else {
+        return Vec::new();
+    };
+    let arch = arch.or_else(|| config.default_arch.clone());
+    let profiles: BTreeSet<&String> = cache
+        .architectures
+        .values()
+        .filter(|architecture| arch.as_ref().is_none_or(|arch| &architecture.name == arch))
+        .flat_map(|architecture| architecture.get_profiles())
+        .collect();
+    profiles.into_iter().cloned().collect()
+}
+
+/// Prints the candidates of `target`, one per line, nothing when they cannot be read
+pub fn print_candidates(target: CompleteTarget) {
+    let Some(config) = read_config() else {
+        return;
+    };
+    let candidates = match target {
+        CompleteTarget::Chroots => chroot_names(&config.chroot_base_dir),
+        CompleteTarget::Profiles { arch } => cached_profiles(&config, arch),
+    };
+    let mut stdout = io::stdout().lock();
+    for candidate in candidates {
+        if writeln!(stdout, "{candidate}").is_err() {
+            return;
+        }
+    }
+}
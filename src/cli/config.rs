@@ -0,0 +1,123 @@
+//! `chrootmanager config`: show and change the settings without editing the TOML file
+
+use crate::cli::command::ConfigAction;
+use crate::cli::common::{assume_yes, chroot_names, confirm};
+use crate::cli::error::ChrootManagerError;
+use crate::cli::load_config;
+use crate::config::{Config, ConfigError, SETTABLE_KEYS};
+use crate::say;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+use colored::Colorize;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+
+/// Keys set in the configuration file, none when there is no file
+fn saved_keys(path: &Path) -> Result<toml::Table, ConfigError> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    Ok(toml::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Print the effective configuration, with whether each value comes from the file or the defaults
+///
+/// Without a configuration file, the defaults are shown instead of
+/// prompting for mirrors.
+async fn show_config() -> Result<(), ChrootManagerError> {
+    let path = Config::default_config_path();
+    let config = if path.exists() { load_config().await? } else { Config::default() };
+    let effective = config.to_table()?;
+    let saved = saved_keys(&path)?;
+
+    if output::is_quiet() {
+        for (key, value) in &effective {
+            println!("{key} = {value}");
+        }
+        return Ok(());
+    }
+
+    if path.exists() {
+        say!("{} Configuration file: {}", Symbol::Folder, path.display());
+    } else {
+        say!("{} Configuration file: {} (not created yet)", Symbol::Folder, path.display());
+    }
+    for (key, value) in &effective {
+        let source = if saved.contains_key(key) { "config file" } else { "default" };
+        println!("   {key:<24} = {value} {}", format!("({source})").dimmed());
+    }
+    for (key, _) in SETTABLE_KEYS.iter().filter(|(key, _)| !effective.contains_key(*key)) {
+        println!("   {key:<24}   {}", "(unset)".dimmed());
+    }
+    Ok(())
+}
+
+/// Warn about the chroots left in the previous base directory, and offer to list them
+fn warn_orphaned_chroots(old_base_dir: &Path, new_base_dir: &Path) -> Result<(), ChrootManagerError> {
+    if old_base_dir == new_base_dir {
+        return Ok(());
+    }
+    let orphaned = chroot_names(old_base_dir);
+    if orphaned.is_empty() {
+        return Ok(());
+    }
+
+    say!(
+        "{}",
+        format!(
+            "{} {} chroot(s) stay in {}, they are no longer managed until moved to the new directory",
+            Symbol::Warning,
+            orphaned.len(),
+            old_base_dir.display()
+        )
+        .yellow()
+    );
+    if (std::io::stdin().is_terminal() || assume_yes()) && confirm("List them?", false)? {
+        for name in &orphaned {
+            println!("   {} {name}", Symbol::Bullet);
+        }
+    }
+    Ok(())
+}
+
+/// Set or unset a key, then save the configuration
+async fn change_config(key: &str, value: Option<&str>) -> Result<(), ChrootManagerError> {
+    let mut config = load_config().await?;
+    let old_base_dir = config.chroot_base_dir.clone();
+
+    match value {
+        Some(value) => config.set_value(key, value)?,
+        None => config.unset_value(key)?,
+    }
+    config.save()?;
+
+    let shown = config.to_table()?.get(key).map(|value| value.to_string());
+    if output::is_quiet() {
+        println!("{key} = {}", shown.as_deref().unwrap_or(""));
+    } else {
+        let message = match shown {
+            Some(value) => format!("{} {key} = {value}", Symbol::Success),
+            None => format!("{} {key} unset", Symbol::Success),
+        };
+        println!("{}", message.green().bold());
+    }
+
+    if key == "chroot_base_dir" {
+        warn_orphaned_chroots(&old_base_dir, &config.chroot_base_dir)?;
+    }
+    Ok(())
+}
+
+/// Runs a config subcommand
+pub async fn run_config_command(action: ConfigAction) -> Result<(), ChrootManagerError> {
+    match action {
+        ConfigAction::Show => show_config().await,
+        ConfigAction::Path => {
+            println!("{}", Config::default_config_path().display());
+            Ok(())
+        }
+        ConfigAction::Set { key, value } => change_config(&key, Some(&value)).await,
+        ConfigAction::Unset { key } => change_config(&key, None).await,
+    }
+}
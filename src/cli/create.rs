@@ -1,52 +1,127 @@
+use crate::cli::common::{
+    display_create_outcome, perform_create, CreateOptions, CreateOutcome, CreateRequest,
+};
 use crate::chroot::ChrootUnit;
-use crate::cli::common::{handle_existing_chroot, finalize_chroot_creation};
-use crate::cli::download::download_stage3_with_cache;
 use crate::cli::error::ChrootManagerError;
+use crate::cli::list_interactive::list_chroots_interactive;
 use crate::cli::load_config;
+use crate::config::Config;
+use crate::diagnostics;
+use crate::platform;
+use crate::cli::profile::{display_profile_info, select_architecture, select_profile};
+use crate::cli::summary;
+use crate::cli::timing::CreatePhase;
 use crate::profile::manager::ProfileManager;
 use crate::profile::selected::SelectedProfile;
 use colored::Colorize;
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::time::Instant;
+use crate::say;
+use crate::ui::output::{self, Verbosity};
+use crate::ui::symbols::Symbol;
 
-/// Creates a new chroot with the specified name, architecture, and profile
-///
-/// This function is used by the non-interactive create command.
-pub async fn create_chroot(
-    name: String,
-    arch: String,
-    profile: String,
-) -> Result<(), ChrootManagerError> {
-    let config = load_config().await?;
-    println!("{}", "📦 Creating chroot...".green().bold());
-    let base_dir_display = config.chroot_base_dir.display();
-    println!("   📂 Base directory: {base_dir_display}");
+/// How missing creation parameters are obtained
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PromptPolicy {
+    /// Missing parameters are an error
+    Never,
+    /// Missing parameters are asked for interactively
+    IfMissing,
+}
 
-    config.ensure_chroot_base_dir()?;
+impl PromptPolicy {
+    /// Build the policy from the `-i` flag, refusing to prompt without a terminal
+    pub fn from_flag(interactive: bool) -> Self {
+        if interactive && std::io::stdin().is_terminal() {
+            PromptPolicy::IfMissing
+        } else {
+            PromptPolicy::Never
+        }
+    }
+}
 
-    let profile_manager = ProfileManager::discover(&config).await?;
+/// Validate the requested architecture, or prompt for it when allowed
+///
+/// Without prompts, `default_arch` from the configuration stands for a
+/// missing architecture, or else the architecture of the host.
+pub(crate) fn resolve_architecture(
+    profile_manager: &ProfileManager,
+    config: &Config,
+    arch: Option<String>,
+    policy: PromptPolicy,
+) -> Result<String, ChrootManagerError> {
+    let arch = match (arch, policy) {
+        (Some(arch), _) => arch,
+        (None, PromptPolicy::IfMissing) => return select_architecture(profile_manager, config),
+        (None, PromptPolicy::Never) => match &config.default_arch {
+            Some(arch) => {
+                say!("{} Using the default architecture {arch}", Symbol::Info);
+                arch.clone()
+            }
+            None => match platform::host_architecture().filter(|host| profile_manager.has_architecture(host)) {
+                Some(host) => {
+                    say!("{} Using the architecture of the host, {host}", Symbol::Info);
+                    host.to_string()
+                }
+                None => {
+                    return Err(ChrootManagerError::Custom(
+                        "Architecture required in non-interactive mode. Use -i for interactive mode, specify -a <arch> or set default_arch in the configuration".to_string(),
+                    ));
+                }
+            },
+        },
+    };
 
-    // Validate architecture
     if !profile_manager.has_architecture(arch.as_str()) {
-        println!("{}", format!("⚠️ The arch '{arch}' is not supported.").yellow().bold());
-        println!("   Available architectures:");
+        say!("{}", format!("{} The arch '{arch}' is not supported.", Symbol::Warning).yellow().bold());
+        say!("   Available architectures:");
         let arch_choices = profile_manager
             .get_architectures()
             .keys()
             .map(|k| k.to_string())
             .collect::<Vec<String>>();
         for arch_name in arch_choices {
-            println!("   • {arch_name}");
+            say!("   {} {arch_name}", Symbol::Bullet);
         }
         return Err(ChrootManagerError::Custom("The architecture is not supported.".to_string()));
     }
 
-    // Validate profile for the selected architecture
-    if !profile_manager.validate_arch_profile(arch.as_str(), profile.as_str()) {
-        println!("{}", format!("⚠️ The profile '{profile}' is not supported for arch '{arch}'.").yellow().bold());
-        println!("   Available profiles for '{arch}':");
-        if let Some(profiles) = profile_manager.get_profiles_for_arch(arch.as_str()) {
+    Ok(arch)
+}
+
+/// Validate the requested profile for the architecture, or prompt for it when allowed
+///
+/// Without prompts, `default_profile` from the configuration stands for a
+/// missing profile.
+pub(crate) fn resolve_profile(
+    profile_manager: &ProfileManager,
+    config: &Config,
+    arch: &str,
+    profile: Option<String>,
+    policy: PromptPolicy,
+) -> Result<String, ChrootManagerError> {
+    let profile = match (profile, policy) {
+        (Some(profile), _) => profile,
+        (None, PromptPolicy::IfMissing) => return select_profile(profile_manager, config, arch),
+        (None, PromptPolicy::Never) => match &config.default_profile {
+            Some(profile) => {
+                say!("{} Using the default profile {profile}", Symbol::Info);
+                profile.clone()
+            }
+            None => {
+                return Err(ChrootManagerError::Custom(
+                    "Profile required in non-interactive mode. Use -i for interactive mode, specify -p <profile> or set default_profile in the configuration".to_string(),
+                ));
+            }
+        },
+    };
+
+    if !profile_manager.validate_arch_profile(arch, profile.as_str()) {
+        say!("{}", format!("{} The profile '{profile}' is not supported for arch '{arch}'.", Symbol::Warning).yellow().bold());
+        say!("   Available profiles for '{arch}':");
+        if let Some(profiles) = profile_manager.get_profiles_for_arch(arch) {
             for profile_name in profiles {
-                println!("   • {profile_name}");
+                say!("   {} {profile_name}", Symbol::Bullet);
             }
         }
         return Err(ChrootManagerError::Custom(
@@ -54,22 +129,187 @@ pub async fn create_chroot(
         ));
     }
 
+    Ok(profile)
+}
+
+/// Creates a new chroot with the specified name, architecture, and profile
+///
+/// Provided values are validated and used as-is; missing ones are prompted
+/// for when the policy allows it, and are an error otherwise. With
+/// `summary_only`, the progress output is replaced by the summary line,
+/// which is logged in any case.
+pub async fn create_chroot(
+    name: String,
+    arch: Option<String>,
+    profile: Option<String>,
+    policy: PromptPolicy,
+    options: CreateOptions,
+    summary_only: bool,
+) -> Result<(), ChrootManagerError> {
+    if summary_only {
+        output::set_verbosity(Verbosity::Quiet);
+    }
+    let requested_profile = arch
+        .as_ref()
+        .zip(profile.as_ref())
+        .map(|(arch, profile)| format!("{arch}-{profile}"));
+
+    let outcome = match resolve_and_create(name.clone(), arch, profile, policy, options).await {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            let line = summary::failed_line(&name, requested_profile.as_deref(), diagnostics::current_phase(), &e);
+            log::info!("{line}");
+            if summary_only {
+                println!("{line}");
+            } else if let Some(hint) = e.hint() {
+                say!("{} {hint}", Symbol::Hint);
+            }
+            return Err(e);
+        }
+    };
+
+    let line = summary::created_line(&outcome);
+    log::info!("{line}");
+    if summary_only {
+        println!("{line}");
+        return Ok(());
+    }
+    display_create_outcome(&outcome);
+
+    // Show the list of chroots interactively
+    if policy == PromptPolicy::IfMissing {
+        list_chroots_interactive(true).await?;
+    }
+
+    Ok(())
+}
+
+/// Resolve the architecture and profile, then create the chroot
+async fn resolve_and_create(
+    name: String,
+    arch: Option<String>,
+    profile: Option<String>,
+    policy: PromptPolicy,
+    options: CreateOptions,
+) -> Result<CreateOutcome, ChrootManagerError> {
+    // Fail before discovering the profiles
+    ChrootUnit::validate_name(&name)?;
+    let config = load_config().await?;
+    say!("{}", format!("{} Creating chroot...", Symbol::Package).green().bold());
+    let base_dir_display = config.chroot_base_dir.display();
+    say!("   {} Base directory: {base_dir_display}", Symbol::Folder);
+
+    config.ensure_chroot_base_dir()?;
+
+    if policy == PromptPolicy::IfMissing && (arch.is_none() || profile.is_none()) {
+        say!("{} Discovering available architectures and profiles...", Symbol::Search);
+    }
+    diagnostics::set_phase(CreatePhase::ProfileResolution.label());
+    let started = Instant::now();
+    let profile_manager = ProfileManager::discover(&config, options.refresh_profiles).await?;
+    let discovery_duration = started.elapsed();
+
+    let arch = resolve_architecture(&profile_manager, &config, arch, policy)?;
+    let profile = resolve_profile(&profile_manager, &config, &arch, profile, policy)?;
+
+    let emulated = platform::qemu_target(&arch).and_then(platform::binfmt_handler).is_some();
+    if !platform::runs_natively(&arch) && !emulated {
+        say!(
+            "{}",
+            format!(
+                "{} {arch} binaries do not run natively on this host, entering the chroot needs qemu-user and binfmt_misc",
+                Symbol::Warning
+            )
+            .yellow()
+        );
+    }
+
     let selected_profile = SelectedProfile::new(arch, profile);
+    if policy == PromptPolicy::IfMissing {
+        display_profile_info(&selected_profile);
+    }
 
-    let chroot_unit = ChrootUnit::new(name.clone(), Some(&selected_profile), &config).await
-        .map_err(ChrootManagerError::Chroot)?;
+    let request = CreateRequest {
+        name,
+        profile: selected_profile,
+        options,
+    };
+    let mut outcome = perform_create(&config, &request).await?;
+    outcome
+        .timings
+        .prepend(CreatePhase::ProfileResolution, discovery_duration);
+    Ok(outcome)
+}
 
-    log::debug!("chroot path: {:?}", chroot_unit.chroot_path);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Architecture;
 
-    // Check if chroot already exists using the common function
-    handle_existing_chroot(&chroot_unit)?;
+    /// Architectures unlike any host, so that the host is never picked
+    fn profile_manager() -> ProfileManager {
+        ProfileManager::with_architectures(vec![
+            Architecture::new("alpha-test".to_string(), vec!["openrc".to_string(), "systemd".to_string()]),
+            Architecture::new("beta-test".to_string(), vec!["musl".to_string()]),
+        ])
+    }
 
-    // Download stage3 archive
-    let cached_path = download_stage3_with_cache(&selected_profile, &config).await?;
-    let cached_path = PathBuf::from(cached_path);
+    fn config(default_arch: Option<&str>, default_profile: Option<&str>) -> Config {
+        Config {
+            default_arch: default_arch.map(str::to_string),
+            default_profile: default_profile.map(str::to_string),
+            ..Config::default()
+        }
+    }
 
-    // Finalize chroot creation using the common function
-    finalize_chroot_creation(&chroot_unit, &cached_path).await?;
+    #[test]
+    fn given_parameters_are_validated_without_prompting() {
+        let manager = profile_manager();
+        let config = config(None, None);
+        // Prompting only when something is missing, both policies use what is given
+        for policy in [PromptPolicy::Never, PromptPolicy::IfMissing] {
+            let arch = resolve_architecture(&manager, &config, Some("beta-test".to_string()), policy).unwrap();
+            assert_eq!(arch, "beta-test");
+            let profile = resolve_profile(&manager, &config, &arch, Some("musl".to_string()), policy).unwrap();
+            assert_eq!(profile, "musl");
 
-    Ok(())
+            assert!(resolve_architecture(&manager, &config, Some("gamma".to_string()), policy).is_err());
+            // The profile exists, but for another architecture
+            assert!(resolve_profile(&manager, &config, &arch, Some("openrc".to_string()), policy).is_err());
+        }
+    }
+
+    #[test]
+    fn missing_parameters_fall_back_on_the_defaults_without_prompts() {
+        let manager = profile_manager();
+        let config = config(Some("alpha-test"), Some("systemd"));
+        let arch = resolve_architecture(&manager, &config, None, PromptPolicy::Never).unwrap();
+        assert_eq!(arch, "alpha-test");
+        assert_eq!(resolve_profile(&manager, &config, &arch, None, PromptPolicy::Never).unwrap(), "systemd");
+
+        // A default the mirrors do not offer is refused like a given value
+        let config = self::config(Some("gamma"), Some("musl"));
+        assert!(resolve_architecture(&manager, &config, None, PromptPolicy::Never).is_err());
+        assert!(resolve_profile(&manager, &config, "alpha-test", None, PromptPolicy::Never).is_err());
+    }
+
+    #[test]
+    fn missing_parameters_without_defaults_are_an_error_without_prompts() {
+        let manager = profile_manager();
+        let config = config(None, None);
+        let error = resolve_architecture(&manager, &config, None, PromptPolicy::Never).unwrap_err();
+        assert!(error.to_string().contains("Architecture required in non-interactive mode"));
+        let error = resolve_profile(&manager, &config, "alpha-test", None, PromptPolicy::Never).unwrap_err();
+        assert!(error.to_string().contains("Profile required in non-interactive mode"));
+    }
+
+    #[test]
+    fn prompts_are_refused_without_a_terminal() {
+        // Tests run with stdin redirected, as a provisioning script does
+        if std::io::stdin().is_terminal() {
+            return;
+        }
+        assert_eq!(PromptPolicy::from_flag(true), PromptPolicy::Never);
+        assert_eq!(PromptPolicy::from_flag(false), PromptPolicy::Never);
+    }
 }
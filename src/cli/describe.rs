@@ -0,0 +1,151 @@
+//! Machine-readable description of the command line
+//!
+//! `chrootmanager __describe --format json` lets wrappers discover the
+//! subcommands and flags without parsing `--help`. The output is built from
+//! the clap definition, so it cannot drift from the actual interface.
+//! Bump [`SCHEMA_VERSION`] on any incompatible change of the JSON layout.
+
+use crate::cli::command::Cli;
+use crate::cli::error::ChrootManagerError;
+use clap::{Arg, ArgAction, Command, CommandFactory, ValueEnum};
+use serde::Serialize;
+
+/// Version of the JSON layout
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Name of the flag that enables prompts on a subcommand
+const INTERACTIVE_FLAG: &str = "interactive";
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum DescribeFormat {
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct CliDescription {
+    schema_version: u32,
+    name: String,
+    version: Option<String>,
+    global_args: Vec<ArgDescription>,
+    subcommands: Vec<SubcommandDescription>,
+}
+
+#[derive(Debug, Serialize)]
+struct SubcommandDescription {
+    name: String,
+    about: Option<String>,
+    /// Whether the subcommand can prompt for missing values
+    interactive: bool,
+    args: Vec<ArgDescription>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    subcommands: Vec<SubcommandDescription>,
+}
+
+#[derive(Debug, Serialize)]
+struct ArgDescription {
+    name: String,
+    long: Option<String>,
+    short: Option<char>,
+    help: Option<String>,
+    positional: bool,
+    required: bool,
+    takes_value: bool,
+    /// Kind of value expected, as a clap value hint (e.g. "DirPath", "Url")
+    value_hint: Option<String>,
+    possible_values: Vec<String>,
+    default_values: Vec<String>,
+}
+
+impl ArgDescription {
+    fn from_arg(arg: &Arg) -> Self {
+        let takes_value = matches!(arg.get_action(), ArgAction::Set | ArgAction::Append);
+        let value_hint = match arg.get_value_hint() {
+            clap::ValueHint::Unknown => None,
+            hint => Some(format!("{hint:?}")),
+        };
+
+        Self {
+            name: arg.get_id().to_string(),
+            long: arg.get_long().map(str::to_string),
+            short: arg.get_short(),
+            help: arg.get_help().map(|help| help.to_string()),
+            positional: arg.is_positional(),
+            required: arg.is_required_set(),
+            takes_value,
+            value_hint,
+            possible_values: arg
+                .get_possible_values()
+                .iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| value.get_name().to_string())
+                .collect(),
+            // Flags always default to false, only report defaults of values
+            default_values: if takes_value {
+                arg.get_default_values()
+                    .iter()
+                    .map(|value| value.to_string_lossy().into_owned())
+                    .collect()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+}
+
+/// Arguments of a command, without the ones generated by clap
+fn describe_args<'a>(args: impl Iterator<Item = &'a Arg>) -> Vec<ArgDescription> {
+    args.filter(|arg| !arg.is_hide_set())
+        .filter(|arg| !matches!(arg.get_action(), ArgAction::Help | ArgAction::Version))
+        .map(ArgDescription::from_arg)
+        .collect()
+}
+
+fn describe_subcommand(command: &Command) -> SubcommandDescription {
+    SubcommandDescription {
+        name: command.get_name().to_string(),
+        about: command.get_about().map(|about| about.to_string()),
+        interactive: command
+            .get_arguments()
+            .any(|arg| arg.get_id() == INTERACTIVE_FLAG),
+        args: describe_args(command.get_arguments().filter(|arg| !arg.is_global_set())),
+        subcommands: command
+            .get_subcommands()
+            .filter(|subcommand| !subcommand.is_hide_set())
+            .filter(|subcommand| subcommand.get_name() != "help")
+            .map(describe_subcommand)
+            .collect(),
+    }
+}
+
+fn describe(command: &mut Command) -> CliDescription {
+    // Propagate the global arguments to the subcommands
+    command.build();
+
+    CliDescription {
+        schema_version: SCHEMA_VERSION,
+        name: command.get_name().to_string(),
+        version: command.get_version().map(str::to_string),
+        global_args: describe_args(command.get_arguments()),
+        subcommands: command
+            .get_subcommands()
+            .filter(|subcommand| !subcommand.is_hide_set())
+            .filter(|subcommand| subcommand.get_name() != "help")
+            .map(describe_subcommand)
+            .collect(),
+    }
+}
+
+/// Prints the description of the command line
+pub fn describe_cli(format: DescribeFormat) -> Result<(), ChrootManagerError> {
+    let description = describe(&mut Cli::command());
+
+    match format {
+        DescribeFormat::Json => {
+            let json = serde_json::to_string_pretty(&description)
+                .map_err(|e| ChrootManagerError::Custom(format!("JSON serialization failed: {e}")))?;
+            println!("{json}");
+        }
+    }
+
+    Ok(())
+}
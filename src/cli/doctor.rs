@@ -0,0 +1,46 @@
+//! `chrootmanager doctor`: check the host before the first chroot
+
+use crate::cli::error::ChrootManagerError;
+use crate::cli::load_config;
+use crate::doctor::run_checks;
+use crate::say;
+use crate::ui::symbols::Symbol;
+use colored::Colorize;
+
+/// Run the environment checks and print their results
+///
+/// Fails when a required check fails; failed optional checks only print
+/// a warning.
+pub async fn run_doctor() -> Result<(), ChrootManagerError> {
+    let config = load_config().await?;
+    say!("{} Checking the environment of chrootmanager...\n", Symbol::Search);
+
+    let results = run_checks(&config).await;
+    let mut required_failures = 0;
+    for result in &results {
+        match &result.outcome {
+            Ok(detail) => say!("{} {}: {detail}", Symbol::Success, result.name),
+            Err(problem) if result.required => {
+                required_failures += 1;
+                println!("{}", format!("{} {}: {problem}", Symbol::Error, result.name).red());
+            }
+            Err(problem) => println!("{}", format!("{} {}: {problem}", Symbol::Warning, result.name).yellow()),
+        }
+        if let Some(hint) = &result.hint {
+            println!("   {} {hint}", Symbol::Hint);
+        }
+    }
+
+    let warnings = results.iter().filter(|result| !result.is_passed()).count() - required_failures;
+    if required_failures > 0 {
+        return Err(ChrootManagerError::Custom(format!(
+            "{required_failures} required check(s) failed, see the hints above"
+        )));
+    }
+    if warnings > 0 {
+        say!("\n{} Ready, with {warnings} warning(s)", Symbol::Success);
+    } else {
+        say!("\n{} Everything chrootmanager needs is in place", Symbol::Success);
+    }
+    Ok(())
+}
@@ -1,15 +1,78 @@
+use crate::cache;
+use crate::cache::index;
+use crate::chroot::ChrootUnit;
+use crate::cli::create::{resolve_architecture, resolve_profile, PromptPolicy};
+use crate::cli::error::ChrootManagerError;
+use crate::cli::load_config;
+use crate::cli::timing::{CreatePhase, PhaseTimings};
 use crate::config::Config;
+use crate::diagnostics;
+use colored::Colorize;
+use crate::cli::common::CreateOptions;
 use crate::downloader::{
-    DownloadProgress, check_stage3_integrity, download_stage3_sha256,
-    download_stage3_with_progress, get_current_stage3_filename,
+    DownloadProgress, HashProgress, Stage3Release, check_stage3_integrity_with_progress,
+    download_release_with_progress, download_stage3_digest, find_previous_stage3, Stage3Digest,
+    resolve_current_release, resolve_snapshot_release, is_not_found_on_mirrors, open_release_stream, partial_path, verified_stream,
+    release_prefix,
 };
 use std::io;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::io::AsyncRead;
+use crate::error::{ChrootError, DownloaderError, OfflineError, SignatureError};
+use crate::http;
+use crate::profile::manager::ProfileManager;
 use crate::profile::selected::SelectedProfile;
+use crate::signature;
+use crate::space;
+use crate::say;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+
+/// Stage3 archive ready to be extracted
+#[derive(Debug, Clone)]
+pub struct Stage3Info {
+    /// Archive filename as published on the mirrors
+    pub filename: String,
+    /// Local path of the verified archive, `None` when it was streamed
+    pub path: Option<PathBuf>,
+    /// SHA256 of the archive, `None` when only another algorithm checked it
+    pub sha256: Option<String>,
+    /// Build timestamp of the archive
+    pub release: Option<String>,
+    /// Mirror the archive was downloaded from, `None` for a cached archive
+    pub mirror: Option<String>,
+}
+
+/// Result of fetching a stage3 through the cache
+#[derive(Debug)]
+pub(crate) struct Stage3Download {
+    /// Archive filename as published on the mirrors
+    pub filename: String,
+    /// Local path of the verified archive
+    pub path: PathBuf,
+    /// SHA256 of the archive, `None` when a cached archive was checked with another algorithm
+    pub sha256: Option<String>,
+    /// Build timestamp of the archive
+    pub release: Option<String>,
+    /// Mirror the archive was downloaded from, `None` on a cache hit
+    pub mirror: Option<String>,
+    /// Whether the archive was served from the cache
+    pub cache_hit: bool,
+    /// Time spent fetching the latest file, downloading and verifying
+    pub timings: PhaseTimings,
+}
+
+/// Stage3 written to disk by a download
+struct FetchedStage3 {
+    path: PathBuf,
+    /// SHA256 computed during the download
+    sha256: String,
+}
 
 /// Utility function to format the size in bytes readably
-fn format_bytes(bytes: u64) -> String {
+pub(crate) fn format_bytes(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
     let mut size = bytes as f64;
     let mut unit_index = 0;
@@ -26,36 +89,92 @@ fn format_bytes(bytes: u64) -> String {
     }
 }
 
-/// Verify the integrity of a stage3 file with its SHA256 hash
+/// Verify the integrity of a stage3 file with its published hash
 async fn verify_stage3_integrity_with_display(
     file_path: &Path,
-    expected_sha256: &str,
+    expected_digest: &Stage3Digest,
 ) -> Result<bool, Box<dyn std::error::Error>> {
-    println!("🔍 SHA256 verification in progress...");
+    let algorithm = expected_digest.algorithm();
+    say!("{} {algorithm} verification in progress...", Symbol::Search);
 
+    let mut last_progress = None;
     let (is_valid, expected, calculated) =
-        check_stage3_integrity(file_path, expected_sha256).await?;
+        check_stage3_integrity_with_progress(file_path, expected_digest, |progress| {
+            display_hash_progress(&progress);
+            last_progress = Some(progress);
+        })
+        .await?;
+    if !output::is_quiet() {
+        println!(); // New line after the progress bar
+    }
 
     if is_valid {
-        println!("✅ SHA256 verification successful");
+        let throughput = last_progress
+            .map(|p| format!(" ({} @ {}/s)", format_bytes(p.hashed), format_bytes(p.bytes_per_sec as u64)))
+            .unwrap_or_default();
+        say!("{} {algorithm} verification successful{throughput}", Symbol::Success);
     } else {
-        println!("❌ SHA256 verification failed");
-        println!("   Expected: {expected}");
-        println!("   Calculated: {calculated}");
+        say!("{} {algorithm} verification failed", Symbol::Error);
+        say!("   Expected: {expected}");
+        say!("   Calculated: {calculated}");
     }
 
     Ok(is_valid)
 }
 
+/// Compare the SHA256 computed during the download with the published one
+fn verify_downloaded_sha256(expected: &str, calculated: &str) -> bool {
+    let is_valid = calculated.eq_ignore_ascii_case(expected);
+    if is_valid {
+        say!("{} SHA256 verification successful (computed during the download)", Symbol::Success);
+    } else {
+        say!("{} SHA256 verification failed", Symbol::Error);
+        say!("   Expected: {expected}");
+        say!("   Calculated: {calculated}");
+    }
+    is_valid
+}
+
+/// Render a progress bar line such as "[████░░░░] 50% (1 MB / 2 MB) @ 1 MB/s"
+fn render_progress_bar(symbol: Symbol, done: u64, total: u64, bytes_per_sec: f64) {
+    const BAR_WIDTH: usize = 40;
+
+    let progress_ratio = (done as f64 / total as f64).min(1.0);
+    let filled_width = (progress_ratio * BAR_WIDTH as f64) as usize;
+    let empty_width = BAR_WIDTH - filled_width;
+
+    let filled_bar = Symbol::ProgressFilled.as_str().repeat(filled_width);
+    let empty_bar = Symbol::ProgressEmpty.as_str().repeat(empty_width);
+
+    let percentage = (progress_ratio * 100.0) as u8;
+    let speed_formatted = format_bytes(bytes_per_sec as u64);
+
+    print!(
+        "\r{} [{}{}] {}% ({} / {}) @ {}/s     ",
+        symbol,
+        filled_bar,
+        empty_bar,
+        percentage,
+        format_bytes(done),
+        format_bytes(total),
+        speed_formatted
+    );
+
+    io::stdout().flush().unwrap();
+}
+
 /// Display a progress bar in the terminal
 fn display_progress(progress: &DownloadProgress) {
-    const BAR_WIDTH: usize = 40;
+    if output::is_quiet() {
+        return;
+    }
 
     if progress.total == 0 {
         // If we don't know the total size, display only the downloaded bytes
         let speed_formatted = format_bytes(progress.speed_bytes_per_sec as u64);
         print!(
-            "\r📥 Downloaded: {} @ {}/s       ",
+            "\r{} Downloaded: {} @ {}/s       ",
+            Symbol::Download,
             format_bytes(progress.downloaded),
             speed_formatted
         );
@@ -63,139 +182,686 @@ fn display_progress(progress: &DownloadProgress) {
         return;
     }
 
-    let progress_ratio = progress.downloaded as f64 / progress.total as f64;
-    let filled_width = (progress_ratio * BAR_WIDTH as f64) as usize;
-    let empty_width = BAR_WIDTH - filled_width;
+    render_progress_bar(
+        Symbol::Download,
+        progress.downloaded,
+        progress.total,
+        progress.speed_bytes_per_sec,
+    );
+}
 
-    let filled_bar = "█".repeat(filled_width);
-    let empty_bar = "░".repeat(empty_width);
+/// Display the progress of a hash verification
+fn display_hash_progress(progress: &HashProgress) {
+    if output::is_quiet() || progress.total == 0 {
+        return;
+    }
 
-    let percentage = (progress_ratio * 100.0) as u8;
-    let speed_formatted = format_bytes(progress.speed_bytes_per_sec as u64);
+    render_progress_bar(Symbol::Search, progress.hashed, progress.total, progress.bytes_per_sec);
+}
 
-    print!(
-        "\r📥 [{}{}] {}% ({} / {}) @ {}/s     ",
-        filled_bar,
-        empty_bar,
-        percentage,
-        format_bytes(progress.downloaded),
-        format_bytes(progress.total),
-        speed_formatted
-    );
+/// Resolve the stage3 for the profile, the latest one unless a release is requested
+async fn resolve_stage3(
+    profile: &SelectedProfile,
+    config: &Config,
+    release: Option<&str>,
+    timings: &mut PhaseTimings,
+) -> Result<Stage3Release, Box<dyn std::error::Error>> {
+    diagnostics::set_phase(CreatePhase::LatestFetch.label());
+    say!("{} Retrieving information on stage 3...", Symbol::Search);
+    let started = Instant::now();
+    let resolved = match release {
+        Some(release) => resolve_snapshot_release(profile, config, release).await?,
+        None => resolve_current_release(profile, config).await?,
+    };
+    timings.record(CreatePhase::LatestFetch, started.elapsed());
+    match release {
+        Some(_) => say!("{} Requested stage3 file: {}", Symbol::Info, resolved.filename),
+        None => say!("{} Current stage3 file: {}", Symbol::Info, resolved.filename),
+    }
+    Ok(resolved)
+}
 
-    io::stdout().flush().unwrap();
+/// Delete the interrupted downloads of older builds of the profile
+///
+/// Failures are only logged, the download can go on without the space.
+fn remove_stale_partials(cache_dir: &Path, profile: &SelectedProfile, current: &str) {
+    let partials = match index::scan_partials(cache_dir) {
+        Ok(partials) => partials,
+        Err(e) => {
+            log::warn!("Unable to list the partial downloads of {}: {e}", cache_dir.display());
+            return;
+        }
+    };
+
+    for partial in index::select_stale_partials(&partials, &profile.get_stage3_pattern(), current) {
+        match std::fs::remove_file(&partial.path) {
+            Ok(()) => log::info!("Removed the stale partial download {}", partial.filename),
+            Err(e) => log::warn!("Unable to remove the stale partial download {}: {e}", partial.filename),
+        }
+    }
 }
 
-/// Download stage3 with a visual progress display
-async fn download_stage3_with_visual_progress(
+/// Download the stage3 into the cache with a visual progress display
+///
+/// An interrupted download is kept in the cache to be resumed.
+async fn fetch_stage3(
     profile: &SelectedProfile,
-    destination_path: &str,
     config: &Config,
-) -> Result<String, Box<dyn std::error::Error>> {
-    println!("🔍 Retrieving information on stage 3...");
-    let filename = get_current_stage3_filename(profile, config).await?;
-    println!("📋 Current stage3 file : {filename}");
+    release: &Stage3Release,
+    timings: &mut PhaseTimings,
+) -> Result<FetchedStage3, Box<dyn std::error::Error>> {
+    let filename = release.filename.as_str();
+    diagnostics::set_phase(CreatePhase::Download.label());
+    say!("{} Downloading : {filename}", Symbol::Download);
 
-    println!("📥 Downloading : {filename}");
+    remove_stale_partials(&config.stage3_cache_dir, profile, filename);
 
-    let result = download_stage3_with_progress(profile, destination_path, config, |progress| {
+    let started = Instant::now();
+    let target_dir = config.stage3_cache_dir.to_string_lossy();
+    let result = download_release_with_progress(profile, release, &target_dir, config, |progress| {
         if progress.downloaded == 0 {
             if progress.total > 0 {
-                println!("📡 Downloading from mirror");
-                println!("📊 File size: {}", format_bytes(progress.total));
+                say!("{} Downloading from mirror", Symbol::Network);
+                say!("{} File size: {}", Symbol::Stats, format_bytes(progress.total));
             } else {
-                println!("📊 File size: unknown");
+                say!("{} File size: unknown", Symbol::Stats);
             }
         }
         display_progress(&progress);
     })
     .await?;
+    timings.record_with_throughput(
+        CreatePhase::Download,
+        started.elapsed(),
+        result.average_speed_bytes_per_sec,
+    );
 
-    println!(); // New line after the progress bar
-    println!("✅ Stage3 downloaded successfully: {}", result.file_path);
-    println!(
-        "📈 Average speed : {}/s     ",
+    say!(); // New line after the progress bar
+    say!("{} Stage3 downloaded successfully: {}", Symbol::Success, result.file_path);
+    say!(
+        "{} Average speed : {}/s     ",
+        Symbol::Speed,
         format_bytes(result.average_speed_bytes_per_sec as u64)
     );
 
-    Ok(result.file_path)
+    Ok(FetchedStage3 {
+        path: PathBuf::from(result.file_path),
+        sha256: result.sha256,
+    })
+}
+
+/// Download the stage3, falling back to the previous snapshot when the latest one is missing
+///
+/// Right after a release, the latest file can reach the mirrors before the
+/// archive. Unless `strict_latest` is set, the most recent older snapshot is
+/// used instead. Returns the release actually downloaded.
+async fn fetch_stage3_with_fallback(
+    profile: &SelectedProfile,
+    config: &Config,
+    release: Stage3Release,
+    strict_latest: bool,
+    timings: &mut PhaseTimings,
+) -> Result<(Stage3Release, FetchedStage3), Box<dyn std::error::Error>> {
+    let error = match fetch_stage3(profile, config, &release, timings).await {
+        Ok(fetched) => return Ok((release, fetched)),
+        Err(e) if !strict_latest && is_not_found_on_mirrors(e.as_ref()) => e,
+        Err(e) => return Err(e),
+    };
+
+    say!("{} {error}, looking for the previous snapshot...", Symbol::Warning);
+    let previous = find_previous_stage3(profile, config, &release)
+        .await
+        .map_err(|e| format!("{error} ({e})"))?;
+    log::warn!(
+        "Substituting {} for {}, which is not on the mirrors yet",
+        previous.filename,
+        release.filename
+    );
+    say!(
+        "{}",
+        format!("{} Using the previous stage3 {} instead", Symbol::Warning, previous.filename).yellow()
+    );
+
+    let fetched = fetch_stage3(profile, config, &previous, timings).await?;
+    Ok((previous, fetched))
+}
+
+/// Verify a freshly downloaded stage3, deleting it when corrupted
+///
+/// A published SHA256 is compared with the hash computed during the
+/// download, other algorithms hash the file again. A missing hash on the
+/// mirrors is only a warning. Returns the verified hash, if any.
+async fn verify_stage3(
+    profile: &SelectedProfile,
+    config: &Config,
+    release: &mut Stage3Release,
+    fetched: &FetchedStage3,
+    timings: &mut PhaseTimings,
+) -> Result<Option<Stage3Digest>, Box<dyn std::error::Error>> {
+    let file_path = fetched.path.as_path();
+    diagnostics::set_phase(CreatePhase::Verify.label());
+    say!("{} Verifying downloaded file integrity...", Symbol::Search);
+    let started = Instant::now();
+    match release.digest(profile, config).await {
+        Ok(expected_digest) => {
+            let algorithm = expected_digest.algorithm();
+            let verified = match &expected_digest {
+                Stage3Digest::Sha256(expected) => Ok(verify_downloaded_sha256(expected, &fetched.sha256)),
+                _ => verify_stage3_integrity_with_display(file_path, &expected_digest).await,
+            };
+            match verified {
+                Ok(true) => {
+                    timings.record(CreatePhase::Verify, started.elapsed());
+                    say!("{} Stage3 downloaded and verified successfully", Symbol::Success);
+                    Ok(Some(expected_digest))
+                }
+                Ok(false) => {
+                    // Delete the corrupted file
+                    if let Err(e) = tokio::fs::remove_file(file_path).await {
+                        log::warn!("Error deleting corrupted file: {e}");
+                    }
+                    Err(format!("The downloaded file is corrupted ({algorithm} verification failed).").into())
+                }
+                Err(e) => {
+                    log::warn!("Error during {algorithm} verification: {e}");
+                    Err(format!("Error during {algorithm} verification: {e}").into())
+                }
+            }
+        }
+        Err(e) => {
+            log::warn!("Unable to download the stage3 hash for verification: {e}");
+            say!("{} File downloaded without hash verification (hash not available)", Symbol::Warning);
+            Ok(None)
+        }
+    }
+}
+
+/// Check the OpenPGP signature of a stage3, reported apart from its hash
+///
+/// A bad signature deletes the archive. A missing key, gpg or signature
+/// fails the creation without touching it, `--no-gpg` skips the check.
+async fn verify_stage3_signature_with_display(
+    profile: &SelectedProfile,
+    config: &Config,
+    release: &Stage3Release,
+    file_path: &Path,
+    options: &CreateOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !options.verify_signature {
+        say!("{} OpenPGP signature check skipped (--no-gpg)", Symbol::Warning);
+        return Ok(());
+    }
+
+    diagnostics::set_phase(CreatePhase::Verify.label());
+    say!("{} OpenPGP signature verification in progress...", Symbol::Search);
+    match signature::verify_stage3_signature(profile, config, release, file_path).await {
+        Ok(fingerprint) => {
+            say!("{} OpenPGP signature verified (key {fingerprint})", Symbol::Success);
+            Ok(())
+        }
+        Err(e @ SignatureError::BadSignature(_)) => {
+            say!("{} OpenPGP signature verification failed", Symbol::Error);
+            if let Err(e) = index::remove_with_sidecar(file_path) {
+                log::warn!("Error deleting the unverified file: {e}");
+            }
+            Err(e.into())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Record the verified hash of a cached stage3 for `cache list`
+///
+/// Sidecars only hold SHA256 hashes, a stage3 verified from its DIGESTS
+/// file gets none.
+fn record_cached_hash(path: &Path, digest: &Stage3Digest) {
+    let Stage3Digest::Sha256(sha256) = digest else {
+        log::debug!("No sidecar for {}, verified with {}", path.display(), digest.algorithm());
+        return;
+    };
+    if let Err(e) = index::write_sidecar(path, sha256) {
+        log::warn!("Unable to write the SHA256 sidecar of {}: {e}", path.display());
+    }
+}
+
+/// Trim the stage3 cache to the configured budget
+///
+/// The file just downloaded and the stage3 recorded in the metadata of
+/// existing chroots are kept.
+fn evict_cached_stage3(config: &Config, downloaded_path: &Path) {
+    let Some(budget) = config.cache_budget() else {
+        return;
+    };
+
+    let mut protected = vec![downloaded_path.to_path_buf()];
+    protected.extend(
+        ChrootUnit::find_units(config)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|unit| unit.metadata.as_ref()?.stage3.as_deref())
+            .map(|stage3| config.get_cache_path(stage3)),
+    );
+
+    match cache::enforce_budget(&config.stage3_cache_dir, budget, &protected) {
+        Ok(evicted) => {
+            for entry in evicted {
+                let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+                say!(
+                    "{}",
+                    format!("   {} Evicted {name} ({}) from the cache", Symbol::Cleanup, format_bytes(entry.size))
+                        .dimmed()
+                );
+            }
+        }
+        Err(e) => log::warn!("Unable to trim the stage3 cache: {e}"),
+    }
+}
+
+/// Fail when the stage3 cache has no room for the archive
+///
+/// The part of an interrupted download already on disk is deducted. Nothing
+/// is checked when the mirror did not publish the size.
+fn ensure_download_space(config: &Config, release: &Stage3Release) -> Result<(), ChrootError> {
+    let Some(size) = release.size else {
+        return Ok(());
+    };
+    let partial = partial_path(&config.get_cache_path(&release.filename).to_string_lossy());
+    let downloaded = std::fs::metadata(partial).map_or(0, |metadata| metadata.len());
+    space::ensure_available(&config.stage3_cache_dir, size.saturating_sub(downloaded))
+}
+
+/// Take the stage3 of the profile from the cache, without network access
+///
+/// The most recent build is used, or the one of the requested release. The
+/// archive is checked against the SHA256 recorded when it was downloaded;
+/// its signature cannot be checked again, the `.asc` is not cached.
+async fn cached_stage3_offline(
+    profile: &SelectedProfile,
+    config: &Config,
+    options: &CreateOptions,
+) -> Result<Stage3Download, Box<dyn std::error::Error>> {
+    let mut timings = PhaseTimings::default();
+    diagnostics::set_phase(CreatePhase::LatestFetch.label());
+    say!("{} Offline, looking for the stage3 in the cache...", Symbol::Cache);
+    let started = Instant::now();
+    let prefix = options.release.as_deref().map(release_prefix).transpose()?;
+    let pattern = profile.get_stage3_pattern();
+    let tarballs = match index::scan(&config.stage3_cache_dir) {
+        Ok(tarballs) => tarballs,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let groups = index::group_by_pattern(&tarballs);
+    let cached = groups
+        .get(&pattern)
+        .and_then(|group| {
+            group.iter().find(|tarball| match (&prefix, &tarball.name) {
+                (Some(prefix), Some(name)) => name.timestamp.starts_with(prefix.as_str()),
+                _ => true,
+            })
+        })
+        .ok_or_else(|| {
+            let stage3 = match &options.release {
+                Some(release) => format!("{pattern} {release}"),
+                None => pattern.clone(),
+            };
+            DownloaderError::Offline(OfflineError::NoCachedStage3(stage3))
+        })?;
+    timings.record(CreatePhase::LatestFetch, started.elapsed());
+    say!("{} Cached stage3 file: {}", Symbol::Info, cached.filename);
+
+    diagnostics::set_phase(CreatePhase::Verify.label());
+    let started = Instant::now();
+    let sha256 = index::read_sidecar(&cached.path)?;
+    match &sha256 {
+        Some(expected) => {
+            let digest = Stage3Digest::Sha256(expected.clone());
+            if !verify_stage3_integrity_with_display(&cached.path, &digest).await? {
+                return Err(format!(
+                    "The cached stage3 {} does not match its recorded SHA256 and cannot be downloaded again offline",
+                    cached.filename
+                )
+                .into());
+            }
+            timings.record(CreatePhase::Verify, started.elapsed());
+            say!("{} Cached stage3 successfully verified: {}", Symbol::Success, cached.path.display());
+        }
+        None => say!("{} No SHA256 recorded for the cached stage3, used without verification", Symbol::Warning),
+    }
+    if options.verify_signature {
+        say!("{} OpenPGP signature check skipped (--offline)", Symbol::Warning);
+    }
+
+    Ok(Stage3Download {
+        filename: cached.filename.clone(),
+        path: cached.path.clone(),
+        sha256,
+        release: cached.name.as_ref().map(|name| name.timestamp.clone()),
+        mirror: None,
+        cache_hit: true,
+        timings,
+    })
 }
 
-// Download function with cache support and SHA256 verification
+// Download function with cache support and hash verification
 pub(crate) async fn download_stage3_with_cache(
     profile: &SelectedProfile,
     config: &Config,
-) -> Result<String, Box<dyn std::error::Error>> {
-    println!("🔍 Retrieving information on stage 3...");
-    let filename = get_current_stage3_filename(profile, config).await?;
-    println!("📋 Current stage3 file: {filename}");
+    options: &CreateOptions,
+) -> Result<Stage3Download, Box<dyn std::error::Error>> {
+    if http::is_offline() {
+        return cached_stage3_offline(profile, config, options).await;
+    }
+
+    let mut timings = PhaseTimings::default();
+    let mut release = resolve_stage3(profile, config, options.release.as_deref(), &mut timings).await?;
 
     // Check if the file already exists in the cache
-    let cached_path = config.get_cache_path(&filename);
+    let cached_path = config.get_cache_path(&release.filename);
 
     if cached_path.exists() {
-        println!("💾 Stage3 found in cache, integrity check...");
+        diagnostics::set_phase(CreatePhase::Verify.label());
+        say!("{} Stage3 found in cache, integrity check...", Symbol::Cache);
 
-        // Download SHA256 hash for verification
-        match download_stage3_sha256(profile, config, &filename).await {
-            Ok(expected_hash) => {
-                match verify_stage3_integrity_with_display(&cached_path, &expected_hash).await {
+        // Download the published hash for verification
+        let started = Instant::now();
+        match release.digest(profile, config).await {
+            Ok(expected_digest) => {
+                match verify_stage3_integrity_with_display(&cached_path, &expected_digest).await {
                     Ok(true) => {
+                        timings.record(CreatePhase::Verify, started.elapsed());
                         let cached_path_display = cached_path.display();
-                        println!("✅ Cached stage3 successfully verified: {cached_path_display}");
-                        return Ok(cached_path.to_string_lossy().to_string());
+                        say!("{} Cached stage3 successfully verified: {cached_path_display}", Symbol::Success);
+                        verify_stage3_signature_with_display(profile, config, &release, &cached_path, options)
+                            .await?;
+                        if !index::sidecar_path(&cached_path).exists() {
+                            record_cached_hash(&cached_path, &expected_digest);
+                        }
+                        let sha256 = match expected_digest {
+                            Stage3Digest::Sha256(sha256) => Some(sha256),
+                            _ => None,
+                        };
+                        return Ok(Stage3Download {
+                            release: release.build_timestamp(),
+                            filename: release.filename,
+                            path: cached_path,
+                            sha256,
+                            mirror: None,
+                            cache_hit: true,
+                            timings,
+                        });
                     }
                     Ok(false) => {
-                        println!("❌ Cached stage3 corrupted, deleting and re-downloading...");
+                        say!("{} Cached stage3 corrupted, deleting and re-downloading...", Symbol::Error);
                         if let Err(e) = tokio::fs::remove_file(&cached_path).await {
                             log::warn!("Error deleting corrupted file: {e}");
                         }
                     }
                     Err(e) => {
-                        log::warn!("Error during SHA256 verification: {e}, re-downloading...")
+                        log::warn!("Error during hash verification: {e}, re-downloading...")
                     }
                 }
             }
-            Err(e) => log::warn!("Unable to download SHA256 hash: {e}, re-downloading..."),
+            Err(e) => log::warn!("Unable to download the stage3 hash: {e}, re-downloading..."),
         }
     }
 
+    if options.check_space {
+        ensure_download_space(config, &release)?;
+    }
+
     // Download to cache
-    let cache_path = config.get_cache_path(&filename);
-    let cache_dir = cache_path.parent().unwrap().to_str().unwrap();
-
-    println!("📦 Downloading stage3 to cache...");
-    let downloaded_path = download_stage3_with_visual_progress(profile, cache_dir, config).await?;
-
-    // Verify the downloaded file
-    println!("🔍 Verifying downloaded file integrity...");
-    match download_stage3_sha256(profile, config, &filename).await {
-        Ok(expected_hash) => {
-            let file_path = Path::new(&downloaded_path);
-            match verify_stage3_integrity_with_display(file_path, &expected_hash).await {
-                Ok(true) => {
-                    println!("✅ Stage3 downloaded and verified successfully");
-                }
-                Ok(false) => {
-                    // Delete the corrupted file
-                    if let Err(e) = tokio::fs::remove_file(file_path).await {
-                        log::warn!("Error deleting corrupted file: {e}");
-                    }
-                    return Err(
-                        "The downloaded file is corrupted (SHA256 verification failed).".into(),
-                    );
-                }
-                Err(e) => {
-                    log::warn!("Error during SHA256 verification: {e}");
-                    return Err(format!("Error during SHA256 verification: {e}").into());
-                }
+    say!("{} Downloading stage3 to cache...", Symbol::Package);
+    let (mut release, fetched) = fetch_stage3_with_fallback(
+        profile,
+        config,
+        release,
+        // A requested release is never replaced by another one
+        options.strict_latest || options.release.is_some(),
+        &mut timings,
+    )
+    .await?;
+    let digest = verify_stage3(profile, config, &mut release, &fetched, &mut timings).await?;
+    let FetchedStage3 { path: downloaded_path, sha256 } = fetched;
+    verify_stage3_signature_with_display(profile, config, &release, &downloaded_path, options).await?;
+    if let Some(digest) = digest {
+        record_cached_hash(&downloaded_path, &digest);
+    }
+
+    if options.evict_cache {
+        evict_cached_stage3(config, &downloaded_path);
+    }
+
+    Ok(Stage3Download {
+        release: release.build_timestamp(),
+        mirror: release.mirror.clone(),
+        filename: release.filename,
+        path: downloaded_path,
+        sha256: Some(sha256),
+        cache_hit: false,
+        timings,
+    })
+}
+
+/// Stage3 download opened to be extracted as it arrives
+pub(crate) struct Stage3Stream {
+    /// Archive filename as published on the mirrors
+    pub filename: String,
+    /// Body of the download, checked against the published hash at its end
+    pub reader: Box<dyn AsyncRead + Unpin + Send>,
+    /// Algorithm of the check, `None` when no hash is available
+    pub algorithm: Option<&'static str>,
+    /// Published SHA256, which the stream fails when it does not match
+    pub sha256: Option<String>,
+    /// Build timestamp of the archive
+    pub release: Option<String>,
+    /// Mirror the archive is streamed from
+    pub mirror: Option<String>,
+    /// Time spent fetching the latest file and the hash
+    pub timings: PhaseTimings,
+}
+
+/// Open a stage3 download for a single use, bypassing the cache
+///
+/// Nothing is written to disk: the archive is hashed as it is read. With
+/// the signature check on, the hash comes from the clearsigned DIGESTS
+/// file, as the detached signature needs the whole archive on disk.
+pub(crate) async fn open_stage3_stream(
+    profile: &SelectedProfile,
+    config: &Config,
+    options: &CreateOptions,
+) -> Result<Stage3Stream, Box<dyn std::error::Error>> {
+    http::ensure_online("Streaming a stage3 with --no-cache").map_err(DownloaderError::from)?;
+    let mut timings = PhaseTimings::default();
+    let release = resolve_stage3(profile, config, options.release.as_deref(), &mut timings).await?;
+
+    // Nothing goes through the cache, the extracted tree needs the space
+    if let (true, Some(size)) = (options.check_space, release.size) {
+        space::ensure_available(&config.chroot_base_dir, size.saturating_mul(space::EXPANSION_FACTOR))?;
+    }
+
+    diagnostics::set_phase(CreatePhase::Download.label());
+    let (release, response) = match open_release_stream(profile, &release, config).await {
+        Ok((_url, response)) => (release, response),
+        Err(error)
+            if !options.strict_latest
+                && options.release.is_none()
+                && matches!(error, DownloaderError::NotFoundOnMirrors(_)) =>
+        {
+            say!("{} {error}, looking for the previous snapshot...", Symbol::Warning);
+            let previous = find_previous_stage3(profile, config, &release)
+                .await
+                .map_err(|e| format!("{error} ({e})"))?;
+            log::warn!(
+                "Substituting {} for {}, which is not on the mirrors yet",
+                previous.filename,
+                release.filename
+            );
+            say!(
+                "{}",
+                format!("{} Using the previous stage3 {} instead", Symbol::Warning, previous.filename).yellow()
+            );
+            let (_url, response) = open_release_stream(profile, &previous, config).await?;
+            (previous, response)
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    diagnostics::set_phase(CreatePhase::Verify.label());
+    let started = Instant::now();
+    let expected = if options.verify_signature {
+        say!("{} OpenPGP verification of the DIGESTS file in progress...", Symbol::Search);
+        let (digest, fingerprint) = signature::verified_stage3_digest(profile, config, &release).await?;
+        say!("{} OpenPGP signature of the DIGESTS file verified (key {fingerprint})", Symbol::Success);
+        Some(digest)
+    } else {
+        say!("{} OpenPGP signature check skipped (--no-gpg)", Symbol::Warning);
+        match download_stage3_digest(profile, config, &release).await {
+            Ok(digest) => Some(digest),
+            Err(e) => {
+                log::warn!("Unable to download the stage3 hash for verification: {e}");
+                say!("{} Streaming without hash verification (hash not available)", Symbol::Warning);
+                None
             }
         }
-        Err(e) => {
-            log::warn!("Unable to download SHA256 hash for verification: {e}");
-            println!("⚠️ File downloaded without SHA256 verification (hash not available)");
+    };
+    timings.record(CreatePhase::Verify, started.elapsed());
+
+    match response.content_length() {
+        Some(total) => say!("{} File size: {}", Symbol::Stats, format_bytes(total)),
+        None => say!("{} File size: unknown", Symbol::Stats),
+    }
+    say!("{} Streaming {} into tar...", Symbol::Download, release.filename);
+    let algorithm = expected.as_ref().map(Stage3Digest::algorithm);
+    let sha256 = match &expected {
+        Some(Stage3Digest::Sha256(sha256)) => Some(sha256.clone()),
+        _ => None,
+    };
+    let reader = verified_stream(
+        response,
+        release.filename.clone(),
+        expected,
+        Box::new(|progress| display_progress(&progress)),
+    );
+
+    Ok(Stage3Stream {
+        release: release.build_timestamp(),
+        mirror: release.mirror.clone(),
+        filename: release.filename,
+        reader: Box::new(reader),
+        algorithm,
+        sha256,
+        timings,
+    })
+}
+
+/// Put a downloaded stage3 into `output_dir`, copying it when the cache keeps it
+///
+/// Otherwise the stage3 is moved, and its cached hash removed. Returns the
+/// path of the stage3 in `output_dir`.
+fn place_download(path: &Path, output_dir: &Path, keep_cached: bool) -> io::Result<PathBuf> {
+    let filename = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "stage3 path without a file name"))?;
+    let target = output_dir.join(filename);
+    // The output directory is the cache directory
+    if target.canonicalize().ok() == Some(path.canonicalize()?) {
+        return Ok(target);
+    }
+
+    if keep_cached {
+        std::fs::copy(path, &target)?;
+        return Ok(target);
+    }
+    // Copied then removed when the output directory is on another filesystem
+    if std::fs::rename(path, &target).is_err() {
+        std::fs::copy(path, &target)?;
+        std::fs::remove_file(path)?;
+    }
+    match std::fs::remove_file(index::sidecar_path(path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            log::warn!("Unable to remove the cached hash of {}: {e}", path.display())
         }
+        _ => {}
+    }
+    Ok(target)
+}
+
+/// Download and verify a stage3 without creating a chroot
+///
+/// The stage3 goes through the cache. With `output`, it is moved into that
+/// directory, unless `keep_download` asks for a copy that leaves the cache
+/// filled. A stage3 that already was in the cache is always left there.
+pub async fn download_stage3(
+    arch: Option<String>,
+    profile: Option<String>,
+    output: Option<PathBuf>,
+    keep_download: bool,
+    options: CreateOptions,
+) -> Result<(), ChrootManagerError> {
+    let config = load_config().await?;
+    let profile_manager = ProfileManager::discover(&config, options.refresh_profiles).await?;
+    let arch = resolve_architecture(&profile_manager, &config, arch, PromptPolicy::Never)?;
+    let profile = resolve_profile(&profile_manager, &config, &arch, profile, PromptPolicy::Never)?;
+    // Refused before downloading
+    if let Some(output_dir) = &output {
+        std::fs::create_dir_all(output_dir)?;
     }
 
-    Ok(downloaded_path)
+    let download = download_stage3_with_cache(&SelectedProfile::new(arch, profile), &config, &options).await?;
+    let Some(output_dir) = output else {
+        say!("{} Stage3 kept in the cache: {}", Symbol::Cache, download.path.display());
+        return Ok(());
+    };
+    let path = place_download(&download.path, &output_dir, keep_download || download.cache_hit)?;
+    say!("{} Stage3 written to {}", Symbol::Success, path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const STAGE3: &str = "stage3-amd64-openrc-20240301T170000Z.tar.xz";
+
+    /// Cache directory holding a stage3 and its hash, and an output directory
+    fn cache_with_stage3() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        fs::create_dir(&cache).unwrap();
+        fs::create_dir(dir.path().join("output")).unwrap();
+        let stage3 = cache.join(STAGE3);
+        fs::write(&stage3, "stage3").unwrap();
+        fs::write(index::sidecar_path(&stage3), "hash").unwrap();
+        let output = dir.path().join("output");
+        (dir, stage3, output)
+    }
+
+    #[test]
+    fn an_output_directory_takes_the_stage3_out_of_the_cache() {
+        let (_dir, stage3, output) = cache_with_stage3();
+        let placed = place_download(&stage3, &output, false).unwrap();
+        assert_eq!(placed, output.join(STAGE3));
+        assert_eq!(fs::read_to_string(&placed).unwrap(), "stage3");
+        assert!(!stage3.exists());
+        assert!(!index::sidecar_path(&stage3).exists());
+    }
+
+    #[test]
+    fn keep_download_leaves_the_stage3_in_the_cache() {
+        let (_dir, stage3, output) = cache_with_stage3();
+        let placed = place_download(&stage3, &output, true).unwrap();
+        assert_eq!(fs::read_to_string(&placed).unwrap(), "stage3");
+        assert!(stage3.exists());
+        assert!(index::sidecar_path(&stage3).exists());
+    }
+
+    #[test]
+    fn the_cache_directory_as_output_keeps_the_stage3() {
+        let (_dir, stage3, _output) = cache_with_stage3();
+        let placed = place_download(&stage3, stage3.parent().unwrap(), false).unwrap();
+        assert_eq!(placed, stage3);
+        assert_eq!(fs::read_to_string(&stage3).unwrap(), "stage3");
+    }
 }
@@ -0,0 +1,27 @@
+use crate::chroot::SessionOptions;
+use crate::cli::common::{enter_chroot_with_unit, find_chroot_unit, upgrade_metadata};
+use crate::cli::error::ChrootManagerError;
+use crate::cli::load_config;
+use crate::say;
+use crate::ui::symbols::Symbol;
+
+/// Enters the named chroot without going through the interactive list
+///
+/// The filesystems are unmounted when the shell exits, whatever its exit status.
+/// With `space_warning`, the free space is checked on exit.
+pub async fn enter_chroot(name: String, space_warning: bool, session: SessionOptions) -> Result<(), ChrootManagerError> {
+    let mut unit = find_chroot_unit(&name).await?;
+
+    say!("{} Authenticating for privileged operations...", Symbol::Lock);
+    unit.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)?;
+    upgrade_metadata(&mut unit);
+
+    let space_threshold = if space_warning {
+        Some(load_config().await?.low_space_threshold())
+    } else {
+        None
+    };
+
+    // A non-zero exit of the shell is not a failure, only failing to start it is
+    enter_chroot_with_unit(&unit, space_threshold.as_ref(), &session)
+}
@@ -1,8 +1,11 @@
-use crate::error::{ConfigError, DownloaderError, MirrorError, ProfileError, ChrootError};
+use crate::error::{ConfigError, DownloaderError, MirrorError, OfflineError, ProfileError, ChrootError, StateError};
 use inquire::InquireError;
 use std::io;
 use thiserror::Error;
 
+/// Hint of the failures of `--offline`
+const OFFLINE_HINT: &str = "Run the command once without --offline to fill the caches, or drop --offline";
+
 #[derive(Error, Debug)]
 pub enum ChrootManagerError {
     #[error("Mirror Error: {0}")]
@@ -23,6 +26,15 @@ pub enum ChrootManagerError {
     #[error("Chroot Error: {0}")]
     Chroot(#[from] ChrootError),
     
+    #[error("State Error: {0}")]
+    State(#[from] StateError),
+    
+    #[error("Offline: {0}")]
+    Offline(#[from] OfflineError),
+    
+    #[error("The chroot '{0}' does not exist.")]
+    ChrootNotFound(String),
+    
     #[error("IO Error: {0}")]
     Io(#[from] io::Error),
     
@@ -33,8 +45,67 @@ pub enum ChrootManagerError {
     Custom(String),
 }
 
+impl ChrootManagerError {
+    /// Stable name of the error category, for machine-readable output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ChrootManagerError::Offline(_)
+            | ChrootManagerError::Download(DownloaderError::Offline(_))
+            | ChrootManagerError::Mirror(MirrorError::Offline(_)) => "offline",
+            ChrootManagerError::Mirror(_) => "mirror",
+            ChrootManagerError::Download(_) => "download",
+            ChrootManagerError::Inquire(_) => "prompt",
+            ChrootManagerError::Config(_) => "config",
+            ChrootManagerError::Profile(_) => "profile",
+            ChrootManagerError::Chroot(_) => "chroot",
+            ChrootManagerError::State(_) => "state",
+            ChrootManagerError::ChrootNotFound(_) => "not-found",
+            ChrootManagerError::Io(_) => "io",
+            ChrootManagerError::Generic(_) | ChrootManagerError::Custom(_) => "other",
+        }
+    }
+
+    /// What the user can do about the error, when something applies
+    pub fn hint(&self) -> Option<&'static str> {
+        let error = match self {
+            ChrootManagerError::Offline(_) | ChrootManagerError::Mirror(MirrorError::Offline(_)) => {
+                return Some(OFFLINE_HINT)
+            }
+            ChrootManagerError::Download(error) => error,
+            _ => return None,
+        };
+        match error {
+            DownloaderError::Offline(_) => Some(OFFLINE_HINT),
+            DownloaderError::AllMirrorsFailed { .. } | DownloaderError::NoMirrorConfigured => {
+                Some("Check the network connection, or choose other mirrors with 'chrootmanager mirror -i'")
+            }
+            DownloaderError::ProxyConnection { .. } => {
+                Some("Check proxy_url in the configuration, or the http_proxy and https_proxy variables")
+            }
+            DownloaderError::NotFoundOnMirrors(_) | DownloaderError::NoPreviousSnapshot(_) => {
+                Some("The mirrors may lag behind, try again later or choose other mirrors with 'chrootmanager mirror -i'")
+            }
+            DownloaderError::NoStage3Found(_) => {
+                Some("Check the profile name, 'chrootmanager create -i' lists the published ones")
+            }
+            DownloaderError::SnapshotNotFound { .. } => {
+                Some("The published snapshots are listed under releases/<arch>/autobuilds/ on the mirrors")
+            }
+            DownloaderError::IncompleteDownload { .. } => {
+                Some("The partial download is kept, run the command again to resume it")
+            }
+            DownloaderError::Io(_) => Some("Check the free space and the permissions of the stage3 cache directory"),
+            _ => None,
+        }
+    }
+}
+
 impl From<Box<dyn std::error::Error>> for ChrootManagerError {
     fn from(error: Box<dyn std::error::Error>) -> Self {
-        ChrootManagerError::Generic(error)
+        // Keep download failures typed, for their hints
+        match error.downcast::<DownloaderError>() {
+            Ok(error) => ChrootManagerError::Download(*error),
+            Err(error) => ChrootManagerError::Generic(error),
+        }
     }
 }
@@ -0,0 +1,33 @@
+use crate::chroot::SessionOptions;
+use crate::cli::common::{find_chroot_unit, record_session};
+use crate::cli::error::ChrootManagerError;
+use crate::say;
+use crate::signals;
+use crate::ui::symbols::Symbol;
+use chrono::Local;
+use std::os::unix::process::ExitStatusExt;
+
+/// Runs a command inside the named chroot and returns its exit code
+///
+/// A command killed by a signal is reported as 128 + the signal number, like a shell does.
+pub async fn exec_in_chroot(name: String, argv: Vec<String>, session: SessionOptions) -> Result<i32, ChrootManagerError> {
+    let unit = find_chroot_unit(&name).await?;
+
+    say!("{} Authenticating for privileged operations...", Symbol::Lock);
+    unit.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)?;
+    unit.ensure_emulation().map_err(ChrootManagerError::Chroot)?;
+
+    let started_at = Local::now();
+    let status = {
+        // Interrupts are meant for the command
+        let _shield = signals::shield_interrupts();
+        unit.exec_command(&argv, &session).map_err(ChrootManagerError::Chroot)?
+    };
+
+    let code = status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1);
+    record_session(&unit.name, started_at, Some(&argv), Some(code));
+    Ok(code)
+}
@@ -0,0 +1,74 @@
+use crate::chroot::archive::ExtractionOptions;
+use crate::chroot::ChrootUnit;
+use crate::cli::common::handle_existing_chroot;
+use crate::cli::error::ChrootManagerError;
+use crate::cli::load_config;
+use crate::say;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+use colored::Colorize;
+use std::path::PathBuf;
+
+/// Creates a chroot from a tar archive of a chroot tree
+///
+/// The profile is taken from the chrootmanager metadata in the archive, or
+/// guessed from its Portage profile. The configured extraction options apply,
+/// with the owners dropped on `no_same_owner` and `strip_components` leading
+/// directories removed.
+pub async fn import_chroot(
+    name: String,
+    archive: PathBuf,
+    no_same_owner: bool,
+    strip_components: u32,
+) -> Result<(), ChrootManagerError> {
+    ChrootUnit::validate_name(&name)?;
+    if !archive.is_file() {
+        return Err(ChrootManagerError::Custom(format!(
+            "The archive {} does not exist.",
+            archive.display()
+        )));
+    }
+
+    let config = load_config().await?;
+    config.ensure_chroot_base_dir()?;
+    let mut unit = ChrootUnit::new(name.clone(), None, &config).await?;
+    let mut extraction = ExtractionOptions::from_config(&config);
+    extraction.strip_components = strip_components;
+    if no_same_owner {
+        extraction.preserve_owner = false;
+    }
+    handle_existing_chroot(&unit, &config)?;
+
+    say!("{} Authenticating for privileged operations...", Symbol::Lock);
+    unit.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)?;
+
+    say!("{} Importing {}...", Symbol::Package, archive.display());
+    unit.prepare_chroot_directory().await?;
+    unit.ensure_empty_for_extraction()?;
+    unit.extract_archive(&archive, &extraction, "Chroot import").await?;
+
+    if unit.adopt_imported_metadata(&extraction)? {
+        if let Some(profile) = &unit.profile {
+            say!("{} Profile: {}", Symbol::Info, profile.to_string().cyan());
+        }
+    } else {
+        say!(
+            "{}",
+            format!("{} No chrootmanager metadata or Portage profile found, the profile is unknown", Symbol::Warning)
+                .yellow()
+        );
+    }
+    unit.copy_network_info(config.copy_host_ca_certs)?;
+
+    if output::is_quiet() {
+        println!("{}", unit.chroot_path.display());
+    } else {
+        println!(
+            "{}",
+            format!("{} Chroot '{name}' imported ({})", Symbol::Success, unit.chroot_path.display())
+                .green()
+                .bold()
+        );
+    }
+    Ok(())
+}
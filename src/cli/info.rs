@@ -0,0 +1,101 @@
+use crate::chroot::mounts::MountState;
+use crate::chroot::ChrootStatus;
+use crate::cli::command::OutputFormat;
+use crate::cli::common::{find_chroot_unit, format_duration};
+use crate::cli::download::format_bytes;
+use crate::cli::error::ChrootManagerError;
+use colored::Colorize;
+use crate::ui::symbols::Symbol;
+use std::time::Duration;
+
+/// Render an optional value, falling back to "unknown"
+fn or_unknown(value: Option<&str>) -> String {
+    value.unwrap_or("unknown").to_string()
+}
+
+fn display_info(info: &ChrootStatus) {
+    println!("{}", format!("{} Chroot '{}'", Symbol::Info, info.name).green().bold());
+    println!("   {} Path: {}", Symbol::Location, info.path.display());
+    println!(
+        "   Architecture: {}",
+        or_unknown(info.architecture.as_deref()).cyan()
+    );
+    println!("   Profile: {}", or_unknown(info.profile.as_deref()).cyan());
+    let make_profile = info
+        .make_profile
+        .as_ref()
+        .map(|path| path.display().to_string());
+    println!("   Portage profile: {}", or_unknown(make_profile.as_deref()));
+    println!("   Stage3: {}", or_unknown(info.stage3.as_deref()));
+    println!("   Stage3 SHA256: {}", or_unknown(info.stage3_sha256.as_deref()));
+    println!("   Snapshot: {}", or_unknown(info.snapshot.as_deref()));
+    println!("   Mirror: {}", or_unknown(info.mirror.as_deref()));
+
+    let created_at = info
+        .created_at
+        .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string());
+    println!("   Created: {}", or_unknown(created_at.as_deref()));
+    println!("   Created by: {}", or_unknown(info.created_by.as_deref()));
+    println!("   Timezone: {}", info.timezone.as_deref().unwrap_or("UTC"));
+    println!("   Locale: {}", info.locale.as_deref().unwrap_or("POSIX"));
+    println!("   Hostname: {}", info.hostname.as_deref().unwrap_or("unset"));
+    let synced_at = info
+        .portage_synced_at
+        .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string());
+    println!("   Portage tree synced: {}", or_unknown(synced_at.as_deref()));
+    let entered_at = info
+        .last_entered_at
+        .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string());
+    println!("   Last entered: {}", or_unknown(entered_at.as_deref()));
+    if let Some(issue) = &info.metadata_issue {
+        println!("   {}", format!("{} {issue}", Symbol::Warning).yellow());
+    }
+
+    if info.mounted {
+        match info.mount_state {
+            MountState::Partial => println!("   Mounted: {}", "partially".yellow()),
+            _ => println!("   Mounted: {}", "yes".yellow()),
+        }
+        for mount_point in &info.mount_points {
+            println!("   {} {}", Symbol::Bullet, mount_point.display());
+        }
+    } else {
+        println!("   Mounted: no");
+    }
+
+    println!("   Disk usage: {}", format_bytes(info.disk_usage_bytes));
+
+    if !info.sessions.is_empty() {
+        println!("   Recent sessions:");
+        for session in info.sessions.iter().rev() {
+            let what = match (&session.command, session.exit_code) {
+                (Some(command), Some(code)) => format!("{command} (exit {code})"),
+                (Some(command), None) => command.clone(),
+                (None, _) => "shell".to_string(),
+            };
+            println!(
+                "   {} {}  {}  {what}",
+                Symbol::Bullet,
+                session.started_at.format("%Y-%m-%d %H:%M:%S"),
+                format_duration(Duration::from_secs(session.duration_secs))
+            );
+        }
+    }
+}
+
+/// Shows the details of a single chroot
+pub async fn show_chroot_info(name: String, format: OutputFormat) -> Result<(), ChrootManagerError> {
+    let unit = find_chroot_unit(&name).await?;
+    let info = unit.status();
+
+    match format {
+        OutputFormat::Text => display_info(&info),
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&info)
+                .map_err(|e| ChrootManagerError::Custom(format!("JSON serialization failed: {e}")))?;
+            println!("{json}");
+        }
+    }
+
+    Ok(())
+}
@@ -1,34 +1,140 @@
+use crate::chroot::mounts::MountState;
+use crate::chroot::ChrootUnit;
+use crate::cli::command::ListFormat;
 use crate::cli::common::load_chroot_units;
 use crate::cli::error::ChrootManagerError;
+use crate::cli::project::filter_project_units;
+use chrono::{DateTime, Local};
 use colored::Colorize;
+use serde::Serialize;
+use crate::say;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+
+/// A chroot as listed by `list --format json`
+#[derive(Serialize)]
+struct ChrootListing {
+    name: String,
+    /// Lossy, so that a chroot whose path is not UTF-8 is still listed
+    path: String,
+    arch: Option<String>,
+    profile: Option<String>,
+    /// Whether any filesystem the chroot needs is mounted
+    mounted: bool,
+    mount_state: MountState,
+    /// Only computed with `--size`, it walks the whole tree
+    size_bytes: Option<u64>,
+    created_at: Option<DateTime<Local>>,
+}
+
+impl ChrootListing {
+    fn new(unit: &ChrootUnit, size: bool) -> Self {
+        let mount_state = unit.mount_state();
+        Self {
+            name: unit.name.clone(),
+            path: unit.chroot_path.to_string_lossy().into_owned(),
+            arch: unit.profile.as_ref().map(|p| p.arch().to_string()),
+            profile: unit.profile.as_ref().map(|p| p.profile().to_string()),
+            mounted: mount_state != MountState::Unmounted,
+            mount_state,
+            size_bytes: size.then(|| unit.disk_usage()),
+            created_at: unit.metadata.as_ref().and_then(|metadata| metadata.created_at),
+        }
+    }
+}
+
+/// Creation date of a chroot as listed, "-" when it was not recorded
+pub(crate) fn created_date(unit: &ChrootUnit) -> String {
+    unit.metadata
+        .as_ref()
+        .and_then(|metadata| metadata.created_at)
+        .map_or_else(|| "-".to_string(), |date| date.format("%Y-%m-%d").to_string())
+}
+
+/// Prints the chroots as a JSON array, an empty one when there is none
+fn print_json(units: &[ChrootUnit], size: bool) -> Result<(), ChrootManagerError> {
+    let listing: Vec<ChrootListing> = units.iter().map(|unit| ChrootListing::new(unit, size)).collect();
+    let json = serde_json::to_string_pretty(&listing)
+        .map_err(|e| ChrootManagerError::Custom(format!("JSON serialization failed: {e}")))?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Prints one tab-separated line per chroot, "-" standing for unknown values
+fn print_plain(units: &[ChrootUnit], size: bool) {
+    for unit in units {
+        let mut fields = vec![
+            unit.name.clone(),
+            unit.chroot_path.display().to_string(),
+            unit.profile.as_ref().map_or_else(|| "-".to_string(), |p| p.arch().to_string()),
+            unit.profile.as_ref().map_or_else(|| "-".to_string(), |p| p.profile().to_string()),
+            unit.mount_state().to_string(),
+            created_date(unit),
+        ];
+        if size {
+            fields.push(unit.disk_usage().to_string());
+        }
+        println!("{}", fields.join("\t"));
+    }
+}
 
 /// Lists all available chroots in a formatted table
 ///
-/// This function is used by the non-interactive list command.
-pub async fn list_chroots() -> Result<(), ChrootManagerError> {
+/// This function is used by the non-interactive list command. With a
+/// project, only its members are listed. The JSON and plain formats only
+/// print the chroots on stdout, the other messages going to stderr.
+pub async fn list_chroots(project: Option<String>, format: ListFormat, size: bool) -> Result<(), ChrootManagerError> {
+    if format != ListFormat::Text {
+        output::reserve_stdout();
+    }
+
     // Load chroot units using the common function
-    let units = load_chroot_units().await?;
+    let mut units = load_chroot_units().await?;
+    if let Some(project) = &project {
+        units = filter_project_units(units, project)?;
+    }
+
+    match format {
+        ListFormat::Json => return print_json(&units, size),
+        ListFormat::Plain => {
+            print_plain(&units, size);
+            return Ok(());
+        }
+        ListFormat::Text => {}
+    }
 
     if units.is_empty() {
         return Ok(());
     }
 
+    if output::is_quiet() {
+        for unit in &units {
+            println!("{}", unit.name);
+        }
+        return Ok(());
+    }
+
     // Display available chroots
-    println!("\n   📋 Available chroots:");
-    println!("   {:<20} {:<15} {}", "NAME", "PROFILE", "PATH");
-    println!("   {}", "─".repeat(60));
+    say!("\n   {} Available chroots:", Symbol::Info);
+    say!("   {:<20} {:<15} {:<12} {:<10} PATH", "NAME", "PROFILE", "CREATED", "MOUNTS");
+    say!("   {}", Symbol::Separator.as_str().repeat(83));
 
     for unit in &units {
-        let profile_name = match &unit.profile {
-            Some(profile) => format!("{profile}"),
-            None => "Undefined".to_string(),
-        };
+        let profile_name = unit.profile_label();
 
         let path_display = unit.chroot_path.display();
-        println!("   {:<20} {:<15} {}", unit.name, profile_name, path_display);
+        let mount_state = unit.mount_state();
+        say!(
+            "   {:<20} {:<15} {:<12} {:<10} {}",
+            unit.name,
+            profile_name,
+            created_date(unit),
+            mount_state.to_string(),
+            path_display
+        );
     }
 
-    println!("\n   {}", format!("✅ {} chroot(s) found", units.len()).green());
+    say!("\n   {}", format!("{} {} chroot(s) found", Symbol::Success, units.len()).green());
 
     Ok(())
 }
@@ -1,52 +1,19 @@
-use crate::chroot::ChrootUnit;
-use crate::cli::common::load_chroot_units;
+use crate::chroot::mounts::MountState;
+use crate::chroot::{ChrootUnit, SessionOptions};
+use crate::cli::common::{enter_chroot_with_unit, load_chroot_units, upgrade_metadata};
 use crate::cli::error::ChrootManagerError;
+use crate::cli::list::created_date;
+use crate::cli::load_config;
 use colored::Colorize;
-use inquire::{InquireError, Select};
-
-/// Enters a chroot environment interactively using a ChrootUnit
-///
-/// This function handles mounting, entering, and cleaning up the chroot.
-fn enter_chroot_with_unit(chroot_unit: &ChrootUnit) -> Result<(), ChrootManagerError> {
-    // Show chroot info
-    println!("✅ Found chroot: {}", chroot_unit.chroot_path.display());
-
-    if let Ok(profile) = chroot_unit.read_arch_profile_info() {
-        println!("📋 Profile: {}", profile.cyan());
-    }
-
-    // Pre-authenticate
-    println!("🔐 Authenticating for privileged operations...");
-    chroot_unit.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)?;
-
-    // Mount filesystems
-    println!("🗄️ Mounting filesystems...");
-    chroot_unit.mount_filesystems().map_err(ChrootManagerError::Chroot)?;
-
-    let result = chroot_unit.enter_chroot_interactive();
-
-    // Always try to unmount, even if chroot failed
-    println!("🧹 Cleaning up filesystems...");
-    if let Err(e) = chroot_unit.unmount_filesystems() {
-        println!("{}", format!("⚠️ Warning: Failed to unmount filesystems: {e}").yellow());
-    } else {
-        println!("{}", "✅ Filesystems unmounted successfully".green());
-    }
-
-    // Handle chroot result
-    match result {
-        Ok(()) => {
-            println!("{}", format!("✅ Successfully exited chroot '{}'", chroot_unit.name).green());
-            Ok(())
-        }
-        Err(e) => Err(ChrootManagerError::Generic(Box::new(e))),
-    }
-}
+use inquire::Select;
+use crate::say;
+use crate::ui::symbols::Symbol;
 
 /// Lists all available chroots interactively and allows entering a selected chroot
 ///
-/// This function is used by the interactive list command.
-pub async fn list_chroots_interactive() -> Result<(), ChrootManagerError> {
+/// This function is used by the interactive list command. With `space_warning`,
+/// a warning is shown on exit when the chroot filesystem is low on space.
+pub async fn list_chroots_interactive(space_warning: bool) -> Result<(), ChrootManagerError> {
     // Load chroot units using the common function
     let units = load_chroot_units().await?;
 
@@ -54,53 +21,40 @@ pub async fn list_chroots_interactive() -> Result<(), ChrootManagerError> {
         return Ok(());
     }
 
-    // Create a list of chroot names for selection
-    let units_choices = units.iter().map(|u| u.name.as_str()).collect::<Vec<_>>();
+    // Create a list of chroots for selection, with their profile and creation date
+    let units_choices = units
+        .iter()
+        .map(|u| {
+            let profile = u.profile_label();
+            match u.mount_state() {
+                MountState::Unmounted => format!("{} ({profile}, {})", u.name, created_date(u)),
+                state => format!("{} ({profile}, {}, {state})", u.name, created_date(u)),
+            }
+        })
+        .collect::<Vec<_>>();
 
     // Prompt user to select a chroot
-    let units_selected: Result<&str, InquireError> =
-        Select::new("📋 List of chroots", units_choices)
-            .without_help_message()
-            .prompt();
-    
-    let units_selected = units_selected?;
-    let unit: Vec<&ChrootUnit> = units.iter().filter(|u| u.name.eq(units_selected)).collect();
-    let unit = unit[0];
+    let units_selected = Select::new(&format!("{} List of chroots", Symbol::Info), units_choices)
+        .without_help_message()
+        .raw_prompt()?;
+    let mut unit: ChrootUnit = units[units_selected.index].clone();
 
     // Pre-authenticate for all upcoming privileged operations
-    println!(
+    say!(
         "{}",
-        "🔐 Requesting authentication for chroot operations..."
+        format!("{} Requesting authentication for chroot operations...", Symbol::Lock)
             .yellow()
             .bold()
     );
     unit.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)?;
+    upgrade_metadata(&mut unit);
 
-    // Mount filesystems and enter chroot
-    unit.mount_filesystems().map_err(ChrootManagerError::Chroot)?;
-
-    // Enter chroot interactively
-    let chroot_result = enter_chroot_with_unit(unit);
-
-    // Always try to unmount filesystems after exiting chroot
-    println!("{}", "🔧 Cleaning up mount points...".yellow().bold());
-
-    // Clean up mount points directly
-    match unit.unmount_filesystems() {
-        Ok(_) => println!("{}", "✅ Mount points cleaned up successfully".green()),
-        Err(e) => {
-            println!("{}", format!("⚠️ Warning during unmounting: {e}").yellow());
-            log::warn!("Failed to unmount some filesystems: {e}");
-        }
-    }
-
-    // Only return the chroot error if it was a real execution error
-    match chroot_result {
-        Ok(()) => Ok(()),
-        Err(e) => {
-            // Log the error but don't fail the entire operation
-            log::warn!("Chroot session ended with: {e}");
-            Ok(())
-        }
-    }
+    // Enter chroot interactively, the filesystems are mounted for the session
+    let space_threshold = if space_warning {
+        Some(load_config().await?.low_space_threshold())
+    } else {
+        None
+    };
+    // A non-zero exit of the shell is not a failure, only failing to start it is
+    enter_chroot_with_unit(&unit, space_threshold.as_ref(), &SessionOptions::default())
 }
@@ -0,0 +1,118 @@
+use crate::chroot::metadata::METADATA_VERSION;
+use crate::chroot::ChrootUnit;
+use crate::cli::common::find_chroot_unit;
+use crate::cli::error::ChrootManagerError;
+use colored::Colorize;
+use crate::say;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+
+/// Metadata versions of a chroot before and after its migration
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Migration {
+    from: u32,
+    to: u32,
+}
+
+/// The migration the metadata of a chroot needs, `None` when it is current
+fn plan_migration(unit: &ChrootUnit) -> Result<Option<Migration>, ChrootManagerError> {
+    let Some(version) = unit.metadata.as_ref().map(|metadata| metadata.metadata_version) else {
+        return Err(ChrootManagerError::Custom(format!(
+            "The chroot '{}' has no metadata, it was not created by chrootmanager.",
+            unit.name
+        )));
+    };
+    unit.ensure_supported_metadata()?;
+    Ok((version != METADATA_VERSION).then_some(Migration {
+        from: version,
+        to: METADATA_VERSION,
+    }))
+}
+
+/// Plan the migration of a chroot, and write its metadata unless `dry_run`
+fn migrate_unit(unit: &mut ChrootUnit, dry_run: bool) -> Result<Option<Migration>, ChrootManagerError> {
+    let Some(migration) = plan_migration(unit)? else {
+        return Ok(None);
+    };
+    if dry_run {
+        return Ok(Some(migration));
+    }
+
+    say!("{} Authenticating for privileged operations...", Symbol::Lock);
+    unit.pre_authenticate_operations()?;
+    unit.migrate_metadata()?;
+    Ok(Some(migration))
+}
+
+/// Upgrades the metadata of a chroot to the current format
+///
+/// With `dry_run`, the migration is only shown.
+pub async fn migrate_chroot(name: String, dry_run: bool) -> Result<(), ChrootManagerError> {
+    let mut unit = find_chroot_unit(&name).await?;
+
+    let Some(Migration { from, to }) = migrate_unit(&mut unit, dry_run)? else {
+        say!("{} The metadata of '{name}' is already current (version {METADATA_VERSION})", Symbol::Success);
+        return Ok(());
+    };
+
+    if output::is_quiet() {
+        println!("{name}");
+    } else if dry_run {
+        println!("{} Metadata of '{name}' would be migrated from version {from} to {to}", Symbol::Info);
+    } else {
+        println!(
+            "{}",
+            format!("{} Metadata of '{name}' migrated from version {from} to {to}", Symbol::Success)
+                .green()
+                .bold()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroot::metadata::{LEGACY_PROFILE_FILE, METADATA_FILE};
+    use std::fs;
+
+    /// Chroot holding only the profile file of older versions
+    fn legacy_chroot() -> (tempfile::TempDir, ChrootUnit) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("legacy");
+        fs::create_dir_all(path.join("etc")).unwrap();
+        fs::write(path.join(LEGACY_PROFILE_FILE), "amd64-openrc").unwrap();
+        let unit = ChrootUnit::load(&path).unwrap();
+        (dir, unit)
+    }
+
+    #[test]
+    fn a_dry_run_writes_nothing() {
+        let (_dir, mut unit) = legacy_chroot();
+        let migration = migrate_unit(&mut unit, true).unwrap();
+        assert_eq!(migration, Some(Migration { from: 0, to: METADATA_VERSION }));
+        assert!(!unit.chroot_path.join(METADATA_FILE).exists());
+        assert_eq!(unit.metadata.as_ref().unwrap().metadata_version, 0);
+    }
+
+    #[test]
+    fn a_chroot_without_metadata_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("etc")).unwrap();
+        let mut unit = ChrootUnit::load(dir.path()).unwrap();
+        assert!(migrate_unit(&mut unit, true).is_err());
+    }
+
+    #[test]
+    fn the_migration_writes_the_current_metadata() {
+        // The metadata is written through sudo otherwise
+        if !nix::unistd::geteuid().is_root() {
+            return;
+        }
+        let (_dir, mut unit) = legacy_chroot();
+        assert!(migrate_unit(&mut unit, false).unwrap().is_some());
+        let reloaded = ChrootUnit::load(&unit.chroot_path).unwrap();
+        assert_eq!(reloaded.metadata.as_ref().unwrap().metadata_version, METADATA_VERSION);
+        assert_eq!(migrate_unit(&mut unit, false).unwrap(), None);
+    }
+}
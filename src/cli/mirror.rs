@@ -1,23 +1,317 @@
 use crate::cli::error::ChrootManagerError;
 use crate::cli::load_config;
-use crate::mirror::verify_mirror_url;
+use crate::config::{MirrorEntry, DEFAULT_MIRROR_URL};
+use crate::cli::download::format_bytes;
+use crate::error::MirrorError;
+use crate::http;
+use crate::mirror::bench::{self, MirrorBench};
+use crate::mirror::{check_mirror_reachable, verify_mirror_url, MirrorListOptions, Mirrors};
 use colored::Colorize;
+use crate::say;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
 
 /// Adds a new mirror to the configuration after verifying it
-pub async fn setup_mirrors(new_mirror: String) -> Result<(), ChrootManagerError> {
+///
+/// With `arch`, the mirror must carry the autobuilds of that architecture.
+pub async fn setup_mirrors(new_mirror: String, arch: Option<String>) -> Result<(), ChrootManagerError> {
+    let mut config = load_config().await?;
+
     // Verify that the URL is a valid Gentoo mirror before adding it
-    println!("🔄 Verifying mirror URL...");
-    verify_mirror_url(&new_mirror).await?;
-    
+    say!("{} Verifying mirror URL...", Symbol::Refresh);
+    verify_mirror_url(&new_mirror, &config, arch.as_deref()).await?;
+
     // If verification succeeds, proceed with adding the mirror
-    let mut config = load_config().await?;
-    
-    config.add_mirror(&new_mirror).await?;
+    config.add_mirror(MirrorEntry::from_url(&new_mirror)).await?;
     
-    println!("{}", format!("✅ Mirror '{new_mirror}' added successfully").green().bold());
+    if output::is_quiet() {
+        println!("{new_mirror}");
+    } else {
+        println!("{}", format!("{} Mirror '{new_mirror}' added successfully", Symbol::Success).green().bold());
+    }
     
     // Save the configuration
     config.save()?;
     
     Ok(())
 }
+
+/// Which of the mirrors matching the filters of `mirror --region` are added
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchSelection {
+    /// Exactly one mirror must match
+    Single,
+    First,
+    All,
+}
+
+/// Names among `available` equal to `filter`, ignoring case, or all without a filter
+fn filter_names<'a>(available: Vec<&'a str>, filter: Option<&str>) -> Vec<&'a str> {
+    match filter {
+        Some(filter) => available
+            .into_iter()
+            .filter(|name| name.eq_ignore_ascii_case(filter))
+            .collect(),
+        None => available,
+    }
+}
+
+/// Adds the mirrors of the Gentoo mirror list matching a region and a country
+///
+/// An empty or, without `--first` or `--all`, ambiguous match fails with
+/// the choices available for the given filters.
+pub async fn add_matching_mirrors(
+    region: Option<String>,
+    country: Option<String>,
+    protocol: String,
+    selection: MatchSelection,
+    options: MirrorListOptions,
+) -> Result<(), ChrootManagerError> {
+    let mut config = load_config().await?;
+    let mirrors = Mirrors::load(&config, &options).await?;
+
+    let regions = filter_names(mirrors.get_regions(), region.as_deref());
+    if regions.is_empty() {
+        return Err(ChrootManagerError::Custom(format!(
+            "No region matches '{}'. Available regions: {}",
+            region.unwrap_or_default(),
+            mirrors.get_regions().join(", ")
+        )));
+    }
+
+    let countries: Vec<(&str, &str)> = regions
+        .iter()
+        .flat_map(|region| {
+            filter_names(mirrors.get_countries(region), country.as_deref())
+                .into_iter()
+                .map(move |country| (*region, country))
+        })
+        .collect();
+    if countries.is_empty() {
+        let available: Vec<&str> = regions.iter().flat_map(|region| mirrors.get_countries(region)).collect();
+        return Err(ChrootManagerError::Custom(format!(
+            "No country matches '{}'. Available countries: {}",
+            country.unwrap_or_default(),
+            available.join(", ")
+        )));
+    }
+
+    let locations: Vec<&str> = countries
+        .iter()
+        .flat_map(|(region, country)| mirrors.get_locations(region, country))
+        .collect();
+    let mut matches = Vec::new();
+    for location in &locations {
+        match mirrors.get_entry(location, &protocol) {
+            Ok(entry) => matches.push(entry),
+            Err(e) => log::debug!("Skipping {location}: {e}"),
+        }
+    }
+
+    let chosen = match (matches.len(), selection) {
+        (0, _) => {
+            let available: Vec<String> = locations
+                .iter()
+                .map(|location| {
+                    let protocols = mirrors.get_protocols(location).unwrap_or_default().join("/");
+                    format!("{location} ({protocols})")
+                })
+                .collect();
+            return Err(ChrootManagerError::Custom(format!(
+                "No mirror matches over {protocol}. Available mirrors: {}",
+                available.join(", ")
+            )));
+        }
+        (1, _) | (_, MatchSelection::All) => matches,
+        (_, MatchSelection::First) => matches.into_iter().take(1).collect(),
+        (_, MatchSelection::Single) => {
+            let available: Vec<String> = matches.iter().map(MirrorEntry::describe).collect();
+            return Err(ChrootManagerError::Custom(format!(
+                "{} mirrors match, narrow the filters or use --first or --all: {}",
+                available.len(),
+                available.join(", ")
+            )));
+        }
+    };
+
+    for entry in chosen {
+        let url = entry.url.clone();
+        let description = entry.describe();
+        config.add_mirror(entry).await?;
+        if output::is_quiet() {
+            println!("{url}");
+        } else {
+            println!("{}", format!("{} Mirror '{description}' added ({url})", Symbol::Success).green().bold());
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows the configured mirrors with their details
+pub async fn show_mirrors() -> Result<(), ChrootManagerError> {
+    let config = load_config().await?;
+
+    if !config.has_mirrors() {
+        say!("{} No mirror configured", Symbol::Warning);
+        return Ok(());
+    }
+
+    if output::is_quiet() {
+        for url in config.mirror_urls() {
+            println!("{url}");
+        }
+        return Ok(());
+    }
+
+    say!("{} Configured mirrors:", Symbol::Info);
+    for (index, mirror) in config.mirrors_url.iter().enumerate() {
+        say!("  {}. {}", index + 1, mirror.describe().cyan());
+        if mirror.label.is_some() {
+            say!("     {}", mirror.url.dimmed());
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the configured mirrors with their index and whether they answer
+///
+/// The mirrors are probed concurrently.
+pub async fn list_mirrors() -> Result<(), ChrootManagerError> {
+    let config = load_config().await?;
+
+    if output::is_quiet() {
+        for url in config.mirror_urls() {
+            println!("{url}");
+        }
+        return Ok(());
+    }
+
+    if !config.has_mirrors() {
+        say!("{} No mirror configured, {DEFAULT_MIRROR_URL} is used", Symbol::Warning);
+        return Ok(());
+    }
+
+    say!("{} Checking the configured mirrors...", Symbol::Network);
+    let probes: Vec<_> = config
+        .mirrors_url
+        .iter()
+        .map(|mirror| {
+            let url = mirror.url.clone();
+            let config = config.clone();
+            tokio::spawn(async move { check_mirror_reachable(&url, &config).await })
+        })
+        .collect();
+
+    for (index, (mirror, probe)) in config.mirrors_url.iter().zip(probes).enumerate() {
+        let status = match probe.await {
+            Ok(Ok(())) => "reachable".green(),
+            Ok(Err(MirrorError::Offline(_))) => "not checked (offline)".yellow(),
+            Ok(Err(e)) => format!("unreachable ({e})").red(),
+            Err(e) => format!("unknown ({e})").yellow(),
+        };
+        let priority = mirror
+            .priority
+            .map(|priority| format!(" (priority {priority})"))
+            .unwrap_or_default();
+        println!("  {}. {}{priority} {status}", index + 1, mirror.url.cyan());
+        if mirror.label.is_some() {
+            say!("     {}", mirror.describe().dimmed());
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes a mirror given by its URL or its index in `mirror list`
+pub async fn remove_mirror(target: String) -> Result<(), ChrootManagerError> {
+    let mut config = load_config().await?;
+    let before: Vec<String> = config.mirror_urls().map(str::to_string).collect();
+
+    if !config.remove_mirror(&target)? {
+        return Err(ChrootManagerError::Custom(format!(
+            "No configured mirror matches '{target}'. See `chrootmanager mirror list`"
+        )));
+    }
+    let url = before
+        .into_iter()
+        .find(|url| !config.mirror_urls().any(|remaining| remaining == url))
+        .unwrap_or(target);
+
+    if output::is_quiet() {
+        println!("{url}");
+    } else {
+        println!("{}", format!("{} Mirror '{url}' removed", Symbol::Success).green().bold());
+    }
+    if !config.has_mirrors() {
+        say!(
+            "{}",
+            format!("{} No mirror left, the default {DEFAULT_MIRROR_URL} will be used", Symbol::Warning).yellow()
+        );
+    }
+
+    Ok(())
+}
+
+fn print_bench_results(results: &[MirrorBench]) {
+    println!("   {:<4} {:<50} {:>10} {:>12}", "RANK", "MIRROR", "LATENCY", "THROUGHPUT");
+    println!("   {}", Symbol::Separator.as_str().repeat(80));
+    for (index, bench) in results.iter().enumerate() {
+        match &bench.result {
+            Ok(timing) => {
+                let throughput = timing
+                    .bytes_per_sec
+                    .map_or_else(|| "-".to_string(), |speed| format!("{}/s", format_bytes(speed as u64)));
+                println!(
+                    "   {:<4} {:<50} {:>8}ms {:>12}",
+                    index + 1,
+                    bench.url,
+                    timing.latency.as_millis(),
+                    throughput
+                );
+            }
+            Err(e) => println!("   {:<4} {:<50} {}", "-", bench.url, format!("failed: {e}").red()),
+        }
+    }
+}
+
+/// Benchmarks the configured mirrors and optionally saves them fastest first
+pub async fn bench_mirrors(apply: bool) -> Result<(), ChrootManagerError> {
+    let mut config = load_config().await?;
+    if !config.has_mirrors() {
+        say!("{} No mirror configured, {DEFAULT_MIRROR_URL} is used", Symbol::Warning);
+        return Ok(());
+    }
+    http::ensure_online("Benchmarking the mirrors")?;
+
+    say!("{} Benchmarking {} mirror(s)...", Symbol::Speed, config.mirrors_url.len());
+    let mut results = bench::bench_mirrors(&config)
+        .await
+        .map_err(|e| ChrootManagerError::Custom(format!("Unable to start the benchmark: {e}")))?;
+    bench::rank(&mut results);
+
+    if output::is_quiet() {
+        for bench in &results {
+            println!("{}", bench.url);
+        }
+    } else {
+        print_bench_results(&results);
+    }
+
+    if results.iter().all(|bench| bench.result.is_err()) {
+        return Err(ChrootManagerError::Custom("No mirror answered".to_string()));
+    }
+
+    if apply {
+        let ranked: Vec<&str> = results.iter().map(|bench| bench.url.as_str()).collect();
+        let priorities_cleared = config.reorder_mirrors(&ranked);
+        config.save()?;
+        say!("{} Mirrors saved from fastest to slowest", Symbol::Success);
+        if priorities_cleared {
+            say!("{} The mirror priorities were cleared so that this order applies", Symbol::Info);
+        }
+    }
+
+    Ok(())
+}
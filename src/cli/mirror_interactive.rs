@@ -1,10 +1,20 @@
 use inquire::{InquireError, Select};
 use crate::cli::error::ChrootManagerError;
 use crate::cli::{configure_mirrors, load_config};
+use crate::config::{MirrorEntry, DEFAULT_MIRROR_URL};
+use crate::mirror::MirrorListOptions;
 use colored::Colorize;
+use crate::say;
+use crate::ui::symbols::Symbol;
 
 /// Sets up mirrors interactively by allowing the user to choose from options
-pub async fn setup_mirrors_interactive() -> Result<(), ChrootManagerError> {
+///
+/// The official list is fetched and filtered as set in `list_options`. With
+/// `ask_protocol`, the protocol of each selected mirror is prompted for.
+pub async fn setup_mirrors_interactive(
+    list_options: MirrorListOptions,
+    ask_protocol: bool,
+) -> Result<(), ChrootManagerError> {
     let mut config = load_config().await?;
 
     let options = vec![
@@ -13,34 +23,34 @@ pub async fn setup_mirrors_interactive() -> Result<(), ChrootManagerError> {
     ];
 
     let mirror_configuration_select: Result<&str, InquireError> =
-        Select::new("🔧 Mirror Configuration", options)
+        Select::new(&format!("{} Mirror Configuration", Symbol::Tool), options)
             .without_help_message()
             .prompt();
 
     match mirror_configuration_select {
         Ok(choice) => match choice {
             "Select a mirror from the official list (recommended)" => {
-                configure_mirrors(&mut config).await?;
+                configure_mirrors(&mut config, list_options, ask_protocol).await?;
                 // Save the configuration after configuring mirrors
                 config.save()?;
             }
             "Use Gentoo's default mirror" => {
-                config.mirrors_url = vec!["https://distfiles.gentoo.org/".to_string()];
-                println!("{}", "✅ Using Gentoo's default mirror".green().bold());
+                config.mirrors_url = vec![MirrorEntry::from_url(DEFAULT_MIRROR_URL)];
+                say!("{}", format!("{} Using Gentoo's default mirror", Symbol::Success).green().bold());
                 // Save the configuration after setting the default mirror
                 config.save()?;
             }
             _ => {
-                println!("{}", "❌ Error during choice".red().bold());
-                println!("Using the default mirror...");
-                config.mirrors_url = vec!["https://distfiles.gentoo.org/".to_string()];
+                say!("{}", format!("{} Error during choice", Symbol::Error).red().bold());
+                say!("Using the default mirror...");
+                config.mirrors_url = vec![MirrorEntry::from_url(DEFAULT_MIRROR_URL)];
                 config.save()?;
             }
         },
         Err(e) => {
-            println!("{}", format!("❌ Error during configuration: {e}").red().bold());
-            println!("Using the default mirror...");
-            config.mirrors_url = vec!["https://distfiles.gentoo.org/".to_string()];
+            say!("{}", format!("{} Error during configuration: {e}", Symbol::Error).red().bold());
+            say!("Using the default mirror...");
+            config.mirrors_url = vec![MirrorEntry::from_url(DEFAULT_MIRROR_URL)];
             config.save()?;
         }
     }
@@ -1,19 +1,40 @@
+pub mod cache;
+pub mod clone;
 pub mod command;
 pub mod common;
+pub mod completions;
+pub mod config;
 pub mod create;
-pub mod create_interactive;
+pub mod describe;
+pub mod doctor;
+pub mod download;
+pub mod enter;
+pub mod exec;
+pub mod import;
 mod error;
+pub mod info;
 pub mod list;
 pub mod list_interactive;
+pub mod migrate;
 pub mod mirror;
 pub mod mirror_interactive;
-pub(crate) mod download;
+pub mod profiles;
+pub mod project;
+pub mod rename;
+pub mod selftest;
+pub mod summary;
+pub mod timing;
+pub mod unmount;
+pub mod why_failed;
 pub(crate) mod profile;
 
-use crate::config::{Config, ConfigError};
-use crate::mirror::Mirrors;
-use inquire::{InquireError, Select};
+use crate::config::{test_mode_dir, Config, ConfigError, MirrorEntry, DEFAULT_MIRROR_URL};
+use crate::mirror::parser::UriInfo;
+use crate::mirror::{MirrorListOptions, Mirrors, PREFERRED_PROTOCOLS};
+use inquire::{InquireError, MultiSelect, Select};
 use std::fs;
+use crate::say;
+use crate::ui::symbols::{self, Symbol};
 
 pub async fn load_config() -> Result<Config, ConfigError> {
     let config_path = Config::default_config_path();
@@ -24,37 +45,51 @@ pub async fn load_config() -> Result<Config, ConfigError> {
         // Try loading with the new format
         match Config::try_parse_config(&config_content) {
             Ok(config) => {
+                config.validate()?;
+                if config.ascii_output {
+                    symbols::set_ascii_output(true);
+                }
                 config.ensure_cache_dir()?;
                 Ok(config)
             }
             Err(_) => {
                 // New format failed, try migrating from the old format
-                println!("🔄 Old configuration migration detected...");
+                say!("{} Old configuration migration detected...", Symbol::Refresh);
                 let migrated_config = Config::migrate_old_config(&config_content)?;
 
                 // Save the new configuration
                 migrated_config.save()?;
-                println!("✅ Configuration migrated successfully!");
+                say!("{} Configuration migrated successfully!", Symbol::Success);
 
                 migrated_config.ensure_cache_dir()?;
 
                 Ok(migrated_config)
             }
         }
+    } else if test_mode_dir().is_some() {
+        // No prompt nor mirror list download in test mode
+        let config = Config {
+            mirrors_url: vec![MirrorEntry::from_url(DEFAULT_MIRROR_URL)],
+            ..Config::default()
+        };
+        config.ensure_cache_dir()?;
+        config.save()?;
+        Ok(config)
     } else {
         // First use — offer mirror selection
-        println!("🎉 Welcome to ChrootManager!");
-        println!("This is your first use.");
-        println!("You need to set up at least one mirror to download stage3 archives.\n");
+        say!("{} Welcome to ChrootManager!", Symbol::Welcome);
+        say!("This is your first use.");
+        say!("You need to set up at least one mirror to download stage3 archives.\n");
 
         let mut config = Config::default();
         config.ensure_cache_dir()?;
-        configure_mirrors(&mut config).await?;
+        configure_mirrors(&mut config, MirrorListOptions::default(), false).await?;
 
-        println!("✅ Initial configuration created!\n");
+        say!("{} Initial configuration created!\n", Symbol::Success);
 
-        println!(
-            "📂 The chroots will be created in: {}\n",
+        say!(
+            "{} The chroots will be created in: {}\n",
+            Symbol::Folder,
             config.chroot_base_dir.display()
         );
 
@@ -62,9 +97,25 @@ pub async fn load_config() -> Result<Config, ConfigError> {
     }
 }
 
+/// Protocols of the URIs with their address families, e.g. "https v4+v6"
+fn uri_labels(uri_infos: &[UriInfo]) -> Vec<String> {
+    uri_infos
+        .iter()
+        .map(|info| format!("{} {}", info.protocol.as_str(), info.connectivity()))
+        .collect()
+}
+
 /// Interactive function to choose which mirror to save in the configuration
-async fn configure_mirrors(config: &mut Config) -> Result<(), ConfigError> {
-    let mirrors = Mirrors::fetch().await?;
+///
+/// Several locations of a country can be picked at once, each is added
+/// over https, or http, unless `ask_protocol` is set. The mirror list is
+/// fetched and filtered as set in `options`.
+async fn configure_mirrors(
+    config: &mut Config,
+    options: MirrorListOptions,
+    ask_protocol: bool,
+) -> Result<(), ConfigError> {
+    let mirrors = Mirrors::load(config, &options).await?;
 
     loop {
         let selected_option: Result<&str, InquireError> = Select::new(
@@ -87,30 +138,58 @@ async fn configure_mirrors(config: &mut Config) -> Result<(), ConfigError> {
                 let selected_country = selected_country?;
 
                 let locations = mirrors.get_locations(selected_region, selected_country);
-                let selected_locations: Result<&str, InquireError> =
-                    Select::new("Select your location", locations).prompt();
-                let selected_locations = selected_locations?;
-
-                let protocols = mirrors.get_protocols(selected_locations);
-                let selected_protocols: Result<&str, InquireError> =
-                    Select::new("Select your protocols", protocols).prompt();
-                let selected_protocols = selected_protocols?;
-
-                let new_mirror = mirrors.get_url(selected_locations, selected_protocols);
-                config.add_mirror(&new_mirror).await?;
+                let labels: Vec<String> = locations
+                    .iter()
+                    .map(|location| {
+                        let uri_infos = mirrors.get_uris_info(location).unwrap_or_default();
+                        format!("{location} ({})", uri_labels(uri_infos).join(", "))
+                    })
+                    .collect();
+                let selected_locations: Vec<&str> = MultiSelect::new("Select your locations", labels)
+                    .raw_prompt()?
+                    .into_iter()
+                    .map(|option| locations[option.index])
+                    .collect();
+
+                let new_mirrors = if ask_protocol {
+                    let mut entries = Vec::new();
+                    for location in &selected_locations {
+                        let uri_infos = mirrors.get_uris_info(location).map_err(Box::new)?;
+                        let selected_protocol =
+                            Select::new(&format!("Select the protocol of {location}"), uri_labels(uri_infos))
+                                .raw_prompt()?;
+                        let protocol = uri_infos[selected_protocol.index].protocol.as_str();
+                        entries.push(mirrors.get_entry(location, protocol).map_err(Box::new)?);
+                    }
+                    entries
+                } else {
+                    match mirrors.get_entries(&selected_locations, PREFERRED_PROTOCOLS) {
+                        Ok(entries) => entries,
+                        Err(e) => {
+                            say!("{} {e}, select other locations or use --ask-protocol", Symbol::Warning);
+                            continue;
+                        }
+                    }
+                };
+                for new_mirror in new_mirrors {
+                    say!("{} {} added", Symbol::Success, new_mirror.describe());
+                    config.add_mirror(new_mirror).await?;
+                }
             }
             Ok("Save configuration") => {
                 // Ensure default mirror
-                config.add_mirror("https://distfiles.gentoo.org").await?;
+                config
+                    .add_mirror(MirrorEntry::from_url("https://distfiles.gentoo.org"))
+                    .await?;
                 break;
             }
             _ => panic!("Unknown option!"),
         }
     }
 
-    println!("\n✅ Updated mirror configuration:");
-    for (index, mirror_url) in config.mirrors_url.iter().enumerate() {
-        println!("  {}. {}", index + 1, mirror_url);
+    say!("\n{} Updated mirror configuration:", Symbol::Success);
+    for (index, mirror) in config.mirrors_url.iter().enumerate() {
+        say!("  {}. {} - {}", index + 1, mirror.describe(), mirror.url);
     }
 
     Ok(())
@@ -1,34 +1,55 @@
 use crate::cli::error::ChrootManagerError;
-use crate::cli::load_config;
+use crate::config::Config;
 use crate::error::ProfileError;
 use crate::error::ProfileError::ArchitectureNotFound;
+use crate::profile::family::{classify_profile, ProfileFamily};
+use crate::platform;
 use crate::profile::{manager::ProfileManager, selected::SelectedProfile};
 use colored::Colorize;
 use inquire::{InquireError, Select};
+use crate::say;
+use crate::ui::symbols::Symbol;
 
 /// Display profile information
 pub(crate) fn display_profile_info(profile: &SelectedProfile) {
-    println!("📋 Selected Profile:");
-    println!("   Architecture: {}", profile.arch().cyan().bold());
-    println!("   Profile: {}", profile.profile().cyan().bold());
-    println!(
+    say!("{} Selected Profile:", Symbol::Info);
+    say!("   Architecture: {}", profile.arch().cyan().bold());
+    say!("   Profile: {}", profile.profile().cyan().bold());
+    say!(
         "   Stage3 pattern: {}",
         profile.get_stage3_pattern().dimmed()
     );
 }
 
-/// Display architecture selection menu and return selected profile
-pub(crate) async fn architecture_profile_selection() -> Result<SelectedProfile, ChrootManagerError>
-{
-    println!("🔍 Discovering available architectures and profiles...");
-
-    // Load config to use configured mirrors
-    let config = load_config().await?;
+/// Position of the configured default in the menu entries, the first entry without one
+fn default_cursor<'a>(mut choices: impl Iterator<Item = &'a str>, default: Option<&str>) -> usize {
+    default
+        .and_then(|default| choices.position(|choice| choice == default))
+        .unwrap_or(0)
+}
 
-    // Use configured mirrors
-    let profile_manager = ProfileManager::discover(&config).await?;
+/// Prompt the user to choose an architecture among the discovered ones
+///
+/// The cursor starts on `default_arch` from the configuration, or on the
+/// architecture of the host, when discovered.
+pub(crate) fn select_architecture(
+    profile_manager: &ProfileManager,
+    config: &Config,
+) -> Result<String, ChrootManagerError> {
     let arch_names = profile_manager.get_architecture_names();
 
+    if config.discover_architectures.is_some() {
+        say!(
+            "{}",
+            format!(
+                "{} Architectures limited by 'discover_architectures' in {}",
+                Symbol::Hint,
+                Config::default_config_path().display()
+            )
+            .dimmed()
+        );
+    }
+
     if arch_names.is_empty() {
         return Err(ChrootManagerError::Profile(
             ProfileError::NoArchitecturesAvailable,
@@ -37,28 +58,62 @@ pub(crate) async fn architecture_profile_selection() -> Result<SelectedProfile,
 
     // Display available architectures
     let arch_strings: Vec<String> = arch_names.iter().map(|s| s.to_string()).collect();
-    let arch_selection: Result<String, InquireError> =
-        Select::new("Select your architecture:", arch_strings).prompt();
-    let selected_arch = arch_selection?;
+    let default = match &config.default_arch {
+        Some(arch) => Some(arch.as_str()),
+        None => platform::host_architecture(),
+    };
+    let cursor = default_cursor(arch_strings.iter().map(String::as_str), default);
+    let arch_selection: Result<String, InquireError> = Select::new("Select your architecture:", arch_strings)
+        .with_starting_cursor(cursor)
+        .prompt();
+    Ok(arch_selection?)
+}
 
+/// Prompt the user to choose a profile available for the given architecture
+///
+/// Unless disabled in the configuration, profiles are sorted by family and
+/// prefixed with it (e.g. "openrc ▸ desktop-openrc"). The cursor starts on
+/// `default_profile` from the configuration, when available.
+pub(crate) fn select_profile(
+    profile_manager: &ProfileManager,
+    config: &Config,
+    arch: &str,
+) -> Result<String, ChrootManagerError> {
     // Get profiles for the selected architecture
     let architecture = profile_manager
-        .get_architecture(&selected_arch)
-        .ok_or_else(|| {
-            ChrootManagerError::Profile(ArchitectureNotFound(selected_arch.to_owned()))
-        })?;
+        .get_architecture(arch)
+        .ok_or_else(|| ChrootManagerError::Profile(ArchitectureNotFound(arch.to_owned())))?;
 
     let profiles = architecture.get_profiles();
     if profiles.is_empty() {
         return Err(ChrootManagerError::Profile(
-            ProfileError::NoProfilesAvailableForArchitecture(selected_arch),
+            ProfileError::NoProfilesAvailableForArchitecture(arch.to_owned()),
         ));
     }
 
-    // Display available profiles
-    let profile_selection: Result<String, InquireError> =
-        Select::new("Select your profile:", profiles.to_vec()).prompt();
-    let selected_profile = profile_selection?;
+    let default_profile = config.default_profile.as_deref();
+    if !config.grouped_profile_menu {
+        // Display available profiles
+        let cursor = default_cursor(profiles.iter().map(String::as_str), default_profile);
+        let profile_selection: Result<String, InquireError> = Select::new("Select your profile:", profiles.to_vec())
+            .with_starting_cursor(cursor)
+            .prompt();
+        return Ok(profile_selection?);
+    }
+
+    let mut grouped: Vec<(ProfileFamily, &String)> = profiles
+        .iter()
+        .map(|profile| (classify_profile(profile), profile))
+        .collect();
+    grouped.sort();
 
-    Ok(SelectedProfile::new(selected_arch, selected_profile))
+    let entries: Vec<String> = grouped
+        .iter()
+        .map(|(family, profile)| format!("{family} {} {profile}", Symbol::Arrow))
+        .collect();
+    let cursor = default_cursor(grouped.iter().map(|(_, profile)| profile.as_str()), default_profile);
+    let selection = Select::new("Select your profile:", entries)
+        .with_starting_cursor(cursor)
+        .raw_prompt()?;
+    Ok(grouped[selection.index].1.clone())
 }
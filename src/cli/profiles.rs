@@ -0,0 +1,94 @@
+//! `chrootmanager profiles`: list the architectures and profiles accepted by `create`
+
+use crate::cli::command::OutputFormat;
+use crate::cli::error::ChrootManagerError;
+use crate::cli::load_config;
+use crate::error::ProfileError;
+use crate::profile::manager::ProfileManager;
+use crate::profile::parser::ProfileSource;
+use crate::profile::Architecture;
+use crate::say;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+use colored::Colorize;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct ProfileListing<'a> {
+    source: &'a ProfileSource,
+    architectures: Vec<&'a Architecture>,
+}
+
+fn display_architecture(architecture: &Architecture) {
+    println!("   {}", architecture.name.cyan().bold());
+    for profile in architecture.get_profiles() {
+        if profile == architecture.get_default_profile() {
+            println!("     {} {profile} {}", Symbol::Bullet, "(default)".dimmed());
+        } else {
+            println!("     {} {profile}", Symbol::Bullet);
+        }
+    }
+}
+
+/// Lists the discovered architectures with their profiles, or the profiles of `arch`
+///
+/// In quiet mode, each line holds an architecture and a profile, or only
+/// the profile with `--arch`. The JSON format only prints the listing on
+/// stdout, the other messages going to stderr.
+pub async fn list_profiles(arch: Option<String>, refresh: bool, format: OutputFormat) -> Result<(), ChrootManagerError> {
+    if format == OutputFormat::Json {
+        output::reserve_stdout();
+    }
+
+    let config = load_config().await?;
+    say!("{} Discovering available architectures and profiles...", Symbol::Search);
+    let profile_manager = ProfileManager::discover(&config, refresh).await?;
+
+    let architectures: Vec<&Architecture> = match &arch {
+        Some(arch) => vec![profile_manager
+            .get_architecture(arch)
+            .ok_or_else(|| ProfileError::ArchitectureNotFound(arch.clone()))?],
+        None => profile_manager
+            .get_architecture_names()
+            .into_iter()
+            .filter_map(|name| profile_manager.get_architecture(name))
+            .collect(),
+    };
+
+    if format == OutputFormat::Json {
+        let listing = ProfileListing {
+            source: profile_manager.source(),
+            architectures,
+        };
+        let json = serde_json::to_string_pretty(&listing)
+            .map_err(|e| ChrootManagerError::Custom(format!("JSON serialization failed: {e}")))?;
+        println!("{json}");
+        return Ok(());
+    }
+
+    if output::is_quiet() {
+        for architecture in architectures {
+            for profile in architecture.get_profiles() {
+                match arch {
+                    Some(_) => println!("{profile}"),
+                    None => println!("{} {profile}", architecture.name),
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let source = profile_manager.source();
+    match source {
+        ProfileSource::Fallback => say!(
+            "{}",
+            format!("{} Source: {source}, recently published profiles may be missing", Symbol::Warning).yellow()
+        ),
+        _ => say!("{} Source: {source}", Symbol::Info),
+    }
+    for architecture in architectures {
+        display_architecture(architecture);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,316 @@
+//! `chrootmanager project`: named groups of chroots operated on together
+//!
+//! Membership is stored in the user state file. Bulk operations run on
+//! [`CONCURRENT_MEMBERS`] members at a time and end with a table of the
+//! per-member results, in the order of the project.
+
+use crate::chroot::ChrootUnit;
+use crate::cli::command::{ProjectCommand, SyncMethod};
+use crate::cli::common::find_chroot_unit;
+use crate::cli::error::ChrootManagerError;
+use crate::cli::unmount::unmount_busy_chroot;
+use crate::cli::load_config;
+use crate::say;
+use crate::state::UserState;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+use colored::Colorize;
+use futures_util::stream::{self, StreamExt};
+use std::sync::Arc;
+
+/// Members a bulk operation runs on at the same time
+const CONCURRENT_MEMBERS: usize = 4;
+
+/// Result of a bulk operation on one member
+struct MemberResult {
+    chroot: String,
+    outcome: Result<String, String>,
+}
+
+/// Run an operation on each member, [`CONCURRENT_MEMBERS`] at a time
+///
+/// The results come in the order of the project. Members whose chroot no
+/// longer exists are reported as failed, as are operations that panicked.
+async fn fan_out<F>(members: &[String], units: &[ChrootUnit], operation: F) -> Vec<MemberResult>
+where
+    F: Fn(&ChrootUnit) -> Result<String, String> + Send + Sync + 'static,
+{
+    let operation = Arc::new(operation);
+    let runs = members.iter().map(|member| {
+        let unit = units.iter().find(|unit| &unit.name == member).cloned();
+        let operation = Arc::clone(&operation);
+        let chroot = member.clone();
+        async move {
+            let outcome = match unit {
+                // The operations block on elevated commands
+                Some(unit) => tokio::task::spawn_blocking(move || operation(&unit))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("operation aborted: {e}"))),
+                None => Err("chroot not found".to_string()),
+            };
+            MemberResult { chroot, outcome }
+        }
+    });
+    stream::iter(runs).buffered(CONCURRENT_MEMBERS).collect().await
+}
+
+/// Print the per-member results, failing when any member failed
+fn report_results(project: &str, results: &[MemberResult]) -> Result<(), ChrootManagerError> {
+    say!("   {:<20} RESULT", "CHROOT");
+    say!("   {}", Symbol::Separator.as_str().repeat(60));
+    for result in results {
+        match &result.outcome {
+            Ok(message) => say!("   {:<20} {}", result.chroot, message.green()),
+            Err(message) => println!("   {:<20} {}", result.chroot, message.red()),
+        }
+    }
+
+    let failures = results.iter().filter(|result| result.outcome.is_err()).count();
+    if failures > 0 {
+        return Err(ChrootManagerError::Custom(format!(
+            "{failures} member(s) of the project '{project}' failed"
+        )));
+    }
+    Ok(())
+}
+
+async fn load_project_units() -> Result<Vec<ChrootUnit>, ChrootManagerError> {
+    let config = load_config().await?;
+    if !config.chroot_base_dir.exists() {
+        return Ok(Vec::new());
+    }
+    ChrootUnit::find_units(&config).map_err(ChrootManagerError::Chroot)
+}
+
+fn show_projects(state: &UserState) {
+    if output::is_quiet() {
+        for name in state.projects.keys() {
+            println!("{name}");
+        }
+        return;
+    }
+
+    if state.projects.is_empty() {
+        say!("   No projects defined");
+        return;
+    }
+    say!("   {:<20} MEMBERS", "PROJECT");
+    say!("   {}", Symbol::Separator.as_str().repeat(60));
+    for (name, members) in &state.projects {
+        say!("   {:<20} {}", name, members.join(", "));
+    }
+}
+
+/// Mount status of each member
+fn show_status(members: &[String], units: &[ChrootUnit]) {
+    say!("   {:<20} {:<15} MOUNTS", "CHROOT", "PROFILE");
+    say!("   {}", Symbol::Separator.as_str().repeat(60));
+    for member in members {
+        let Some(unit) = units.iter().find(|unit| &unit.name == member) else {
+            println!("   {:<20} {}", member, "not found".red());
+            continue;
+        };
+        let profile = unit
+            .profile
+            .as_ref()
+            .map(|profile| profile.to_string())
+            .unwrap_or_else(|| "Undefined".to_string());
+        let mounts = match unit.active_mounts() {
+            Ok(mounts) if mounts.is_empty() => "not mounted".to_string(),
+            Ok(mounts) => format!("{} mounted", mounts.len()),
+            Err(e) => format!("unknown ({e})"),
+        };
+        say!("   {:<20} {:<15} {}", member, profile, mounts);
+    }
+}
+
+/// Authenticate once for the privileged operations on the members, when any exists
+fn authenticate_for_members(members: &[String], units: &[ChrootUnit]) -> Result<(), ChrootManagerError> {
+    let Some(first) = units.iter().find(|unit| members.contains(&unit.name)) else {
+        return Ok(());
+    };
+    say!("{} Authenticating for privileged operations...", Symbol::Lock);
+    first.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)
+}
+
+/// Unmount the filesystems of every member that has some mounted
+///
+/// See [`unmount_busy_chroot`] for `kill` and `lazy`.
+async fn unmount_members(
+    project: &str,
+    members: &[String],
+    units: &[ChrootUnit],
+    kill: bool,
+    lazy: bool,
+) -> Result<(), ChrootManagerError> {
+    authenticate_for_members(members, units)?;
+    let results = fan_out(members, units, move |unit| {
+        let mounted = unit.active_mounts().map_err(|e| e.to_string())?;
+        if mounted.is_empty() {
+            return Ok("not mounted".to_string());
+        }
+        unmount_busy_chroot(unit, mounted.len(), kill, lazy).map_err(|e| e.to_string())
+    })
+    .await;
+    report_results(project, &results)
+}
+
+/// Sync the Portage tree of every member
+///
+/// The output of the sync commands is captured, a failure is reported with
+/// the last line of its error output.
+async fn update_members(
+    project: &str,
+    members: &[String],
+    units: &[ChrootUnit],
+    method: SyncMethod,
+) -> Result<(), ChrootManagerError> {
+    authenticate_for_members(members, units)?;
+    let argv = method.command();
+    say!("{} Syncing the Portage trees ({})...", Symbol::Refresh, argv.join(" "));
+    let results = fan_out(members, units, move |unit| {
+        unit.ensure_emulation().map_err(|e| e.to_string())?;
+        let output = unit.run_command(&argv).map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let last_line = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or_default();
+            return Err(format!("{} failed ({}): {}", argv.join(" "), output.status, last_line.trim()));
+        }
+        if let Err(e) = unit.record_portage_sync() {
+            log::warn!("Unable to record the Portage tree sync of {}: {e}", unit.name);
+        }
+        Ok("Portage tree synced".to_string())
+    })
+    .await;
+    report_results(project, &results)
+}
+
+/// Runs a project subcommand
+pub async fn run_project_command(command: ProjectCommand) -> Result<(), ChrootManagerError> {
+    let path = UserState::path();
+    let mut state = UserState::load(&path)?;
+
+    match command {
+        ProjectCommand::Create { name } => {
+            state.create_project(&name)?;
+            state.save(&path)?;
+            say!("{} Project '{name}' created", Symbol::Success);
+        }
+        ProjectCommand::Delete { name } => {
+            state.delete_project(&name)?;
+            state.save(&path)?;
+            say!("{} Project '{name}' deleted, its chroots were kept", Symbol::Success);
+        }
+        ProjectCommand::Add { project, chroot } => {
+            state.project(&project)?;
+            find_chroot_unit(&chroot).await?;
+            if state.add_member(&project, &chroot)? {
+                state.save(&path)?;
+                say!("{} '{chroot}' added to the project '{project}'", Symbol::Success);
+            } else {
+                say!("{} '{chroot}' is already a member of the project '{project}'", Symbol::Info);
+            }
+        }
+        ProjectCommand::Remove { project, chroot } => {
+            state.remove_member(&project, &chroot)?;
+            state.save(&path)?;
+            say!("{} '{chroot}' removed from the project '{project}'", Symbol::Success);
+        }
+        ProjectCommand::List => show_projects(&state),
+        ProjectCommand::Status { name } => {
+            let members = state.project(&name)?;
+            show_status(members, &load_project_units().await?);
+        }
+        ProjectCommand::Unmount { name, kill, lazy } => {
+            let members = state.project(&name)?;
+            unmount_members(&name, members, &load_project_units().await?, kill, lazy).await?;
+        }
+        ProjectCommand::Update { name, sync_method } => {
+            let members = state.project(&name)?;
+            update_members(&name, members, &load_project_units().await?, sync_method).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Keep only the chroots that are members of a project
+pub fn filter_project_units(
+    units: Vec<ChrootUnit>,
+    project: &str,
+) -> Result<Vec<ChrootUnit>, ChrootManagerError> {
+    let state = UserState::load(&UserState::path())?;
+    let members = state.project(project)?;
+    Ok(units
+        .into_iter()
+        .filter(|unit| members.contains(&unit.name))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn unit(name: &str) -> ChrootUnit {
+        ChrootUnit {
+            name: name.to_string(),
+            chroot_path: PathBuf::from("/nonexistent").join(name),
+            profile: None,
+            metadata: None,
+            shared_distfiles: None,
+        }
+    }
+
+    fn members(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn fan_out_keeps_the_project_order_and_collects_each_failure() {
+        let units = [unit("a"), unit("b"), unit("c")];
+        let results = fan_out(&members(&["c", "gone", "a", "b"]), &units, |unit| {
+            if unit.name == "a" {
+                Err("failed".to_string())
+            } else {
+                Ok(format!("{} done", unit.name))
+            }
+        })
+        .await;
+
+        let chroots: Vec<&str> = results.iter().map(|result| result.chroot.as_str()).collect();
+        assert_eq!(chroots, ["c", "gone", "a", "b"]);
+        assert_eq!(results[0].outcome, Ok("c done".to_string()));
+        assert_eq!(results[1].outcome, Err("chroot not found".to_string()));
+        assert_eq!(results[2].outcome, Err("failed".to_string()));
+        assert_eq!(results[3].outcome, Ok("b done".to_string()));
+
+        let error = report_results("web", &results).unwrap_err();
+        assert!(error.to_string().contains("2 member(s) of the project 'web' failed"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn fan_out_runs_members_concurrently() {
+        let names: Vec<String> = (0..CONCURRENT_MEMBERS * 2).map(|i| format!("m{i}")).collect();
+        let units: Vec<ChrootUnit> = names.iter().map(|name| unit(name)).collect();
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let (running_in, peak_in) = (Arc::clone(&running), Arc::clone(&peak));
+        let results = fan_out(&names, &units, move |_| {
+            let now = running_in.fetch_add(1, Ordering::SeqCst) + 1;
+            peak_in.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(100));
+            running_in.fetch_sub(1, Ordering::SeqCst);
+            Ok(String::new())
+        })
+        .await;
+
+        assert!(results.iter().all(|result| result.outcome.is_ok()));
+        let peak = peak.load(Ordering::SeqCst);
+        assert!(peak > 1 && peak <= CONCURRENT_MEMBERS, "peak of {peak} members");
+        assert!(report_results("web", &results).is_ok());
+    }
+}
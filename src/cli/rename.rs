@@ -0,0 +1,44 @@
+use crate::cli::common::find_chroot_unit;
+use crate::cli::error::ChrootManagerError;
+use crate::cli::load_config;
+use crate::say;
+use crate::state::UserState;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+use colored::Colorize;
+
+/// Renames a chroot, along with its membership in projects
+pub async fn rename_chroot(old: String, new: String) -> Result<(), ChrootManagerError> {
+    let config = load_config().await?;
+    let unit = find_chroot_unit(&old).await?;
+    // Fail before asking for a password
+    unit.check_rename(&new, &config)?;
+
+    say!("{} Authenticating for privileged operations...", Symbol::Lock);
+    unit.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)?;
+    let renamed = unit.rename(&new, &config)?;
+
+    // The chroot is renamed already, a stale project entry is only worth a warning
+    let path = UserState::path();
+    let updated = UserState::load(&path).and_then(|mut state| {
+        if state.rename_member(&old, &new) {
+            state.save(&path)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = updated {
+        say!("{}", format!("{} Unable to update the projects of '{old}': {e}", Symbol::Warning).yellow());
+    }
+
+    if output::is_quiet() {
+        println!("{new}");
+    } else {
+        println!(
+            "{}",
+            format!("{} Chroot '{old}' renamed to '{new}' ({})", Symbol::Success, renamed.chroot_path.display())
+                .green()
+                .bold()
+        );
+    }
+    Ok(())
+}
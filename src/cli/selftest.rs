@@ -0,0 +1,308 @@
+//! `chrootmanager selftest`
+//!
+//! Runs the privileged operations of a chroot session against a throwaway
+//! directory in the cache: mount, unmount, stage3 extraction, including its
+//! extended attributes, and chroot execution. The same ChrootUnit methods as
+//! the real commands are used.
+
+use crate::chroot::archive::ExtractionOptions;
+use crate::chroot::{ChrootUnit, Stage3Source};
+use crate::cli::error::ChrootManagerError;
+use crate::error::ChrootError;
+use crate::cli::load_config;
+use colored::Colorize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::say;
+use crate::ui::output;
+use crate::ui::symbols::Symbol;
+
+/// Filesystems mounted by `ChrootUnit::mount_filesystems`, relative to the root
+const EXPECTED_MOUNTS: &[&str] = &["proc", "sys", "dev", "dev/pts", "dev/shm"];
+
+/// Static busybox used to build the test archive
+const BUSYBOX_CANDIDATES: &[&str] = &["/bin/busybox", "/usr/bin/busybox", "/sbin/busybox"];
+
+/// Extended attribute set on the test archive member, restored by the extraction
+const TEST_XATTR: &str = "user.chrootmanager.selftest";
+
+/// ELF program header type of the dynamic loader path
+const PT_INTERP: u32 = 3;
+
+/// Outcome of one step of the self test
+enum StepResult {
+    Passed,
+    Failed(String),
+    Skipped(String),
+}
+
+/// Collects and prints the step results
+#[derive(Default)]
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn record(&mut self, step: &str, result: StepResult) -> bool {
+        match result {
+            StepResult::Passed => {
+                say!("{} {step}", Symbol::Success);
+                true
+            }
+            StepResult::Failed(reason) => {
+                self.failures += 1;
+                println!("{}", format!("{} {step}: {reason}", Symbol::Error).red());
+                false
+            }
+            StepResult::Skipped(reason) => {
+                say!("{} {step}: skipped ({reason})", Symbol::Hint);
+                true
+            }
+        }
+    }
+
+    /// Record the result of a fallible step, returning whether it passed
+    fn check<E: std::fmt::Display>(&mut self, step: &str, result: Result<(), E>) -> bool {
+        let result = match result {
+            Ok(()) => StepResult::Passed,
+            Err(e) => StepResult::Failed(e.to_string()),
+        };
+        self.record(step, result)
+    }
+}
+
+/// Mount points of `EXPECTED_MOUNTS` missing from the mount table
+fn missing_mounts(unit: &ChrootUnit) -> Result<Vec<String>, String> {
+    let mounted: Vec<PathBuf> = unit
+        .active_mounts()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|entry| entry.mount_point)
+        .collect();
+
+    Ok(EXPECTED_MOUNTS
+        .iter()
+        .filter(|relative| !mounted.contains(&unit.chroot_path.join(relative)))
+        .map(|relative| format!("/{relative}"))
+        .collect())
+}
+
+/// Whether an ELF executable has no dynamic loader
+fn is_static_elf(path: &Path) -> bool {
+    let Ok(bytes) = fs::read(path) else {
+        return false;
+    };
+    if bytes.len() < 0x40 || &bytes[..4] != b"\x7fELF" {
+        return false;
+    }
+
+    let is_64 = bytes[4] == 2;
+    let little_endian = bytes[5] == 1;
+    let read = |offset: usize, size: usize| -> Option<u64> {
+        let field = bytes.get(offset..offset + size)?;
+        let value = field.iter().enumerate().fold(0u64, |acc, (i, byte)| {
+            let shift = if little_endian { i } else { size - 1 - i } * 8;
+            acc | (u64::from(*byte) << shift)
+        });
+        Some(value)
+    };
+
+    let header = if is_64 {
+        (read(0x20, 8), read(0x36, 2), read(0x38, 2))
+    } else {
+        (read(0x1C, 4), read(0x2A, 2), read(0x2C, 2))
+    };
+    let (Some(phoff), Some(phentsize), Some(phnum)) = header else {
+        return false;
+    };
+
+    (0..phnum).all(|index| {
+        let offset = (phoff + index * phentsize) as usize;
+        read(offset, 4).is_some_and(|p_type| p_type as u32 != PT_INTERP)
+    })
+}
+
+fn find_static_busybox() -> Option<PathBuf> {
+    BUSYBOX_CANDIDATES
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| is_static_elf(path))
+}
+
+/// Run a tool of the attr package, failing on a non-zero exit
+fn run_attr_tool(tool: &str, args: &[&std::ffi::OsStr]) -> Result<String, String> {
+    let output = Command::new(tool)
+        .args(args)
+        .output()
+        .map_err(|e| format!("{tool} not available ({e})"))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Set [`TEST_XATTR`] on a file, to its own name
+fn set_test_xattr(path: &Path) -> Result<(), String> {
+    run_attr_tool("setfattr", &["-n".as_ref(), TEST_XATTR.as_ref(), "-v".as_ref(), "busybox".as_ref(), path.as_os_str()])
+        .map(|_| ())
+}
+
+/// Check that [`TEST_XATTR`] survived the extraction
+fn check_test_xattr(path: &Path) -> Result<(), String> {
+    let value = run_attr_tool("getfattr", &["--only-values".as_ref(), "-n".as_ref(), TEST_XATTR.as_ref(), path.as_os_str()])?;
+    match value.as_str() {
+        "busybox" => Ok(()),
+        _ => Err(format!("{TEST_XATTR} is {value:?} after extraction")),
+    }
+}
+
+/// Build a stage3-like archive holding only a static busybox
+///
+/// The busybox carries [`TEST_XATTR`] when the filesystem and the attr tools
+/// allow it; otherwise, the reason is returned along with the archive.
+fn build_test_archive(busybox: &Path, work_dir: &Path) -> Result<(PathBuf, Option<String>), String> {
+    let staging = work_dir.join("staging");
+    let bin = staging.join("bin");
+    fs::create_dir_all(&bin).map_err(|e| e.to_string())?;
+    fs::copy(busybox, bin.join("busybox")).map_err(|e| e.to_string())?;
+    let xattr_skipped = set_test_xattr(&bin.join("busybox")).err();
+
+    let archive = work_dir.join("selftest.tar.xz");
+    let output = Command::new("tar")
+        .arg("--xattrs")
+        .arg("cJf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&staging)
+        .arg(".")
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok((archive, xattr_skipped))
+}
+
+/// Mount, check and unmount the filesystems of the scratch chroot
+fn mount_cycle(unit: &ChrootUnit, report: &mut Report) -> bool {
+    let mounts = match unit.mount_filesystems() {
+        Ok(mounts) => {
+            report.check("Mount filesystems", Ok::<(), ChrootError>(()));
+            mounts
+        }
+        Err(e) => {
+            report.check("Mount filesystems", Err(e));
+            return false;
+        }
+    };
+
+    let mounted = match missing_mounts(unit) {
+        Ok(missing) if missing.is_empty() => Ok(()),
+        Ok(missing) => Err(format!("not mounted: {}", missing.join(", "))),
+        Err(e) => Err(e),
+    };
+    report.check("Filesystems listed in the mount table", mounted);
+
+    report.check("Unmount filesystems", mounts.unmount());
+
+    let remaining = unit
+        .active_mounts()
+        .map_err(|e| e.to_string())
+        .and_then(|mounts| match mounts.first() {
+            None => Ok(()),
+            Some(entry) => Err(format!("still mounted: {}", entry.mount_point.display())),
+        });
+    report.check("No filesystem left mounted", remaining)
+}
+
+/// Extract a busybox archive and run it with chroot
+async fn exec_cycle(unit: &ChrootUnit, work_dir: &Path, report: &mut Report) {
+    let Some(busybox) = find_static_busybox() else {
+        let reason = "no static busybox found".to_string();
+        report.record("Extract test archive", StepResult::Skipped(reason.clone()));
+        report.record("Extended attributes restored", StepResult::Skipped(reason.clone()));
+        report.record("Run /bin/busybox true in the chroot", StepResult::Skipped(reason));
+        return;
+    };
+
+    let (archive, xattr_skipped) = match build_test_archive(&busybox, work_dir) {
+        Ok(archive) => archive,
+        Err(e) => {
+            report.record("Build test archive", StepResult::Failed(e));
+            return;
+        }
+    };
+
+    if !report.check("Extract test archive", unit.extract_stage3(Stage3Source::File(&archive), &ExtractionOptions::default()).await) {
+        return;
+    }
+
+    match xattr_skipped {
+        Some(reason) => report.record("Extended attributes restored", StepResult::Skipped(reason)),
+        None => report.check("Extended attributes restored", check_test_xattr(&unit.chroot_path.join("bin/busybox"))),
+    };
+
+    let root = unit.chroot_path.to_string_lossy();
+    let result = unit.execute_command_with_logging(
+        "chroot",
+        &[&root, "/bin/busybox", "true"],
+        "Self test chroot execution",
+    );
+    report.check("Run /bin/busybox true in the chroot", result.map(|_| ()));
+}
+
+/// Exercises the privileged chroot operations in a scratch directory
+pub async fn run_selftest() -> Result<(), ChrootManagerError> {
+    let config = load_config().await?;
+    fs::create_dir_all(&config.stage3_cache_dir)?;
+
+    let work_dir = tempfile::Builder::new()
+        .prefix(".selftest-")
+        .tempdir_in(&config.stage3_cache_dir)?;
+    let root = work_dir.path().join("root");
+    for relative in ["proc", "sys", "dev"] {
+        fs::create_dir_all(root.join(relative))?;
+    }
+    let unit = ChrootUnit::with_path("selftest".to_string(), root);
+
+    say!("{}", format!("{} Running the self test in {}", Symbol::Tool, work_dir.path().display()).bold());
+
+    let mut report = Report::default();
+    if !report.check("Authenticate", unit.pre_authenticate_operations()) {
+        return Err(ChrootManagerError::Custom("Self test failed: authentication".to_string()));
+    }
+
+    if mount_cycle(&unit, &mut report) {
+        exec_cycle(&unit, work_dir.path(), &mut report).await;
+    }
+
+    // Never delete through a filesystem that is still mounted
+    let still_mounted = unit.active_mounts().map(|mounts| !mounts.is_empty()).unwrap_or(true);
+    if still_mounted {
+        let kept = work_dir.keep();
+        report.record(
+            "Remove scratch directory",
+            StepResult::Failed(format!("filesystems still mounted, {} was kept", kept.display())),
+        );
+    } else {
+        // Extracted files belong to root, remove them with the same privileges
+        let path = work_dir.path().to_string_lossy().into_owned();
+        let removed = unit.execute_elevated("rm", &["-rf", &path]).map(|_| ());
+        report.check("Remove scratch directory", removed);
+    }
+
+    if report.failures > 0 {
+        return Err(ChrootManagerError::Custom(format!(
+            "Self test failed: {} step(s) failed",
+            report.failures
+        )));
+    }
+
+    if output::is_quiet() {
+        println!("ok");
+    } else {
+        println!("{}", format!("{} All self test steps passed", Symbol::Success).green().bold());
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! One-line summaries of a chroot creation, for provisioning logs
+//!
+//! Each line starts with the outcome followed by `key=value` fields in a
+//! fixed order:
+//!
+//! ```text
+//! created name=ci-amd64 profile=amd64-openrc stage3=20240915T163200Z cache=hit duration=2m41s path=/srv/chroots/ci-amd64
+//! failed name=ci-amd64 profile=amd64-openrc phase=download error=download message="Download Error: ..."
+//! ```
+//!
+//! Values containing spaces, quotes or `=` are double-quoted and escaped.
+
+use crate::cache::index::Stage3Name;
+use crate::cli::common::{format_duration, CreateOutcome};
+use crate::cli::error::ChrootManagerError;
+
+/// Quote a value when it would break the `key=value` splitting
+fn field(key: &str, value: &str) -> String {
+    let needs_quoting = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '=' || c.is_control());
+    if needs_quoting {
+        format!("{key}={value:?}")
+    } else {
+        format!("{key}={value}")
+    }
+}
+
+/// Summary line of a successful creation
+///
+/// The stage3 is identified by its build timestamp, or by its filename when
+/// it does not follow the mirror naming.
+pub fn created_line(outcome: &CreateOutcome) -> String {
+    let stage3 = Stage3Name::parse(&outcome.stage3.filename)
+        .map(|name| name.timestamp)
+        .unwrap_or_else(|| outcome.stage3.filename.clone());
+    let cache = if outcome.cache_hit { "hit" } else { "miss" };
+
+    let fields = [
+        field("name", &outcome.chroot.name),
+        field("profile", &outcome.chroot.profile.to_string()),
+        field("stage3", &stage3),
+        field("cache", cache),
+        field("duration", &format_duration(outcome.duration)),
+        field("path", &outcome.chroot.path.to_string_lossy()),
+    ];
+    format!("created {}", fields.join(" "))
+}
+
+/// Summary line of a failed creation
+///
+/// The profile is omitted when it was not resolved yet, the phase when the
+/// failure happened before the first one.
+pub fn failed_line(
+    name: &str,
+    profile: Option<&str>,
+    phase: Option<&str>,
+    error: &ChrootManagerError,
+) -> String {
+    let mut fields = vec![field("name", name)];
+    if let Some(profile) = profile {
+        fields.push(field("profile", profile));
+    }
+    if let Some(phase) = phase {
+        fields.push(field("phase", phase));
+    }
+    fields.push(field("error", error.kind()));
+    fields.push(field("message", &error.to_string()));
+    format!("failed {}", fields.join(" "))
+}
@@ -0,0 +1,135 @@
+use crate::cli::common::format_duration;
+use crate::cli::download::format_bytes;
+use std::time::Duration;
+
+/// Steps of the chroot creation pipeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CreatePhase {
+    ProfileResolution,
+    LatestFetch,
+    Download,
+    Verify,
+    Extract,
+    Finalize,
+    Sync,
+    Hooks,
+}
+
+impl CreatePhase {
+    /// Short label used in the summary line
+    pub fn label(&self) -> &'static str {
+        match self {
+            CreatePhase::ProfileResolution => "profiles",
+            CreatePhase::LatestFetch => "latest",
+            CreatePhase::Download => "download",
+            CreatePhase::Verify => "verify",
+            CreatePhase::Extract => "extract",
+            CreatePhase::Finalize => "finalize",
+            CreatePhase::Sync => "sync",
+            CreatePhase::Hooks => "hooks",
+        }
+    }
+}
+
+/// Time spent in one phase
+#[derive(Debug, Clone)]
+pub struct PhaseTiming {
+    pub phase: CreatePhase,
+    pub duration: Duration,
+    /// Average throughput in bytes per second, for phases transferring data
+    pub bytes_per_sec: Option<f64>,
+}
+
+/// Ordered collection of phase timings
+#[derive(Debug, Clone, Default)]
+pub struct PhaseTimings {
+    timings: Vec<PhaseTiming>,
+}
+
+impl PhaseTimings {
+    /// Record the duration of a phase
+    pub fn record(&mut self, phase: CreatePhase, duration: Duration) {
+        self.timings.push(PhaseTiming {
+            phase,
+            duration,
+            bytes_per_sec: None,
+        });
+    }
+
+    /// Record the duration of a phase along with its throughput
+    pub fn record_with_throughput(&mut self, phase: CreatePhase, duration: Duration, bytes_per_sec: f64) {
+        self.timings.push(PhaseTiming {
+            phase,
+            duration,
+            bytes_per_sec: Some(bytes_per_sec),
+        });
+    }
+
+    /// Append the timings of another collection
+    pub fn extend(&mut self, other: PhaseTimings) {
+        self.timings.extend(other.timings);
+    }
+
+    /// Insert a timing before all the others
+    pub fn prepend(&mut self, phase: CreatePhase, duration: Duration) {
+        self.timings.insert(
+            0,
+            PhaseTiming {
+                phase,
+                duration,
+                bytes_per_sec: None,
+            },
+        );
+    }
+
+    /// Compact summary such as "download 3m12s @ 2.1 MB/s, verify 18s, extract 1m44s"
+    pub fn summary(&self) -> String {
+        self.timings
+            .iter()
+            .map(|timing| match timing.bytes_per_sec {
+                Some(speed) => format!(
+                    "{} {} @ {}/s",
+                    timing.phase.label(),
+                    format_duration(timing.duration),
+                    format_bytes(speed as u64)
+                ),
+                None => format!("{} {}", timing.phase.label(), format_duration(timing.duration)),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_lists_the_phases_in_order_with_their_throughput() {
+        let mut timings = PhaseTimings::default();
+        timings.record_with_throughput(CreatePhase::Download, Duration::from_secs(192), 2.1 * 1024.0 * 1024.0);
+        timings.record(CreatePhase::Verify, Duration::from_secs(18));
+
+        let mut extraction = PhaseTimings::default();
+        extraction.record(CreatePhase::Extract, Duration::from_secs(104));
+        timings.extend(extraction);
+        timings.prepend(CreatePhase::ProfileResolution, Duration::from_millis(1500));
+
+        assert_eq!(
+            timings.summary(),
+            "profiles 1s, download 3m12s @ 2.1 MB/s, verify 18s, extract 1m44s"
+        );
+    }
+
+    #[test]
+    fn summary_of_no_phase_is_empty() {
+        assert_eq!(PhaseTimings::default().summary(), "");
+    }
+
+    #[test]
+    fn long_phases_are_shown_in_hours() {
+        let mut timings = PhaseTimings::default();
+        timings.record(CreatePhase::Sync, Duration::from_secs(2 * 3600 + 5 * 60 + 30));
+        assert_eq!(timings.summary(), "sync 2h05m");
+    }
+}
@@ -0,0 +1,104 @@
+//! `chrootmanager unmount`: tear down the mounts a crashed session left behind
+
+use crate::chroot::ChrootUnit;
+use crate::cli::common::find_chroot_unit;
+use crate::cli::error::ChrootManagerError;
+use crate::cli::load_config;
+use crate::error::ChrootError;
+use crate::say;
+use crate::ui::symbols::Symbol;
+use colored::Colorize;
+
+/// Unmount a chroot with `count` mounted filesystems, describing what was done
+///
+/// With `kill`, the processes keeping it busy are terminated and it is
+/// unmounted again. With `lazy`, what is still busy is then detached.
+pub(crate) fn unmount_busy_chroot(
+    unit: &ChrootUnit,
+    count: usize,
+    kill: bool,
+    lazy: bool,
+) -> Result<String, ChrootError> {
+    let mut done = format!("{count} filesystem(s) unmounted");
+    let mut result = unit.unmount_filesystems().map(|_| ());
+    if let (true, Err(ChrootError::StillMounted(remaining))) = (kill, &result) {
+        let busy = unit.busy_processes(remaining);
+        unit.terminate_processes(&busy)?;
+        done.push_str(&format!(" after terminating {} process(es)", busy.len()));
+        result = unit.unmount_filesystems().map(|_| ());
+    }
+    if lazy && matches!(result, Err(ChrootError::StillMounted(_))) {
+        done.push_str(", the busy ones detached");
+        result = unit.detach_filesystems().map(|_| ());
+    }
+    result.map(|()| done)
+}
+
+/// Unmount the named chroot, or with `all` every chroot with mounted filesystems
+///
+/// Chroots without mounts are left alone, and authentication is only asked
+/// for once, when something needs unmounting. See [`unmount_busy_chroot`]
+/// for `kill` and `lazy`.
+pub async fn unmount_chroots(name: Option<String>, all: bool, kill: bool, lazy: bool) -> Result<(), ChrootManagerError> {
+    let units = if all {
+        let config = load_config().await?;
+        if !config.chroot_base_dir.exists() {
+            Vec::new()
+        } else {
+            ChrootUnit::find_units(&config).map_err(ChrootManagerError::Chroot)?
+        }
+    } else {
+        let name = name.ok_or_else(|| ChrootManagerError::Custom("A chroot name or --all is required".to_string()))?;
+        vec![find_chroot_unit(&name).await?]
+    };
+
+    let mut mounted = Vec::new();
+    for unit in units {
+        let mounts = unit.active_mounts().map_err(ChrootManagerError::Chroot)?;
+        if !mounts.is_empty() {
+            mounted.push((unit, mounts.len()));
+        }
+    }
+
+    let Some((first, _)) = mounted.first() else {
+        say!("{} No filesystem is mounted, nothing to do", Symbol::Success);
+        return Ok(());
+    };
+    say!("{} Authenticating for privileged operations...", Symbol::Lock);
+    first.pre_authenticate_operations().map_err(ChrootManagerError::Chroot)?;
+
+    let mut busy = 0;
+    let mut results = Vec::new();
+    for (unit, count) in &mounted {
+        say!("{} Unmounting the {count} filesystem(s) of '{}'...", Symbol::Cleanup, unit.name);
+        let outcome = match unmount_busy_chroot(unit, *count, kill, lazy) {
+            Ok(done) => Ok(done),
+            Err(ChrootError::StillMounted(remaining)) => {
+                busy += 1;
+                let remaining: Vec<String> = remaining.iter().map(|path| path.display().to_string()).collect();
+                Err(format!("still mounted: {}", remaining.join(", ")))
+            }
+            Err(e) => {
+                busy += 1;
+                Err(e.to_string())
+            }
+        };
+        results.push((unit.name.as_str(), outcome));
+    }
+
+    say!("   {:<20} RESULT", "CHROOT");
+    say!("   {}", Symbol::Separator.as_str().repeat(60));
+    for (name, outcome) in &results {
+        match outcome {
+            Ok(message) => say!("   {:<20} {}", name, message.green()),
+            Err(message) => println!("   {:<20} {}", name, message.red()),
+        }
+    }
+
+    if busy > 0 {
+        return Err(ChrootManagerError::Custom(format!(
+            "{busy} chroot(s) still have mounted filesystems, see the processes listed above"
+        )));
+    }
+    Ok(())
+}
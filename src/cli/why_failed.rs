@@ -0,0 +1,73 @@
+use crate::cli::command::OutputFormat;
+use crate::cli::download::format_bytes;
+use crate::cli::error::ChrootManagerError;
+use crate::diagnostics::FailureReport;
+use colored::Colorize;
+use crate::ui::symbols::Symbol;
+
+fn display_report(report: &FailureReport) {
+    println!(
+        "{}",
+        format!("{} Last failure: {}", Symbol::Error, report.command.join(" ")).red().bold()
+    );
+    println!("   Date: {}", report.failed_at.format("%Y-%m-%d %H:%M:%S"));
+    println!("   Phase reached: {}", report.phase.as_deref().unwrap_or("unknown"));
+
+    println!("\n{} Error:", Symbol::Info);
+    for (depth, message) in report.error_chain.iter().enumerate() {
+        let indent = "   ".repeat(depth + 1);
+        if depth == 0 {
+            println!("{indent}{message}");
+        } else {
+            println!("{indent}{} caused by: {message}", Symbol::Arrow);
+        }
+    }
+
+    if !report.mirror_attempts.is_empty() {
+        println!("\n{} Mirror attempts:", Symbol::Network);
+        for attempt in &report.mirror_attempts {
+            println!("   {} {} {}: {}", Symbol::Bullet, attempt.host, attempt.file.dimmed(), attempt.outcome);
+        }
+    }
+
+    match &report.config {
+        Some(config) => {
+            println!("\n{} Configuration:", Symbol::Tool);
+            println!("   Chroot directory: {}", config.chroot_base_dir.display());
+            println!("   Cache directory: {}", config.stage3_cache_dir.display());
+            println!("   Mirrors: {}", config.mirror_hosts.join(", "));
+        }
+        None => println!("\n{} Configuration: not readable", Symbol::Tool),
+    }
+
+    let env = &report.environment;
+    let free = |bytes: Option<u64>| bytes.map(format_bytes).unwrap_or_else(|| "unknown".to_string());
+    println!("\n{} Environment:", Symbol::Stats);
+    println!("   chrootmanager {} on {}/{}", env.chrootmanager_version, env.os, env.arch);
+    println!("   Free space: {} (chroots), {} (cache)", free(env.chroot_dir_free_bytes), free(env.cache_dir_free_bytes));
+    println!("   sudo available: {}", if env.sudo_available { "yes" } else { "no" });
+}
+
+/// Shows the report written by the last failed command
+pub fn show_last_failure(format: OutputFormat) -> Result<(), ChrootManagerError> {
+    let path = FailureReport::path();
+    if !path.exists() {
+        println!("{} No failure recorded", Symbol::Success);
+        return Ok(());
+    }
+
+    let report = FailureReport::load(&path)?;
+    match format {
+        OutputFormat::Text => {
+            display_report(&report);
+            println!("\n{} Attach {} to bug reports", Symbol::Hint, path.display());
+        }
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&report)
+                .map_err(|e| ChrootManagerError::Custom(format!("JSON serialization failed: {e}")))?;
+            println!("{json}");
+        }
+    }
+
+    Ok(())
+}
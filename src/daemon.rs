@@ -0,0 +1,234 @@
+//! Session bus service for graphical frontends
+//!
+//! `chrootmanager daemon` exports `org.chrootmanager.Manager1` on the session
+//! bus. Privileged operations go through polkit instead of sudo, since the
+//! daemon has no terminal to prompt on.
+
+use crate::chroot::archive::ExtractionOptions;
+use crate::chroot::{ChrootUnit, Stage3Source};
+use crate::cli::common::{finalize_chroot_creation, ChrootSetup};
+use crate::cli::download::Stage3Info;
+use crate::config::Config;
+use crate::downloader::{
+    check_stage3_integrity, download_release_with_progress, resolve_current_release, DownloadProgress,
+    Stage3Digest,
+};
+use crate::elevation::use_polkit;
+use crate::profile::manager::ProfileManager;
+use crate::profile::selected::SelectedProfile;
+use crate::signature::verify_stage3_signature;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use zbus::object_server::SignalEmitter;
+use zbus::{connection, fdo, interface};
+
+pub const SERVICE_NAME: &str = "org.chrootmanager.Manager1";
+pub const OBJECT_PATH: &str = "/org/chrootmanager/Manager1";
+
+/// Object exported on the bus
+pub struct Manager {
+    config: Config,
+}
+
+impl Manager {
+    pub fn new(config: Config) -> Self {
+        Self { config }
+    }
+
+    fn find_unit(&self, name: &str) -> fdo::Result<ChrootUnit> {
+        validate_name(name)?;
+        let path = self.config.chroot_base_dir.join(name);
+        if !path.exists() {
+            return Err(fdo::Error::FileNotFound(format!("The chroot '{name}' does not exist.")));
+        }
+        ChrootUnit::load(&path)
+            .map(|unit| unit.with_config_mounts(&self.config))
+            .map_err(failed)
+    }
+}
+
+fn failed(e: impl std::fmt::Display) -> fdo::Error {
+    fdo::Error::Failed(e.to_string())
+}
+
+/// Reject names that would resolve outside of the chroot base directory
+fn validate_name(name: &str) -> fdo::Result<()> {
+    ChrootUnit::validate_name(name).map_err(|e| fdo::Error::InvalidArgs(e.to_string()))
+}
+
+#[interface(name = "org.chrootmanager.Manager1")]
+impl Manager {
+    /// List the chroots as (name, profile, path) tuples
+    async fn list_chroots(&self) -> fdo::Result<Vec<(String, String, String)>> {
+        let units = ChrootUnit::find_units(&self.config).map_err(failed)?;
+        Ok(units
+            .into_iter()
+            .map(|unit| {
+                let profile = unit.profile.map(|p| p.to_string()).unwrap_or_default();
+                (unit.name, profile, unit.chroot_path.display().to_string())
+            })
+            .collect())
+    }
+
+    /// Create a chroot and return its path
+    ///
+    /// Download progress is reported through the `DownloadProgress` signal.
+    async fn create_chroot(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        name: String,
+        arch: String,
+        profile: String,
+    ) -> fdo::Result<String> {
+        validate_name(&name)?;
+        let profile_manager = ProfileManager::discover(&self.config, false).await.map_err(failed)?;
+        if !profile_manager.validate_arch_profile(&arch, &profile) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "The profile '{profile}' is not supported for arch '{arch}'."
+            )));
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<DownloadProgress>();
+        let forward_emitter = emitter.to_owned();
+        let forward_name = name.clone();
+        let forwarder = tokio::spawn(async move {
+            while let Some(progress) = rx.recv().await {
+                if let Err(e) = Manager::download_progress(
+                    &forward_emitter,
+                    &forward_name,
+                    progress.downloaded,
+                    progress.total,
+                )
+                .await
+                {
+                    log::warn!("Unable to emit download progress: {e}");
+                }
+            }
+        });
+
+        // The creation pipeline is not Send, run it on a blocking thread
+        let config = self.config.clone();
+        let selected = SelectedProfile::new(arch, profile);
+        let handle = tokio::runtime::Handle::current();
+        let result = tokio::task::spawn_blocking(move || {
+            handle.block_on(create_with_progress(config, name, selected, tx))
+        })
+        .await
+        .map_err(failed)?;
+
+        let _ = forwarder.await;
+        result.map(|path| path.display().to_string()).map_err(fdo::Error::Failed)
+    }
+
+    /// Mount the chroot filesystems and return the command line to spawn in a terminal
+    async fn enter_prepare(&self, name: String) -> fdo::Result<String> {
+        let unit = self.find_unit(&name)?;
+        let mounts = unit.mount_filesystems().map_err(failed)?;
+        let (command, _bashrc_path) = unit.get_chroot_command_for_terminal().map_err(failed)?;
+        // The terminal session outlives this call, enter_finish unmounts
+        mounts.leave_mounted();
+        Ok(command)
+    }
+
+    /// Unmount the chroot filesystems once the terminal session is over
+    async fn enter_finish(&self, name: String) -> fdo::Result<()> {
+        let unit = self.find_unit(&name)?;
+        unit.cleanup(None).map_err(failed)
+    }
+
+    /// Emitted while a stage3 archive is downloaded
+    #[zbus(signal)]
+    async fn download_progress(
+        emitter: &SignalEmitter<'_>,
+        name: &str,
+        downloaded: u64,
+        total: u64,
+    ) -> zbus::Result<()>;
+}
+
+/// Creation sequence of the daemon, reporting download progress on a channel
+async fn create_with_progress(
+    config: Config,
+    name: String,
+    profile: SelectedProfile,
+    progress: mpsc::UnboundedSender<DownloadProgress>,
+) -> Result<PathBuf, String> {
+    let unit = ChrootUnit::new(name.clone(), Some(&profile), &config)
+        .await
+        .map_err(|e| e.to_string())?;
+    if unit.chroot_path.exists() {
+        return Err(format!("The chroot '{name}' already exists."));
+    }
+
+    let mut release = resolve_current_release(&profile, &config)
+        .await
+        .map_err(|e| e.to_string())?;
+    let cached_path = config.get_cache_path(&release.filename);
+
+    // A cached archive is extracted without being hashed again
+    let mut sha256 = None;
+    if !cached_path.exists() {
+        let cache_dir = config.stage3_cache_dir.to_string_lossy().into_owned();
+        let download = download_release_with_progress(&profile, &release, &cache_dir, &config, |p| {
+            let _ = progress.send(p);
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let expected = release
+            .digest(&profile, &config)
+            .await
+            .map_err(|e| e.to_string())?;
+        let valid = match &expected {
+            Stage3Digest::Sha256(hash) => download.sha256.eq_ignore_ascii_case(hash),
+            _ => {
+                check_stage3_integrity(&cached_path, &expected)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .0
+            }
+        };
+        if !valid {
+            let _ = std::fs::remove_file(&cached_path);
+            return Err(format!("The downloaded file is corrupted ({} verification failed).", expected.algorithm()));
+        }
+        if let Err(e) = verify_stage3_signature(&profile, &config, &release, &cached_path).await {
+            let _ = std::fs::remove_file(&cached_path);
+            return Err(e.to_string());
+        }
+        sha256 = Some(download.sha256);
+    }
+    drop(progress);
+
+    let source = Stage3Source::File(&cached_path);
+    let stage3 = Stage3Info {
+        filename: release.filename.clone(),
+        path: Some(cached_path.clone()),
+        release: release.build_timestamp(),
+        // Only known for an archive downloaded now
+        mirror: sha256.is_some().then(|| release.mirror.clone()).flatten(),
+        sha256,
+    };
+    let extraction = ExtractionOptions::from_config(&config);
+    let setup = ChrootSetup::from_config(&config, true, true);
+    finalize_chroot_creation(&unit, source, Some(&stage3), false, &extraction, &setup)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(unit.chroot_path)
+}
+
+/// Serve `org.chrootmanager.Manager1` on the session bus until interrupted
+pub async fn run(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    use_polkit();
+
+    let _connection = connection::Builder::session()?
+        .name(SERVICE_NAME)?
+        .serve_at(OBJECT_PATH, Manager::new(config))?
+        .build()
+        .await?;
+
+    log::info!("Serving {SERVICE_NAME} on the session bus");
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
@@ -0,0 +1,323 @@
+//! Environment checks of `chrootmanager doctor`
+//!
+//! Each check looks at one requirement of chrootmanager on the host and says
+//! how to fix it when it is not met. Required checks are those without which
+//! no chroot can be created or entered; the others only limit what works.
+
+use crate::chroot::mounts;
+use crate::cli::download::format_bytes;
+use crate::config::Config;
+use crate::elevation::{is_sudo_available, running_as_root};
+use crate::mirror::check_mirror_reachable;
+use crate::space;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Programs run, elevated, by chrootmanager
+pub const REQUIRED_PROGRAMS: &[&str] = &["tar", "mount", "umount", "chroot"];
+
+/// Directories searched for programs besides `PATH`, as sudo runs with its own
+const SYSTEM_PROGRAM_DIRS: &[&str] = &["/usr/local/sbin", "/usr/local/bin", "/usr/sbin", "/usr/bin", "/sbin", "/bin"];
+
+/// Names of the checks run on the filesystem of the chroots
+const FILESYSTEM_CHECK: &str = "chroot filesystem";
+const DISK_SPACE_CHECK: &str = "free disk space";
+
+/// Outcome of one check
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    /// What was checked, such as "sudo"
+    pub name: String,
+    /// Whether a failure prevents chrootmanager from working
+    pub required: bool,
+    /// `Ok` with what was found, or `Err` with the problem
+    pub outcome: Result<String, String>,
+    /// How to fix a failure
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    fn passed(name: impl Into<String>, required: bool, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            required,
+            outcome: Ok(detail.into()),
+            hint: None,
+        }
+    }
+
+    fn failed(name: impl Into<String>, required: bool, problem: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            required,
+            outcome: Err(problem.into()),
+            hint: Some(hint.into()),
+        }
+    }
+
+    pub fn is_passed(&self) -> bool {
+        self.outcome.is_ok()
+    }
+}
+
+/// Run every check, in the order they are shown
+pub async fn run_checks(config: &Config) -> Vec<CheckResult> {
+    let mut results = vec![check_elevation()];
+    results.extend(REQUIRED_PROGRAMS.iter().map(|program| check_program(program)));
+    results.push(check_writable("chroot_base_dir", &config.chroot_base_dir));
+    results.push(check_writable("stage3_cache_dir", &config.stage3_cache_dir));
+    results.push(check_mount_options(&config.chroot_base_dir));
+    results.push(check_disk_space(config));
+    for url in config.mirror_urls() {
+        results.push(check_mirror(url, config).await);
+    }
+    results
+}
+
+/// Privileged commands can be run, as root or through sudo
+pub fn check_elevation() -> CheckResult {
+    if running_as_root() {
+        CheckResult::passed("privilege elevation", true, "running as root")
+    } else if is_sudo_available() {
+        CheckResult::passed("privilege elevation", true, "sudo is available")
+    } else {
+        CheckResult::failed(
+            "privilege elevation",
+            true,
+            "sudo is not installed",
+            "Install sudo and allow your user to run commands with it, or run chrootmanager as root",
+        )
+    }
+}
+
+/// Location of `program` in `PATH` or the usual system directories
+fn find_program(program: &str) -> Option<PathBuf> {
+    let path_dirs = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default();
+    path_dirs
+        .into_iter()
+        .chain(SYSTEM_PROGRAM_DIRS.iter().map(PathBuf::from))
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// A program run by chrootmanager is installed
+pub fn check_program(program: &str) -> CheckResult {
+    let name = format!("{program} command");
+    match find_program(program) {
+        Some(path) => CheckResult::passed(name, true, path.display().to_string()),
+        None => CheckResult::failed(
+            name,
+            true,
+            "not found",
+            format!("Install the package providing {program} (coreutils, util-linux or tar)"),
+        ),
+    }
+}
+
+/// Closest existing directory at or above `path`
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|ancestor| ancestor.is_dir())
+}
+
+/// The directory, or the first existing parent it would be created in, is writable
+pub fn check_writable(name: &str, dir: &Path) -> CheckResult {
+    let Some(existing) = existing_ancestor(dir) else {
+        return CheckResult::failed(
+            name,
+            true,
+            format!("no parent of {} exists", dir.display()),
+            format!("Set {name} to an absolute path in the configuration"),
+        );
+    };
+    match tempfile::tempfile_in(existing) {
+        Ok(_) if existing == dir => CheckResult::passed(name, true, format!("{} is writable", dir.display())),
+        Ok(_) => CheckResult::passed(
+            name,
+            true,
+            format!("{} will be created in {}", dir.display(), existing.display()),
+        ),
+        Err(e) => CheckResult::failed(
+            name,
+            true,
+            format!("{} is not writable: {e}", existing.display()),
+            format!("Fix the permissions of {} or set {name} to a directory you own", existing.display()),
+        ),
+    }
+}
+
+/// The filesystem of the chroots allows device files, setuid programs and executables
+pub fn check_mount_options(base_dir: &Path) -> CheckResult {
+    match mounts::filesystem_of(base_dir) {
+        Ok(Some(entry)) => check_filesystem(&entry),
+        Ok(None) => CheckResult::passed(FILESYSTEM_CHECK, true, "not checked, no mount holds chroot_base_dir"),
+        Err(e) => CheckResult::passed(FILESYSTEM_CHECK, true, format!("not checked, the mount table is unreadable: {e}")),
+    }
+}
+
+/// The options of the mount holding the chroots, see [`check_mount_options`]
+fn check_filesystem(entry: &mounts::MountEntry) -> CheckResult {
+    let name = FILESYSTEM_CHECK;
+    let blocking = entry.restrictive_options();
+    if blocking.is_empty() {
+        return CheckResult::passed(
+            name,
+            true,
+            format!("{} ({}) is mounted without nodev, nosuid or noexec", entry.mount_point.display(), entry.fstype),
+        );
+    }
+    CheckResult::failed(
+        name,
+        true,
+        format!("{} is mounted {}", entry.mount_point.display(), blocking.join(",")),
+        format!(
+            "Set chroot_base_dir to a directory on another filesystem, or remount {} without {}",
+            entry.mount_point.display(),
+            blocking.join(" and ")
+        ),
+    )
+}
+
+/// The filesystem of the chroots has more free space than the warning threshold
+pub fn check_disk_space(config: &Config) -> CheckResult {
+    match existing_ancestor(&config.chroot_base_dir).and_then(space::filesystem_space) {
+        Some(filesystem) => check_space(&filesystem, config),
+        None => CheckResult::passed(DISK_SPACE_CHECK, false, "not checked, df failed"),
+    }
+}
+
+/// The free space of the filesystem of the chroots, see [`check_disk_space`]
+fn check_space(filesystem: &space::FilesystemSpace, config: &Config) -> CheckResult {
+    let name = DISK_SPACE_CHECK;
+    let available = format_bytes(filesystem.available);
+    if config.low_space_threshold().is_low(filesystem) {
+        return CheckResult::failed(
+            name,
+            false,
+            format!("only {available} ({:.0}%) free", filesystem.available_percent()),
+            "Free some space, for instance with `chrootmanager cache clean`, or move chroot_base_dir",
+        );
+    }
+    CheckResult::passed(name, false, format!("{available} free"))
+}
+
+/// A configured mirror answers
+pub async fn check_mirror(url: &str, config: &Config) -> CheckResult {
+    let name = format!("mirror {url}");
+    match check_mirror_reachable(url, config).await {
+        Ok(()) => CheckResult::passed(name, false, "reachable"),
+        Err(e) => CheckResult::failed(
+            name,
+            false,
+            format!("unreachable: {e}"),
+            "Check the network and proxy settings, or pick another mirror with `chrootmanager mirror`",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn programs_are_found_in_the_path_or_reported_missing() {
+        let found = check_program("sh");
+        assert!(found.is_passed(), "{found:?}");
+        assert!(found.required);
+
+        let missing = check_program("chrootmanager-no-such-program");
+        assert_eq!(missing.outcome, Err("not found".to_string()));
+        assert!(missing.required);
+        assert!(missing.hint.is_some());
+    }
+
+    #[test]
+    fn a_directory_is_writable_or_created_in_a_writable_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = check_writable("chroot_base_dir", dir.path());
+        assert_eq!(existing.outcome, Ok(format!("{} is writable", dir.path().display())));
+
+        let missing = dir.path().join("chroots/gentoo");
+        let created = check_writable("chroot_base_dir", &missing);
+        assert_eq!(
+            created.outcome,
+            Ok(format!("{} will be created in {}", missing.display(), dir.path().display()))
+        );
+
+        let relative = check_writable("stage3_cache_dir", Path::new("relative/cache"));
+        assert!(!relative.is_passed());
+        assert!(relative.hint.unwrap().contains("stage3_cache_dir"));
+    }
+
+    #[test]
+    fn restrictive_mount_options_fail_the_filesystem_check() {
+        let entries = mounts::parse_mountinfo(
+            "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+36 22 8:2 / /home rw,nosuid,nodev,relatime shared:2 - ext4 /dev/sda2 rw
+",
+        );
+
+        assert!(check_filesystem(&entries[0]).is_passed());
+        let home = check_filesystem(&entries[1]);
+        assert!(home.required);
+        assert_eq!(home.outcome, Err("/home is mounted nodev,nosuid".to_string()));
+        assert!(home.hint.unwrap().ends_with("without nodev and nosuid"));
+    }
+
+    #[test]
+    fn low_free_space_is_only_a_warning() {
+        let config = Config { low_space_bytes: Some("10G".to_string()), low_space_percent: Some(5), ..Config::default() };
+        let roomy = space::FilesystemSpace { total: 100 << 30, available: 50 << 30 };
+        assert!(check_space(&roomy, &config).is_passed());
+
+        for space in [
+            space::FilesystemSpace { total: 100 << 30, available: 5 << 30 },
+            space::FilesystemSpace { total: 1000 << 30, available: 20 << 30 },
+        ] {
+            let result = check_space(&space, &config);
+            assert!(!result.is_passed(), "{space:?}");
+            assert!(!result.required);
+        }
+    }
+
+    #[tokio::test]
+    async fn an_unreachable_mirror_is_only_a_warning() {
+        // Nothing listens on the port of a listener that was closed
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+
+        let result = check_mirror(&url, &Config::default()).await;
+        assert_eq!(result.name, format!("mirror {url}"));
+        assert!(!result.is_passed());
+        assert!(!result.required);
+    }
+
+    #[tokio::test]
+    async fn every_check_runs_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            chroot_base_dir: dir.path().join("chroots"),
+            stage3_cache_dir: dir.path().join("cache"),
+            mirrors_url: Vec::new(),
+            ..Config::default()
+        };
+        let names: Vec<String> = run_checks(&config).await.into_iter().map(|result| result.name).collect();
+        assert_eq!(
+            names,
+            [
+                "privilege elevation",
+                "tar command",
+                "mount command",
+                "umount command",
+                "chroot command",
+                "chroot_base_dir",
+                "stage3_cache_dir",
+                FILESYSTEM_CHECK,
+                DISK_SPACE_CHECK,
+            ]
+        );
+    }
+}
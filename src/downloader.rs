@@ -3,14 +3,38 @@
 //! This module provides functionality to download stage3 tarballs and verify their integrity
 //! using the new profile management system.
 
-use crate::config::Config;
-use sha2::{Digest, Sha256};
-use std::time::Duration;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use crate::cache::index::Stage3Name;
+use crate::config::{Config, DEFAULT_MIRROR_URL};
+use crate::diagnostics;
+use crate::error::DownloaderError;
+use crate::http;
+use crate::permissions::SHARED_FILE_MODE;
+use crate::say;
+use crate::signals;
+use crate::ui::symbols::Symbol;
+use blake2::Blake2b512;
+use sha2::digest::DynDigest;
+use sha2::{Digest, Sha256, Sha512};
+use std::fs::Permissions;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader, ReadBuf};
 use tokio_stream::StreamExt;
+use tokio_util::io::StreamReader;
+use crate::profile::parser::index_directories;
 use crate::profile::selected::SelectedProfile;
 
+/// Extensions of the stage3 archives, xz for current builds and the others
+/// for older or niche architectures
+const STAGE3_EXTENSIONS: &[&str] = &[".tar.xz", ".tar.bz2", ".tar.gz", ".tar.zst"];
+
+/// Buffer size used to hash files, 1 MiB
+const HASH_BUFFER_SIZE: usize = 1024 * 1024;
+
 /// Represents the progress information during download
 #[derive(Debug, Clone)]
 pub struct DownloadProgress {
@@ -22,6 +46,14 @@ pub struct DownloadProgress {
     pub filename: String,
 }
 
+/// Progress of a hash computation
+#[derive(Debug, Clone, Copy)]
+pub struct HashProgress {
+    pub hashed: u64,
+    pub total: u64,
+    pub bytes_per_sec: f64,
+}
+
 /// Represents the result of a download attempt
 #[derive(Debug)]
 pub struct DownloadResult {
@@ -33,10 +65,70 @@ pub struct DownloadResult {
     #[allow(dead_code)]
     pub total_bytes: u64,
     pub average_speed_bytes_per_sec: f64,
+    /// SHA256 of the whole file, resumed part included, computed while downloading
+    pub sha256: String,
 }
 
-/// Generate a stage3 URL based on the mirror's base URL and selected profile
-fn build_stage3_url(base_mirror_url: &str, profile: &SelectedProfile) -> String {
+/// A published stage3 archive
+///
+/// Resolved once per operation by [`resolve_current_release`] or
+/// [`find_previous_stage3`], then passed to every request about the
+/// archive, which share its client and try the mirror that listed it first.
+#[derive(Debug, Clone)]
+pub struct Stage3Release {
+    pub filename: String,
+    /// Timestamp directory under autobuilds, `None` for `current-stage3-<arch>-<profile>`
+    pub snapshot: Option<String>,
+    /// Base URL of the mirror the archive was found on
+    pub mirror: Option<String>,
+    /// Size of the archive, when the mirror published it
+    pub size: Option<u64>,
+    client: reqwest::Client,
+    /// Published hash, once downloaded
+    digest: Option<Stage3Digest>,
+}
+
+impl Stage3Release {
+    /// Published hash of the archive, downloaded on the first call
+    pub async fn digest(
+        &mut self,
+        profile: &SelectedProfile,
+        config: &Config,
+    ) -> Result<Stage3Digest, DownloaderError> {
+        if let Some(digest) = &self.digest {
+            return Ok(digest.clone());
+        }
+        let digest = download_stage3_digest(profile, config, self).await?;
+        self.digest = Some(digest.clone());
+        Ok(digest)
+    }
+
+    /// Build timestamp of the archive, from its snapshot directory or its filename
+    pub fn build_timestamp(&self) -> Option<String> {
+        self.snapshot
+            .clone()
+            .or_else(|| Stage3Name::parse(&self.filename).map(|name| name.timestamp))
+    }
+
+    /// URLs of `<filename><suffix>` on the mirrors, the mirror of the release first
+    fn file_urls(&self, profile: &SelectedProfile, config: &Config, suffix: &str) -> Vec<String> {
+        let mut mirrors = mirror_base_urls(config);
+        if let Some(position) = mirrors.iter().position(|mirror| Some(mirror) == self.mirror.as_ref()) {
+            let mirror = mirrors.remove(position);
+            mirrors.insert(0, mirror);
+        }
+        mirrors
+            .iter()
+            .map(|mirror_url| {
+                let base_url = build_stage3_url(mirror_url, profile, self.snapshot.as_deref());
+                format!("{base_url}{}{suffix}", self.filename)
+            })
+            .collect()
+    }
+}
+
+/// Generate the autobuilds URL of an architecture based on the mirror's base URL
+fn build_autobuilds_url(base_mirror_url: &str, profile: &SelectedProfile) -> String {
     let mut mirror_url = base_mirror_url.to_string();
 
     // Ensure the URL ends with a slash
@@ -44,32 +136,32 @@ fn build_stage3_url(base_mirror_url: &str, profile: &SelectedProfile) -> String
         mirror_url.push('/');
     }
 
-    format!(
-        "{mirror_url}releases/{}/autobuilds/current-stage3-{}-{}/",
-        profile.arch(),
-        profile.arch(),
-        profile.profile()
-    )
+    format!("{mirror_url}releases/{}/autobuilds/", profile.arch())
 }
 
-/// Build stage3 URLs using configured mirrors with fallback
-fn get_stage3_url(profile: &SelectedProfile, config: &Config) -> Vec<String> {
-    let mut urls = Vec::new();
+/// Generate a stage3 URL based on the mirror's base URL and selected profile
+///
+/// Without a snapshot, the directory of the current stage3 is used.
+pub(crate) fn build_stage3_url(base_mirror_url: &str, profile: &SelectedProfile, snapshot: Option<&str>) -> String {
+    let autobuilds_url = build_autobuilds_url(base_mirror_url, profile);
+    match snapshot {
+        Some(snapshot) => format!("{autobuilds_url}{snapshot}/"),
+        None => format!(
+            "{autobuilds_url}current-stage3-{}-{}/",
+            profile.arch(),
+            profile.profile()
+        ),
+    }
+}
 
-    // Use configured mirrors
+/// Base URLs of the configured mirrors, or of the default mirror when none is configured
+fn mirror_base_urls(config: &Config) -> Vec<String> {
     if config.has_mirrors() {
-        for mirror_url in &config.mirrors_url {
-            let url = build_stage3_url(mirror_url, profile);
-            urls.push(url);
-        }
+        config.mirror_urls().map(str::to_string).collect()
     } else {
-        // Default URL if no mirror is configured
         log::warn!("No mirrors configured, using default mirror");
-        let default_url = build_stage3_url("https://distfiles.gentoo.org/", profile);
-        urls.push(default_url);
+        vec![DEFAULT_MIRROR_URL.to_string()]
     }
-
-    urls
 }
 
 /// Calculate download speed in bytes per second
@@ -77,89 +169,203 @@ fn calculate_speed_bytes_per_sec(bytes: u64, duration: Duration) -> f64 {
     bytes as f64 / duration.as_secs_f64()
 }
 
-/// Download stage3 with a progress callback using the new profile system
-pub async fn download_stage3_with_progress<F>(
+/// Download a given stage3 release with a progress callback
+pub async fn download_release_with_progress<F>(
     profile: &SelectedProfile,
+    release: &Stage3Release,
     destination_path: &str,
     config: &Config,
     mut progress_callback: F,
-) -> Result<DownloadResult, Box<dyn std::error::Error>>
+) -> Result<DownloadResult, DownloaderError>
 where
     F: FnMut(DownloadProgress),
 {
-    let filename = get_current_stage3_filename(profile, config).await?;
-    let base_urls = get_stage3_url(profile, config);
-
-    // Build download URLs
-    let download_urls: Vec<String> = base_urls
-        .iter()
-        .map(|base_url| format!("{base_url}{filename}"))
-        .collect();
+    let filename = release.filename.clone();
+    let download_urls = release.file_urls(profile, config, "");
 
     log::debug!("download_urls: {download_urls:?}");
 
     let full_path = format!("{destination_path}/{filename}");
+    let client = &release.client;
 
-    let client = reqwest::Client::new();
+    // A mirror closing the connection early leaves the partial download,
+    // resumed from the next mirrors
+    let mut mirrors = download_urls.as_slice();
+    loop {
+        let error = match download_release_from(mirrors, client, &filename, &full_path, config, &mut progress_callback)
+            .await
+        {
+            Ok(result) => return Ok(result),
+            Err(e) => e,
+        };
+        let DownloaderError::IncompleteDownload { url, .. } = &error else {
+            return Err(error);
+        };
+        let next = mirrors.iter().position(|mirror| mirror == url).map_or(mirrors.len(), |i| i + 1);
+        if next >= mirrors.len() {
+            return Err(error);
+        }
+        log::warn!("{error}, resuming from the next mirror");
+        mirrors = &mirrors[next..];
+    }
+}
+
+/// Download `filename` to `full_path` from the first mirror of `download_urls` that answers
+///
+/// A stream ending before the announced length fails with
+/// [`DownloaderError::IncompleteDownload`], leaving the partial download.
+async fn download_release_from<F>(
+    download_urls: &[String],
+    client: &reqwest::Client,
+    filename: &str,
+    full_path: &str,
+    config: &Config,
+    progress_callback: &mut F,
+) -> Result<DownloadResult, DownloaderError>
+where
+    F: FnMut(DownloadProgress),
+{
+    let filename = filename.to_string();
+    let full_path = full_path.to_string();
+    let part_path = partial_path(&full_path);
 
-    // Attempt to download with different mirrors
-    let (successful_url, response) = try_download_with_mirrors(&download_urls, &client).await?;
+    // Resume a previous attempt, restarting from scratch if no mirror accepts the range
+    let existing = tokio::fs::metadata(&part_path).await.map(|m| m.len()).unwrap_or(0);
+    let resume_from = (existing > 0).then_some(existing);
+    let (successful_url, response) =
+        match try_download_range_with_mirrors(download_urls, client, resume_from, config).await {
+            Ok(found) => found,
+            Err(e) if resume_from.is_some() => {
+                log::warn!("Unable to resume the download of {filename} ({e}), restarting");
+                try_download_with_mirrors(download_urls, client, config).await?
+            }
+            Err(e) => return Err(e),
+        };
 
-    let total_size = response.content_length().unwrap_or(0);
+    // A server ignoring the range answers 200 with the whole file
+    let offset = match resume_from {
+        Some(offset) if response.status() == reqwest::StatusCode::PARTIAL_CONTENT => {
+            log::info!("Resuming the download of {filename} at {offset} bytes");
+            offset
+        }
+        Some(_) => {
+            log::info!("The mirror does not support resuming, restarting {filename}");
+            0
+        }
+        None => 0,
+    };
+    let content_length = response.content_length();
+    if content_length.is_none() {
+        log::info!("{successful_url} sends no content length, a truncated download cannot be detected");
+    }
+    let total_size = content_length.map_or(0, |length| length + offset);
 
     // Initial progress callback
     progress_callback(DownloadProgress {
-        downloaded: 0,
+        downloaded: offset,
         total: total_size,
         speed_bytes_per_sec: 0.0,
         filename: filename.clone(),
     });
 
-    let mut file = File::create(&full_path).await?;
-    let mut downloaded = 0u64;
+    // Unlike a mirror failure, a termination signal removes the partial download
+    let operation = signals::start_operation();
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .mode(SHARED_FILE_MODE)
+        .open(&part_path)
+        .await?;
+    file.set_permissions(Permissions::from_mode(SHARED_FILE_MODE)).await?;
+
+    // The bytes of a resumed download are hashed once, the rest as it arrives
+    let mut hasher = Sha256::new();
+    if offset > 0 {
+        hash_file_into(&part_path, &mut hasher).await?;
+    }
+
+    // Bytes transferred in this session
+    let mut transferred = 0u64;
     let mut stream = response.bytes_stream();
 
     // Variables for speed calculation and display frequency
     let mut last_update = std::time::Instant::now();
-    let mut last_downloaded = 0u64;
+    let mut last_transferred = 0u64;
     let start_time = std::time::Instant::now();
     let update_interval = Duration::from_millis(250); // Update every 250 ms
 
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
+    loop {
+        let chunk = tokio::select! {
+            chunk = stream.next() => chunk,
+            _ = operation.cancelled() => {
+                drop(file);
+                say!("   {} Removing the partial download {filename}", Symbol::Cleanup);
+                if let Err(e) = tokio::fs::remove_file(&part_path).await {
+                    log::warn!("Unable to remove the partial download {part_path}: {e}");
+                }
+                return Err(DownloaderError::Interrupted);
+            }
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        // The connection closing before the announced length ends the
+        // stream with an error, unless the length is unknown
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) if content_length.is_some() => {
+                log::debug!("Download from {successful_url} interrupted: {e}");
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
         file.write_all(&chunk).await?;
-        downloaded += chunk.len() as u64;
+        Digest::update(&mut hasher, &chunk);
+        transferred += chunk.len() as u64;
 
         // Update progress periodically
         let now = std::time::Instant::now();
         if now.duration_since(last_update) >= update_interval {
             let speed = if last_update != start_time {
-                let bytes_since_last = downloaded - last_downloaded;
+                let bytes_since_last = transferred - last_transferred;
                 let time_since_last = now.duration_since(last_update);
                 calculate_speed_bytes_per_sec(bytes_since_last, time_since_last)
             } else {
                 let total_time = now.duration_since(start_time);
-                calculate_speed_bytes_per_sec(downloaded, total_time)
+                calculate_speed_bytes_per_sec(transferred, total_time)
             };
 
             progress_callback(DownloadProgress {
-                downloaded,
+                downloaded: offset + transferred,
                 total: total_size,
                 speed_bytes_per_sec: speed,
                 filename: filename.clone(),
             });
 
             last_update = now;
-            last_downloaded = downloaded;
+            last_transferred = transferred;
         }
     }
+    file.flush().await?;
+    drop(file);
+
+    if content_length.is_some_and(|length| transferred < length) {
+        return Err(DownloaderError::IncompleteDownload {
+            url: successful_url,
+            expected: total_size,
+            got: offset + transferred,
+        });
+    }
+    tokio::fs::rename(&part_path, &full_path).await?;
 
     // Final callback with average speed
     let total_time = std::time::Instant::now().duration_since(start_time);
-    let avg_speed = calculate_speed_bytes_per_sec(downloaded, total_time);
+    let avg_speed = calculate_speed_bytes_per_sec(transferred, total_time);
 
     progress_callback(DownloadProgress {
-        downloaded,
+        downloaded: offset + transferred,
         total: total_size,
         speed_bytes_per_sec: avg_speed,
         filename: filename.clone(),
@@ -168,127 +374,593 @@ where
     Ok(DownloadResult {
         successful_url,
         file_path: full_path,
-        total_bytes: downloaded,
+        total_bytes: transferred,
         average_speed_bytes_per_sec: avg_speed,
+        sha256: to_hex(&hasher.finalize()),
     })
 }
 
+/// Start the download of a stage3 release, for a caller reading it as it arrives
+///
+/// Returns the mirror URL used and the response, whose body is the archive.
+pub async fn open_release_stream(
+    profile: &SelectedProfile,
+    release: &Stage3Release,
+    config: &Config,
+) -> Result<(String, reqwest::Response), DownloaderError> {
+    let download_urls = release.file_urls(profile, config, "");
+    try_download_with_mirrors(&download_urls, &release.client, config).await
+}
+
+/// Reader over a stage3 download, hashed as it is read
+///
+/// Progress is reported like [`download_release_with_progress`]. At the end
+/// of the body, the hash is compared with the expected digest, if any, and
+/// a mismatch is returned as an [`io::ErrorKind::InvalidData`] error instead
+/// of the end of file, so a consumer never sees a complete unverified stream.
+pub struct VerifiedStream<R> {
+    inner: R,
+    expected: Option<Stage3Digest>,
+    hasher: Option<Box<dyn DynDigest + Send>>,
+    progress_callback: Box<dyn FnMut(DownloadProgress) + Send>,
+    filename: String,
+    downloaded: u64,
+    total: u64,
+    start_time: Instant,
+    last_update: Instant,
+    finished: bool,
+}
+
+/// Wrap a stage3 response into a [`VerifiedStream`]
+pub fn verified_stream(
+    response: reqwest::Response,
+    filename: String,
+    expected: Option<Stage3Digest>,
+    progress_callback: Box<dyn FnMut(DownloadProgress) + Send>,
+) -> VerifiedStream<impl AsyncRead + Unpin + Send> {
+    let total = response.content_length().unwrap_or(0);
+    let body = Box::pin(response.bytes_stream().map(|chunk| chunk.map_err(io::Error::other)));
+    let now = Instant::now();
+    VerifiedStream {
+        inner: StreamReader::new(body),
+        hasher: expected.as_ref().map(new_hasher),
+        expected,
+        progress_callback,
+        filename,
+        downloaded: 0,
+        total,
+        start_time: now,
+        last_update: now,
+        finished: false,
+    }
+}
+
+impl<R> VerifiedStream<R> {
+    fn report(&mut self, speed_bytes_per_sec: f64) {
+        (self.progress_callback)(DownloadProgress {
+            downloaded: self.downloaded,
+            total: self.total,
+            speed_bytes_per_sec,
+            filename: self.filename.clone(),
+        });
+    }
+
+    /// Compare the hash of the whole body with the expected digest
+    fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        let average = calculate_speed_bytes_per_sec(self.downloaded, self.start_time.elapsed());
+        self.report(average);
+
+        let (Some(expected), Some(hasher)) = (&self.expected, &mut self.hasher) else {
+            return Ok(());
+        };
+        let calculated = to_hex(&hasher.finalize_reset());
+        if calculated.eq_ignore_ascii_case(expected.hash()) {
+            log::info!("{} of the streamed {} verified", expected.algorithm(), self.filename);
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} verification failed (expected {}, calculated {calculated})",
+                    expected.algorithm(),
+                    expected.hash()
+                ),
+            ))
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for VerifiedStream<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        let read = &buf.filled()[before..];
+        if read.is_empty() {
+            return Poll::Ready(this.finish());
+        }
+        if let Some(hasher) = &mut this.hasher {
+            hasher.update(read);
+        }
+        this.downloaded += read.len() as u64;
+
+        if this.last_update.elapsed() >= Duration::from_millis(250) {
+            let speed = calculate_speed_bytes_per_sec(this.downloaded, this.start_time.elapsed());
+            this.report(speed);
+            this.last_update = Instant::now();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Path of the incomplete download of `full_path`, kept to resume it
+pub fn partial_path(full_path: &str) -> String {
+    format!("{full_path}.part")
+}
+
+/// Kind of a request error, without the URL it carries
+fn network_outcome(config: &Config, error: &reqwest::Error) -> String {
+    let kind = if http::is_proxy_failure(config, error) {
+        "proxy connection failed"
+    } else if error.is_timeout() {
+        "timeout"
+    } else if error.is_connect() {
+        "connection failed"
+    } else {
+        "request failed"
+    };
+    format!("Network error : {kind}")
+}
+
 /// Function to attempt downloading a file with multiple mirrors
+///
+/// Fails with [`DownloaderError::NotFoundOnMirrors`] when every mirror answered 404.
 async fn try_download_with_mirrors(
     urls: &[String],
     client: &reqwest::Client,
-) -> Result<(String, reqwest::Response), Box<dyn std::error::Error>> {
-    let mut last_error = None;
+    config: &Config,
+) -> Result<(String, reqwest::Response), DownloaderError> {
+    try_download_range_with_mirrors(urls, client, None, config).await
+}
+
+/// Whether a failed request may succeed when sent again to the same mirror
+fn is_transient(status: reqwest::StatusCode) -> bool {
+    status.is_server_error()
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+}
+
+async fn try_download_range_with_mirrors(
+    urls: &[String],
+    client: &reqwest::Client,
+    range_start: Option<u64>,
+    config: &Config,
+) -> Result<(String, reqwest::Response), DownloaderError> {
+    http::ensure_online("Downloading from the mirrors")?;
+    let retry = &config.download_retry;
+    let mut failures = Vec::new();
+    let mut all_not_found = !urls.is_empty();
+    let attempts = retry.attempts.max(1);
 
     for (index, url) in urls.iter().enumerate() {
         log::debug!("Attempting mirror {} : {}", index + 1, url);
-        log::debug!("Downloading {url}");
-        match client.get(url).send().await {
-            Ok(response) => {
-                if response.status().is_success() {
-                    log::debug!("Success with mirror {}", index + 1);
-                    return Ok((url.clone(), response));
-                } else {
-                    log::debug!(
-                        "Mirror {} failed - Status: {}",
-                        index + 1,
-                        response.status()
-                    );
-                    last_error = Some(format!("HTTP Status {}", response.status()));
+        let mut attempt = 1;
+        let failure = loop {
+            log::debug!("Downloading {url}");
+            let mut request = client.get(url);
+            if let Some(start) = range_start {
+                request = request.header(reqwest::header::RANGE, format!("bytes={start}-"));
+            }
+            let (failure, transient) = match request.send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    diagnostics::record_mirror_attempt(url, format!("HTTP Status {status}"));
+                    if status.is_success() {
+                        log::debug!("Success with mirror {}", index + 1);
+                        return Ok((url.clone(), response));
+                    }
+                    log::debug!("Mirror {} failed - Status: {}", index + 1, status);
+                    if status != reqwest::StatusCode::NOT_FOUND {
+                        all_not_found = false;
+                    }
+                    (format!("HTTP Status {status}"), is_transient(status))
+                }
+                Err(e) => {
+                    log::debug!("Error with mirror {} : {}", index + 1, e);
+                    diagnostics::record_mirror_attempt(url, network_outcome(config, &e));
+                    all_not_found = false;
+                    (network_outcome(config, &e), true)
                 }
+            };
+
+            if !transient || attempt >= attempts {
+                break if attempt > 1 {
+                    format!("{failure} after {attempt} attempts")
+                } else {
+                    failure
+                };
             }
-            Err(e) => {
-                log::debug!("Error with mirror {} : {}", index + 1, e);
-                last_error = Some(format!("Network error : {e}"));
+            let delay = retry.delay(attempt);
+            log::info!("{failure} from {url}, retrying in {delay:?} (attempt {}/{attempts})", attempt + 1);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        };
+        failures.push(format!("{url}: {failure}"));
+    }
+
+    if all_not_found {
+        let file = urls[0].rsplit('/').next().unwrap_or_default().to_string();
+        return Err(DownloaderError::NotFoundOnMirrors(file));
+    }
+
+    if failures.is_empty() {
+        return Err(DownloaderError::NoMirrorConfigured);
+    }
+    Err(DownloaderError::AllMirrorsFailed { failures })
+}
+
+/// Whether an error means the file is missing from every mirror
+pub fn is_not_found_on_mirrors(error: &(dyn std::error::Error + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<DownloaderError>(),
+        Some(DownloaderError::NotFoundOnMirrors(_))
+    )
+}
+
+/// Whether `name` is an autobuilds timestamp such as `20250720T170337Z`
+fn is_snapshot_timestamp(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    bytes.len() == 16
+        && bytes[..8].iter().all(u8::is_ascii_digit)
+        && bytes[8] == b'T'
+        && bytes[9..15].iter().all(u8::is_ascii_digit)
+        && bytes[15] == b'Z'
+}
+
+/// Extract the snapshot directories linked from an autobuilds index page, newest first
+pub fn parse_snapshot_dirs(html: &str) -> Vec<String> {
+    let mut snapshots: Vec<String> = index_directories(html)
+        .into_iter()
+        .filter(|name| is_snapshot_timestamp(name))
+        .collect();
+
+    snapshots.sort_unstable_by(|a, b| b.cmp(a));
+    snapshots.dedup();
+    snapshots
+}
+
+/// Extension of a stage3 archive, `None` for other files
+fn stage3_extension(filename: &str) -> Option<&'static str> {
+    STAGE3_EXTENSIONS
+        .iter()
+        .find(|extension| filename.ends_with(*extension))
+        .copied()
+}
+
+/// Timestamp of a stage3 filename (`stage3-amd64-openrc-20250720T170337Z.tar.xz`)
+fn stage3_timestamp<'a>(filename: &'a str, pattern: &str) -> Option<&'a str> {
+    filename
+        .strip_prefix(pattern)?
+        .strip_prefix('-')?
+        .strip_suffix(stage3_extension(filename)?)
+}
+
+/// Look for the stage3 of the profile in the snapshot directory `snapshot`
+///
+/// Each extension is tried in order, on each mirror, with a HEAD request.
+async fn probe_snapshot(
+    profile: &SelectedProfile,
+    config: &Config,
+    client: &reqwest::Client,
+    snapshot: &str,
+    extensions: &[&str],
+) -> Option<Stage3Release> {
+    let pattern = profile.get_stage3_pattern();
+    let mirrors = mirror_base_urls(config);
+
+    for extension in extensions {
+        let filename = format!("{pattern}-{snapshot}{extension}");
+        for mirror_url in &mirrors {
+            let url = format!("{}{filename}", build_stage3_url(mirror_url, profile, Some(snapshot)));
+            let result = client.head(&url).send().await;
+            match &result {
+                Ok(response) => diagnostics::record_mirror_attempt(&url, format!("HTTP Status {}", response.status())),
+                Err(e) => diagnostics::record_mirror_attempt(&url, network_outcome(config, e)),
+            }
+            match result {
+                Ok(response) if response.status().is_success() => {
+                    log::debug!("Stage3 found at {url}");
+                    return Some(Stage3Release {
+                        filename,
+                        snapshot: Some(snapshot.to_string()),
+                        mirror: Some(mirror_url.clone()),
+                        // content_length() is the size of the empty HEAD body
+                        size: response
+                            .headers()
+                            .get(reqwest::header::CONTENT_LENGTH)
+                            .and_then(|length| length.to_str().ok()?.parse().ok()),
+                        client: client.clone(),
+                        digest: None,
+                    });
+                }
+                Ok(response) => log::debug!("{url}: HTTP Status {}", response.status()),
+                Err(e) => log::debug!("{url}: {e}"),
             }
         }
     }
+    None
+}
 
-    Err(format!(
-        "All mirrors failed. Last error: {}",
-        last_error.unwrap_or_else(|| "No specific error".to_string())
-    )
-    .into())
+/// Number of older snapshots probed before giving up
+const PREVIOUS_SNAPSHOT_LIMIT: usize = 5;
+
+/// Find the most recent snapshot older than `current` that contains the profile's stage3
+///
+/// Used when the latest file was published before its archive reached the mirrors.
+pub async fn find_previous_stage3(
+    profile: &SelectedProfile,
+    config: &Config,
+    current: &Stage3Release,
+) -> Result<Stage3Release, DownloaderError> {
+    let pattern = profile.get_stage3_pattern();
+    let current_timestamp = stage3_timestamp(&current.filename, &pattern)
+        .ok_or_else(|| DownloaderError::UnexpectedFilename(current.filename.clone()))?;
+    // Older snapshots are assumed to use the compression of the current one
+    let extension = stage3_extension(&current.filename).unwrap_or(".tar.xz");
+
+    let mirrors = mirror_base_urls(config);
+    let index_urls: Vec<String> = mirrors
+        .iter()
+        .map(|mirror_url| build_autobuilds_url(mirror_url, profile))
+        .collect();
+
+    let client = &current.client;
+    let (_successful_url, response) = try_download_with_mirrors(&index_urls, client, config).await?;
+    let snapshots = parse_snapshot_dirs(&response.text().await?);
+
+    let candidates = snapshots
+        .iter()
+        .filter(|snapshot| snapshot.as_str() < current_timestamp)
+        .take(PREVIOUS_SNAPSHOT_LIMIT);
+
+    for snapshot in candidates {
+        if let Some(release) = probe_snapshot(profile, config, client, snapshot, &[extension]).await {
+            return Ok(release);
+        }
+    }
+
+    Err(DownloaderError::NoPreviousSnapshot(pattern))
 }
 
-/// Get the current stage3 filename for the specified profile
-pub async fn get_current_stage3_filename(
+/// Whether `filename` is a stage3 archive of exactly `pattern`
+///
+/// The pattern must be followed by the build timestamp
+/// (`stage3-<arch>-<profile>-<YYYYMMDD>T...`), so that `stage3-amd64-openrc`
+/// does not match `stage3-amd64-openrc-splitusr-...`.
+pub fn matches_stage3_pattern(filename: &str, pattern: &str) -> bool {
+    let Some(rest) = filename
+        .strip_prefix(pattern)
+        .and_then(|rest| rest.strip_prefix('-'))
+    else {
+        return false;
+    };
+
+    let bytes = rest.as_bytes();
+    bytes.len() > 9
+        && bytes[..8].iter().all(u8::is_ascii_digit)
+        && bytes[8] == b'T'
+        && stage3_extension(rest).is_some()
+}
+
+/// Find the stage3 archive of `pattern` and its size in the content of a latest file
+///
+/// Lines look like `20231201T170504Z/stage3-amd64-openrc-20231201T170504Z.tar.xz 123456789`,
+/// possibly wrapped in a PGP signature. Any path before the filename is dropped.
+pub fn find_stage3_entry(content: &str, pattern: &str) -> Option<(String, Option<u64>)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .find_map(|line| {
+            let mut tokens = line.split_whitespace();
+            let filename = tokens
+                .by_ref()
+                .map(|token| token.rsplit('/').next().unwrap_or(token))
+                .find(|filename| matches_stage3_pattern(filename, pattern))?;
+            Some((filename.to_string(), tokens.next().and_then(|size| size.parse().ok())))
+        })
+}
+
+/// Resolve the current stage3 of the profile from its latest file
+///
+/// The client created here is kept in the release for the requests that follow.
+pub async fn resolve_current_release(
     profile: &SelectedProfile,
     config: &Config,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let base_urls = get_stage3_url(profile, config);
+) -> Result<Stage3Release, DownloaderError> {
+    let mirrors = mirror_base_urls(config);
 
     // Build URLs for the latest file using the new stage3 pattern
-    let latest_urls: Vec<String> = base_urls
+    let latest_urls: Vec<String> = mirrors
         .iter()
-        .map(|base_url| format!("{base_url}latest-{}.txt", profile.get_stage3_pattern()))
+        .map(|mirror_url| {
+            let base_url = build_stage3_url(mirror_url, profile, None);
+            format!("{base_url}latest-{}.txt", profile.get_stage3_pattern())
+        })
         .collect();
 
-    let client = reqwest::Client::new();
+    let client = http::build_client(config)?;
 
     // Attempt to download the latest file with different mirrors
-    let (_successful_url, response) = try_download_with_mirrors(&latest_urls, &client).await?;
+    let (successful_url, response) = try_download_with_mirrors(&latest_urls, &client, config).await?;
 
     log::debug!("The latest file downloaded successfully");
 
+    let mirror = latest_urls
+        .iter()
+        .position(|url| *url == successful_url)
+        .map(|index| mirrors[index].clone());
     let content = response.text().await?;
 
-    // Parse the content to extract the filename
-    // The typical format is: timestamp filename size
-    // Example: 20231201T170504Z stage3-amd64-openrc-20231201T170504Z.tar.xz 123456789
-    for line in content.lines() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
+    let (filename, size) = find_stage3_entry(&content, &profile.get_stage3_pattern())
+        .ok_or_else(|| DownloaderError::NoStage3Found(profile.to_string()))?;
+    Ok(Stage3Release {
+        filename,
+        snapshot: None,
+        mirror,
+        size,
+        client,
+        digest: None,
+    })
+}
 
-        // Split by spaces/tabs
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 2 {
-            // The filename is usually the second element
-            let filename = parts[1];
+/// Day of a `--release` date, `YYYY-MM-DD` or `YYYYMMDD`, in the compact form of the snapshots
+fn release_date(release: &str) -> Option<String> {
+    ["%Y-%m-%d", "%Y%m%d"]
+        .iter()
+        .find_map(|format| chrono::NaiveDate::parse_from_str(release, format).ok())
+        .map(|date| date.format("%Y%m%d").to_string())
+}
 
-            // Check that it's actually a stage3 file with the correct pattern
-            if filename.contains(&profile.get_stage3_pattern()) && filename.ends_with(".tar.xz") {
-                return Ok(filename.to_string());
-            }
-        }
+/// Prefix of the snapshot timestamps a `--release` designates
+///
+/// A timestamp designates itself, a date every snapshot of that day.
+pub fn release_prefix(release: &str) -> Result<String, DownloaderError> {
+    if is_snapshot_timestamp(release) {
+        return Ok(release.to_string());
+    }
+    release_date(release).ok_or_else(|| DownloaderError::InvalidRelease(release.to_string()))
+}
 
-        // Fallback: if the format is different, look for a .tar.xz file with our pattern
-        if line.contains(&profile.get_stage3_pattern()) && line.contains(".tar.xz") {
-            // Extract the filename from the line
-            if let Some(start) = line.find(&profile.get_stage3_pattern()) {
-                let remaining = &line[start..];
-                if let Some(end) = remaining.find(".tar.xz") {
-                    let filename = &remaining[..end + 7]; // +7 to include ".tar.xz"
-                    return Ok(filename.to_string());
-                }
-            }
+/// Resolve the stage3 of the profile published in a given snapshot
+///
+/// `release` is either an autobuilds timestamp such as `20240301T164822Z`,
+/// or a date, in which case the last snapshot of that day is used. The
+/// latest file is not read: the archive is looked up directly in the
+/// snapshot directory, whatever its compression.
+pub async fn resolve_snapshot_release(
+    profile: &SelectedProfile,
+    config: &Config,
+    release: &str,
+) -> Result<Stage3Release, DownloaderError> {
+    http::ensure_online("Looking up a stage3 release on the mirrors")?;
+    let client = http::build_client(config)?;
+
+    let snapshot = if is_snapshot_timestamp(release) {
+        release.to_string()
+    } else {
+        let date = release_date(release).ok_or_else(|| DownloaderError::InvalidRelease(release.to_string()))?;
+        let index_urls: Vec<String> = mirror_base_urls(config)
+            .iter()
+            .map(|mirror_url| build_autobuilds_url(mirror_url, profile))
+            .collect();
+        let (_successful_url, response) = try_download_with_mirrors(&index_urls, &client, config).await?;
+        let snapshots = parse_snapshot_dirs(&response.text().await?);
+
+        // Several builds can be published the same day, newest first
+        let snapshot = snapshots
+            .into_iter()
+            .find(|snapshot| snapshot.starts_with(&date))
+            .ok_or_else(|| DownloaderError::SnapshotNotFound {
+                pattern: profile.get_stage3_pattern(),
+                release: release.to_string(),
+            })?;
+        log::debug!("Snapshot {snapshot} selected for {release}");
+        snapshot
+    };
+
+    probe_snapshot(profile, config, &client, &snapshot, STAGE3_EXTENSIONS)
+        .await
+        .ok_or_else(|| DownloaderError::SnapshotNotFound {
+            pattern: profile.get_stage3_pattern(),
+            release: snapshot,
+        })
+}
+
+/// Hash published for a stage3, tagged with its algorithm
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stage3Digest {
+    /// From the `<filename>.sha256` file
+    Sha256(String),
+    /// From the `# SHA512 HASH` section of `<filename>.DIGESTS`
+    Sha512(String),
+    /// From the `# BLAKE2B HASH` section of `<filename>.DIGESTS`
+    Blake2b(String),
+}
+
+impl Stage3Digest {
+    /// Algorithm name as displayed, e.g. "SHA256"
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Stage3Digest::Sha256(_) => "SHA256",
+            Stage3Digest::Sha512(_) => "SHA512",
+            Stage3Digest::Blake2b(_) => "BLAKE2b",
         }
     }
 
-    Err(format!("No stage3 file found for profile {profile}").into())
+    /// Expected hash, in hexadecimal
+    pub fn hash(&self) -> &str {
+        match self {
+            Stage3Digest::Sha256(hash) | Stage3Digest::Sha512(hash) | Stage3Digest::Blake2b(hash) => hash,
+        }
+    }
 }
 
-/// Download the SHA256 file for a given stage3 archive
-pub async fn download_stage3_sha256(
+/// Download the hash of a stage3 from the mirrors
+///
+/// The `<filename>.sha256` file is tried first, then the combined
+/// `<filename>.DIGESTS` file for mirrors that do not carry it. See
+/// [`Stage3Release::digest`] to download it once per operation.
+pub async fn download_stage3_digest(
     profile: &SelectedProfile,
     config: &Config,
-    filename: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let base_urls = get_stage3_url(profile, config);
-    let sha256_filename = format!("{filename}.sha256");
+    release: &Stage3Release,
+) -> Result<Stage3Digest, DownloaderError> {
+    let filename = release.filename.as_str();
+    let sha256_urls = release.file_urls(profile, config, ".sha256");
+    let sha256_error = match download_stage3_sha256(&sha256_urls, filename, &release.client, config).await {
+        Ok(hash) => return Ok(Stage3Digest::Sha256(hash)),
+        Err(e) => e,
+    };
+    log::info!("No SHA256 file for {filename} ({sha256_error}), trying the DIGESTS file");
 
-    // Build URLs for the SHA256 file
-    let sha256_urls: Vec<String> = base_urls
-        .iter()
-        .map(|base_url| format!("{base_url}{sha256_filename}"))
-        .collect();
+    // Missing from every mirror means not published, other failures are kept
+    let digests = match download_stage3_digests_file(profile, config, release).await {
+        Ok(digests) => digests,
+        Err(DownloaderError::NotFoundOnMirrors(_)) => {
+            return Err(DownloaderError::ChecksumNotFound(filename.to_string()))
+        }
+        Err(e) => return Err(e),
+    };
 
-    let client = reqwest::Client::new();
+    parse_digests(&digests, filename).ok_or_else(|| DownloaderError::ChecksumNotFound(filename.to_string()))
+}
 
+/// Download the `<filename>.DIGESTS` file of a stage3, clearsigned by the release key
+pub async fn download_stage3_digests_file(
+    profile: &SelectedProfile,
+    config: &Config,
+    release: &Stage3Release,
+) -> Result<String, DownloaderError> {
+    let digests_urls = release.file_urls(profile, config, ".DIGESTS");
+    let (_successful_url, response) = try_download_with_mirrors(&digests_urls, &release.client, config).await?;
+    Ok(response.text().await?)
+}
+
+/// Download the `<filename>.sha256` file and extract the hash of the stage3
+async fn download_stage3_sha256(
+    sha256_urls: &[String],
+    filename: &str,
+    client: &reqwest::Client,
+    config: &Config,
+) -> Result<String, DownloaderError> {
     // Attempt to download the SHA256 file with different mirrors
-    let (_successful_url, response) = try_download_with_mirrors(&sha256_urls, &client).await?;
+    let (_successful_url, response) = try_download_with_mirrors(sha256_urls, client, config).await?;
 
     let sha256_content = response.text().await?;
 
@@ -305,25 +977,153 @@ pub async fn download_stage3_sha256(
             let file_in_hash = parts[1];
 
             // Check that it's the right file
-            if file_in_hash.ends_with(&filename) || file_in_hash == filename {
+            if file_in_hash.ends_with(filename) || file_in_hash == filename {
                 return Ok(hash.to_string());
             }
         }
     }
 
-    Err("SHA256 hash isn't found in the file".into())
+    Err(DownloaderError::ChecksumNotFound(filename.to_string()))
+}
+
+/// Extract the hash of a stage3 from a DIGESTS file, SHA512 preferred
+///
+/// The file lists each hash under a `# <ALGORITHM> HASH` header, for the
+/// archive and its CONTENTS, and may be clearsigned. Only lines naming the
+/// archive itself are considered.
+pub(crate) fn parse_digests(content: &str, filename: &str) -> Option<Stage3Digest> {
+    let mut section = "";
+    let mut sha512 = None;
+    let mut blake2b = None;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('#') {
+            section = header.trim().strip_suffix("HASH").unwrap_or("").trim();
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        let (Some(hash), Some(file_in_hash)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        if file_in_hash.rsplit('/').next() != Some(filename) {
+            continue;
+        }
+        match section {
+            "SHA512" => sha512 = Some(Stage3Digest::Sha512(hash.to_string())),
+            "BLAKE2B" => blake2b = Some(Stage3Digest::Blake2b(hash.to_string())),
+            _ => {}
+        }
+    }
+
+    sha512.or(blake2b)
+}
+
+/// Download the detached OpenPGP signature (`<filename>.asc`) of a stage3
+pub async fn download_stage3_signature(
+    profile: &SelectedProfile,
+    config: &Config,
+    release: &Stage3Release,
+) -> Result<String, DownloaderError> {
+    let signature_urls = release.file_urls(profile, config, ".asc");
+    let (_successful_url, response) = try_download_with_mirrors(&signature_urls, &release.client, config).await?;
+    Ok(response.text().await?)
 }
 
 /// Calculate the SHA256 hash of a local file
 pub async fn calculate_file_sha256(
     file_path: &std::path::Path,
-) -> Result<String, Box<dyn std::error::Error>> {
+) -> Result<String, DownloaderError> {
+    calculate_file_sha256_with_progress(file_path, |_| {}).await
+}
+
+/// Calculate the SHA256 hash of a local file, reporting the bytes hashed
+///
+/// The callback is called periodically and once more when the hash is complete.
+pub async fn calculate_file_sha256_with_progress<F>(
+    file_path: &std::path::Path,
+    progress_callback: F,
+) -> Result<String, DownloaderError>
+where
+    F: FnMut(HashProgress),
+{
+    calculate_file_hash_with_progress::<Sha256, F>(file_path, progress_callback).await
+}
+
+/// Calculate the hash of a local file with the algorithm of a digest
+pub async fn calculate_file_digest_with_progress<F>(
+    file_path: &std::path::Path,
+    digest: &Stage3Digest,
+    progress_callback: F,
+) -> Result<String, DownloaderError>
+where
+    F: FnMut(HashProgress),
+{
+    match digest {
+        Stage3Digest::Sha256(_) => calculate_file_hash_with_progress::<Sha256, F>(file_path, progress_callback).await,
+        Stage3Digest::Sha512(_) => calculate_file_hash_with_progress::<Sha512, F>(file_path, progress_callback).await,
+        Stage3Digest::Blake2b(_) => {
+            calculate_file_hash_with_progress::<Blake2b512, F>(file_path, progress_callback).await
+        }
+    }
+}
+
+/// Feed the content of a file into a hasher
+async fn hash_file_into<D: Digest>(file_path: &str, hasher: &mut D) -> io::Result<()> {
+    let file = tokio::fs::File::open(file_path).await?;
+    let mut reader = BufReader::with_capacity(HASH_BUFFER_SIZE, file);
+    loop {
+        let buffer = reader.fill_buf().await?;
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        hasher.update(buffer);
+        let read = buffer.len();
+        reader.consume(read);
+    }
+}
+
+/// Hasher of the algorithm of a digest
+fn new_hasher(digest: &Stage3Digest) -> Box<dyn DynDigest + Send> {
+    match digest {
+        Stage3Digest::Sha256(_) => Box::new(Sha256::new()),
+        Stage3Digest::Sha512(_) => Box::new(Sha512::new()),
+        Stage3Digest::Blake2b(_) => Box::new(Blake2b512::new()),
+    }
+}
+
+fn to_hex(hash: &[u8]) -> String {
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+async fn calculate_file_hash_with_progress<D, F>(
+    file_path: &std::path::Path,
+    mut progress_callback: F,
+) -> Result<String, DownloaderError>
+where
+    D: Digest,
+    F: FnMut(HashProgress),
+{
     use tokio::fs::File;
     use tokio::io::AsyncReadExt;
 
-    let mut file = File::open(file_path).await?;
-    let mut hasher = Sha256::new();
-    let mut buffer = vec![0u8; 8192]; // 8KB buffer
+    let file = File::open(file_path).await?;
+    let total = file.metadata().await?.len();
+    let mut file = BufReader::with_capacity(HASH_BUFFER_SIZE, file);
+    let mut hasher = D::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+
+    let mut hashed = 0u64;
+    let start_time = std::time::Instant::now();
+    let mut last_update = start_time;
+    let update_interval = Duration::from_millis(250);
+
+    progress_callback(HashProgress {
+        hashed,
+        total,
+        bytes_per_sec: 0.0,
+    });
 
     loop {
         let bytes_read = file.read(&mut buffer).await?;
@@ -331,19 +1131,224 @@ pub async fn calculate_file_sha256(
             break;
         }
         hasher.update(&buffer[..bytes_read]);
+        hashed += bytes_read as u64;
+
+        let now = std::time::Instant::now();
+        if now.duration_since(last_update) >= update_interval {
+            progress_callback(HashProgress {
+                hashed,
+                total,
+                bytes_per_sec: calculate_speed_bytes_per_sec(hashed, now.duration_since(start_time)),
+            });
+            last_update = now;
+        }
     }
 
-    Ok(format!("{:x}", hasher.finalize()))
+    progress_callback(HashProgress {
+        hashed,
+        total,
+        bytes_per_sec: calculate_speed_bytes_per_sec(hashed, start_time.elapsed()),
+    });
+
+    Ok(to_hex(&hasher.finalize()))
 }
 
-/// Check the integrity of a stage3 file with its SHA256 hash
+/// Check the integrity of a stage3 file with its published hash
 /// Returns (is_valid, expected_hash, calculated_hash)
+#[cfg_attr(not(feature = "dbus"), allow(dead_code))]
 pub async fn check_stage3_integrity(
     file_path: &std::path::Path,
-    expected_sha256: &str,
-) -> Result<(bool, String, String), Box<dyn std::error::Error>> {
-    let calculated_hash = calculate_file_sha256(file_path).await?;
-    let is_valid = calculated_hash.to_lowercase() == expected_sha256.to_lowercase();
+    expected: &Stage3Digest,
+) -> Result<(bool, String, String), DownloaderError> {
+    check_stage3_integrity_with_progress(file_path, expected, |_| {}).await
+}
 
-    Ok((is_valid, expected_sha256.to_string(), calculated_hash))
+/// Check the integrity of a stage3 file, reporting the hashing progress
+pub async fn check_stage3_integrity_with_progress<F>(
+    file_path: &std::path::Path,
+    expected: &Stage3Digest,
+    progress_callback: F,
+) -> Result<(bool, String, String), DownloaderError>
+where
+    F: FnMut(HashProgress),
+{
+    let calculated_hash = calculate_file_digest_with_progress(file_path, expected, progress_callback).await?;
+    let is_valid = calculated_hash.eq_ignore_ascii_case(expected.hash());
+
+    Ok((is_valid, expected.hash().to_string(), calculated_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// latest-stage3-amd64-openrc.txt as published on the mirrors
+    const LATEST_OPENRC: &str = "\
+-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA256
+
+# Latest as of Sun, 03 Mar 2024 17:35:01 +0000
+# ts=1709487301
+20240303T170409Z/stage3-amd64-openrc-20240303T170409Z.tar.xz 277493912
+-----BEGIN PGP SIGNATURE-----
+
+iQIzBAEBCAAdFiEE
+-----END PGP SIGNATURE-----
+";
+
+    /// Excerpt of latest-stage3.txt, listing every profile of the architecture
+    const LATEST_ALL: &str = "\
+# Latest as of Sun, 03 Mar 2024 17:35:01 +0000
+# ts=1709487301
+20240303T170409Z/stage3-amd64-desktop-openrc-20240303T170409Z.tar.xz 383291468
+20240303T170409Z/stage3-amd64-hardened-openrc-20240303T170409Z.tar.xz 265316140
+20240303T170409Z/stage3-amd64-openrc-splitusr-20240303T170409Z.tar.xz 277400820
+20240303T170409Z/stage3-amd64-openrc-20240303T170409Z.tar.xz 277493912
+20240303T170409Z/stage3-amd64-systemd-20240303T170409Z.tar.xz 297870928
+";
+
+    /// stage3-amd64-openrc-20240303T170409Z.tar.xz.DIGESTS, clearsigned, hashes shortened
+    const DIGESTS: &str = "\
+-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA512
+
+# BLAKE2B HASH
+6b1e5e0fa3b2 stage3-amd64-openrc-20240303T170409Z.tar.xz
+# SHA512 HASH
+1f2e3d4c5b6a stage3-amd64-openrc-20240303T170409Z.tar.xz
+# BLAKE2B HASH
+0a0b0c0d0e0f stage3-amd64-openrc-20240303T170409Z.tar.xz.CONTENTS.gz
+# SHA512 HASH
+a9b8c7d6e5f4 stage3-amd64-openrc-20240303T170409Z.tar.xz.CONTENTS.gz
+-----BEGIN PGP SIGNATURE-----
+
+iQIzBAEBCgAdFiEE
+-----END PGP SIGNATURE-----
+";
+
+    #[test]
+    fn parse_digests_prefers_sha512_of_the_archive() {
+        assert_eq!(
+            parse_digests(DIGESTS, "stage3-amd64-openrc-20240303T170409Z.tar.xz"),
+            Some(Stage3Digest::Sha512("1f2e3d4c5b6a".to_string()))
+        );
+        assert_eq!(
+            parse_digests(DIGESTS, "stage3-amd64-openrc-20240303T170409Z.tar.xz.CONTENTS.gz"),
+            Some(Stage3Digest::Sha512("a9b8c7d6e5f4".to_string()))
+        );
+        assert_eq!(parse_digests(DIGESTS, "stage3-amd64-systemd-20240303T170409Z.tar.xz"), None);
+    }
+
+    #[test]
+    fn parse_digests_falls_back_to_blake2b() {
+        let blake2b_only = "\
+# BLAKE2B HASH
+6b1e5e0fa3b2  20240303T170409Z/stage3-amd64-openrc-20240303T170409Z.tar.xz
+# SHA256 HASH
+c0ffee  stage3-amd64-openrc-20240303T170409Z.tar.xz
+";
+        assert_eq!(
+            parse_digests(blake2b_only, "stage3-amd64-openrc-20240303T170409Z.tar.xz"),
+            Some(Stage3Digest::Blake2b("6b1e5e0fa3b2".to_string()))
+        );
+    }
+
+    #[test]
+    fn find_stage3_entry_reads_signed_latest_files() {
+        assert_eq!(
+            find_stage3_entry(LATEST_OPENRC, "stage3-amd64-openrc"),
+            Some(("stage3-amd64-openrc-20240303T170409Z.tar.xz".to_string(), Some(277493912)))
+        );
+        assert_eq!(find_stage3_entry(LATEST_OPENRC, "stage3-amd64-systemd"), None);
+    }
+
+    #[test]
+    fn find_stage3_entry_skips_longer_profiles() {
+        assert_eq!(
+            find_stage3_entry(LATEST_ALL, "stage3-amd64-openrc"),
+            Some(("stage3-amd64-openrc-20240303T170409Z.tar.xz".to_string(), Some(277493912)))
+        );
+        assert_eq!(
+            find_stage3_entry(LATEST_ALL, "stage3-amd64-openrc-splitusr").map(|(filename, _)| filename),
+            Some("stage3-amd64-openrc-splitusr-20240303T170409Z.tar.xz".to_string())
+        );
+    }
+
+    #[test]
+    fn matches_stage3_pattern_only_accepts_archives() {
+        let archive = "stage3-amd64-openrc-20240303T170409Z.tar.xz";
+        assert!(matches_stage3_pattern(archive, "stage3-amd64-openrc"));
+        for sidecar in [".asc", ".DIGESTS", ".sha256", ".CONTENTS.gz"] {
+            assert!(!matches_stage3_pattern(&format!("{archive}{sidecar}"), "stage3-amd64-openrc"));
+        }
+    }
+
+    #[test]
+    fn matches_stage3_pattern_table() {
+        let cases = [
+            ("stage3-amd64-openrc-20240303T170409Z.tar.xz", "stage3-amd64-openrc", true),
+            ("stage3-amd64-openrc-splitusr-20240303T170409Z.tar.xz", "stage3-amd64-openrc", false),
+            ("stage3-amd64-openrc-splitusr-20240303T170409Z.tar.xz", "stage3-amd64-openrc-splitusr", true),
+            ("stage3-amd64-desktop-openrc-20240303T170409Z.tar.xz", "stage3-amd64-openrc", false),
+            ("stage3-amd64-hardened-openrc-20240303T170409Z.tar.xz", "stage3-amd64-hardened-openrc", true),
+            ("stage3-arm64-systemd-20240303T233159Z.tar.bz2", "stage3-arm64-systemd", true),
+            ("stage3-arm64-systemd-20240303T233159Z.tar.zst", "stage3-arm64-systemd", true),
+            ("stage3-arm64-systemd-20240303T233159Z.tar.lz", "stage3-arm64-systemd", false),
+            ("stage3-amd64-openrc-latest.tar.xz", "stage3-amd64-openrc", false),
+            ("stage3-amd64-openrc20240303T170409Z.tar.xz", "stage3-amd64-openrc", false),
+            ("stage3-i686-openrc-20240303T170409Z.tar.xz", "stage3-amd64-openrc", false),
+        ];
+        for (filename, pattern, expected) in cases {
+            assert_eq!(matches_stage3_pattern(filename, pattern), expected, "{filename} / {pattern}");
+        }
+    }
+
+    #[test]
+    fn release_prefix_table() {
+        let cases = [
+            ("20240303T170409Z", Some("20240303T170409Z")),
+            ("2024-03-03", Some("20240303")),
+            ("20240303", Some("20240303")),
+            ("2024-02-30", None),
+            ("20240303T1704Z", None),
+            ("latest", None),
+            ("", None),
+        ];
+        for (release, expected) in cases {
+            assert_eq!(release_prefix(release).ok().as_deref(), expected, "{release}");
+        }
+    }
+
+    #[test]
+    fn find_stage3_entry_without_size() {
+        let content = "stage3-arm64-systemd-20240303T233159Z.tar.xz\n";
+        assert_eq!(
+            find_stage3_entry(content, "stage3-arm64-systemd"),
+            Some(("stage3-arm64-systemd-20240303T233159Z.tar.xz".to_string(), None))
+        );
+    }
+
+    #[test]
+    fn snapshot_dirs_are_listed_newest_first() {
+        let html = r#"<a href="/releases/amd64/">Parent Directory</a>
+<a href="20240301T170000Z/">20240301T170000Z/</a>
+<a href="current-stage3-amd64-openrc/">current-stage3-amd64-openrc/</a>
+<a href="20240308T170000Z/">20240308T170000Z/</a>
+<a href="latest-stage3.txt">latest-stage3.txt</a>
+<a href="2024030T170000Z/">2024030T170000Z/</a>"#;
+        assert_eq!(parse_snapshot_dirs(html), ["20240308T170000Z", "20240301T170000Z"]);
+    }
+
+    #[test]
+    fn only_a_missing_archive_triggers_the_fallback() {
+        let missing: Box<dyn std::error::Error> =
+            Box::new(DownloaderError::NotFoundOnMirrors("stage3.tar.xz".to_string()));
+        assert!(is_not_found_on_mirrors(missing.as_ref()));
+        let offline: Box<dyn std::error::Error> = Box::new(DownloaderError::NoMirrorConfigured);
+        assert!(!is_not_found_on_mirrors(offline.as_ref()));
+        assert_eq!(
+            stage3_timestamp("stage3-amd64-openrc-20240308T170000Z.tar.xz", "stage3-amd64-openrc"),
+            Some("20240308T170000Z")
+        );
+    }
 }
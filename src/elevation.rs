@@ -1,18 +1,117 @@
 use crate::error::ElevationError;
 use log::{debug, info, warn};
 use std::process::{Command, Output};
+#[cfg(feature = "dbus")]
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
 use std::thread;
 
-/// Global shared elevation instance to maintain authentication across operations
-static GLOBAL_ELEVATION: OnceLock<Arc<Mutex<SecureElevation>>> = OnceLock::new();
+/// Elevation state shared by every privileged operation, created on first use
+///
+/// A single instance holds the authentication cache, so that authenticating
+/// once covers all the following commands.
+static SHARED_ELEVATION: OnceLock<Mutex<SecureElevation>> = OnceLock::new();
 
-/// Get the global elevation instance
-pub(crate) fn get_global_elevation() -> Arc<Mutex<SecureElevation>> {
-    GLOBAL_ELEVATION
-        .get_or_init(|| Arc::new(Mutex::new(SecureElevation::new())))
-        .clone()
+/// Whether sudo is installed, checked once
+static SUDO_AVAILABLE: OnceLock<bool> = OnceLock::new();
+
+/// Whether privileged commands go through polkit instead of sudo
+#[cfg(feature = "dbus")]
+static USE_POLKIT: AtomicBool = AtomicBool::new(false);
+
+/// Use polkit (pkexec) for privilege elevation, as the daemon has no terminal for sudo
+#[cfg(feature = "dbus")]
+pub(crate) fn use_polkit() {
+    USE_POLKIT.store(true, Ordering::Relaxed);
+}
+
+fn polkit_enabled() -> bool {
+    #[cfg(feature = "dbus")]
+    {
+        USE_POLKIT.load(Ordering::Relaxed)
+    }
+    #[cfg(not(feature = "dbus"))]
+    {
+        false
+    }
+}
+
+/// Program prefixed to privileged command lines
+pub(crate) fn elevation_program() -> &'static str {
+    if polkit_enabled() {
+        "pkexec"
+    } else {
+        "sudo"
+    }
+}
+
+/// Whether the process already runs as root
+///
+/// Privileged commands then run directly, without sudo or polkit.
+pub(crate) fn running_as_root() -> bool {
+    nix::unistd::geteuid().is_root()
+}
+
+/// How privileged commands are run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ElevationMethod {
+    /// Through pkexec, for the daemon
+    Polkit,
+    /// Directly, the process runs as root
+    Root,
+    /// Through `sudo -n`, once a sudo session is established
+    Sudo,
+}
+
+impl ElevationMethod {
+    /// Method used by this process
+    pub(crate) fn current() -> Self {
+        if polkit_enabled() {
+            Self::Polkit
+        } else if running_as_root() {
+            Self::Root
+        } else {
+            Self::Sudo
+        }
+    }
+
+    /// Command running `command` with this method, its arguments left to add
+    fn command(self, command: &str) -> Command {
+        match self {
+            Self::Polkit => {
+                let mut cmd = Command::new("pkexec");
+                cmd.arg(command);
+                cmd
+            }
+            Self::Root => Command::new(command),
+            Self::Sudo => {
+                let mut cmd = Command::new("sudo");
+                cmd.arg("-n"); // Non-interactive mode (will fail if the session expired)
+                cmd.arg(command);
+                cmd
+            }
+        }
+    }
+}
+
+/// Get the shared elevation instance, creating it on first use
+pub(crate) fn shared_elevation() -> &'static Mutex<SecureElevation> {
+    SHARED_ELEVATION.get_or_init(|| Mutex::new(SecureElevation::new()))
+}
+
+/// Whether privileged commands can run without asking for a password
+///
+/// Always true with polkit, which authorizes each command itself. Never
+/// creates the shared instance, so read-only commands do not touch the
+/// elevation subsystem.
+pub(crate) fn is_authenticated() -> bool {
+    polkit_enabled()
+        || running_as_root()
+        || SHARED_ELEVATION
+            .get()
+            .and_then(|elevation| elevation.lock().ok())
+            .is_some_and(|elevation| elevation.is_authenticated())
 }
 
 /// Authentication cache to avoid repeated elevation requests
@@ -191,6 +290,16 @@ impl SecureElevation {
 
     /// Pre-authenticate to establish a sudo session and avoid multiple password prompts
     pub fn pre_authenticate(&self) -> Result<(), ElevationError> {
+        if polkit_enabled() {
+            // polkit authorizes each command through the session agent
+            return Ok(());
+        }
+
+        if running_as_root() {
+            debug!("Running as root, no elevation needed");
+            return Ok(());
+        }
+
         if !is_sudo_available() {
             return Err(ElevationError::SudoNotAvailable);
         }
@@ -206,27 +315,51 @@ impl SecureElevation {
 
     /// Executes a command with privilege elevation using sudo
     pub fn execute_command(&self, command: &str, args: &[&str]) -> Result<Output, ElevationError> {
+        let output = self.command(command, args)?.output()?;
+        self.check_output(command, output)
+    }
+
+    /// Builds an elevated command capturing its output, without running it
+    ///
+    /// Lets callers release the elevation lock while a long command such as
+    /// an archive extraction runs; its output goes through
+    /// [`check_output`](Self::check_output) afterwards.
+    pub fn command(&self, command: &str, args: &[&str]) -> Result<Command, ElevationError> {
+        let method = ElevationMethod::current();
+        if method == ElevationMethod::Sudo {
+            self.ensure_sudo_session()?;
+        }
+
+        debug!("Executing ({method:?}): {} {}", command, args.join(" "));
+        let mut cmd = method.command(command);
+        cmd.args(args);
+        Ok(cmd)
+    }
+
+    /// Checks that sudo is installed, authenticating when no session is active
+    fn ensure_sudo_session(&self) -> Result<(), ElevationError> {
         if !is_sudo_available() {
             return Err(ElevationError::SudoNotAvailable);
         }
-
-        // Ensure we're authenticated
         if !self.cache.is_authenticated() {
             warn!("No active sudo session, attempting to authenticate...");
             self.cache.authenticate()?;
         }
+        Ok(())
+    }
 
-        let mut cmd = Command::new("sudo");
-        cmd.arg("-n"); // Non-interactive mode (will fail if the session expired)
-        cmd.arg(command);
-        for arg in args {
-            cmd.arg(arg);
+    /// Turns the elevation failures in the output of a command built by [`command`](Self::command) into errors
+    pub fn check_output(&self, command: &str, output: Output) -> Result<Output, ElevationError> {
+        if polkit_enabled() {
+            // pkexec exits with 126 when the authorization is refused or dismissed
+            if output.status.code() == Some(126) {
+                warn!("polkit authorization refused for {command}");
+                return Err(ElevationError::AccessDenied);
+            }
+            return Ok(output);
         }
 
-        debug!("Executing with sudo: {} {}", command, args.join(" "));
-        let output = cmd.output()?;
-        
-        if !output.status.success() {
+        if !running_as_root() && !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             
             if stderr.contains("a password is required") || stderr.contains("sorry, try again") {
@@ -244,41 +377,53 @@ impl SecureElevation {
         Ok(output)
     }
 
-    /// Executes a command interactively (for chroot operations)
-    pub fn execute_command_interactive(
-        &self,
-        command: &str,
-        args: &[&str],
-    ) -> Result<Output, ElevationError> {
-        if !is_sudo_available() {
-            return Err(ElevationError::SudoNotAvailable);
-        }
+    /// Builds an elevated command attached to the terminal, without running it
+    ///
+    /// Lets callers release the elevation lock while a long interactive
+    /// session runs.
+    pub fn interactive_command(&self, command: &str, args: &[&str]) -> Result<Command, ElevationError> {
+        let method = ElevationMethod::current();
+        if method == ElevationMethod::Sudo {
+            if !is_sudo_available() {
+                return Err(ElevationError::SudoNotAvailable);
+            }
 
-        if !self.is_authenticated() {
-            return Err(ElevationError::AuthenticationRequired);
+            if !self.is_authenticated() {
+                return Err(ElevationError::AuthenticationRequired);
+            }
         }
-
-        let mut cmd = Command::new("sudo");
-        cmd.arg("-n"); // Non-interactive for privilege escalation
-        cmd.arg(command);
+        let mut cmd = method.command(command);
         cmd.args(args);
         cmd.stdin(std::process::Stdio::inherit());
         cmd.stdout(std::process::Stdio::inherit());
         cmd.stderr(std::process::Stdio::inherit());
+        Ok(cmd)
+    }
 
-        let output = cmd.output()?;
-        Ok(output)
+    /// Builds an elevated command reading its standard input from a pipe, without running it
+    ///
+    /// Lets callers feed data to the command as it arrives, such as an
+    /// archive being downloaded. The standard error is captured.
+    pub fn piped_command(&self, command: &str, args: &[&str]) -> Result<Command, ElevationError> {
+        let method = ElevationMethod::current();
+        if method == ElevationMethod::Sudo {
+            self.ensure_sudo_session()?;
+        }
+        let mut cmd = method.command(command);
+
+        debug!("Piping into elevated command: {} {}", command, args.join(" "));
+        cmd.args(args);
+        cmd.stdin(std::process::Stdio::piped());
+        cmd.stdout(std::process::Stdio::null());
+        cmd.stderr(std::process::Stdio::piped());
+        Ok(cmd)
     }
 
     /// Batch executes multiple commands to optimize sudo session usage
     pub fn execute_batch_commands(&self, commands: Vec<(&str, Vec<&str>)>) -> Result<Vec<Output>, ElevationError> {
-        if !is_sudo_available() {
-            return Err(ElevationError::SudoNotAvailable);
-        }
-
         // Ensure we're authenticated before batch execution
-        if !self.cache.is_authenticated() {
-            self.cache.authenticate()?;
+        if ElevationMethod::current() == ElevationMethod::Sudo {
+            self.ensure_sudo_session()?;
         }
 
         let mut results = Vec::new();
@@ -292,7 +437,7 @@ impl SecureElevation {
 
     /// Checks if authentication is cached
     pub fn is_authenticated(&self) -> bool {
-        self.cache.is_authenticated()
+        running_as_root() || self.cache.is_authenticated()
     }
 
     /// Invalidates the authentication cache and stops session keeper
@@ -311,9 +456,35 @@ impl Default for SecureElevation {
 
 /// Checks if sudo is available on the system
 pub(crate) fn is_sudo_available() -> bool {
-    Command::new("which")
-        .arg("sudo")
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
-}
\ No newline at end of file
+    *SUDO_AVAILABLE.get_or_init(|| {
+        Command::new("which")
+            .arg("sudo")
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Program and arguments of a command
+    fn command_line(cmd: &Command) -> (String, Vec<String>) {
+        let program = cmd.get_program().to_string_lossy().into_owned();
+        (program, cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect())
+    }
+
+    #[test]
+    fn each_elevation_method_runs_its_program() {
+        let expected = [
+            (ElevationMethod::Polkit, "pkexec", vec!["mount", "-t", "proc"]),
+            (ElevationMethod::Root, "mount", vec!["-t", "proc"]),
+            (ElevationMethod::Sudo, "sudo", vec!["-n", "mount", "-t", "proc"]),
+        ];
+        for (method, program, args) in expected {
+            let mut cmd = method.command("mount");
+            cmd.args(["-t", "proc"]);
+            assert_eq!(command_line(&cmd), (program.to_string(), args.iter().map(|arg| arg.to_string()).collect()));
+        }
+    }
+}
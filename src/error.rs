@@ -1,6 +1,6 @@
 use inquire::InquireError;
 use std::io;
-use std::io::Error;
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -15,10 +15,8 @@ pub enum ProfileError {
 
 #[derive(Error, Debug)]
 pub enum DownloaderError {
-    // These variants are kept for comprehensive error handling in future scenarios
-    #[allow(dead_code)]
     #[error("IO Error: {0}")]
-    Io(Error),
+    Io(#[from] io::Error),
     #[allow(dead_code)]
     #[error("Error parsing empty profile")]
     ReadProfileEmpty,
@@ -27,10 +25,47 @@ pub enum DownloaderError {
     CantReadProfile(String),
     #[error("Error retrieving mirror: {0}")]
     RetrievingMirror(String),
+    #[error("{0} was not found on any mirror")]
+    NotFoundOnMirrors(String),
+    #[error("No stage3 found for profile {0}")]
+    NoStage3Found(String),
+    #[error("Unexpected stage3 filename: {0}")]
+    UnexpectedFilename(String),
+    #[error("No previous snapshot of {0} found on the mirrors")]
+    NoPreviousSnapshot(String),
+    #[error("Invalid release {0}, expected a snapshot such as 20240301T164822Z or a date such as 2024-03-01")]
+    InvalidRelease(String),
+    #[error("No {pattern} stage3 of release {release} found on the mirrors")]
+    SnapshotNotFound { pattern: String, release: String },
+    #[error("No mirror configured")]
+    NoMirrorConfigured,
+    #[error("All mirrors failed: {}", failures.join("; "))]
+    AllMirrorsFailed { failures: Vec<String> },
+    #[error("No published hash of {0} was found on the mirrors")]
+    ChecksumNotFound(String),
+    #[error("Download interrupted by a termination signal")]
+    Interrupted,
+    #[error("Download from {url} ended early, {got} of {expected} bytes received")]
+    IncompleteDownload { url: String, expected: u64, got: u64 },
+    #[error("Proxy connection failed ({proxy}): {message}")]
+    ProxyConnection { proxy: String, message: String },
+    #[error("{0}")]
+    Offline(#[from] OfflineError),
     #[error("Reqwest Error: {0}")]
     Reqwest(#[from] reqwest::Error),
 }
 
+/// Failures of `--offline`, where nothing is requested from the network
+#[derive(Error, Debug)]
+pub enum OfflineError {
+    #[error("{0} requires network access, which --offline forbids")]
+    NetworkRequired(String),
+    #[error("No cached mirror list in {}", .0.display())]
+    NoCachedMirrorList(PathBuf),
+    #[error("No {0} stage3 in the cache")]
+    NoCachedStage3(String),
+}
+
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("IO Error: {0}")]
@@ -43,6 +78,23 @@ pub enum ConfigError {
     TomlSer(#[from] toml::ser::Error),
     #[error("Downloader Error: {0}")]
     Downloader(#[from] DownloaderError),
+    // Boxed, MirrorError holds a ConfigError
+    #[error("Mirror Error: {0}")]
+    Mirror(#[from] Box<MirrorError>),
+    #[error("Unknown architecture in {key}: {arch}")]
+    UnknownArchitecture { key: &'static str, arch: String },
+    #[error("Invalid profile in default_profile: '{0}'")]
+    InvalidProfile(String),
+    #[error("Invalid size in configuration: {0}")]
+    InvalidSize(String),
+    #[error("Invalid percentage in configuration: {0} (expected 0 to 100)")]
+    InvalidPercentage(u8),
+    #[error("Invalid log level in configuration: {0} (expected off, error, warn, info, debug or trace)")]
+    InvalidLogLevel(String),
+    #[error("Unknown or read-only configuration key: {0}")]
+    UnknownKey(String),
+    #[error("Invalid value for {key}: '{value}' ({expected})")]
+    InvalidValue { key: String, value: String, expected: &'static str },
 }
 
 #[derive(Error, Debug)]
@@ -78,6 +130,90 @@ pub enum ChrootError {
     ElevationError(String),
     #[error("No profile")]
     NoProfile,
+    #[error("Chroot operations are only supported on Linux (this system is {0})")]
+    UnsupportedPlatform(&'static str),
+    #[error("Invalid chroot metadata: {0}")]
+    InvalidMetadata(String),
+    #[error("The metadata of the chroot '{name}' is too old (version {version}), run `chrootmanager migrate {name}`")]
+    MetadataTooOld { name: String, version: u32 },
+    #[error("The chroot '{name}' was created by a newer chrootmanager (metadata version {version}, this version supports up to {})", crate::chroot::metadata::METADATA_VERSION)]
+    MetadataTooNew { name: String, version: u32 },
+    #[error("Failed to mount the chroot filesystems: {0}")]
+    MountFailed(String),
+    #[error("Failed to unmount the chroot filesystems: {0}")]
+    UnmountFailed(String),
+    #[error(
+        "Filesystems are still mounted in the chroot: {}",
+        .0.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ")
+    )]
+    StillMounted(Vec<PathBuf>),
+    #[error("Refusing to delete {}: it is not inside the chroot directory {}", path.display(), base_dir.display())]
+    OutsideBaseDir { path: PathBuf, base_dir: PathBuf },
+    #[error("The shell of the chroot could not be started ({0}), check that /bin/bash runs in it")]
+    ShellNotStarted(std::process::ExitStatus),
+    #[error("Interrupted by a termination signal")]
+    Interrupted,
+    #[error("Refusing to rename the chroot: {} is still mounted", .0.display())]
+    MountedDuringRename(PathBuf),
+    #[error("Refusing to clone the chroot: {} is still mounted", .0.display())]
+    MountedDuringClone(PathBuf),
+    #[error("Invalid chroot name '{0}': it must not be empty, contain '/' or be '.' or '..'")]
+    InvalidName(String),
+    #[error("A chroot named '{0}' already exists")]
+    NameTaken(String),
+    #[error("The extracted chroot is for {found}, not {expected} ({} was checked). The directory was left for inspection", binary.display())]
+    ArchitectureMismatch {
+        expected: String,
+        found: String,
+        binary: PathBuf,
+    },
+    #[error(
+        "Not enough free space in {}: {} needed, {} available. Use --force to try anyway",
+        path.display(),
+        crate::cli::download::format_bytes(*needed),
+        crate::cli::download::format_bytes(*available)
+    )]
+    InsufficientSpace { path: PathBuf, needed: u64, available: u64 },
+    #[error("{arch} binaries cannot run on this host: {reason}")]
+    EmulationUnavailable { arch: String, reason: String },
+    #[error(
+        "{} is on {}, mounted {options}: device files, setuid programs or executables would not work in a chroot there. \
+         Set chroot_base_dir to a directory on another filesystem or remount it without these options, \
+         or use --ignore-fs-checks to create it anyway",
+        path.display(),
+        mount_point.display()
+    )]
+    RestrictiveMount { path: PathBuf, mount_point: PathBuf, options: String },
+    #[error("Invalid timezone '{0}': it must be a zoneinfo name such as Europe/Paris")]
+    InvalidTimezone(String),
+    #[error("The post-create hook {} does not exist or cannot be read", .0.display())]
+    HookNotFound(PathBuf),
+    #[error("The post-create hook {} failed ({status})", hook.display())]
+    HookFailed { hook: PathBuf, status: std::process::ExitStatus },
+    #[error("The stage3 stream failed during extraction: {0}")]
+    Stage3Stream(io::Error),
+    #[error("The chroot directory {} is not empty ({count} entries, including '{first}'). Use --force-extract to extract over it", path.display())]
+    DirectoryNotEmpty {
+        path: PathBuf,
+        count: usize,
+        first: String,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum StateError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Toml Deserialisation Error: {0}")]
+    TomlDe(#[from] toml::de::Error),
+    #[error("Toml Serialization Error: {0}")]
+    TomlSer(#[from] toml::ser::Error),
+    #[error("The project '{0}' does not exist")]
+    UnknownProject(String),
+    #[error("The project '{0}' already exists")]
+    ProjectExists(String),
+    #[error("The chroot '{chroot}' is not a member of the project '{project}'")]
+    NotAMember { project: String, chroot: String },
 }
 
 #[derive(Error, Debug)]
@@ -98,4 +234,32 @@ pub enum MirrorError {
     InvalidFormat(String),
     #[error("XML document does not contain a root element 'mirrors'")]
     NoRootElementIntoMirrors,
+    #[error("{url} answered with status code {status}")]
+    BadStatus { url: String, status: reqwest::StatusCode },
+    #[error("Not a Gentoo mirror, the releases directory {0} is not available")]
+    NoReleases(String),
+    #[error("The mirror carries no autobuilds for {arch}, {url} is not available")]
+    NoAutobuilds { arch: String, url: String },
+    #[error("No mirror found at location {0}")]
+    LocationNotFound(String),
+    #[error("The mirror at {location} is not available over {protocol}")]
+    ProtocolNotAvailable { location: String, protocol: String },
+    #[error("{0}")]
+    Offline(#[from] OfflineError),
+}
+
+#[derive(Error, Debug)]
+pub enum SignatureError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("The Gentoo release key {} is missing. Install app-crypt/openpgp-keys-gentoo-release, set release_key in the configuration or use --no-gpg", .0.display())]
+    KeyNotFound(PathBuf),
+    #[error("gpg is required to verify the stage3 signature ({0}). Install it or use --no-gpg")]
+    GpgUnavailable(io::Error),
+    #[error("The stage3 signature is not available on the mirrors ({0}). Use --no-gpg to skip the check")]
+    SignatureUnavailable(String),
+    #[error("Unable to import the release key {}: {message}", path.display())]
+    KeyImport { path: PathBuf, message: String },
+    #[error("Bad OpenPGP signature: {0}")]
+    BadSignature(String),
 }
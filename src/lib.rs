@@ -2,7 +2,21 @@ pub mod error;
 pub mod config;
 pub mod chroot;
 pub mod downloader;
+pub mod http;
 pub mod profile;
 pub mod mirror;
+pub mod cache;
+pub mod space;
+pub mod state;
+pub mod permissions;
+pub mod platform;
+pub mod signals;
+pub mod diagnostics;
+pub mod logging;
+pub mod doctor;
+pub mod signature;
 mod elevation;
-pub mod cli;
\ No newline at end of file
+pub mod cli;
+pub mod ui;
+#[cfg(feature = "dbus")]
+pub mod daemon;
\ No newline at end of file
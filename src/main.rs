@@ -3,64 +3,199 @@ mod error;
 mod config;
 mod chroot;
 mod downloader;
+mod http;
 mod profile;
 mod mirror;
+mod cache;
+mod space;
+mod state;
+mod permissions;
+mod platform;
+mod signals;
+mod diagnostics;
+mod logging;
+mod doctor;
+mod signature;
 mod elevation;
+mod ui;
+#[cfg(feature = "dbus")]
+mod daemon;
 
+use chroot::SessionOptions;
 use clap::Parser;
-use cli::command::{Cli, Commands};
-use cli::create_interactive::create_chroot_interactive;
-use cli::create::create_chroot;
+use cli::command::{Cli, Commands, MirrorAction};
+use cli::common::CreateOptions;
+use cli::create::{create_chroot, PromptPolicy};
 use cli::list_interactive::list_chroots_interactive;
 use cli::mirror_interactive::setup_mirrors_interactive;
-use cli::mirror::setup_mirrors;
+use cli::mirror::{setup_mirrors, MatchSelection};
+use mirror::MirrorListOptions;
+use crate::ui::output::Verbosity;
+use crate::ui::symbols::Symbol;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::init();
-
     let cli = Cli::parse();
 
-    match cli.command.unwrap_or(Commands::List { interactive: true }) {
-        Commands::Create { name, arch, profile, interactive } => {
-            if interactive {
-                // Interactive mode explicitly requested with -i
-                create_chroot_interactive(name).await?
-            } else {
-                match (arch, profile) {
-                    (Some(arch), Some(profile)) => {
-                        // Non-interactive mode with all parameters provided
-                        create_chroot(name, arch, profile).await?
-                    },
-                    _ => {
-                        // Default non-interactive mode, but missing parameters
-                        eprintln!("❌ Error: Architecture and profile required in non-interactive mode");
-                        eprintln!("💡 Use -i for interactive mode or specify -a <arch> -p <profile>");
-                        std::process::exit(1);
-                    }
+    let verbosity = Verbosity::from_flags(cli.quiet, cli.verbose);
+    let default_filter = if verbosity == Verbosity::Verbose { "info" } else { "error" };
+    let file_log = logging::FileLogSettings::resolve(cli.log_file.clone());
+    let log_path = file_log.as_ref().map(|settings| settings.path.clone());
+    if let Err(e) = logging::init(default_filter, file_log) {
+        let path = log_path.unwrap_or_default();
+        eprintln!("{} Unable to open the log file {}: {e}", Symbol::Warning, path.display());
+    }
+    ui::output::set_verbosity(verbosity);
+    http::set_offline(cli.offline);
+    cli::common::set_assume_yes(cli.yes || cli::common::assume_yes_from_env());
+    ui::symbols::init_from_env();
+    signals::install()?;
+
+    let result = run(cli).await;
+    if let Err(e) = &result {
+        signals::exit_if_interrupted();
+        diagnostics::record_failure(std::env::args().collect(), e.as_ref());
+    }
+    result
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let command = cli.command.unwrap_or(Commands::List { interactive: true, no_space_warning: false, project: None, format: cli::command::ListFormat::Text, size: false });
+    if command.needs_chroot_operations() {
+        if let Err(e) = platform::ensure_supported() {
+            eprintln!("{} Error: {e}", Symbol::Error);
+            std::process::exit(1);
+        }
+    }
+
+    match command {
+        Commands::Create { name, arch, profile, interactive, no_cache, no_evict, force_extract, strict_latest, allow_tmpfs, ignore_fs_checks, no_same_owner, no_gpg, force, release, refresh_profiles, summary_only, no_template, no_localize, sync, sync_method, hooks, keep_on_hook_failure } => {
+            let options = CreateOptions {
+                use_cache: !no_cache,
+                evict_cache: !no_evict,
+                force_extract,
+                strict_latest,
+                allow_tmpfs,
+                ignore_fs_checks,
+                no_same_owner,
+                verify_signature: !no_gpg,
+                check_space: !force,
+                release,
+                refresh_profiles,
+                apply_template: !no_template,
+                localize: !no_localize,
+                sync,
+                sync_method,
+                hooks,
+                keep_on_hook_failure,
+            };
+            // With -i, only the missing parameters are prompted for
+            let result = create_chroot(name, arch, profile, PromptPolicy::from_flag(interactive), options, summary_only).await;
+            if let Err(e) = result {
+                if !summary_only {
+                    return Err(e.into());
                 }
+                signals::exit_if_interrupted();
+                // The summary line already describes the error
+                diagnostics::record_failure(std::env::args().collect(), &e);
+                std::process::exit(1);
             }
         },
-        Commands::List { interactive } => {
+        Commands::List { interactive, no_space_warning, project, format, size } => {
             if interactive {
-                list_chroots_interactive().await?
+                list_chroots_interactive(!no_space_warning).await?
             } else {
-                cli::list::list_chroots().await?
+                cli::list::list_chroots(project, format, size).await?
             }
         },
-        Commands::Mirror { new_mirror, interactive } => {
-            if interactive {
-                setup_mirrors_interactive().await?
+        Commands::Enter { name, no_space_warning, env, workdir } => {
+            cli::enter::enter_chroot(name, !no_space_warning, SessionOptions { env, workdir }).await?
+        },
+        Commands::Exec { name, env, workdir, command } => {
+            let code = cli::exec::exec_in_chroot(name, command, SessionOptions { env, workdir }).await?;
+            if code != 0 {
+                signals::exit_if_interrupted();
+                std::process::exit(code);
+            }
+        },
+        Commands::Mirror { action: Some(action), .. } => match action {
+            MirrorAction::List => cli::mirror::list_mirrors().await?,
+            MirrorAction::Remove { target } => cli::mirror::remove_mirror(target).await?,
+            MirrorAction::Bench { apply } => cli::mirror::bench_mirrors(apply).await?,
+        },
+        Commands::Mirror { action: None, new_mirror, interactive, show, region, country, protocol, first, all, refresh, include_partial, ipv6_only, ask_protocol, arch } => {
+            let list_options = MirrorListOptions { refresh, include_partial, ipv6_only };
+            if show {
+                cli::mirror::show_mirrors().await?
+            } else if interactive {
+                setup_mirrors_interactive(list_options, ask_protocol).await?
+            } else if region.is_some() || country.is_some() {
+                let selection = if all {
+                    MatchSelection::All
+                } else if first {
+                    MatchSelection::First
+                } else {
+                    MatchSelection::Single
+                };
+                cli::mirror::add_matching_mirrors(region, country, protocol, selection, list_options).await?
             } else {
                 match new_mirror {
                     None => {
-                        eprintln!("❌ Error: A mirror URL is required in non-interactive mode");
+                        eprintln!("{} Error: A mirror URL is required in non-interactive mode", Symbol::Error);
                         std::process::exit(1);
                     }
-                    Some(new_mirror) => setup_mirrors(new_mirror).await?
+                    Some(new_mirror) => setup_mirrors(new_mirror, arch).await?
                 }
             }
         },
+        Commands::Download { arch, profile, output, keep_download, strict_latest, no_gpg, force, release } => {
+            let options = CreateOptions {
+                use_cache: true,
+                evict_cache: true,
+                force_extract: false,
+                strict_latest,
+                allow_tmpfs: false,
+                ignore_fs_checks: false,
+                no_same_owner: false,
+                verify_signature: !no_gpg,
+                check_space: !force,
+                release,
+                refresh_profiles: false,
+                apply_template: false,
+                localize: false,
+                sync: false,
+                sync_method: cli::command::SyncMethod::Webrsync,
+                hooks: Vec::new(),
+                keep_on_hook_failure: false,
+            };
+            cli::download::download_stage3(arch, profile, output, keep_download, options).await?
+        },
+        Commands::Profiles { arch, refresh, format } => cli::profiles::list_profiles(arch, refresh, format).await?,
+        Commands::Info { name, format } => cli::info::show_chroot_info(name, format).await?,
+        Commands::WhyFailed { format } => cli::why_failed::show_last_failure(format)?,
+        Commands::Import { name, archive, no_same_owner, strip_components } => {
+            cli::import::import_chroot(name, archive, no_same_owner, strip_components).await?
+        },
+        Commands::Rename { old, new } => cli::rename::rename_chroot(old, new).await?,
+        Commands::Clone { source, dest } => cli::clone::clone_chroot(source, dest).await?,
+        Commands::Project { command } => cli::project::run_project_command(command).await?,
+        Commands::Cache { action } => cli::cache::run_cache_command(action).await?,
+        Commands::Config { action } => cli::config::run_config_command(action).await?,
+        Commands::Unmount { name, all, kill, lazy } => cli::unmount::unmount_chroots(name, all, kill, lazy).await?,
+        Commands::Migrate { name, dry_run } => cli::migrate::migrate_chroot(name, dry_run).await?,
+        Commands::Selftest => cli::selftest::run_selftest().await?,
+        Commands::Doctor => cli::doctor::run_doctor().await?,
+        Commands::Describe { format } => cli::describe::describe_cli(format)?,
+        Commands::Completions { shell } => cli::completions::print_completions(shell)?,
+        Commands::Complete { target } => cli::completions::print_candidates(target),
+        #[cfg(feature = "dbus")]
+        Commands::Daemon => {
+            if !config::Config::default_config_path().exists() {
+                eprintln!("{} Error: Run chrootmanager once to configure mirrors before starting the daemon", Symbol::Error);
+                std::process::exit(1);
+            }
+            daemon::run(cli::load_config().await?).await?
+        },
     };
 
     Ok(())
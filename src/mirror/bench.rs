@@ -0,0 +1,161 @@
+//! Benchmark of the configured mirrors
+//!
+//! Each mirror is probed with a HEAD request to `releases/`, for latency,
+//! and with the download of the first [`SAMPLE_BYTES`] of the current
+//! default stage3, for throughput. The probes run concurrently and a
+//! failing mirror does not stop the others.
+
+use crate::config::Config;
+use crate::downloader::{build_stage3_url, resolve_current_release};
+use crate::http;
+use crate::profile::selected::SelectedProfile;
+use futures_util::future::join_all;
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+use tokio_stream::StreamExt;
+
+/// Size of the stage3 range downloaded to estimate throughput
+pub const SAMPLE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Time allowed for each request
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Measures of a mirror that answered
+#[derive(Debug, Clone)]
+pub struct MirrorTiming {
+    /// Round trip of the HEAD request to `releases/`
+    pub latency: Duration,
+    /// Bytes per second over the sample, `None` when no stage3 was known
+    pub bytes_per_sec: Option<f64>,
+}
+
+/// Outcome of the benchmark of one mirror
+#[derive(Debug, Clone)]
+pub struct MirrorBench {
+    pub url: String,
+    pub result: Result<MirrorTiming, String>,
+}
+
+fn with_trailing_slash(url: &str) -> String {
+    if url.ends_with('/') {
+        url.to_string()
+    } else {
+        format!("{url}/")
+    }
+}
+
+/// Describe a request error without repeating the URL
+fn describe_error(error: &reqwest::Error) -> String {
+    if error.is_timeout() {
+        "timeout".to_string()
+    } else if error.is_connect() {
+        "connection failed".to_string()
+    } else if let Some(status) = error.status() {
+        format!("HTTP status {status}")
+    } else {
+        "request failed".to_string()
+    }
+}
+
+/// Time a HEAD request to the releases directory
+async fn measure_latency(client: &reqwest::Client, mirror_url: &str) -> Result<Duration, String> {
+    let url = format!("{}releases/", with_trailing_slash(mirror_url));
+    let started = Instant::now();
+    let response = client.head(&url).send().await.map_err(|e| describe_error(&e))?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP status {}", response.status()));
+    }
+    Ok(started.elapsed())
+}
+
+/// Download the start of the stage3 and compute the throughput
+async fn measure_throughput(client: &reqwest::Client, url: &str) -> Result<f64, String> {
+    let started = Instant::now();
+    let response = client
+        .get(url)
+        .header(reqwest::header::RANGE, format!("bytes=0-{}", SAMPLE_BYTES - 1))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| describe_error(&e))?;
+
+    // Servers ignoring the range send the whole file, stop at the sample size
+    let mut received: u64 = 0;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        received += chunk.map_err(|e| describe_error(&e))?.len() as u64;
+        if received >= SAMPLE_BYTES {
+            break;
+        }
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    if received == 0 || elapsed == 0.0 {
+        return Err("empty response".to_string());
+    }
+    Ok(received as f64 / elapsed)
+}
+
+async fn bench_mirror(
+    client: &reqwest::Client,
+    mirror_url: &str,
+    stage3: Option<&(SelectedProfile, String)>,
+) -> MirrorBench {
+    let result = async {
+        let latency = measure_latency(client, mirror_url).await?;
+        let bytes_per_sec = match stage3 {
+            Some((profile, filename)) => {
+                let url = format!("{}{filename}", build_stage3_url(mirror_url, profile, None));
+                Some(measure_throughput(client, &url).await?)
+            }
+            None => None,
+        };
+        Ok(MirrorTiming { latency, bytes_per_sec })
+    }
+    .await;
+
+    MirrorBench {
+        url: mirror_url.to_string(),
+        result,
+    }
+}
+
+/// Benchmark every configured mirror, in configuration order
+///
+/// Throughput is only measured when the current default stage3 can be
+/// resolved from one of the mirrors.
+pub async fn bench_mirrors(config: &Config) -> Result<Vec<MirrorBench>, reqwest::Error> {
+    let profile = SelectedProfile::default();
+    let stage3 = match resolve_current_release(&profile, config).await {
+        Ok(release) => Some((profile, release.filename)),
+        Err(e) => {
+            log::warn!("Unable to resolve a stage3 to measure throughput: {e}");
+            None
+        }
+    };
+
+    let client = http::client_builder(config)?.timeout(PROBE_TIMEOUT).build()?;
+    let probes = config
+        .mirror_urls()
+        .map(|url| bench_mirror(&client, url, stage3.as_ref()));
+    Ok(join_all(probes).await)
+}
+
+/// Order from fastest to slowest, failed mirrors last
+///
+/// Throughput decides first, latency breaks ties and ranks mirrors without
+/// a throughput measure. The sort is stable, failed mirrors keep their order.
+pub fn rank(results: &mut [MirrorBench]) {
+    results.sort_by(|a, b| match (&a.result, &b.result) {
+        (Ok(a), Ok(b)) => {
+            let throughput = |timing: &MirrorTiming| timing.bytes_per_sec.unwrap_or(0.0);
+            throughput(b)
+                .partial_cmp(&throughput(a))
+                .unwrap_or(Ordering::Equal)
+                .then(a.latency.cmp(&b.latency))
+        }
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => Ordering::Equal,
+    });
+}
@@ -1,12 +1,49 @@
 use self::parser::{Mirror, Protocol, UriInfo, get_mirrors};
+use crate::config::{Config, MirrorEntry};
 use crate::error::{DownloaderError, MirrorError};
+use crate::http;
 use std::collections::HashSet;
+use crate::say;
+use crate::ui::symbols::Symbol;
 
+pub mod bench;
 pub mod parser;
 
+/// Protocols picked for the mirrors selected without asking, in order of preference
+pub const PREFERRED_PROTOCOLS: &[Protocol] = &[Protocol::Https, Protocol::Http];
+
+/// How the Gentoo mirror list is fetched and filtered before mirrors are picked from it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MirrorListOptions {
+    /// Download the list even if the cached copy is recent
+    pub refresh: bool,
+    /// Keep the URIs marked partial, which may not carry the stage3 archives
+    pub include_partial: bool,
+    /// Only keep the URIs reachable over IPv6, also implied on hosts without IPv4 route
+    pub ipv6_only: bool,
+}
+
+/// Whether the host has a route to `address`, no packet is sent
+fn has_route(bind: &str, address: &str) -> bool {
+    std::net::UdpSocket::bind(bind)
+        .and_then(|socket| socket.connect(address))
+        .is_ok()
+}
+
+/// Whether the host only reaches the network over IPv6
+fn is_ipv6_only_host() -> bool {
+    // Documentation addresses, any route to them is the default route
+    !has_route("0.0.0.0:0", "192.0.2.1:53") && has_route("[::]:0", "[2001:db8::1]:53")
+}
+
 /// Verifies if a URL is a valid Gentoo mirror by checking if it responds and has the expected structure
-pub async fn verify_mirror_url(url: &str) -> Result<(), MirrorError> {
-    println!("🔄 Verifying mirror URL: {url}");
+///
+/// With `arch`, the mirror must also carry the autobuilds of that
+/// architecture, where the stage3 archives are published. Each level
+/// fails with its own error.
+pub async fn verify_mirror_url(url: &str, config: &Config, arch: Option<&str>) -> Result<(), MirrorError> {
+    http::ensure_online("Verifying a mirror")?;
+    say!("{} Verifying mirror URL: {url}", Symbol::Refresh);
 
     // Ensure the URL ends with a slash
     let url = if url.ends_with('/') {
@@ -16,32 +53,79 @@ pub async fn verify_mirror_url(url: &str) -> Result<(), MirrorError> {
     };
 
     // Create a client with a timeout
-    let client = reqwest::Client::builder()
+    let client = http::client_builder(config)?
         .timeout(std::time::Duration::from_secs(10))
         .build()?;
 
     // Check if the URL responds
-    let response = client.get(&url).send().await?;
+    let response = client.get(&url).send().await.map_err(|e| http::request_error(config, e))?;
 
     if !response.status().is_success() {
-        return Err(MirrorError::InvalidFormat(format!(
-            "Mirror URL returned status code: {}",
-            response.status()
-        )));
+        return Err(MirrorError::BadStatus {
+            url,
+            status: response.status(),
+        });
     }
 
     // Check for common Gentoo mirror directories
     // Try to access the releases directory which should exist in a valid Gentoo mirror
     let releases_url = format!("{url}releases/");
-    let releases_response = client.get(&releases_url).send().await?;
+    let releases_response = client
+        .get(&releases_url)
+        .send()
+        .await
+        .map_err(|e| http::request_error(config, e))?;
 
     if !releases_response.status().is_success() {
-        return Err(MirrorError::InvalidFormat(format!(
-            "URL does not appear to be a valid Gentoo mirror. Could not access the releases directory: {releases_url}"
-        )));
+        return Err(MirrorError::NoReleases(releases_url));
+    }
+
+    if let Some(arch) = arch {
+        let autobuilds_url = format!("{releases_url}{arch}/autobuilds/");
+        let autobuilds_response = client
+            .head(&autobuilds_url)
+            .send()
+            .await
+            .map_err(|e| http::request_error(config, e))?;
+        if !autobuilds_response.status().is_success() {
+            return Err(MirrorError::NoAutobuilds {
+                arch: arch.to_string(),
+                url: autobuilds_url,
+            });
+        }
+        say!("{} Autobuilds for {arch} found", Symbol::Success);
+    }
+
+    // Partial mirrors do not necessarily carry the autobuilds
+    match get_mirrors(config, false).await {
+        Ok(mirrors) => {
+            if (Mirrors { mirrors }).find_uri(&url).is_some_and(|info| info.partial) {
+                say!(
+                    "{} This mirror is marked partial in the Gentoo mirror list, it may not carry the stage3 archives",
+                    Symbol::Warning
+                );
+            }
+        }
+        Err(e) => log::debug!("Unable to check whether the mirror is partial: {e}"),
     }
 
-    println!("✅ Mirror URL verified successfully");
+    say!("{} Mirror URL verified successfully", Symbol::Success);
+    Ok(())
+}
+
+/// Check that a mirror answers, without validating its layout
+pub async fn check_mirror_reachable(url: &str, config: &Config) -> Result<(), MirrorError> {
+    http::ensure_online("Checking a mirror")?;
+    let client = http::client_builder(config)?
+        .timeout(std::time::Duration::from_secs(5))
+        .build()?;
+    let response = client.head(url).send().await.map_err(|e| http::request_error(config, e))?;
+    if !response.status().is_success() {
+        return Err(MirrorError::BadStatus {
+            url: url.to_string(),
+            status: response.status(),
+        });
+    }
     Ok(())
 }
 
@@ -51,21 +135,61 @@ pub struct Mirrors {
 }
 
 impl Mirrors {
-    pub async fn fetch() -> Result<Self, DownloaderError> {
-        println!("\n🔄 Retrieving the list of mirror...");
+    /// Mirror list, downloaded again with `refresh` even if the cached copy is recent
+    pub async fn fetch(config: &Config, refresh: bool) -> Result<Self, DownloaderError> {
+        say!("\n{} Retrieving the list of mirror...", Symbol::Refresh);
 
-        let mirrors = match get_mirrors().await {
+        let mirrors = match get_mirrors(config, refresh).await {
             Ok(mirrors) => mirrors,
+            Err(MirrorError::Offline(e)) => return Err(e.into()),
             Err(e) => {
                 return Err(DownloaderError::RetrievingMirror(e.to_string()));
             }
         };
 
-        println!("✅ {} mirror found\n", mirrors.len());
+        say!("{} {} mirror found\n", Symbol::Success, mirrors.len());
 
         Ok(Self { mirrors })
     }
 
+    /// Mirror list fetched and filtered as set in `options`
+    pub async fn load(config: &Config, options: &MirrorListOptions) -> Result<Self, DownloaderError> {
+        let mirrors = Self::fetch(config, options.refresh).await?;
+        let ipv6_only = options.ipv6_only || is_ipv6_only_host();
+        if ipv6_only && !options.ipv6_only {
+            say!("{} No IPv4 route, only the mirrors reachable over IPv6 are listed", Symbol::Info);
+        }
+        Ok(mirrors.filter(ipv6_only, !options.include_partial))
+    }
+
+    /// Mirrors restricted to the URIs reachable over IPv6 and, with
+    /// `exclude_partial`, to the URIs of complete mirrors
+    ///
+    /// Locations left without any URI are dropped.
+    pub fn filter(&self, ipv6_only: bool, exclude_partial: bool) -> Mirrors {
+        let mirrors = self
+            .mirrors
+            .iter()
+            .filter_map(|mirror| {
+                let mut mirror = mirror.clone();
+                mirror
+                    .group
+                    .mirrors
+                    .retain(|info| (!ipv6_only || info.ipv6) && !(exclude_partial && info.partial));
+                (!mirror.group.mirrors.is_empty()).then_some(mirror)
+            })
+            .collect();
+        Mirrors { mirrors }
+    }
+
+    /// URI of the list equal to `url`, ignoring a trailing slash
+    pub fn find_uri(&self, url: &str) -> Option<&UriInfo> {
+        self.mirrors
+            .iter()
+            .flat_map(|mirror| &mirror.group.mirrors)
+            .find(|info| info.uri.trim_end_matches('/') == url.trim_end_matches('/'))
+    }
+
     pub fn get_regions(&self) -> Vec<&str> {
         let mut regions: HashSet<&str> = HashSet::new();
 
@@ -101,32 +225,201 @@ impl Mirrors {
         locations
     }
 
-    pub fn get_uris_info(&self, location: &str) -> Vec<UriInfo> {
-        let uri_infos: Vec<Vec<UriInfo>> = self
-            .mirrors
+    /// URIs of the mirror at a location, which may have left the list since it was shown
+    pub fn get_uris_info(&self, location: &str) -> Result<&[UriInfo], MirrorError> {
+        self.mirrors
             .iter()
-            .filter(|m| m.name.eq(location))
-            .map(|m| m.group.mirrors.clone())
-            .collect();
-        uri_infos[0].clone()
+            .find(|m| m.name.eq(location))
+            .map(|m| m.group.mirrors.as_slice())
+            .ok_or_else(|| MirrorError::LocationNotFound(location.to_string()))
     }
 
-    pub fn get_protocols(&self, location: &str) -> Vec<&str> {
-        let uri_infos = self.get_uris_info(location);
+    pub fn get_protocols(&self, location: &str) -> Result<Vec<&str>, MirrorError> {
+        let uri_infos = self.get_uris_info(location)?;
         let mut protocols: Vec<&str> = uri_infos
             .iter()
             .map(|info| info.protocol.as_str())
             .collect();
         protocols.sort();
-        protocols
+        Ok(protocols)
     }
 
-    pub fn get_url(&self, location: &str, protocol: &str) -> String {
-        let uri_infos = self.get_uris_info(location);
-        let uri_infos_filtered: Vec<&UriInfo> = uri_infos
+    /// Configuration entry for the mirror at a location, with its details
+    pub fn get_entry(&self, location: &str, protocol: &str) -> Result<MirrorEntry, MirrorError> {
+        let country = self
+            .mirrors
             .iter()
-            .filter(|info| info.protocol.eq(&Protocol::from(protocol)))
-            .collect();
-        uri_infos_filtered[0].uri.clone()
+            .find(|m| m.name.eq(location))
+            .map(|m| m.group.country_name.clone());
+
+        Ok(MirrorEntry {
+            url: self.get_url(location, protocol)?,
+            label: Some(location.to_string()),
+            country,
+            protocol: Some(protocol.to_string()),
+            priority: None,
+        })
+    }
+
+    /// Configuration entries for several locations, each over the first of `preferred` it offers
+    pub fn get_entries(&self, locations: &[&str], preferred: &[Protocol]) -> Result<Vec<MirrorEntry>, MirrorError> {
+        locations
+            .iter()
+            .map(|location| {
+                let uri_infos = self.get_uris_info(location)?;
+                let protocol = preferred
+                    .iter()
+                    .find(|protocol| uri_infos.iter().any(|info| info.protocol.eq(*protocol)))
+                    .ok_or_else(|| MirrorError::ProtocolNotAvailable {
+                        location: location.to_string(),
+                        protocol: preferred.iter().map(Protocol::as_str).collect::<Vec<_>>().join(" or "),
+                    })?;
+                self.get_entry(location, protocol.as_str())
+            })
+            .collect()
+    }
+
+    pub fn get_url(&self, location: &str, protocol: &str) -> Result<String, MirrorError> {
+        self.get_uris_info(location)?
+            .iter()
+            .find(|info| info.protocol.eq(&Protocol::from(protocol)))
+            .map(|info| info.uri.clone())
+            .ok_or_else(|| MirrorError::ProtocolNotAvailable {
+                location: location.to_string(),
+                protocol: protocol.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parser::MirrorGroup;
+    use super::*;
+
+    fn uri(protocol: Protocol, uri: &str) -> UriInfo {
+        UriInfo {
+            protocol,
+            ipv4: true,
+            ipv6: false,
+            partial: false,
+            uri: uri.to_string(),
+        }
+    }
+
+    fn mirror(name: &str, country_name: &str, uris: Vec<UriInfo>) -> Mirror {
+        Mirror {
+            name: name.to_string(),
+            group: MirrorGroup {
+                name: name.to_string(),
+                region: "Europe".to_string(),
+                country_code: country_name[..2].to_uppercase(),
+                country_name: country_name.to_string(),
+                mirrors: uris,
+            },
+        }
+    }
+
+    /// Mirrors of the list as it was when the locations were shown
+    fn mirrors() -> Mirrors {
+        Mirrors {
+            mirrors: vec![
+                mirror(
+                    "SUNET",
+                    "Sweden",
+                    vec![
+                        uri(Protocol::Http, "http://ftp.sunet.se/mirror/gentoo/"),
+                        uri(Protocol::Https, "https://ftp.sunet.se/mirror/gentoo/"),
+                        uri(Protocol::Rsync, "rsync://ftp.sunet.se/mirror/gentoo/"),
+                    ],
+                ),
+                mirror(
+                    "Leaseweb",
+                    "Netherlands",
+                    vec![uri(Protocol::Http, "http://mirror.leaseweb.com/gentoo/")],
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn url_of_a_listed_location() {
+        let mirrors = mirrors();
+        assert_eq!(
+            mirrors.get_url("SUNET", "https").unwrap(),
+            "https://ftp.sunet.se/mirror/gentoo/"
+        );
+        assert_eq!(
+            mirrors.get_url("SUNET", "HTTP").unwrap(),
+            "http://ftp.sunet.se/mirror/gentoo/"
+        );
+        assert_eq!(mirrors.get_protocols("SUNET").unwrap(), ["http", "https", "rsync"]);
+        assert_eq!(mirrors.get_uris_info("Leaseweb").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn unknown_location_is_an_error() {
+        let mirrors = mirrors();
+        assert!(matches!(
+            mirrors.get_uris_info("Gone"),
+            Err(MirrorError::LocationNotFound(location)) if location == "Gone"
+        ));
+        assert!(matches!(
+            mirrors.get_url("Gone", "https"),
+            Err(MirrorError::LocationNotFound(_))
+        ));
+        assert!(matches!(
+            mirrors.get_protocols("Gone"),
+            Err(MirrorError::LocationNotFound(_))
+        ));
+        assert!(matches!(
+            mirrors.get_entry("Gone", "https"),
+            Err(MirrorError::LocationNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_protocol_is_an_error() {
+        let mirrors = mirrors();
+        assert!(matches!(
+            mirrors.get_url("Leaseweb", "https"),
+            Err(MirrorError::ProtocolNotAvailable { location, protocol }) if location == "Leaseweb" && protocol == "https"
+        ));
+        assert!(matches!(
+            mirrors.get_url("SUNET", "gopher"),
+            Err(MirrorError::ProtocolNotAvailable { .. })
+        ));
+    }
+
+    #[test]
+    fn entries_use_the_first_preferred_protocol_of_each_location() {
+        let entries = mirrors()
+            .get_entries(&["SUNET", "Leaseweb"], PREFERRED_PROTOCOLS)
+            .unwrap();
+        let urls: Vec<&str> = entries.iter().map(|entry| entry.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://ftp.sunet.se/mirror/gentoo/",
+                "http://mirror.leaseweb.com/gentoo/"
+            ]
+        );
+        assert_eq!(entries[0].protocol.as_deref(), Some("https"));
+        assert_eq!(entries[1].protocol.as_deref(), Some("http"));
+        assert_eq!(entries[1].label.as_deref(), Some("Leaseweb"));
+        assert_eq!(entries[1].country.as_deref(), Some("Netherlands"));
+        assert!(mirrors().get_entries(&[], PREFERRED_PROTOCOLS).unwrap().is_empty());
+    }
+
+    #[test]
+    fn entries_fail_when_a_location_is_unusable() {
+        let mirrors = mirrors();
+        assert!(matches!(
+            mirrors.get_entries(&["SUNET", "Gone"], PREFERRED_PROTOCOLS),
+            Err(MirrorError::LocationNotFound(location)) if location == "Gone"
+        ));
+        assert!(matches!(
+            mirrors.get_entries(&["Leaseweb"], &[Protocol::Https, Protocol::Ftp]),
+            Err(MirrorError::ProtocolNotAvailable { protocol, .. }) if protocol == "https or ftp"
+        ));
     }
 }
@@ -1,9 +1,17 @@
-use crate::error::MirrorError;
+use crate::config::Config;
+use crate::error::{MirrorError, OfflineError};
+use crate::http;
 use log::{debug, info, warn};
+use std::fs;
 use std::io::Cursor;
+use std::path::Path;
+use std::time::SystemTime;
+use crate::say;
+use crate::ui::symbols::Symbol;
 use xml::reader::{EventReader, XmlEvent};
 
-const MIRRORS_URL: &str = "https://api.gentoo.org/mirrors/distfiles.xml";
+/// Default source of the mirror list, see `mirrorlist_url` in the configuration
+pub const MIRRORS_URL: &str = "https://api.gentoo.org/mirrors/distfiles.xml";
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Protocol {
@@ -84,6 +92,21 @@ impl MirrorGroup {
 }
 
 impl UriInfo {
+    /// Address families of the URI and whether it is partial, e.g. "v4+v6"
+    pub fn connectivity(&self) -> String {
+        let families = match (self.ipv4, self.ipv6) {
+            (true, true) => "v4+v6",
+            (true, false) => "v4",
+            (false, true) => "v6",
+            (false, false) => "no address",
+        };
+        if self.partial {
+            format!("{families}, partial")
+        } else {
+            families.to_string()
+        }
+    }
+
     fn new() -> Self {
         UriInfo {
             protocol: Protocol::Unknown,
@@ -179,11 +202,9 @@ fn parse_mirrors_xml(data: &[u8]) -> Result<Vec<Mirror>, MirrorError> {
                         // Copy current group information
                         current_mirror.group = current_group.clone();
                     }
-                    "name" => {
-                        if in_mirror {
-                            debug!("Start of a name element for the mirror");
-                            in_name = true;
-                        }
+                    "name" if in_mirror => {
+                        debug!("Start of a name element for the mirror");
+                        in_name = true;
                     }
                     "uri" => {
                         if !in_mirror {
@@ -219,75 +240,67 @@ fn parse_mirrors_xml(data: &[u8]) -> Result<Vec<Mirror>, MirrorError> {
                 let element_name = name.local_name;
 
                 match element_name.as_str() {
-                    "mirrorgroup" => {
-                        if in_mirrorgroup {
-                            debug!(
-                                "End of the mirrorgroup: region='{}', country_code='{}', country_name='{}'",
-                                current_group.region,
-                                current_group.country_code,
-                                current_group.country_name
-                            );
-                            in_mirrorgroup = false;
-                        }
+                    "mirrorgroup" if in_mirrorgroup => {
+                        debug!(
+                            "End of the mirrorgroup: region='{}', country_code='{}', country_name='{}'",
+                            current_group.region,
+                            current_group.country_code,
+                            current_group.country_name
+                        );
+                        in_mirrorgroup = false;
                     }
-                    "mirror" => {
-                        if in_mirror {
-                            debug!(
-                                "End of mirror: '{}' with {} URIs",
-                                current_mirror.name,
-                                current_mirror.group.mirrors.len()
-                            );
+                    "mirror" if in_mirror => {
+                        debug!(
+                            "End of mirror: '{}' with {} URIs",
+                            current_mirror.name,
+                            current_mirror.group.mirrors.len()
+                        );
 
-                            // Set the group name if necessary
-                            current_mirror.group.name = current_mirror.name.clone();
-
-                            // Accept mirrors that have at least URIs
-                            if !current_mirror.group.mirrors.is_empty() {
-                                // Si le nom est vide, utiliser la première URI comme nom
-                                if current_mirror.name.is_empty() {
-                                    current_mirror.name =
-                                        current_mirror.group.mirrors[0].uri.clone();
-                                    current_mirror.group.name = current_mirror.name.clone();
-                                    warn!(
-                                        "Missing mirror name, using URI : {}",
-                                        current_mirror.name
-                                    );
-                                }
-                                mirrors.push(current_mirror.clone());
-                                debug!("Mirror added : {}", current_mirror.name);
-                            } else {
-                                warn!("Mirror ignored because no URI : {}", current_mirror.name);
+                        // Set the group name if necessary
+                        current_mirror.group.name = current_mirror.name.clone();
+
+                        // Accept mirrors that have at least URIs
+                        if !current_mirror.group.mirrors.is_empty() {
+                            // Si le nom est vide, utiliser la première URI comme nom
+                            if current_mirror.name.is_empty() {
+                                current_mirror.name =
+                                    current_mirror.group.mirrors[0].uri.clone();
+                                current_mirror.group.name = current_mirror.name.clone();
+                                warn!(
+                                    "Missing mirror name, using URI : {}",
+                                    current_mirror.name
+                                );
                             }
-                            current_mirror = Mirror::new();
-                            in_mirror = false;
+                            mirrors.push(current_mirror.clone());
+                            debug!("Mirror added : {}", current_mirror.name);
+                        } else {
+                            warn!("Mirror ignored because no URI : {}", current_mirror.name);
                         }
+                        current_mirror = Mirror::new();
+                        in_mirror = false;
                     }
-                    "name" => {
-                        if in_name {
-                            let name_text = current_text.trim();
-                            debug!("Mirror name found: '{name_text}'");
-                            if !name_text.is_empty() {
-                                current_mirror.name = name_text.to_string();
-                            }
-                            in_name = false;
+                    "name" if in_name => {
+                        let name_text = current_text.trim();
+                        debug!("Mirror name found: '{name_text}'");
+                        if !name_text.is_empty() {
+                            current_mirror.name = name_text.to_string();
                         }
+                        in_name = false;
                     }
-                    "uri" => {
-                        if in_uri {
-                            let uri_text = current_text.trim();
-                            debug!("End of URI : '{uri_text}'");
-
-                            if !uri_text.is_empty() {
-                                current_uri.uri = uri_text.to_string();
-                                // Directly add the URI to the current mirror group
-                                current_mirror.group.mirrors.push(current_uri.clone());
-                                debug!("URI added : {uri_text}");
-                            } else {
-                                warn!("Empty URI ignored");
-                            }
-                            current_uri = UriInfo::new();
-                            in_uri = false;
+                    "uri" if in_uri => {
+                        let uri_text = current_text.trim();
+                        debug!("End of URI : '{uri_text}'");
+
+                        if !uri_text.is_empty() {
+                            current_uri.uri = uri_text.to_string();
+                            // Directly add the URI to the current mirror group
+                            current_mirror.group.mirrors.push(current_uri.clone());
+                            debug!("URI added : {uri_text}");
+                        } else {
+                            warn!("Empty URI ignored");
                         }
+                        current_uri = UriInfo::new();
+                        in_uri = false;
                     }
                     _ => {}
                 }
@@ -308,16 +321,90 @@ fn parse_mirrors_xml(data: &[u8]) -> Result<Vec<Mirror>, MirrorError> {
     Ok(mirrors)
 }
 
-pub async fn get_mirrors() -> Result<Vec<Mirror>, MirrorError> {
-    info!("Data recovery from {MIRRORS_URL}");
+/// Download the raw mirror list
+async fn download_mirror_list(config: &Config) -> Result<Vec<u8>, MirrorError> {
+    let url = config.mirrorlist_url.as_str();
+    info!("Data recovery from {url}");
 
-    let client = reqwest::Client::builder()
+    let client = http::client_builder(config)?
         .timeout(std::time::Duration::from_secs(30))
         .build()?;
-    let response = client.get(MIRRORS_URL).send().await?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| http::request_error(config, e))?;
     let data = response.bytes().await?;
 
     info!("Data received: {} bytes", data.len());
 
-    parse_mirrors_xml(&data)
+    Ok(data.to_vec())
+}
+
+/// Content and modification time of the cached mirror list, if any
+fn read_cached_list(path: &Path) -> Option<(Vec<u8>, SystemTime)> {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+    let data = fs::read(path).ok()?;
+    Some((data, modified))
+}
+
+fn write_cached_list(path: &Path, data: &[u8]) -> Result<(), std::io::Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data)
+}
+
+/// Get the mirror list, from the cache while it is younger than the configured TTL
+///
+/// With `refresh`, or once the cache is stale, the list is downloaded and
+/// the cache replaced. When the download fails, the cached list is used
+/// whatever its age. Offline, only the cache is read.
+pub async fn get_mirrors(config: &Config, refresh: bool) -> Result<Vec<Mirror>, MirrorError> {
+    let cache_path = config.mirror_list_cache_path();
+    let cached = read_cached_list(&cache_path);
+
+    if http::is_offline() {
+        let Some((data, _)) = cached else {
+            return Err(OfflineError::NoCachedMirrorList(cache_path).into());
+        };
+        if refresh {
+            say!("{} Offline, the cached mirror list is used as is", Symbol::Warning);
+        }
+        info!("Using the mirror list cached in {}", cache_path.display());
+        return parse_mirrors_xml(&data);
+    }
+
+    if let (false, Some((data, modified))) = (refresh, &cached) {
+        let age = modified.elapsed().unwrap_or_default();
+        if age < config.mirror_list_ttl() {
+            match parse_mirrors_xml(data) {
+                Ok(mirrors) => {
+                    info!("Using the mirror list cached in {}", cache_path.display());
+                    return Ok(mirrors);
+                }
+                Err(e) => warn!("Ignoring the cached mirror list {}: {e}", cache_path.display()),
+            }
+        }
+    }
+
+    let downloaded = match download_mirror_list(config).await {
+        Ok(data) => parse_mirrors_xml(&data).map(|mirrors| (mirrors, data)),
+        Err(e) => Err(e),
+    };
+    match downloaded {
+        Ok((mirrors, data)) => {
+            if let Err(e) = write_cached_list(&cache_path, &data) {
+                warn!("Unable to cache the mirror list in {}: {e}", cache_path.display());
+            }
+            Ok(mirrors)
+        }
+        Err(e) => {
+            let Some(mirrors) = cached.and_then(|(data, _)| parse_mirrors_xml(&data).ok()) else {
+                return Err(e);
+            };
+            say!("{} Unable to download the mirror list ({e}), using the cached copy", Symbol::Warning);
+            Ok(mirrors)
+        }
+    }
 }
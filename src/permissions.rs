@@ -0,0 +1,81 @@
+//! Explicit modes for the files and directories created by chrootmanager
+//!
+//! The modes are applied after creation so that neither a permissive nor a
+//! restrictive umask changes them: configuration and state are private to
+//! the user, while the cache and chroot directories must stay readable by
+//! the root-run extraction.
+
+use std::fs::{self, DirBuilder, OpenOptions, Permissions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+/// Cache, chroot base and configuration directories
+pub const SHARED_DIR_MODE: u32 = 0o755;
+/// State directory
+pub const PRIVATE_DIR_MODE: u32 = 0o700;
+/// Configuration, state and audit files
+pub const PRIVATE_FILE_MODE: u32 = 0o600;
+/// Cached stage3 archives and their checksums
+pub const SHARED_FILE_MODE: u32 = 0o644;
+
+/// Create a directory and its missing parents, giving the directory itself `mode`
+///
+/// The mode of an existing directory is left unchanged.
+pub fn create_dir_all(path: &Path, mode: u32) -> io::Result<()> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    DirBuilder::new().recursive(true).mode(mode).create(path)?;
+    fs::set_permissions(path, Permissions::from_mode(mode))
+}
+
+/// Write a file, creating or truncating it, and give it `mode`
+pub fn write_file(path: &Path, contents: impl AsRef<[u8]>, mode: u32) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(path)?;
+    file.set_permissions(Permissions::from_mode(mode))?;
+    file.write_all(contents.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn created_directories_get_the_requested_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("a/b/state");
+        create_dir_all(&state, PRIVATE_DIR_MODE).unwrap();
+        assert_eq!(mode(&state), PRIVATE_DIR_MODE);
+
+        // An existing directory is left as it is
+        fs::set_permissions(&state, Permissions::from_mode(0o750)).unwrap();
+        create_dir_all(&state, SHARED_DIR_MODE).unwrap();
+        assert_eq!(mode(&state), 0o750);
+    }
+
+    #[test]
+    fn written_files_get_the_requested_mode_even_when_they_existed() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.toml");
+        fs::write(&config, "old").unwrap();
+        fs::set_permissions(&config, Permissions::from_mode(0o666)).unwrap();
+
+        write_file(&config, "new", PRIVATE_FILE_MODE).unwrap();
+        assert_eq!(mode(&config), PRIVATE_FILE_MODE);
+        assert_eq!(fs::read_to_string(&config).unwrap(), "new");
+
+        let stage3 = dir.path().join("stage3.tar.xz");
+        write_file(&stage3, "", SHARED_FILE_MODE).unwrap();
+        assert_eq!(mode(&stage3), SHARED_FILE_MODE);
+    }
+}
@@ -0,0 +1,218 @@
+//! Platform support
+//!
+//! Chroots rely on Linux mounts (proc, sysfs, devtmpfs bind mounts) and on
+//! `/proc/self/mounts`, so privileged operations are refused on any other
+//! system. The crate still builds on other unix systems, and the commands
+//! that only read the configuration or talk to the mirrors keep working.
+
+use crate::error::ChrootError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Whether chroot operations are supported on this system
+pub fn is_supported() -> bool {
+    cfg!(target_os = "linux")
+}
+
+/// Refuse chroot operations outside of Linux
+pub fn ensure_supported() -> Result<(), ChrootError> {
+    if is_supported() {
+        Ok(())
+    } else {
+        Err(ChrootError::UnsupportedPlatform(std::env::consts::OS))
+    }
+}
+
+/// Gentoo architecture name of a machine name, as printed by `uname -m`
+/// or found in [`std::env::consts::ARCH`]
+pub fn gentoo_architecture(machine: &str) -> Option<&'static str> {
+    let architecture = match machine {
+        "x86_64" | "amd64" => "amd64",
+        "i386" | "i486" | "i586" | "i686" | "x86" => "x86",
+        "aarch64" | "arm64" => "arm64",
+        machine if machine.starts_with("arm") => "arm",
+        "ppc64" | "ppc64le" | "powerpc64" => "ppc64",
+        "ppc" | "powerpc" => "ppc",
+        "riscv64" | "riscv32" => "riscv",
+        "s390x" | "s390" => "s390",
+        "sparc64" | "sparc" => "sparc",
+        "mips" | "mips64" | "mipsel" | "mips64el" => "mips",
+        "alpha" => "alpha",
+        "parisc" | "parisc64" => "hppa",
+        "ia64" => "ia64",
+        "loongarch64" => "loong",
+        _ => return None,
+    };
+    Some(architecture)
+}
+
+/// Gentoo architecture of the running kernel
+///
+/// `uname -m` is asked first, a 32-bit build can run on a 64-bit kernel;
+/// the architecture the binary was built for is used when it fails.
+pub fn host_architecture() -> Option<&'static str> {
+    let machine = Command::new("uname")
+        .arg("-m")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    machine
+        .as_deref()
+        .and_then(gentoo_architecture)
+        .or_else(|| gentoo_architecture(std::env::consts::ARCH))
+}
+
+/// Whether binaries of `arch` run on the host without emulation
+///
+/// An unknown host is given the benefit of the doubt.
+pub fn runs_natively(arch: &str) -> bool {
+    match host_architecture() {
+        Some(host) => host == arch || (host == "amd64" && arch == "x86"),
+        None => true,
+    }
+}
+
+/// Directory where the kernel lists the binfmt_misc handlers
+const BINFMT_MISC_DIR: &str = "/proc/sys/fs/binfmt_misc";
+
+/// qemu-user target emulating a Gentoo architecture, as in `qemu-<target>`
+///
+/// Little-endian variants, such as ppc64le, are registered under their own
+/// name and are not covered.
+pub fn qemu_target(arch: &str) -> Option<&'static str> {
+    let target = match arch {
+        "amd64" => "x86_64",
+        "x86" => "i386",
+        "arm64" => "aarch64",
+        "arm" => "arm",
+        "ppc64" => "ppc64",
+        "ppc" => "ppc",
+        "riscv" => "riscv64",
+        "s390" => "s390x",
+        "sparc" => "sparc64",
+        "mips" => "mips",
+        "alpha" => "alpha",
+        "hppa" => "hppa",
+        "m68k" => "m68k",
+        "loong" => "loongarch64",
+        _ => return None,
+    };
+    Some(target)
+}
+
+/// An enabled binfmt_misc handler
+#[derive(Debug, Clone)]
+pub struct BinfmtHandler {
+    /// Emulator run for the matching binaries, as seen from the host
+    pub interpreter: PathBuf,
+    /// Whether the kernel opened the interpreter at registration (`F` flag),
+    /// it then does not need to exist inside the chroot
+    pub fix_binary: bool,
+}
+
+/// Parse a binfmt_misc entry, `None` when it is disabled
+fn parse_binfmt_entry(content: &str) -> Option<BinfmtHandler> {
+    let mut enabled = false;
+    let mut interpreter = None;
+    let mut fix_binary = false;
+    for line in content.lines() {
+        match line.split_once(' ') {
+            Some(("interpreter", path)) => interpreter = Some(PathBuf::from(path.trim())),
+            Some(("flags:", flags)) => fix_binary = flags.contains('F'),
+            _ if line.trim() == "enabled" => enabled = true,
+            _ => {}
+        }
+    }
+    interpreter
+        .filter(|_| enabled)
+        .map(|interpreter| BinfmtHandler { interpreter, fix_binary })
+}
+
+/// Enabled binfmt_misc handler of a qemu-user target
+///
+/// Both the `qemu-<target>` name of the qemu and systemd registrations and
+/// the `qemu-<target>-static` name of the Debian ones are looked up.
+pub fn binfmt_handler(target: &str) -> Option<BinfmtHandler> {
+    [format!("qemu-{target}"), format!("qemu-{target}-static")]
+        .iter()
+        .find_map(|name| {
+            let content = fs::read_to_string(Path::new(BINFMT_MISC_DIR).join(name)).ok()?;
+            parse_binfmt_entry(&content)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gentoo_architectures_of_machine_names() {
+        for (machine, architecture) in [
+            ("x86_64", "amd64"),
+            ("amd64", "amd64"),
+            ("i686", "x86"),
+            ("aarch64", "arm64"),
+            ("arm64", "arm64"),
+            ("armv7l", "arm"),
+            ("riscv64", "riscv"),
+            ("ppc64le", "ppc64"),
+            ("ppc64", "ppc64"),
+            ("s390x", "s390"),
+            ("loongarch64", "loong"),
+        ] {
+            assert_eq!(gentoo_architecture(machine), Some(architecture), "{machine}");
+        }
+        assert_eq!(gentoo_architecture("vax"), None);
+        assert_eq!(gentoo_architecture(""), None);
+    }
+
+    #[test]
+    fn qemu_targets_map_back_to_their_architecture() {
+        for (arch, target) in [
+            ("amd64", "x86_64"),
+            ("x86", "i386"),
+            ("arm64", "aarch64"),
+            ("riscv", "riscv64"),
+            ("ppc64", "ppc64"),
+            ("s390", "s390x"),
+            ("loong", "loongarch64"),
+        ] {
+            assert_eq!(qemu_target(arch), Some(target), "{arch}");
+            assert_eq!(gentoo_architecture(target), Some(arch), "{target}");
+        }
+        // Machine names are not architectures
+        assert_eq!(qemu_target("aarch64"), None);
+        assert_eq!(qemu_target("x86_64"), None);
+    }
+
+    #[test]
+    fn native_architectures() {
+        let host = host_architecture().expect("the test host has a known architecture");
+        assert!(runs_natively(host));
+        assert!(!runs_natively(if host == "arm64" { "riscv" } else { "arm64" }));
+        assert_eq!(runs_natively("x86"), host == "amd64" || host == "x86");
+    }
+
+    #[test]
+    fn parses_binfmt_entries() {
+        let qemu = "enabled\ninterpreter /usr/bin/qemu-aarch64\nflags: POCF\noffset 0\n\
+                    magic 7f454c460201010000000000000000000200b700\n\
+                    mask ffffffffffffff00fffffffffffffffffeffffff\n";
+        let handler = parse_binfmt_entry(qemu).unwrap();
+        assert_eq!(handler.interpreter, Path::new("/usr/bin/qemu-aarch64"));
+        assert!(handler.fix_binary);
+
+        let debian = "enabled\ninterpreter /usr/libexec/qemu-binfmt/aarch64-binfmt-P\nflags: P\noffset 0\n";
+        let handler = parse_binfmt_entry(debian).unwrap();
+        assert_eq!(
+            handler.interpreter,
+            Path::new("/usr/libexec/qemu-binfmt/aarch64-binfmt-P")
+        );
+        assert!(!handler.fix_binary);
+
+        assert!(parse_binfmt_entry(&qemu.replacen("enabled", "disabled", 1)).is_none());
+        assert!(parse_binfmt_entry("enabled\nflags: F\n").is_none());
+    }
+}
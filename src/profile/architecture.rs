@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 /// Represents an architecture with its available profiles
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Architecture {
     /// Architecture name (e.g., "amd64", "arm64")
     pub name: String,
@@ -0,0 +1,107 @@
+//! Grouping of profiles by variant family, for menus
+
+use std::fmt;
+
+/// Variant family of a stage3 profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProfileFamily {
+    OpenRc,
+    Systemd,
+    Musl,
+    Hardened,
+    Llvm,
+    Other,
+}
+
+impl ProfileFamily {
+    /// Short label used as a menu prefix
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProfileFamily::OpenRc => "openrc",
+            ProfileFamily::Systemd => "systemd",
+            ProfileFamily::Musl => "musl",
+            ProfileFamily::Hardened => "hardened",
+            ProfileFamily::Llvm => "llvm",
+            ProfileFamily::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ProfileFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// Classify a profile name such as "desktop-openrc" or "musl-hardened"
+///
+/// The libc and toolchain variants take precedence over the init system, so
+/// "musl-llvm" is a musl profile and "hardened-systemd" a hardened one.
+pub fn classify_profile(name: &str) -> ProfileFamily {
+    let parts: Vec<&str> = name.split('-').collect();
+    let has = |part: &str| parts.contains(&part);
+
+    if has("musl") {
+        ProfileFamily::Musl
+    } else if has("hardened") {
+        ProfileFamily::Hardened
+    } else if has("llvm") {
+        ProfileFamily::Llvm
+    } else if has("systemd") {
+        ProfileFamily::Systemd
+    } else if has("openrc") {
+        ProfileFamily::OpenRc
+    } else {
+        ProfileFamily::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_known_profile_has_its_family() {
+        let known = [
+            // amd64
+            ("desktop-openrc", ProfileFamily::OpenRc),
+            ("desktop-systemd", ProfileFamily::Systemd),
+            ("hardened-selinux-openrc", ProfileFamily::Hardened),
+            ("hardened-openrc", ProfileFamily::Hardened),
+            ("hardened-systemd", ProfileFamily::Hardened),
+            ("llvm-openrc", ProfileFamily::Llvm),
+            ("llvm-systemd", ProfileFamily::Llvm),
+            ("musl-hardened", ProfileFamily::Musl),
+            ("musl-llvm", ProfileFamily::Musl),
+            ("musl", ProfileFamily::Musl),
+            ("no-multilib-openrc", ProfileFamily::OpenRc),
+            ("no-multilib-systemd", ProfileFamily::Systemd),
+            ("openrc-splitusr", ProfileFamily::OpenRc),
+            ("openrc", ProfileFamily::OpenRc),
+            ("systemd", ProfileFamily::Systemd),
+            ("x32-openrc", ProfileFamily::OpenRc),
+            ("x32-systemd", ProfileFamily::Systemd),
+            // arm64
+            ("aarch64be-openrc", ProfileFamily::OpenRc),
+            ("aarch64be-systemd", ProfileFamily::Systemd),
+            ("systemd-splitusr", ProfileFamily::Systemd),
+            // No init system in the name
+            ("nomultilib", ProfileFamily::Other),
+            ("minimal", ProfileFamily::Other),
+        ];
+        for (profile, family) in known {
+            assert_eq!(classify_profile(profile), family, "{profile}");
+        }
+    }
+
+    #[test]
+    fn families_sort_in_menu_order() {
+        let mut grouped: Vec<(ProfileFamily, &str)> = ["musl", "minimal", "systemd", "llvm-openrc", "openrc"]
+            .into_iter()
+            .map(|profile| (classify_profile(profile), profile))
+            .collect();
+        grouped.sort();
+        let labels: Vec<String> = grouped.iter().map(|(family, profile)| format!("{family} {profile}")).collect();
+        assert_eq!(labels, ["openrc openrc", "systemd systemd", "musl musl", "llvm llvm-openrc", "other minimal"]);
+    }
+}
@@ -1,22 +1,40 @@
 use crate::error::DownloaderError;
-use crate::profile::{architecture::Architecture, parser};
+use crate::profile::parser::{self, ProfileSource};
+use crate::profile::architecture::Architecture;
 use std::collections::HashMap;
 
 /// Profile manager that discovers available architectures and profiles from mirrors
 #[derive(Debug)]
 pub struct ProfileManager {
     architectures: HashMap<String, Architecture>,
+    source: ProfileSource,
 }
 
 impl ProfileManager {
     /// Create a new profile manager by discovering profiles from configured mirrors
-    pub async fn discover(config: &crate::config::Config) -> Result<Self, DownloaderError> {
-        let parser = parser::ProfileParser::new();
+    ///
+    /// A recent discovery is reused from the profile cache, unless `refresh` is set.
+    pub async fn discover(config: &crate::config::Config, refresh: bool) -> Result<Self, DownloaderError> {
+        let parser = parser::ProfileParser::new(config);
 
         // Use configured mirrors
-        let architectures = parser.discover_profiles_from_config_mirrors(config).await?;
+        let (architectures, source) = parser.discover_profiles_from_config_mirrors(config, refresh).await?;
 
-        Ok(Self { architectures })
+        Ok(Self { architectures, source })
+    }
+
+    /// Manager over a fixed set of architectures
+    #[cfg(test)]
+    pub(crate) fn with_architectures(architectures: Vec<Architecture>) -> Self {
+        Self {
+            architectures: architectures.into_iter().map(|arch| (arch.name.clone(), arch)).collect(),
+            source: ProfileSource::Fallback,
+        }
+    }
+
+    /// Where the architectures and profiles come from
+    pub fn source(&self) -> &ProfileSource {
+        &self.source
     }
 
     /// Get all available architecture names
@@ -3,9 +3,10 @@
 //! This module provides functionality to discover and manage different architecture
 //! profiles available on Gentoo mirrors without hardcoded enums.
 
-use crate::profile::architecture::Architecture;
+pub use crate::profile::architecture::Architecture;
 
 pub mod parser;
+pub mod family;
 mod architecture;
 pub(crate) mod manager;
 pub(crate) mod selected;
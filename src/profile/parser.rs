@@ -1,46 +1,238 @@
 //! Profile parser module for discovering available profiles from Gentoo mirrors
+//!
+//! The last successful discovery is cached, for `--offline` and for when
+//! no configured mirror answers.
 
+use crate::config::Config;
 use crate::error::DownloaderError;
+use crate::http;
 use crate::profile::Architecture;
 use log::{debug, info, warn};
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, StreamExt};
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::ui::symbols::Symbol;
+
+/// Autobuilds pages of a mirror fetched at the same time during discovery
+const CONCURRENT_ARCH_REQUESTS: usize = 6;
+
+/// Names of the directories linked from a mirror index page, sorted
+///
+/// Every `a[href]` is read, whatever the server formats the page like.
+/// Relative, absolute and full URL links are reduced to their last path
+/// segment; links to files and to the parent directory are left out.
+pub fn index_directories(html: &str) -> Vec<String> {
+    let document = Html::parse_document(html);
+    let Ok(anchors) = Selector::parse("a[href]") else {
+        return Vec::new();
+    };
+
+    let mut directories: Vec<String> = document
+        .select(&anchors)
+        // Apache links its parent directory by absolute path
+        .filter(|anchor| anchor.text().collect::<String>().trim() != "Parent Directory")
+        .filter_map(|anchor| anchor.value().attr("href"))
+        .filter_map(|href| {
+            let path = href.split(['?', '#']).next()?.trim();
+            let name = path.strip_suffix('/')?.rsplit('/').next()?;
+            (!name.is_empty() && name != "." && name != "..").then(|| name.to_string())
+        })
+        .collect();
+    directories.sort();
+    directories.dedup();
+    directories
+}
+
+/// Check if a string is a known Gentoo architecture name
+pub fn is_known_architecture(name: &str) -> bool {
+    matches!(
+        name,
+        "amd64"
+            | "arm64"
+            | "arm"
+            | "x86"
+            | "ppc64"
+            | "ppc"
+            | "sparc"
+            | "alpha"
+            | "hppa"
+            | "ia64"
+            | "mips"
+            | "riscv"
+            | "s390"
+    )
+}
+
+/// Where discovered architectures and profiles come from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProfileSource {
+    /// Crawled from the mirror
+    Mirror { mirror: String },
+    /// Read from the profile cache, crawled from the mirror at that time
+    Cache { mirror: String, discovered_at: DateTime<Utc> },
+    /// Hardcoded list, when no mirror could be crawled
+    Fallback,
+}
+
+impl std::fmt::Display for ProfileSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileSource::Mirror { mirror } => write!(f, "discovered on {mirror}"),
+            ProfileSource::Cache { mirror, discovered_at } => write!(
+                f,
+                "cached, discovered on {mirror} at {}",
+                discovered_at.format("%Y-%m-%d %H:%M UTC")
+            ),
+            ProfileSource::Fallback => write!(f, "built-in list, no mirror could be crawled"),
+        }
+    }
+}
+
+/// Discovered architectures, as written to the profile cache
+///
+/// The mirrors and the architecture filter of the discovery are kept, a
+/// cache made with other settings is not used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileCache {
+    /// Mirror the profiles were discovered from
+    pub mirror: String,
+    /// Configured mirrors at the time, in order
+    pub mirrors: Vec<String>,
+    /// `discover_architectures` at the time
+    pub discover_architectures: Option<Vec<String>>,
+    pub discovered_at: DateTime<Utc>,
+    pub architectures: HashMap<String, Architecture>,
+}
+
+impl ProfileCache {
+    fn new(config: &Config, mirror: &str, architectures: HashMap<String, Architecture>) -> Self {
+        Self {
+            mirror: mirror.to_string(),
+            mirrors: config.mirror_urls().map(str::to_string).collect(),
+            discover_architectures: config.discover_architectures.clone(),
+            discovered_at: Utc::now(),
+            architectures,
+        }
+    }
+
+    /// Read the cache, `None` when it is missing or unreadable
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                warn!("Ignoring the profile cache {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Whether the cache was made with the current mirrors and architecture filter
+    pub fn matches(&self, config: &Config) -> bool {
+        self.mirrors.iter().map(String::as_str).eq(config.mirror_urls())
+            && self.discover_architectures == config.discover_architectures
+    }
+
+    fn source(&self) -> ProfileSource {
+        ProfileSource::Cache {
+            mirror: self.mirror.clone(),
+            discovered_at: self.discovered_at,
+        }
+    }
+
+    /// Whether the cache is younger than the configured TTL
+    pub fn is_fresh(&self, config: &Config) -> bool {
+        let age = Utc::now().signed_duration_since(self.discovered_at);
+        age.to_std().is_ok_and(|age| age < config.profile_cache_ttl())
+    }
+}
 
 /// Parser for discovering profiles from Gentoo mirrors
 pub struct ProfileParser {
     client: reqwest::Client,
+    /// Proxy in use, to report its connection failures
+    proxy: Option<String>,
 }
 
 impl ProfileParser {
-    /// Create a new profile parser
-    pub fn new() -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
+    /// Create a new profile parser, with the proxy settings of the configuration
+    pub fn new(config: &Config) -> Self {
+        let client = http::client_builder(config)
+            .and_then(|builder| builder.timeout(std::time::Duration::from_secs(30)).build())
             .unwrap_or_default();
 
-        Self { client }
+        Self {
+            client,
+            proxy: http::active_proxy(config),
+        }
     }
 
     /// Discover profiles using only the configured mirrors
+    ///
+    /// The profile cache is used while it is fresh and made from the same
+    /// mirrors, unless `refresh` is set. Offline, or when no configured
+    /// mirror answers, the cache is used whatever its age, and the
+    /// hardcoded profiles without any.
     pub async fn discover_profiles_from_config_mirrors(
         &self,
         config: &crate::config::Config,
-    ) -> Result<HashMap<String, Architecture>, DownloaderError> {
-        info!("🔍 Discovering available profiles from configured mirrors...");
+        refresh: bool,
+    ) -> Result<(HashMap<String, Architecture>, ProfileSource), DownloaderError> {
+        info!("{} Discovering available profiles from configured mirrors...", Symbol::Search);
         debug!("Config has_mirrors: {}", config.has_mirrors());
         debug!("Number of configured mirrors: {}", config.mirrors_url.len());
 
+        let allowed = config.discover_architectures.as_deref();
+        if let Some(allowed) = allowed {
+            debug!("Discovery limited to architectures: {allowed:?}");
+        }
+
+        let cache_path = config.profile_cache_path();
+        if http::is_offline() {
+            info!("{} Offline, using the cached profiles", Symbol::Cache);
+            return Ok(self.get_cached_architectures(config, allowed));
+        }
+        if !refresh {
+            match ProfileCache::load(&cache_path) {
+                Some(cache) if cache.matches(config) && cache.is_fresh(config) => {
+                    info!(
+                        "{} Using the profiles discovered on {} at {}",
+                        Symbol::Cache,
+                        cache.mirror,
+                        cache.discovered_at.format("%Y-%m-%d %H:%M UTC")
+                    );
+                    let source = cache.source();
+                    return Ok((cache.architectures, source));
+                }
+                Some(_) => debug!("The profile cache {} is stale or from other mirrors", cache_path.display()),
+                None => debug!("No profile cache in {}", cache_path.display()),
+            }
+        }
+
         // Check if mirrors are configured
         if !config.has_mirrors() {
-            warn!("⚠️ No mirrors configured using fallback architectures");
-            return Ok(self.get_fallback_architectures());
+            warn!("{} No mirrors configured using fallback architectures", Symbol::Warning);
+            return Ok((self.get_fallback_architectures(allowed), ProfileSource::Fallback));
         }
 
         // Try each configured mirror
-        for (index, mirror_url) in config.mirrors_url.iter().enumerate() {
+        for (index, mirror_url) in config.mirror_urls().enumerate() {
             debug!("Trying to configure mirror {index}: {mirror_url}", index = index + 1);
 
-            match self.discover_from_mirror(mirror_url).await {
+            match self.discover_from_mirror(mirror_url, allowed).await {
                 Ok(architectures) => {
                     debug!("Successfully discovered {count} architectures from mirror: {mirror_url}", 
                            count = architectures.len());
@@ -48,8 +240,13 @@ impl ProfileParser {
                         debug!("  - Architecture '{arch_name}' with {count} profiles: {profiles:?}", 
                                count = arch.profiles.len(), profiles = arch.profiles);
                     }
-                    info!("✅ Successfully discovered profiles from configured mirror: {mirror_url}");
-                    return Ok(architectures);
+                    info!("{} Successfully discovered profiles from configured mirror: {mirror_url}", Symbol::Success);
+                    let cache = ProfileCache::new(config, mirror_url, architectures);
+                    if let Err(e) = cache.save(&cache_path) {
+                        warn!("Unable to cache the discovered profiles in {}: {e}", cache_path.display());
+                    }
+                    let source = ProfileSource::Mirror { mirror: cache.mirror };
+                    return Ok((cache.architectures, source));
                 }
                 Err(e) => {
                     warn!("Failed to discover from configured mirror {mirror_url}: {e}");
@@ -59,23 +256,56 @@ impl ProfileParser {
             }
         }
 
-        // If all configured mirrors fail, return hardcoded fallback
-        warn!("⚠️ Could not discover profiles from any configured mirror using fallback");
-        debug!("Falling back to hardcoded architectures");
-        Ok(self.get_fallback_architectures())
+        warn!("{} Could not discover profiles from any configured mirror", Symbol::Warning);
+        Ok(self.get_cached_architectures(config, allowed))
+    }
+
+    /// Architectures of the last discovery, or the hardcoded fallback without any
+    fn get_cached_architectures(
+        &self,
+        config: &Config,
+        allowed: Option<&[String]>,
+    ) -> (HashMap<String, Architecture>, ProfileSource) {
+        let cache_path = config.profile_cache_path();
+        let cached = ProfileCache::load(&cache_path).map(|cache| {
+            let source = cache.source();
+            let mut architectures = cache.architectures;
+            architectures.retain(|name, _| allowed.is_none_or(|allowed| allowed.contains(name)));
+            (architectures, source)
+        });
+        match cached.filter(|(architectures, _)| !architectures.is_empty()) {
+            Some(cached) => {
+                debug!("Using the profiles cached in {}", cache_path.display());
+                cached
+            }
+            None => {
+                warn!("{} No cached profiles using fallback", Symbol::Warning);
+                debug!("Falling back to hardcoded architectures");
+                (self.get_fallback_architectures(allowed), ProfileSource::Fallback)
+            }
+        }
     }
 
     /// Discover profiles from a specific mirror
+    ///
+    /// When `allowed` is set, only these architectures are crawled. An
+    /// architecture whose autobuilds page fails is skipped.
     async fn discover_from_mirror(
         &self,
         base_url: &str,
+        allowed: Option<&[String]>,
     ) -> Result<HashMap<String, Architecture>, DownloaderError> {
         let releases_url = format!("{}/releases/", base_url.trim_end_matches('/'));
 
         debug!("Fetching releases from: {releases_url}");
 
         // Make HTTP request to get release page
-        let response = self.client.get(&releases_url).send().await?;
+        let response = self
+            .client
+            .get(&releases_url)
+            .send()
+            .await
+            .map_err(|e| http::proxied_error(self.proxy.clone(), e))?;
         debug!("HTTP response status: {}", response.status());
         debug!("HTTP response headers: {headers:?}", headers = response.headers());
         
@@ -84,18 +314,29 @@ impl ProfileParser {
         debug!("First 500 chars of HTML: {preview}", preview = &content[..content.len().min(500)]);
 
         // Parse HTML to find architecture directories
-        let architectures = self.parse_architecture_directories(&content)?;
+        let mut architectures = self.parse_architecture_directories(&content)?;
         debug!("Parsed architectures from HTML: {architectures:?}");
 
-        let mut result = HashMap::new();
+        if let Some(allowed) = allowed {
+            architectures.retain(|arch| allowed.contains(arch));
+            debug!("Architectures kept by the discovery filter: {architectures:?}");
+        }
 
-        for arch_name in &architectures {
-            debug!("Discovering profiles for architecture: {arch_name}");
+        let mut result = HashMap::new();
 
-            match self
-                .discover_profiles_for_arch(&releases_url, arch_name.as_str())
-                .await
-            {
+        // The autobuilds pages are fetched concurrently, handled as they arrive
+        let fetches: Vec<_> = architectures
+            .iter()
+            .map(|arch_name| {
+                debug!("Discovering profiles for architecture: {arch_name}");
+                let releases_url = releases_url.as_str();
+                async move { (arch_name, self.discover_profiles_for_arch(releases_url, arch_name.as_str()).await) }
+            })
+            .collect();
+        let mut discoveries = stream::iter(fetches).buffer_unordered(CONCURRENT_ARCH_REQUESTS);
+
+        while let Some((arch_name, discovered)) = discoveries.next().await {
+            match discovered {
                 Ok(profiles) => {
                     debug!("Found {count} profiles for {arch_name}: {profiles:?}", 
                            count = profiles.len());
@@ -127,37 +368,22 @@ impl ProfileParser {
     }
 
     /// Parse HTML content to find architecture directories
+    ///
+    /// Known architectures are listed first. Other directories are kept as
+    /// candidates, an architecture is only recognized by the autobuilds
+    /// directory found under it.
     fn parse_architecture_directories(&self, html: &str) -> Result<Vec<String>, DownloaderError> {
         debug!("Starting HTML parsing for architecture directories");
-        let mut architectures = Vec::new();
-        let mut line_count = 0;
-
-        // Simple HTML parsing to find href links that look like architectures
-        for line in html.lines() {
-            line_count += 1;
-            
-            if line.contains("href=") && line.contains("/") {
-                debug!("Line {line_count}: Found href link: {line}", line = line.trim());
-                
-                // Extract directory names that look like architectures
-                if let Some(arch) = self.extract_architecture_from_line(line) {
-                    debug!("Line {line_count}: Extracted potential architecture: '{arch}'");
-                    
-                    if self.is_valid_architecture(&arch) {
-                        debug!("Line {line_count}: '{arch}' is a valid architecture");
-                        architectures.push(arch);
-                    } else {
-                        debug!("Line {line_count}: '{arch}' is not a valid architecture");
-                    }
-                } else {
-                    debug!("Line {line_count}: Could not extract architecture from line");
-                }
-            }
+        let directories = index_directories(html);
+        debug!("Directories of the releases page: {directories:?}");
+
+        let (mut architectures, candidates): (Vec<String>, Vec<String>) = directories
+            .into_iter()
+            .partition(|name| self.is_valid_architecture(name));
+        if !candidates.is_empty() {
+            debug!("Directories probed for autobuilds: {candidates:?}");
         }
-
-        debug!("Processed {line_count} lines total");
-        architectures.sort();
-        architectures.dedup();
+        architectures.extend(candidates);
 
         debug!("Found architectures: {architectures:?}");
         info!("Architecture discovery completed: found {count} architectures", 
@@ -165,56 +391,11 @@ impl ProfileParser {
         Ok(architectures)
     }
 
-    /// Extract architecture name from HTML line
-    fn extract_architecture_from_line(&self, line: &str) -> Option<String> {
-        debug!("Extracting architecture from line: {line}", line = line.trim());
-        
-        // Look for patterns like href="amd64/" or href="arm64/"
-        if let Some(start) = line.find("href=\"") {
-            let start = start + 6; // Skip 'href="'
-            debug!("Found href start at position: {start}");
-            
-            if let Some(end) = line[start..].find('"') {
-                let href = &line[start..start + end];
-                debug!("Extracted href: '{href}'");
-                
-                if href.ends_with('/') {
-                    let arch = href.trim_end_matches('/');
-                    debug!("Extracted architecture candidate: '{arch}'");
-                    return Some(arch.to_string());
-                } else {
-                    debug!("Href does not end with '/', skipping: '{href}'");
-                }
-            } else {
-                debug!("Could not find the closing quote for href");
-            }
-        } else {
-            debug!("No href found in the line");
-        }
-        
-        None
-    }
-
     /// Check if a string looks like a valid architecture name
     fn is_valid_architecture(&self, name: &str) -> bool {
         // Known architecture patterns
-        let valid = matches!(
-            name,
-            "amd64"
-                | "arm64"
-                | "arm"
-                | "x86"
-                | "ppc64"
-                | "ppc"
-                | "sparc"
-                | "alpha"
-                | "hppa"
-                | "ia64"
-                | "mips"
-                | "riscv"
-                | "s390"
-        );
-        
+        let valid = is_known_architecture(name);
+
         debug!("Architecture validation for '{name}': {valid}");
         valid
     }
@@ -230,7 +411,12 @@ impl ProfileParser {
         debug!("Fetching autobuilds directory from: {autobuilds_url}");
 
         // Make an HTTP request to get autobuilds page
-        let response = self.client.get(&autobuilds_url).send().await?;
+        let response = self
+            .client
+            .get(&autobuilds_url)
+            .send()
+            .await
+            .map_err(|e| http::proxied_error(self.proxy.clone(), e))?;
         debug!("HTTP response status for {arch} autobuilds: {status}", status = response.status());
 
         if !response.status().is_success() {
@@ -253,30 +439,14 @@ impl ProfileParser {
     fn parse_autobuilds_directories(&self, html: &str, arch: &str) -> Result<Vec<String>, DownloaderError> {
         debug!("Parsing autobuilds directories for architecture: {arch}");
 
-        let mut profiles = Vec::new();
         let current_stage3_prefix = format!("current-stage3-{arch}-");
-        let mut line_count = 0;
-        let mut directories_found = 0;
-
         debug!("Looking for directories with the prefix: '{current_stage3_prefix}'");
 
-        for line in html.lines() {
-            line_count += 1;
-
-            if line.contains("href=") && line.contains(&current_stage3_prefix) {
-                directories_found += 1;
-                debug!("Line {line_count}: Found current-stage3 directory line: {line}", line = line.trim());
-
-                if let Some(profile) = self.extract_profile_from_autobuilds_line(line, arch) {
-                    debug!("Line {line_count}: Extracted profile: '{profile}'");
-                    profiles.push(profile);
-                } else {
-                    debug!("Line {line_count}: Could not extract profile from autobuilds line");
-                }
-            }
-        }
-
-        debug!("Processed {line_count} lines, found {directories_found} current-stage3 directories");
+        let mut profiles: Vec<String> = index_directories(html)
+            .into_iter()
+            .filter_map(|name| name.strip_prefix(&current_stage3_prefix).map(str::to_string))
+            .filter(|profile| !profile.is_empty())
+            .collect();
 
         profiles.sort();
         profiles.dedup();
@@ -292,44 +462,8 @@ impl ProfileParser {
         Ok(profiles)
     }
 
-    /// Extract profile name from current-stage3 directory name in HTML
-    fn extract_profile_from_autobuilds_line(&self, line: &str, arch: &str) -> Option<String> {
-        debug!("Extracting profile from autobuilds line for {arch}: {line}", line = line.trim());
-
-        let current_stage3_prefix = format!("current-stage3-{arch}-");
-
-        // Clean the HTML line first to handle malformed HTML
-        let clean_line = line.replace("\"", "").replace(">", " ");
-        debug!("Cleaned line: {clean_line}");
-
-        // Find the directory name
-        if let Some(start) = clean_line.find(&current_stage3_prefix) {
-            debug!("Found current-stage3 prefix at position: {start}");
-
-            let remaining = &clean_line[start + current_stage3_prefix.len()..];
-            debug!("Remaining part after prefix: '{remaining}'");
-
-            // Find the end of the directory name (before / or space)
-            if let Some(end) = remaining.find('/') {
-                let profile = &remaining[..end];
-                debug!("Found profile before slash: '{profile}'");
-                return Some(profile.to_string());
-            } else if let Some(end) = remaining.find(' ') {
-                let profile = &remaining[..end];
-                debug!("Found profile before space: '{profile}'");
-                return Some(profile.to_string());
-            }
-
-            debug!("Could not parse the profile from the remaining part: '{remaining}'");
-        } else {
-            debug!("Current-stage3 prefix '{current_stage3_prefix}' is not found in the cleaned line");
-        }
-
-        None
-    }
-
     /// Get fallback architectures when mirror discovery fails
-    fn get_fallback_architectures(&self) -> HashMap<String, Architecture> {
+    fn get_fallback_architectures(&self, allowed: Option<&[String]>) -> HashMap<String, Architecture> {
         debug!("Creating fallback architectures");
         let mut architectures = HashMap::new();
 
@@ -390,6 +524,10 @@ impl ProfileParser {
             Architecture::new("sparc".to_string(), sparc_profiles),
         );
 
+        if let Some(allowed) = allowed {
+            architectures.retain(|name, _| allowed.contains(name));
+        }
+
         debug!("Created {count} fallback architectures: {archs:?}", 
                count = architectures.len(), archs = architectures.keys().collect::<Vec<_>>());
         
@@ -397,8 +535,245 @@ impl ProfileParser {
     }
 }
 
-impl Default for ProfileParser {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apache mod_autoindex page of a releases directory
+    const APACHE_RELEASES: &str = r#"<!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 3.2 Final//EN">
+<html>
+ <head>
+  <title>Index of /gentoo/releases</title>
+ </head>
+ <body>
+<h1>Index of /gentoo/releases</h1>
+  <table>
+   <tr><th valign="top"><img src="/icons/blank.gif" alt="[ICO]"></th><th><a href="?C=N;O=D">Name</a></th><th><a href="?C=M;O=A">Last modified</a></th><th><a href="?C=S;O=A">Size</a></th></tr>
+   <tr><th colspan="4"><hr></th></tr>
+<tr><td valign="top"><img src="/icons/back.gif" alt="[PARENTDIR]"></td><td><a href="/gentoo/">Parent Directory</a></td><td>&nbsp;</td><td align="right">  - </td></tr>
+<tr><td valign="top"><img src="/icons/folder.gif" alt="[DIR]"></td><td><a href="alpha/">alpha/</a></td><td align="right">2024-11-04 10:12  </td><td align="right">  - </td></tr>
+<tr><td valign="top"><img src="/icons/folder.gif" alt="[DIR]"></td><td><a href="amd64/">amd64/</a></td><td align="right">2024-11-04 10:12  </td><td align="right">  - </td></tr>
+<tr><td valign="top"><img src="/icons/folder.gif" alt="[DIR]"></td><td><a href="loong/">loong/</a></td><td align="right">2024-11-04 10:12  </td><td align="right">  - </td></tr>
+<tr><td valign="top"><img src="/icons/folder.gif" alt="[DIR]"></td><td><a href="snapshots/">snapshots/</a></td><td align="right">2024-11-04 10:12  </td><td align="right">  - </td></tr>
+<tr><td valign="top"><img src="/icons/text.gif" alt="[TXT]"></td><td><a href="verify-digests.sh">verify-digests.sh</a></td><td align="right">2023-02-20 09:00  </td><td align="right">1.2K</td></tr>
+   <tr><th colspan="4"><hr></th></tr>
+</table>
+</body></html>
+"#;
+
+    /// nginx autoindex page of an autobuilds directory
+    const NGINX_AUTOBUILDS: &str = r#"<html>
+<head><title>Index of /gentoo/releases/amd64/autobuilds/</title></head>
+<body>
+<h1>Index of /gentoo/releases/amd64/autobuilds/</h1><hr><pre><a href="../">../</a>
+<a href="20241103T170400Z/">20241103T170400Z/</a>                                  04-Nov-2024 05:33                   -
+<a href="current-stage3-amd64-desktop-openrc/">current-stage3-amd64-desktop-openrc/</a>               04-Nov-2024 05:33                   -
+<a href="current-stage3-amd64-desktop-systemd/">current-stage3-amd64-desktop-syst..&gt;</a>              04-Nov-2024 05:33                   -
+<a href="current-stage3-amd64-openrc/">current-stage3-amd64-openrc/</a>                       04-Nov-2024 05:33                   -
+<a href="current-stage3-amd64-systemd/">current-stage3-amd64-systemd/</a>                      04-Nov-2024 05:33                   -
+<a href="current-stage3-arm64-openrc/">current-stage3-arm64-openrc/</a>                       04-Nov-2024 05:33                   -
+<a href="latest-stage3-amd64-openrc.txt">latest-stage3-amd64-openrc.txt</a>                     04-Nov-2024 05:33                 683
+</pre><hr></body>
+</html>
+"#;
+
+    /// lighttpd dirlisting page of a releases directory
+    const LIGHTTPD_RELEASES: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>Index of /gentoo/releases/</title></head>
+<body>
+<h2>Index of /gentoo/releases/</h2>
+<div class="list">
+<table summary="Directory Listing" cellpadding="0" cellspacing="0">
+<thead><tr><th class="n">Name</th><th class="m">Last Modified</th><th class="s">Size</th><th class="t">Type</th></tr></thead>
+<tbody>
+<tr class="d"><td class="n"><a href="../">Parent Directory</a>/</td><td class="m">&nbsp;</td><td class="s">- &nbsp;</td><td class="t">Directory</td></tr>
+<tr class="d"><td class="n"><a href="arm64/">arm64</a>/</td><td class="m">2024-Nov-04 10:12:01</td><td class="s">- &nbsp;</td><td class="t">Directory</td></tr>
+<tr class="d"><td class="n"><a href="m68k/">m68k</a>/</td><td class="m">2024-Nov-04 10:12:01</td><td class="s">- &nbsp;</td><td class="t">Directory</td></tr>
+<tr class="d"><td class="n"><a href="riscv/">riscv</a>/</td><td class="m">2024-Nov-04 10:12:01</td><td class="s">- &nbsp;</td><td class="t">Directory</td></tr>
+<tr><td class="n"><a href="verify-digests.sh">verify-digests.sh</a></td><td class="m">2023-Feb-20 09:00:00</td><td class="s">1.2K</td><td class="t">application/x-sh</td></tr>
+</tbody>
+</table>
+</div>
+<div class="foot">lighttpd/1.4.76</div>
+</body>
+</html>
+"#;
+
+    #[test]
+    fn directories_of_an_apache_index_page() {
+        assert_eq!(
+            index_directories(APACHE_RELEASES),
+            ["alpha", "amd64", "loong", "snapshots"]
+        );
+    }
+
+    #[test]
+    fn directories_of_a_hand_written_page() {
+        // Several anchors per line, single quotes, absolute and full URL links
+        let html = "<p><a href='x86/'>x86</a> <a href='/gentoo/releases/hppa/?C=M'>hppa</a> \
+                    <a href=\"https://mirror.example/gentoo/releases/ppc/#top\">ppc</a> <a href='./'>.</a></p>";
+        assert_eq!(index_directories(html), ["hppa", "ppc", "x86"]);
+        assert!(index_directories("no links at all").is_empty());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn architectures_of_a_releases_page() {
+        let parser = ProfileParser::new(&Config::default());
+        // Known architectures first, the other directories are probed for autobuilds
+        assert_eq!(
+            parser.parse_architecture_directories(LIGHTTPD_RELEASES).unwrap(),
+            ["arm64", "riscv", "m68k"]
+        );
+        assert_eq!(
+            parser.parse_architecture_directories(APACHE_RELEASES).unwrap(),
+            ["alpha", "amd64", "loong", "snapshots"]
+        );
+    }
+
+    #[test]
+    fn profiles_of_an_autobuilds_page() {
+        let parser = ProfileParser::new(&Config::default());
+        assert_eq!(
+            parser.parse_autobuilds_directories(NGINX_AUTOBUILDS, "amd64").unwrap(),
+            ["desktop-openrc", "desktop-systemd", "openrc", "systemd"]
+        );
+        assert_eq!(
+            parser.parse_autobuilds_directories(NGINX_AUTOBUILDS, "arm64").unwrap(),
+            ["openrc"]
+        );
+        // Without any current-stage3 directory, openrc is assumed
+        assert_eq!(
+            parser.parse_autobuilds_directories(APACHE_RELEASES, "amd64").unwrap(),
+            ["openrc"]
+        );
+    }
+
+    fn cache_config(mirrors: &[&str]) -> Config {
+        Config {
+            mirrors_url: mirrors.iter().map(|url| crate::config::MirrorEntry::from_url(url)).collect(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn the_profile_cache_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache/profiles.json");
+        let config = cache_config(&["https://a.example/gentoo/"]);
+        let architectures = HashMap::from([(
+            "amd64".to_string(),
+            Architecture::new("amd64".to_string(), vec!["desktop-openrc".to_string(), "openrc".to_string()]),
+        )]);
+        ProfileCache::new(&config, "https://a.example/gentoo/", architectures).save(&path).unwrap();
+
+        let cache = ProfileCache::load(&path).unwrap();
+        assert_eq!(cache.mirror, "https://a.example/gentoo/");
+        assert_eq!(cache.mirrors, ["https://a.example/gentoo/"]);
+        let amd64 = &cache.architectures["amd64"];
+        assert_eq!(amd64.profiles, ["desktop-openrc", "openrc"]);
+        assert_eq!(amd64.default_profile, "openrc");
+
+        fs::write(&path, "{ not json").unwrap();
+        assert!(ProfileCache::load(&path).is_none());
+        assert!(ProfileCache::load(&dir.path().join("missing.json")).is_none());
+    }
+
+    #[test]
+    fn the_profile_cache_is_only_used_with_the_same_settings_until_it_expires() {
+        let config = cache_config(&["https://a.example/gentoo/", "https://b.example/gentoo/"]);
+        let mut cache = ProfileCache::new(&config, "https://a.example/gentoo/", HashMap::new());
+        assert!(cache.matches(&config));
+        assert!(cache.is_fresh(&config));
+
+        // Other mirrors, or the same in another order
+        assert!(!cache.matches(&cache_config(&["https://a.example/gentoo/"])));
+        assert!(!cache.matches(&cache_config(&["https://b.example/gentoo/", "https://a.example/gentoo/"])));
+        let filtered = Config { discover_architectures: Some(vec!["amd64".to_string()]), ..config.clone() };
+        assert!(!cache.matches(&filtered));
+
+        cache.discovered_at = Utc::now() - chrono::Duration::hours(config.profile_cache_ttl_hours as i64 + 1);
+        assert!(!cache.is_fresh(&config));
+        let longer = Config { profile_cache_ttl_hours: config.profile_cache_ttl_hours + 2, ..config };
+        assert!(cache.is_fresh(&longer));
+    }
+
+    /// Mirror serving a releases page of six architectures, returning its URL
+    ///
+    /// Each architecture has its own profiles, except arm64 whose autobuilds
+    /// page is missing and x86 whose connection is closed without an answer.
+    fn start_mirror() -> String {
+        use std::io::{BufRead, BufReader, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                std::thread::spawn(move || {
+                    let mut request_line = String::new();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    reader.read_line(&mut request_line).unwrap();
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                        line.clear();
+                    }
+                    let path = request_line.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let arch = path.strip_prefix("/releases/").and_then(|rest| rest.strip_suffix("/autobuilds/"));
+                    let body = match arch {
+                        None if path == "/releases/" => ["alpha", "amd64", "arm64", "hppa", "riscv", "x86"]
+                            .iter()
+                            .map(|arch| format!("<a href=\"{arch}/\">{arch}/</a>\n"))
+                            .collect(),
+                        Some("x86") => return,
+                        Some(arch) if arch != "arm64" => format!(
+                            "<a href=\"current-stage3-{arch}-openrc/\">x</a>\n<a href=\"current-stage3-{arch}-{arch}-systemd/\">x</a>\n"
+                        ),
+                        _ => {
+                            let _ = write!(stream, "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                            return;
+                        }
+                    };
+                    // Slow enough for the requests to overlap
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                });
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn concurrent_discovery_finds_what_a_sequential_one_does() {
+        let mirror = start_mirror();
+        let parser = ProfileParser::new(&Config::default());
+
+        let releases_url = format!("{mirror}releases/");
+        let mut sequential = std::collections::BTreeMap::new();
+        for arch in ["alpha", "amd64", "arm64", "hppa", "riscv", "x86"] {
+            if let Ok(profiles) = parser.discover_profiles_for_arch(&releases_url, arch).await {
+                if !profiles.is_empty() {
+                    sequential.insert(arch.to_string(), profiles);
+                }
+            }
+        }
+
+        let concurrent: std::collections::BTreeMap<String, Vec<String>> = parser
+            .discover_from_mirror(&mirror, None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(name, arch)| (name, arch.profiles))
+            .collect();
+        assert_eq!(concurrent, sequential);
+        // The missing page and the dropped connection are skipped
+        assert_eq!(concurrent.keys().collect::<Vec<_>>(), ["alpha", "amd64", "hppa", "riscv"]);
+        assert_eq!(concurrent["riscv"], ["openrc", "riscv-systemd"]);
+
+        let allowed = ["amd64".to_string(), "arm64".to_string()];
+        let filtered = parser.discover_from_mirror(&mirror, Some(&allowed)).await.unwrap();
+        assert_eq!(filtered.keys().collect::<Vec<_>>(), ["amd64"]);
+    }
+}
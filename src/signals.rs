@@ -0,0 +1,266 @@
+//! Termination signal handling
+//!
+//! Operations that leave state behind when interrupted (mounted filesystems,
+//! partially extracted chroots) register a cleanup action for their
+//! duration. When SIGINT, SIGTERM or SIGHUP is received, the registered
+//! actions run in reverse order of registration and the process exits with
+//! `128 + signal`.
+//! The same signal usually kills the child process being waited on, so the
+//! command fails meanwhile: [`exit_if_interrupted`] makes its caller wait for
+//! the cleanup instead of exiting with a plain error status.
+//!
+//!
+//! Long operations (downloads, extractions, mounts) hold an [`Operation`]
+//! and stop once the signal is received; the cleanup actions wait for them,
+//! so that nothing is removed while still being written.
+//! Each action is announced before it runs, so an interrupted command says
+//! what it cleaned up: mounts are unmounted, partially extracted chroots
+//! deleted and partial downloads removed. A download cut short by the
+//! network, not by a signal, is kept in the cache to be resumed.
+
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::{Condvar, LazyLock, Mutex};
+use std::time::Duration;
+use crate::say;
+use crate::ui::symbols::Symbol;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+
+/// Maximum time given to the cleanup actions before exiting anyway
+const CLEANUP_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Maximum time given to the running operations to stop before cleaning up anyway
+const OPERATION_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Signal numbers, used for the exit status
+const SIGHUP: i32 = 1;
+const SIGINT: i32 = 2;
+const SIGTERM: i32 = 15;
+
+type CleanupAction = Box<dyn FnOnce() + Send>;
+
+struct Registration {
+    id: u64,
+    description: String,
+    action: CleanupAction,
+}
+
+static CLEANUPS: Mutex<Vec<Registration>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static IGNORE_INTERRUPT: AtomicBool = AtomicBool::new(false);
+/// Number of the termination signal received, 0 until then
+static RECEIVED_SIGNAL: AtomicI32 = AtomicI32::new(0);
+/// Set once the cleanup actions have run
+static CLEANUP_DONE: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+/// Cancelled when a termination signal is received
+static CANCELLATION: LazyLock<CancellationToken> = LazyLock::new(CancellationToken::new);
+/// Number of running operations, see [`Operation`]
+static OPERATIONS: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+
+/// Keeps a cleanup action registered until dropped
+#[must_use = "the cleanup action is unregistered when the guard is dropped"]
+pub struct CleanupGuard {
+    id: u64,
+}
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        if let Ok(mut cleanups) = CLEANUPS.lock() {
+            cleanups.retain(|registration| registration.id != self.id);
+        }
+    }
+}
+
+/// Register an action to run if the process is terminated by a signal
+pub fn on_termination<F>(description: impl Into<String>, action: F) -> CleanupGuard
+where
+    F: FnOnce() + Send + 'static,
+{
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let registration = Registration {
+        id,
+        description: description.into(),
+        action: Box::new(action),
+    };
+    CLEANUPS.lock().unwrap().push(registration);
+    CleanupGuard { id }
+}
+
+/// Long operation, stopping when a termination signal is received
+///
+/// The cleanup actions wait until every operation is dropped, or
+/// [`OPERATION_STOP_TIMEOUT`] has passed.
+#[must_use = "the cleanup no longer waits for the operation once dropped"]
+pub struct Operation {
+    token: CancellationToken,
+}
+
+/// Start an operation that checks for termination signals
+pub fn start_operation() -> Operation {
+    *OPERATIONS.0.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+    Operation {
+        token: CANCELLATION.clone(),
+    }
+}
+
+impl Operation {
+    /// Whether a termination signal was received
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Completes once a termination signal is received
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        let (running, stopped) = &OPERATIONS;
+        let mut running = running.lock().unwrap_or_else(|e| e.into_inner());
+        *running = running.saturating_sub(1);
+        stopped.notify_all();
+    }
+}
+
+/// Wait for the running operations to stop, at most [`OPERATION_STOP_TIMEOUT`]
+fn wait_for_operations() {
+    let (running, stopped) = &OPERATIONS;
+    let running = running.lock().unwrap_or_else(|e| e.into_inner());
+    let (running, _) = stopped
+        .wait_timeout_while(running, OPERATION_STOP_TIMEOUT, |running| *running > 0)
+        .unwrap_or_else(|e| e.into_inner());
+    if *running > 0 {
+        log::warn!("{} operation(s) still running, cleaning up anyway", *running);
+    }
+}
+
+/// Ignores Ctrl-C until dropped
+#[must_use = "Ctrl-C is handled again when the shield is dropped"]
+pub struct InterruptShield {
+    _private: (),
+}
+
+/// Ignore Ctrl-C while an interactive child owns the terminal
+///
+/// The terminal sends SIGINT to the whole foreground process group, so Ctrl-C
+/// typed in an interactive chroot shell must not tear the session down.
+pub fn shield_interrupts() -> InterruptShield {
+    IGNORE_INTERRUPT.store(true, Ordering::Relaxed);
+    InterruptShield { _private: () }
+}
+
+impl Drop for InterruptShield {
+    fn drop(&mut self) {
+        IGNORE_INTERRUPT.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Start listening for termination signals
+///
+/// Must be called from within the tokio runtime.
+pub fn install() -> std::io::Result<()> {
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    tokio::spawn(async move {
+        let signo = loop {
+            tokio::select! {
+                _ = interrupt.recv() => {
+                    if IGNORE_INTERRUPT.load(Ordering::Relaxed) {
+                        log::debug!("Ignoring SIGINT during an interactive session");
+                        continue;
+                    }
+                    break SIGINT;
+                }
+                _ = terminate.recv() => break SIGTERM,
+                _ = hangup.recv() => break SIGHUP,
+            }
+        };
+
+        RECEIVED_SIGNAL.store(signo, Ordering::SeqCst);
+        CANCELLATION.cancel();
+        log::warn!("Received signal {signo}, cleaning up");
+        say!();
+        say!("{} Interrupted, cleaning up before exiting", Symbol::Warning);
+        let cleanup = tokio::task::spawn_blocking(|| {
+            wait_for_operations();
+            run_cleanups();
+            let (done, finished) = &CLEANUP_DONE;
+            *done.lock().unwrap() = true;
+            finished.notify_all();
+        });
+        if tokio::time::timeout(CLEANUP_GRACE_PERIOD, cleanup).await.is_err() {
+            log::warn!("Cleanup did not complete within {}s", CLEANUP_GRACE_PERIOD.as_secs());
+        }
+        std::process::exit(128 + signo);
+    });
+
+    Ok(())
+}
+
+/// Wait for the cleanup of a received termination signal, then exit with `128 + signal`
+///
+/// Returns right away when no signal was received. Called before exiting on
+/// an error, which may only be the interrupted child process, so that the
+/// cleanup is not cut short.
+pub fn exit_if_interrupted() {
+    let signo = RECEIVED_SIGNAL.load(Ordering::SeqCst);
+    if signo == 0 {
+        return;
+    }
+
+    let (done, finished) = &CLEANUP_DONE;
+    let done = done.lock().unwrap_or_else(|e| e.into_inner());
+    let (done, _) = finished
+        .wait_timeout_while(done, CLEANUP_GRACE_PERIOD, |done| !*done)
+        .unwrap_or_else(|e| e.into_inner());
+    if !*done {
+        log::warn!("Cleanup did not complete within {}s", CLEANUP_GRACE_PERIOD.as_secs());
+    }
+    std::process::exit(128 + signo);
+}
+
+/// Run the registered cleanup actions, most recent first
+fn run_cleanups() {
+    let registrations = match CLEANUPS.lock() {
+        Ok(mut cleanups) => std::mem::take(&mut *cleanups),
+        Err(_) => return,
+    };
+
+    for registration in registrations.into_iter().rev() {
+        log::info!("Cleanup: {}", registration.description);
+        say!("   {} {}", Symbol::Cleanup, registration.description);
+        (registration.action)();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn cleanups_run_most_recent_first_and_only_while_registered() {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let record = |name: &'static str| {
+            let ran = Arc::clone(&ran);
+            move || ran.lock().unwrap().push(name)
+        };
+
+        let _mounts = on_termination("unmount", record("unmount"));
+        let download = on_termination("remove the partial download", record("download"));
+        let _extraction = on_termination("remove the partial chroot", record("extraction"));
+        drop(download);
+
+        run_cleanups();
+        assert_eq!(*ran.lock().unwrap(), ["extraction", "unmount"]);
+
+        // The actions run once, the registry is empty afterwards
+        run_cleanups();
+        assert_eq!(ran.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,176 @@
+//! OpenPGP verification of the stage3 archives
+//!
+//! Gentoo publishes a detached `<archive>.asc` signature next to every
+//! stage3, made with the release key. The check runs `gpg` in a throwaway
+//! home directory holding only that key, so the keyring of the user is
+//! neither read nor modified, and a signature is only accepted when it is
+//! good and made by a key that has not expired or been revoked.
+//!
+//! Streamed stages are never on disk, their hash is taken from the
+//! `<archive>.DIGESTS` file instead, which is clearsigned with the same key.
+
+use crate::config::Config;
+use crate::downloader::{
+    download_stage3_digests_file, download_stage3_signature, parse_digests, Stage3Digest, Stage3Release,
+};
+use crate::error::SignatureError;
+use crate::profile::selected::SelectedProfile;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Release key installed by `app-crypt/openpgp-keys-gentoo-release`
+pub const DEFAULT_RELEASE_KEY: &str = "/usr/share/openpgp-keys/gentoo-release.asc";
+
+/// Key the stage3 signatures are checked against
+pub fn release_key_path(config: &Config) -> PathBuf {
+    config
+        .release_key
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_RELEASE_KEY))
+}
+
+fn gpg(home: &Path, args: &[&std::ffi::OsStr]) -> Result<Output, SignatureError> {
+    Command::new("gpg")
+        .arg("--homedir")
+        .arg(home)
+        .args(["--batch", "--no-tty", "--no-auto-key-retrieve"])
+        .args(args)
+        .output()
+        .map_err(SignatureError::GpgUnavailable)
+}
+
+/// Throwaway gpg home directory holding only the key at `key`
+fn keyring_with(key: &Path) -> Result<TempDir, SignatureError> {
+    if !key.is_file() {
+        return Err(SignatureError::KeyNotFound(key.to_path_buf()));
+    }
+
+    let home = tempfile::Builder::new().prefix("chrootmanager-gpg-").tempdir()?;
+    let import = gpg(home.path(), &["--import".as_ref(), key.as_os_str()])?;
+    if !import.status.success() {
+        return Err(SignatureError::KeyImport {
+            path: key.to_path_buf(),
+            message: String::from_utf8_lossy(&import.stderr).trim().to_string(),
+        });
+    }
+    Ok(home)
+}
+
+/// Fingerprint of the signing key when gpg reported a good signature
+fn good_signature(verify: &Output) -> Result<String, SignatureError> {
+    let status = String::from_utf8_lossy(&verify.stdout);
+    log::debug!("gpg --verify status:\n{status}");
+
+    let mut good = false;
+    let mut fingerprint = None;
+    for line in status.lines() {
+        let mut fields = line.split_whitespace().skip_while(|field| *field == "[GNUPG:]");
+        match fields.next() {
+            Some("GOODSIG") => good = true,
+            Some("VALIDSIG") => fingerprint = fields.next().map(str::to_string),
+            _ => {}
+        }
+    }
+
+    match fingerprint {
+        Some(fingerprint) if good && verify.status.success() => Ok(fingerprint),
+        _ => {
+            let stderr = String::from_utf8_lossy(&verify.stderr);
+            let reason = stderr
+                .lines()
+                .find(|line| line.contains("BAD signature") || line.contains("expired") || line.contains("revoked"))
+                .or_else(|| stderr.lines().last())
+                .unwrap_or("gpg rejected the signature");
+            Err(SignatureError::BadSignature(reason.trim_start_matches("gpg: ").to_string()))
+        }
+    }
+}
+
+/// Verify `signature` over `file` against the key at `key`
+///
+/// Returns the fingerprint of the signing key.
+pub fn verify_detached_signature(file: &Path, signature: &Path, key: &Path) -> Result<String, SignatureError> {
+    let home = keyring_with(key)?;
+    let verify = gpg(
+        home.path(),
+        &["--status-fd".as_ref(), "1".as_ref(), "--verify".as_ref(), signature.as_os_str(), file.as_os_str()],
+    )?;
+    good_signature(&verify)
+}
+
+/// Verify a clearsigned document against the key at `key`
+///
+/// Returns the signed text, without what may surround the signed block,
+/// and the fingerprint of the signing key.
+pub fn verify_clearsigned(document: &Path, key: &Path) -> Result<(String, String), SignatureError> {
+    let home = keyring_with(key)?;
+    let signed = home.path().join("signed.txt");
+    let verify = gpg(
+        home.path(),
+        &[
+            "--status-fd".as_ref(),
+            "1".as_ref(),
+            "--output".as_ref(),
+            signed.as_os_str(),
+            "--decrypt".as_ref(),
+            document.as_os_str(),
+        ],
+    )?;
+    let fingerprint = good_signature(&verify)?;
+    Ok((std::fs::read_to_string(signed)?, fingerprint))
+}
+
+/// Download the signature of a stage3 and verify the archive with it
+///
+/// Returns the fingerprint of the signing key.
+pub async fn verify_stage3_signature(
+    profile: &SelectedProfile,
+    config: &Config,
+    release: &Stage3Release,
+    file: &Path,
+) -> Result<String, SignatureError> {
+    let key = release_key_path(config);
+    if !key.is_file() {
+        return Err(SignatureError::KeyNotFound(key));
+    }
+
+    let armored = download_stage3_signature(profile, config, release)
+        .await
+        .map_err(|e| SignatureError::SignatureUnavailable(e.to_string()))?;
+    let directory = tempfile::Builder::new().prefix("chrootmanager-asc-").tempdir()?;
+    let signature = directory.path().join(format!("{}.asc", release.filename));
+    std::fs::write(&signature, armored)?;
+
+    verify_detached_signature(file, &signature, &key)
+}
+
+/// Download the clearsigned DIGESTS file of a stage3 and take its hash from it
+///
+/// Used when the archive itself is never on disk for a detached signature
+/// to be checked: a hash from a verified DIGESTS file carries the same
+/// guarantee once the stream matches it. Returns the digest and the
+/// fingerprint of the signing key.
+pub async fn verified_stage3_digest(
+    profile: &SelectedProfile,
+    config: &Config,
+    release: &Stage3Release,
+) -> Result<(Stage3Digest, String), SignatureError> {
+    let key = release_key_path(config);
+    if !key.is_file() {
+        return Err(SignatureError::KeyNotFound(key));
+    }
+
+    let digests = download_stage3_digests_file(profile, config, release)
+        .await
+        .map_err(|e| SignatureError::SignatureUnavailable(e.to_string()))?;
+    let directory = tempfile::Builder::new().prefix("chrootmanager-asc-").tempdir()?;
+    let document = directory.path().join(format!("{}.DIGESTS", release.filename));
+    std::fs::write(&document, digests)?;
+
+    let (signed, fingerprint) = verify_clearsigned(&document, &key)?;
+    let digest = parse_digests(&signed, &release.filename).ok_or_else(|| {
+        SignatureError::SignatureUnavailable(format!("no hash of {} in the signed DIGESTS file", release.filename))
+    })?;
+    Ok((digest, fingerprint))
+}
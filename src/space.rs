@@ -0,0 +1,330 @@
+//! Disk space estimation before stage3 extraction and low space detection
+//!
+//! The uncompressed size of an xz archive is read from its index with
+//! `xz --robot --list`, without decompressing it, and estimated from the
+//! compressed size for other formats. Free space is queried with `df`.
+
+use crate::error::ChrootError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Default free space under which a filesystem is considered low on space
+const DEFAULT_LOW_SPACE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+const DEFAULT_LOW_SPACE_PERCENT: u8 = 5;
+
+/// Ratio of the extracted size to the compressed size, when the former is unknown
+///
+/// A stage3 expands about 3 to 4 times.
+pub const EXPANSION_FACTOR: u64 = 4;
+
+/// Maximum number of entries visited when looking for large directories
+const SCAN_ENTRY_LIMIT: usize = 20_000;
+
+/// Space requirements of an extraction
+#[derive(Debug, Clone, Default)]
+pub struct SpaceEstimate {
+    /// Uncompressed size of the stage3 archive
+    pub required: Option<u64>,
+    /// Free space on the chroot filesystem
+    pub available: Option<u64>,
+}
+
+impl SpaceEstimate {
+    /// Whether the extraction is known not to fit in the free space
+    pub fn exceeds_available(&self) -> bool {
+        matches!((self.required, self.available), (Some(required), Some(available)) if required > available)
+    }
+
+    /// Whether the extraction would bring the usage over the quota
+    pub fn exceeds_quota(&self, used: u64, quota: u64) -> bool {
+        self.required.is_some_and(|required| used.saturating_add(required) > quota)
+    }
+}
+
+/// Probe the uncompressed size of a stage3 archive
+///
+/// Only xz archives carry their uncompressed size; `None` is returned for
+/// other formats or when `xz` is not installed.
+pub fn uncompressed_size(archive: &Path) -> Option<u64> {
+    if archive.extension().is_none_or(|ext| ext != "xz") {
+        return None;
+    }
+
+    let output = Command::new("xz").arg("--robot").arg("--list").arg(archive).output();
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_xz_robot_list(&String::from_utf8_lossy(&output.stdout))
+        }
+        Ok(output) => {
+            log::debug!("xz --list failed: {}", String::from_utf8_lossy(&output.stderr));
+            None
+        }
+        Err(e) => {
+            log::debug!("Unable to run xz: {e}");
+            None
+        }
+    }
+}
+
+/// Extract the uncompressed size from the `totals` line of `xz --robot --list`
+///
+/// `totals  <streams>  <blocks>  <compressed>  <uncompressed>  <ratio> ...`
+pub fn parse_xz_robot_list(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("totals\t"))
+        .and_then(|fields| fields.split('\t').nth(3))
+        .and_then(|size| size.parse().ok())
+}
+
+/// Size and free space of a filesystem
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilesystemSpace {
+    pub total: u64,
+    /// Free space available to the user
+    pub available: u64,
+}
+
+impl FilesystemSpace {
+    /// Free space as a percentage of the filesystem size
+    pub fn available_percent(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.available as f64 * 100.0 / self.total as f64
+    }
+}
+
+/// Free space under which a filesystem is considered low on space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LowSpaceThreshold {
+    pub bytes: u64,
+    pub percent: u8,
+}
+
+impl Default for LowSpaceThreshold {
+    fn default() -> Self {
+        Self {
+            bytes: DEFAULT_LOW_SPACE_BYTES,
+            percent: DEFAULT_LOW_SPACE_PERCENT,
+        }
+    }
+}
+
+impl LowSpaceThreshold {
+    /// Whether the free space is below either the size or the percentage threshold
+    pub fn is_low(&self, space: &FilesystemSpace) -> bool {
+        space.available < self.bytes || space.available_percent() < f64::from(self.percent)
+    }
+}
+
+/// Size and free space of the filesystem holding `path`
+pub fn filesystem_space(path: &Path) -> Option<FilesystemSpace> {
+    let output = Command::new("df")
+        .args(["-B1", "--output=size,avail"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_df_size_avail(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse the output of `df -B1 --output=size,avail`
+///
+/// `1B-blocks  Avail` header followed by `<size>  <avail>`
+fn parse_df_size_avail(output: &str) -> Option<FilesystemSpace> {
+    let line = output.lines().nth(1)?;
+    let mut fields = line.split_whitespace().map(|field| field.parse().ok());
+    Some(FilesystemSpace {
+        total: fields.next()??,
+        available: fields.next()??,
+    })
+}
+
+/// Free space available to the user on the filesystem holding `path`
+pub fn available_space(path: &Path) -> Option<u64> {
+    filesystem_space(path).map(|space| space.available)
+}
+
+/// Fail when less than `needed` bytes are free on the filesystem holding `path`
+///
+/// Nothing is checked when the free space cannot be queried.
+pub fn ensure_available(path: &Path, needed: u64) -> Result<(), ChrootError> {
+    match available_space(path) {
+        Some(available) if available < needed => Err(ChrootError::InsufficientSpace {
+            path: path.to_path_buf(),
+            needed,
+            available,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Largest directories found `depth` levels below `dir`, biggest first
+///
+/// At most [`SCAN_ENTRY_LIMIT`] entries are visited so the scan stays quick
+/// on huge trees; sizes are then lower bounds. Unreadable entries are skipped.
+pub fn largest_subdirectories(dir: &Path, depth: usize, count: usize) -> Vec<(PathBuf, u64)> {
+    fn size_of(path: &Path, budget: &mut usize) -> u64 {
+        let Ok(entries) = fs::read_dir(path) else {
+            return 0;
+        };
+
+        let mut total = 0;
+        for entry in entries.filter_map(|e| e.ok()) {
+            if *budget == 0 {
+                break;
+            }
+            *budget -= 1;
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => total += size_of(&entry.path(), budget),
+                Ok(metadata) => total += metadata.len(),
+                Err(_) => {}
+            }
+        }
+        total
+    }
+
+    fn directories_at(dir: &Path, depth: usize) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let directories = entries
+            .filter_map(|e| e.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+            .map(|entry| entry.path());
+        if depth <= 1 {
+            directories.collect()
+        } else {
+            directories.flat_map(|path| directories_at(&path, depth - 1)).collect()
+        }
+    }
+
+    let mut budget = SCAN_ENTRY_LIMIT;
+    let mut sizes: Vec<(PathBuf, u64)> = directories_at(dir, depth)
+        .into_iter()
+        .map(|path| {
+            let size = size_of(&path, &mut budget);
+            (path, size)
+        })
+        .filter(|(_, size)| *size > 0)
+        .collect();
+
+    sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    sizes.truncate(count);
+    sizes
+}
+
+/// Estimate the space needed to extract `archive` into a directory under `target_dir`
+pub fn estimate(archive: &Path, target_dir: &Path) -> SpaceEstimate {
+    let required = uncompressed_size(archive).or_else(|| {
+        let compressed = fs::metadata(archive).ok()?.len();
+        Some(compressed.saturating_mul(EXPANSION_FACTOR))
+    });
+    SpaceEstimate {
+        required,
+        available: available_space(target_dir),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_uncompressed_size_comes_from_the_totals_line() {
+        let output = "name\tstage3.tar.xz\nfile\t1\t1\t104\t5000\t0.021\tCRC64\t0\ntotals\t1\t1\t104\t5000\t0.021\tCRC64\t0\t1\n";
+        assert_eq!(parse_xz_robot_list(output), Some(5000));
+        assert_eq!(parse_xz_robot_list("name\tstage3.tar.xz\n"), None);
+    }
+
+    #[test]
+    fn the_xz_probe_reads_a_fixture_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("stage3.tar");
+        fs::write(&archive, vec![0u8; 100_000]).unwrap();
+        // The fixture is compressed on the fly, xz being what the probe runs
+        if Command::new("xz").arg(&archive).status().is_err() {
+            return;
+        }
+        let archive = dir.path().join("stage3.tar.xz");
+        assert_eq!(uncompressed_size(&archive), Some(100_000));
+
+        // Without an xz index, the compressed size is scaled
+        let gzip = dir.path().join("stage3.tar.gz");
+        fs::write(&gzip, vec![0u8; 1000]).unwrap();
+        assert_eq!(uncompressed_size(&gzip), None);
+        assert_eq!(estimate(&gzip, dir.path()).required, Some(1000 * EXPANSION_FACTOR));
+    }
+
+    #[test]
+    fn the_estimate_is_compared_with_the_free_space_and_the_quota() {
+        let estimate = SpaceEstimate {
+            required: Some(5 << 30),
+            available: Some(4 << 30),
+        };
+        assert!(estimate.exceeds_available());
+        assert!(estimate.exceeds_quota(16 << 30, 20 << 30));
+        assert!(!estimate.exceeds_quota(10 << 30, 20 << 30));
+
+        // Unknown sizes never warn
+        let unknown = SpaceEstimate::default();
+        assert!(!unknown.exceeds_available());
+        assert!(!unknown.exceeds_quota(u64::MAX, 0));
+    }
+
+    const GB: u64 = 1 << 30;
+
+    #[test]
+    fn free_space_is_low_under_either_threshold() {
+        let threshold = LowSpaceThreshold::default();
+        let space = |available, total| FilesystemSpace { total, available };
+        // 2 GB and 5% by default
+        assert!(!threshold.is_low(&space(10 * GB, 100 * GB)));
+        assert!(threshold.is_low(&space(GB, 10 * GB)));
+        assert!(threshold.is_low(&space(4 * GB, 100 * GB)));
+        assert!(!threshold.is_low(&space(3 * GB, 10 * GB)));
+
+        let strict = LowSpaceThreshold { bytes: 0, percent: 50 };
+        assert!(strict.is_low(&space(3 * GB, 10 * GB)));
+        // An unknown size counts as full
+        assert_eq!(space(GB, 0).available_percent(), 0.0);
+    }
+
+    #[test]
+    fn df_output_gives_the_size_and_free_space() {
+        let output = "1B-blocks       Avail\n 105089261568 42035708928\n";
+        assert_eq!(
+            parse_df_size_avail(output),
+            Some(FilesystemSpace {
+                total: 105_089_261_568,
+                available: 42_035_708_928
+            })
+        );
+        assert_eq!(parse_df_size_avail("1B-blocks Avail\n"), None);
+        assert_eq!(parse_df_size_avail("1B-blocks Avail\n- -\n"), None);
+    }
+
+    #[test]
+    fn the_largest_build_directories_come_first() {
+        let portage = tempfile::tempdir().unwrap();
+        for (package, size) in [("dev-lang/rust", 3000), ("sys-devel/gcc", 2000), ("app-misc/jq", 10)] {
+            let work = portage.path().join(package).join("work");
+            fs::create_dir_all(&work).unwrap();
+            fs::write(work.join("object.o"), vec![0u8; size]).unwrap();
+        }
+        fs::create_dir_all(portage.path().join("app-misc/empty")).unwrap();
+
+        let largest = largest_subdirectories(portage.path(), 2, 2);
+        let names: Vec<(String, u64)> = largest
+            .into_iter()
+            .map(|(path, size)| (path.strip_prefix(portage.path()).unwrap().display().to_string(), size))
+            .collect();
+        assert_eq!(names, [("dev-lang/rust".to_string(), 3000), ("sys-devel/gcc".to_string(), 2000)]);
+    }
+}
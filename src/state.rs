@@ -0,0 +1,233 @@
+//! Per-user state kept between runs
+//!
+//! State lives in `~/.local/state/chrootmanager` (or `$XDG_STATE_HOME`), next
+//! to the failure report. `state.toml` holds the projects, named groups of
+//! chroots operated on together, and the recent sessions of each chroot.
+
+use crate::config::{test_mode_dir, user_home_dir};
+use crate::error::StateError;
+use crate::permissions::{self, PRIVATE_DIR_MODE, PRIVATE_FILE_MODE};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const STATE_FILE: &str = "state.toml";
+
+/// Sessions kept for each chroot, the oldest are dropped
+const SESSION_HISTORY_LEN: usize = 10;
+
+/// Directory holding the state files of chrootmanager
+///
+/// `XDG_STATE_HOME` is ignored in test mode.
+pub fn state_dir() -> PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute() && test_mode_dir().is_none())
+        .unwrap_or_else(|| user_home_dir().join(".local").join("state"))
+        .join("chrootmanager")
+}
+
+/// A shell or command run in a chroot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub started_at: DateTime<Local>,
+    pub duration_secs: u64,
+    /// Command run with `exec`, none for an interactive shell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Exit code of the command, unknown for a shell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+/// Content of `state.toml`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UserState {
+    /// Chroot names of each project
+    #[serde(default)]
+    pub projects: BTreeMap<String, Vec<String>>,
+    /// Recent sessions of each chroot, oldest first
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sessions: BTreeMap<String, Vec<SessionRecord>>,
+}
+
+impl UserState {
+    pub fn path() -> PathBuf {
+        state_dir().join(STATE_FILE)
+    }
+
+    /// Read the state file, empty when it does not exist yet
+    pub fn load(path: &Path) -> Result<Self, StateError> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(toml::from_str(&content)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), StateError> {
+        if let Some(parent) = path.parent() {
+            permissions::create_dir_all(parent, PRIVATE_DIR_MODE)?;
+        }
+        permissions::write_file(path, toml::to_string_pretty(self)?, PRIVATE_FILE_MODE)?;
+        Ok(())
+    }
+
+    /// Members of a project
+    pub fn project(&self, name: &str) -> Result<&[String], StateError> {
+        self.projects
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| StateError::UnknownProject(name.to_string()))
+    }
+
+    pub fn create_project(&mut self, name: &str) -> Result<(), StateError> {
+        if self.projects.contains_key(name) {
+            return Err(StateError::ProjectExists(name.to_string()));
+        }
+        self.projects.insert(name.to_string(), Vec::new());
+        Ok(())
+    }
+
+    /// Remove a project, returning its members
+    pub fn delete_project(&mut self, name: &str) -> Result<Vec<String>, StateError> {
+        self.projects
+            .remove(name)
+            .ok_or_else(|| StateError::UnknownProject(name.to_string()))
+    }
+
+    /// Add a chroot to a project, returning false when it already was a member
+    pub fn add_member(&mut self, project: &str, chroot: &str) -> Result<bool, StateError> {
+        let members = self
+            .projects
+            .get_mut(project)
+            .ok_or_else(|| StateError::UnknownProject(project.to_string()))?;
+        if members.iter().any(|member| member == chroot) {
+            return Ok(false);
+        }
+        members.push(chroot.to_string());
+        Ok(true)
+    }
+
+    /// Follow a chroot rename in every project and in the sessions, returning whether any changed
+    pub fn rename_member(&mut self, old: &str, new: &str) -> bool {
+        let mut changed = false;
+        if let Some(sessions) = self.sessions.remove(old) {
+            self.sessions.insert(new.to_string(), sessions);
+            changed = true;
+        }
+        for member in self.projects.values_mut().flatten() {
+            if member == old {
+                *member = new.to_string();
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    pub fn remove_member(&mut self, project: &str, chroot: &str) -> Result<(), StateError> {
+        let members = self
+            .projects
+            .get_mut(project)
+            .ok_or_else(|| StateError::UnknownProject(project.to_string()))?;
+        let before = members.len();
+        members.retain(|member| member != chroot);
+        if members.len() == before {
+            return Err(StateError::NotAMember {
+                project: project.to_string(),
+                chroot: chroot.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Recent sessions of a chroot, oldest first
+    pub fn sessions(&self, chroot: &str) -> &[SessionRecord] {
+        self.sessions.get(chroot).map_or(&[], Vec::as_slice)
+    }
+
+    /// Add a session of a chroot, dropping the oldest beyond the history length
+    pub fn record_session(&mut self, chroot: &str, session: SessionRecord) {
+        let sessions = self.sessions.entry(chroot.to_string()).or_default();
+        sessions.push(session);
+        let excess = sessions.len().saturating_sub(SESSION_HISTORY_LEN);
+        sessions.drain(..excess);
+    }
+
+    /// Forget the sessions of a deleted chroot, returning whether it had any
+    pub fn forget_sessions(&mut self, chroot: &str) -> bool {
+        self.sessions.remove(chroot).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(command: &str) -> SessionRecord {
+        SessionRecord {
+            started_at: Local::now(),
+            duration_secs: 1,
+            command: Some(command.to_string()),
+            exit_code: Some(0),
+        }
+    }
+
+    #[test]
+    fn session_history_keeps_the_most_recent_and_follows_renames() {
+        let mut state = UserState::default();
+        for i in 0..SESSION_HISTORY_LEN + 2 {
+            state.record_session("dev", session(&format!("true {i}")));
+        }
+        let sessions = state.sessions("dev");
+        assert_eq!(sessions.len(), SESSION_HISTORY_LEN);
+        assert_eq!(sessions[0].command.as_deref(), Some("true 2"));
+
+        assert!(state.rename_member("dev", "work"));
+        assert!(state.sessions("dev").is_empty());
+        assert_eq!(state.sessions("work").len(), SESSION_HISTORY_LEN);
+
+        let saved = toml::to_string_pretty(&state).unwrap();
+        let loaded: UserState = toml::from_str(&saved).unwrap();
+        assert_eq!(loaded.sessions("work"), state.sessions("work"));
+    }
+
+    #[test]
+    fn projects_are_created_filled_and_deleted() {
+        let mut state = UserState::default();
+        state.create_project("web").unwrap();
+        assert!(matches!(state.create_project("web"), Err(StateError::ProjectExists(_))));
+        assert!(state.add_member("web", "front").unwrap());
+        assert!(state.add_member("web", "back").unwrap());
+        assert!(!state.add_member("web", "front").unwrap());
+        assert!(matches!(state.add_member("api", "front"), Err(StateError::UnknownProject(_))));
+        assert_eq!(state.project("web").unwrap(), ["front", "back"]);
+
+        state.remove_member("web", "front").unwrap();
+        assert!(matches!(state.remove_member("web", "front"), Err(StateError::NotAMember { .. })));
+        assert_eq!(state.delete_project("web").unwrap(), ["back"]);
+        assert!(matches!(state.project("web"), Err(StateError::UnknownProject(_))));
+    }
+
+    #[test]
+    fn the_state_file_round_trips_and_starts_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/state.toml");
+        assert!(UserState::load(&path).unwrap().projects.is_empty());
+
+        let mut state = UserState::default();
+        state.create_project("web").unwrap();
+        state.add_member("web", "front").unwrap();
+        state.save(&path).unwrap();
+        assert_eq!(UserState::load(&path).unwrap().project("web").unwrap(), ["front"]);
+
+        // Private to the user, whatever the umask
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), PRIVATE_FILE_MODE);
+        assert_eq!(mode(path.parent().unwrap()), PRIVATE_DIR_MODE);
+    }
+}
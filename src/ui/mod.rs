@@ -0,0 +1,4 @@
+//! Terminal output helpers shared by the CLI
+
+pub mod output;
+pub mod symbols;
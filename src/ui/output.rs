@@ -0,0 +1,84 @@
+//! Output verbosity
+//!
+//! Informational output goes through [`say!`](crate::say), which is silenced
+//! in quiet mode. Each command then prints a single result line:
+//!
+//! - `create`: the path of the created chroot
+//! - `list`: the name of each chroot, one per line
+//! - `info`: the details, as without `--quiet`
+//! - `mirror <url>`: the added URL; `mirror --show`: one URL per line
+//! - `migrate`: the name of the chroot, when its metadata was upgraded
+//! - `selftest`: "ok", failed steps are still reported
+//!
+//! Prompts are still shown, quiet does not mean non-interactive.
+//!
+//! Commands printing a machine-readable result, such as `list --format json`,
+//! reserve stdout for it: informational output then goes to stderr.
+
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// Amount of output printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+
+impl Verbosity {
+    /// Build the verbosity from the `-q` and `-v` flags
+    pub fn from_flags(quiet: bool, verbose: bool) -> Self {
+        if quiet {
+            Verbosity::Quiet
+        } else if verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+}
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// Whether only result lines are printed
+pub fn is_quiet() -> bool {
+    verbosity() == Verbosity::Quiet
+}
+
+/// Send the informational output to stderr, stdout only carrying the result
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+/// Whether informational output goes to stderr
+pub fn is_stdout_reserved() -> bool {
+    STDOUT_RESERVED.load(Ordering::Relaxed)
+}
+
+/// Print a line of informational output, unless in quiet mode
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::ui::output::is_quiet() {
+            if $crate::ui::output::is_stdout_reserved() {
+                eprintln!($($arg)*);
+            } else {
+                println!($($arg)*);
+            }
+        }
+    };
+}
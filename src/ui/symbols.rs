@@ -0,0 +1,251 @@
+//! Symbols used in terminal output, with ASCII fallbacks
+//!
+//! Every emoji or special character printed by the CLI goes through [`Symbol`],
+//! so that terminals without Unicode support (Linux console, non UTF-8 locale)
+//! get a readable ASCII rendering instead of replacement boxes.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether symbols are rendered in ASCII
+static ASCII_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Symbols printed by the CLI
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symbol {
+    Success,
+    Error,
+    Warning,
+    Hint,
+    Search,
+    Info,
+    Folder,
+    Location,
+    Refresh,
+    Download,
+    Package,
+    Stats,
+    Speed,
+    Network,
+    Cache,
+    Lock,
+    Tool,
+    Mount,
+    Cleanup,
+    Trash,
+    Rocket,
+    Welcome,
+    Timer,
+    Bullet,
+    Arrow,
+    Separator,
+    ProgressFilled,
+    ProgressEmpty,
+}
+
+impl Symbol {
+    /// Unicode rendering
+    pub fn unicode(&self) -> &'static str {
+        match self {
+            Symbol::Success => "✅",
+            Symbol::Error => "❌",
+            Symbol::Warning => "⚠️",
+            Symbol::Hint => "💡",
+            Symbol::Search => "🔍",
+            Symbol::Info => "📋",
+            Symbol::Folder => "📂",
+            Symbol::Location => "📍",
+            Symbol::Refresh => "🔄",
+            Symbol::Download => "📥",
+            Symbol::Package => "📦",
+            Symbol::Stats => "📊",
+            Symbol::Speed => "📈",
+            Symbol::Network => "📡",
+            Symbol::Cache => "💾",
+            Symbol::Lock => "🔐",
+            Symbol::Tool => "🔧",
+            Symbol::Mount => "🗄️",
+            Symbol::Cleanup => "🧹",
+            Symbol::Trash => "🗑️",
+            Symbol::Rocket => "🚀",
+            Symbol::Welcome => "🎉",
+            Symbol::Timer => "⏱️",
+            Symbol::Bullet => "•",
+            Symbol::Arrow => "▸",
+            Symbol::Separator => "─",
+            Symbol::ProgressFilled => "█",
+            Symbol::ProgressEmpty => "░",
+        }
+    }
+
+    /// ASCII rendering
+    pub fn ascii(&self) -> &'static str {
+        match self {
+            Symbol::Success => "[OK]",
+            Symbol::Error => "[ERROR]",
+            Symbol::Warning => "[WARN]",
+            Symbol::Hint => "[HINT]",
+            Symbol::Bullet => "-",
+            Symbol::Arrow => ">",
+            Symbol::Separator => "-",
+            Symbol::ProgressFilled => "#",
+            Symbol::ProgressEmpty => "-",
+            _ => "*",
+        }
+    }
+
+    /// Rendering for the current output mode
+    pub fn as_str(&self) -> &'static str {
+        if is_ascii_output() {
+            self.ascii()
+        } else {
+            self.unicode()
+        }
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Check whether the locale announces a UTF-8 charset
+///
+/// The variables are looked up in the POSIX precedence order
+/// (`LC_ALL`, then `LC_CTYPE`, then `LANG`); the first non-empty one wins.
+pub fn locale_supports_unicode<F>(lookup: F) -> bool
+where
+    F: Fn(&str) -> Option<String>,
+{
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| lookup(name))
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            let value = value.to_lowercase();
+            value.contains("utf-8") || value.contains("utf8")
+        })
+        .unwrap_or(false)
+}
+
+/// Select the output mode from the environment locale
+pub fn init_from_env() {
+    let unicode = locale_supports_unicode(|name| std::env::var(name).ok());
+    set_ascii_output(!unicode);
+}
+
+/// Force the output mode
+pub fn set_ascii_output(ascii: bool) {
+    ASCII_OUTPUT.store(ascii, Ordering::Relaxed);
+}
+
+/// Whether symbols are currently rendered in ASCII
+pub fn is_ascii_output() -> bool {
+    ASCII_OUTPUT.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const ALL: [Symbol; 28] = [
+        Symbol::Success,
+        Symbol::Error,
+        Symbol::Warning,
+        Symbol::Hint,
+        Symbol::Search,
+        Symbol::Info,
+        Symbol::Folder,
+        Symbol::Location,
+        Symbol::Refresh,
+        Symbol::Download,
+        Symbol::Package,
+        Symbol::Stats,
+        Symbol::Speed,
+        Symbol::Network,
+        Symbol::Cache,
+        Symbol::Lock,
+        Symbol::Tool,
+        Symbol::Mount,
+        Symbol::Cleanup,
+        Symbol::Trash,
+        Symbol::Rocket,
+        Symbol::Welcome,
+        Symbol::Timer,
+        Symbol::Bullet,
+        Symbol::Arrow,
+        Symbol::Separator,
+        Symbol::ProgressFilled,
+        Symbol::ProgressEmpty,
+    ];
+
+    fn locale(vars: &[(&str, &str)]) -> bool {
+        locale_supports_unicode(|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn the_first_set_locale_variable_decides() {
+        assert!(locale(&[("LANG", "fr_FR.UTF-8")]));
+        assert!(locale(&[("LC_CTYPE", "en_US.utf8"), ("LANG", "C")]));
+        assert!(!locale(&[("LC_ALL", "C"), ("LANG", "en_US.UTF-8")]));
+        // An empty variable is skipped
+        assert!(locale(&[("LC_ALL", ""), ("LANG", "en_US.UTF-8")]));
+        assert!(!locale(&[("LANG", "POSIX")]));
+        assert!(!locale(&[]));
+    }
+
+    #[test]
+    fn both_renderings_of_the_progress_bar_and_separator() {
+        let bar = |ascii: bool| {
+            [Symbol::ProgressFilled, Symbol::ProgressFilled, Symbol::ProgressEmpty, Symbol::Separator]
+                .iter()
+                .map(|symbol| if ascii { symbol.ascii() } else { symbol.unicode() })
+                .collect::<String>()
+        };
+        assert_eq!(bar(true), "##--");
+        assert_eq!(bar(false), "██░─");
+    }
+
+    #[test]
+    fn every_ascii_rendering_is_ascii() {
+        for symbol in ALL {
+            assert!(symbol.ascii().is_ascii(), "{symbol:?}");
+            assert!(!symbol.unicode().is_ascii(), "{symbol:?}");
+        }
+    }
+
+    /// Emoji, arrows, box drawing and block elements
+    fn is_symbol_char(c: char) -> bool {
+        matches!(c as u32, 0x2190..=0x21FF | 0x2500..=0x25FF | 0x2600..=0x27BF | 0x1F300..=0x1FAFF)
+    }
+
+    fn check_dir(dir: &Path, offenders: &mut Vec<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                check_dir(&path, offenders);
+            } else if path.extension().is_some_and(|ext| ext == "rs") && !path.ends_with("ui/symbols.rs") {
+                let content = std::fs::read_to_string(&path).unwrap();
+                for (number, line) in content.lines().enumerate() {
+                    // Comments may show what the output looks like
+                    if !line.trim_start().starts_with("//") && line.chars().any(is_symbol_char) {
+                        offenders.push(format!("{}:{}", path.display(), number + 1));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn symbols_are_only_written_in_this_module() {
+        let mut offenders = Vec::new();
+        check_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut offenders);
+        assert!(offenders.is_empty(), "symbols outside ui::symbols: {offenders:#?}");
+    }
+}
@@ -0,0 +1,187 @@
+//! Golden tests of the command line
+//!
+//! Each directory of `tests/golden` is a case: `args` holds the arguments,
+//! one per line, and `stdout`, `stderr` and `status` the expected output and
+//! exit code. The binary runs in test mode with a temporary directory as
+//! home, seeded with the `home` directory of the case when there is one.
+//! That directory is shown as `[HOME]` in the expected output.
+//!
+//! Run with `CHROOTMANAGER_BLESS=1` to write the actual output as expected.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Copy the `source` tree into `dest`, creating the directories
+fn copy_tree(source: &Path, dest: &Path) {
+    fs::create_dir_all(dest).unwrap();
+    for entry in fs::read_dir(source).unwrap() {
+        let entry = entry.unwrap();
+        let target = dest.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_tree(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), &target).unwrap();
+        }
+    }
+}
+
+/// Compare `actual` with the `name` file of the case, or write it when blessing
+fn check(case: &Path, name: &str, actual: &str, bless: bool, failures: &mut Vec<String>) {
+    let path = case.join(name);
+    if bless {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    if expected != actual {
+        failures.push(format!(
+            "{}: {name} differs\n--- expected\n{expected}\n--- actual\n{actual}",
+            case.display()
+        ));
+    }
+}
+
+/// Run a case, returning the differences with the expected files
+fn run_case(case: &Path, bless: bool) -> Vec<String> {
+    let home = TempDir::new().unwrap();
+    if case.join("home").is_dir() {
+        copy_tree(&case.join("home"), home.path());
+    }
+    let args = fs::read_to_string(case.join("args")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_chrootmanager"))
+        .args(args.lines().filter(|line| !line.is_empty()))
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("CHROOTMANAGER_TEST_MODE", home.path())
+        .env("LC_ALL", "C")
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+
+    let home_str = home.path().to_string_lossy();
+    let normalize = |bytes: &[u8]| String::from_utf8_lossy(bytes).replace(home_str.as_ref(), "[HOME]");
+    let status = format!("{}\n", output.status.code().unwrap_or(-1));
+
+    let mut failures = Vec::new();
+    check(case, "stdout", &normalize(&output.stdout), bless, &mut failures);
+    check(case, "stderr", &normalize(&output.stderr), bless, &mut failures);
+    check(case, "status", &status, bless, &mut failures);
+    failures
+}
+
+#[test]
+fn golden() {
+    let bless = std::env::var_os("CHROOTMANAGER_BLESS").is_some();
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut cases: Vec<_> = fs::read_dir(&root)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.join("args").is_file())
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no case in {}", root.display());
+
+    let failures: Vec<String> = cases.iter().flat_map(|case| run_case(case, bless)).collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
+
+#[test]
+fn list_json_accepts_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    // Not a golden case, git does not keep such file names portably
+    let home = TempDir::new().unwrap();
+    let chroots = home.path().join(".local/share/chrootmanager/chroots");
+    fs::create_dir_all(chroots.join(OsStr::from_bytes(b"bad\xffname")).join("etc")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_chrootmanager"))
+        .args(["list", "--format", "json"])
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("CHROOTMANAGER_TEST_MODE", home.path())
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let listing: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let path = listing[0]["path"].as_str().unwrap();
+    assert!(path.ends_with("/bad\u{FFFD}name"), "{path}");
+}
+
+#[test]
+fn list_never_touches_the_elevation_programs() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = TempDir::new().unwrap();
+    copy_tree(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/list-plain/home"), home.path());
+
+    // Stand-ins that only record being run
+    let bin = TempDir::new().unwrap();
+    let calls = bin.path().join("calls");
+    for program in ["sudo", "pkexec", "which"] {
+        let script = bin.path().join(program);
+        fs::write(&script, format!("#!/bin/sh\necho {program} >> '{}'\nexit 1\n", calls.display())).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let mut path = std::ffi::OsString::from(bin.path());
+    path.push(":");
+    path.push(std::env::var_os("PATH").unwrap_or_default());
+
+    for args in [&["list"][..], &["list", "--format", "json"], &["list", "--format", "plain"]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_chrootmanager"))
+            .args(args)
+            .env_clear()
+            .env("PATH", &path)
+            .env("CHROOTMANAGER_TEST_MODE", home.path())
+            .env("LC_ALL", "C")
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(
+            !calls.exists(),
+            "{args:?} ran {}",
+            fs::read_to_string(&calls).unwrap_or_default()
+        );
+    }
+}
+
+#[test]
+fn removing_the_last_mirror_warns_about_the_default_one() {
+    let home = TempDir::new().unwrap();
+    let config_dir = home.path().join(".config/chrootmanager");
+    fs::create_dir_all(&config_dir).unwrap();
+    let config = format!(
+        "chroot_base_dir = \"{home}/chroots\"\nstage3_cache_dir = \"{home}/cache\"\n\n[[mirrors_url]]\nurl = \"https://a.example/gentoo/\"\n",
+        home = home.path().display()
+    );
+    fs::write(config_dir.join("config.toml"), config).unwrap();
+    let mirror = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_chrootmanager"))
+            .arg("mirror")
+            .args(args)
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .env("CHROOTMANAGER_TEST_MODE", home.path())
+            .env("LC_ALL", "C")
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap()
+    };
+
+    let output = mirror(&["remove", "2"]);
+    assert!(!output.status.success());
+
+    let output = mirror(&["remove", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("Mirror 'https://a.example/gentoo/' removed"), "{stdout}");
+    assert!(stdout.contains("No mirror left, the default"), "{stdout}");
+    let saved = fs::read_to_string(config_dir.join("config.toml")).unwrap();
+    assert!(!saved.contains("a.example"), "{saved}");
+}
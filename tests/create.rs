@@ -0,0 +1,279 @@
+//! Chroot creation from a local mirror
+//!
+//! A local HTTP server stands in for the mirror and serves a tiny stage3,
+//! built with `tar` when the test starts, along with its SHA256 file. The
+//! binary runs in test mode with a temporary directory as home, its
+//! configuration pointing at that server.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::Arc;
+use std::thread;
+use tempfile::TempDir;
+
+const LATEST: &str = "20240308T170000Z";
+const PREVIOUS: &str = "20240301T170000Z";
+
+fn stage3_name(snapshot: &str) -> String {
+    format!("stage3-amd64-openrc-{snapshot}.tar.xz")
+}
+
+/// Answer one request with the file whose key ends the path, or a 404
+fn serve(mut stream: TcpStream, files: &HashMap<String, Vec<u8>>) {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // Headers, up to the empty line
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+        line.clear();
+    }
+    let mut fields = request_line.split_whitespace();
+    let method = fields.next().unwrap_or_default();
+    let path = fields.next().unwrap_or("/");
+
+    match files.iter().find(|(key, _)| path.ends_with(key.as_str())) {
+        Some((_, body)) => {
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            if method != "HEAD" {
+                let _ = stream.write_all(body);
+            }
+        }
+        None => {
+            let _ = write!(
+                stream,
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            );
+        }
+    }
+}
+
+/// Start the mirror serving `files` by path suffix, returning its URL
+fn start_mirror(files: HashMap<String, Vec<u8>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let files = Arc::new(files);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let files = Arc::clone(&files);
+            thread::spawn(move || serve(stream, &files));
+        }
+    });
+    url
+}
+
+/// Files of a mirror announcing the `latest` snapshot, with a tiny stage3 of `served` only
+///
+/// The stage3 holds the files the creation writes to and comes with its
+/// SHA256 file. The autobuilds index lists both snapshots.
+fn mirror_files(work: &Path, latest: &str, served: &str) -> HashMap<String, Vec<u8>> {
+    let tree = work.join("tree");
+    fs::create_dir_all(tree.join("etc")).unwrap();
+    fs::create_dir_all(tree.join("usr/bin")).unwrap();
+    fs::write(tree.join("etc/os-release"), "NAME=Gentoo\n").unwrap();
+    let name = stage3_name(served);
+    let archive = work.join(&name);
+    let status = Command::new("tar")
+        .arg("-cJf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&tree)
+        .arg(".")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let stage3 = fs::read(&archive).unwrap();
+    let sha256 = Command::new("sha256sum").arg(&archive).output().unwrap();
+    let sha256 = String::from_utf8_lossy(&sha256.stdout);
+    let sha256 = sha256.split_whitespace().next().unwrap();
+    let latest_name = stage3_name(latest);
+    HashMap::from([
+        (
+            "/latest-stage3-amd64-openrc.txt".to_string(),
+            format!("# Latest stage3\n{latest}/{latest_name} {}\n", stage3.len()).into_bytes(),
+        ),
+        (
+            "/autobuilds/".to_string(),
+            format!("<a href=\"{PREVIOUS}/\">{PREVIOUS}/</a>\n<a href=\"{LATEST}/\">{LATEST}/</a>\n").into_bytes(),
+        ),
+        (format!("/{name}.sha256"), format!("{sha256}  {name}\n").into_bytes()),
+        (format!("/{name}"), stage3),
+    ])
+}
+
+/// Home of a test run, configured to download from `mirror`
+fn home_with_mirror(mirror: &str) -> TempDir {
+    let home = TempDir::new().unwrap();
+    let config_dir = home.path().join(".config/chrootmanager");
+    fs::create_dir_all(&config_dir).unwrap();
+    let config = format!(
+        "chroot_base_dir = \"{home}/chroots\"\nstage3_cache_dir = \"{home}/cache\"\n\n[[mirrors_url]]\nurl = \"{mirror}\"\n",
+        home = home.path().display()
+    );
+    fs::write(config_dir.join("config.toml"), config).unwrap();
+    home
+}
+
+fn run(home: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chrootmanager"))
+        .args(args)
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("CHROOTMANAGER_TEST_MODE", home)
+        .env("LC_ALL", "C")
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn quiet_create_prints_only_the_chroot_path() {
+    // tar runs through sudo otherwise, which may ask for a password
+    if !nix::unistd::geteuid().is_root() {
+        return;
+    }
+    let work = TempDir::new().unwrap();
+    let home = home_with_mirror(&start_mirror(mirror_files(work.path(), LATEST, LATEST)));
+
+    let output = run(
+        home.path(),
+        &[
+            "-q",
+            "create",
+            "gentoo",
+            "--arch",
+            "amd64",
+            "--profile",
+            "openrc",
+            "--no-gpg",
+            "--allow-tmpfs",
+            "--ignore-fs-checks",
+        ],
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stdout}\n{stderr}");
+    let chroot = home.path().join("chroots/gentoo");
+    assert_eq!(stdout, format!("{}\n", chroot.display()), "{stderr}");
+    assert!(chroot.join("etc/os-release").exists());
+}
+
+#[test]
+fn a_latest_stage3_missing_on_the_mirror_gives_way_to_the_previous_snapshot() {
+    if !nix::unistd::geteuid().is_root() {
+        return;
+    }
+    let work = TempDir::new().unwrap();
+    let home = home_with_mirror(&start_mirror(mirror_files(work.path(), LATEST, PREVIOUS)));
+    let create = |name: &str, extra: &[&str]| {
+        let mut args = vec![
+            "create",
+            name,
+            "--arch",
+            "amd64",
+            "--profile",
+            "openrc",
+            "--no-gpg",
+            "--allow-tmpfs",
+            "--ignore-fs-checks",
+        ];
+        args.extend(extra);
+        run(home.path(), &args)
+    };
+
+    // Reproducibility first: no substitution
+    let output = create("strict", &["--strict-latest"]);
+    assert!(!output.status.success());
+    assert!(!home.path().join("chroots/strict").exists());
+
+    let output = create("gentoo", &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{stdout}\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains(&format!("Using the previous stage3 {} instead", stage3_name(PREVIOUS))),
+        "{stdout}"
+    );
+    let metadata = fs::read_to_string(home.path().join("chroots/gentoo/etc/chrootmanager.toml")).unwrap();
+    assert!(metadata.contains(PREVIOUS), "{metadata}");
+}
+
+#[test]
+fn a_failed_download_leaves_a_report_for_why_failed() {
+    let work = TempDir::new().unwrap();
+    let home = home_with_mirror(&start_mirror(mirror_files(work.path(), LATEST, PREVIOUS)));
+    let output = run(
+        home.path(),
+        &[
+            "download",
+            "--arch",
+            "amd64",
+            "--profile",
+            "openrc",
+            "--strict-latest",
+            "--no-gpg",
+        ],
+    );
+    assert!(!output.status.success());
+
+    let output = run(home.path(), &["why-failed", "--format", "json"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(report["report_version"], 1, "{stdout}");
+    assert!(report["command"].as_array().unwrap().iter().any(|arg| arg == "download"), "{stdout}");
+    assert_eq!(report["phase"], "download", "{stdout}");
+    assert!(!report["error_chain"].as_array().unwrap().is_empty(), "{stdout}");
+    // Mirrors are reduced to their hostname
+    assert_eq!(report["config"]["mirror_hosts"], serde_json::json!(["127.0.0.1"]), "{stdout}");
+    assert!(!stdout.contains("http://127.0.0.1"), "{stdout}");
+    let attempts = report["mirror_attempts"].as_array().unwrap();
+    assert!(
+        attempts.iter().any(|attempt| attempt["file"] == stage3_name(LATEST).as_str()
+            && attempt["outcome"].as_str().unwrap().contains("404")),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn a_restrictive_umask_leaves_the_cache_readable() {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    let work = TempDir::new().unwrap();
+    let home = home_with_mirror(&start_mirror(mirror_files(work.path(), LATEST, LATEST)));
+
+    let output = Command::new("sh")
+        .args(["-c", "umask 077 && exec \"$0\" \"$@\""])
+        .arg(env!("CARGO_BIN_EXE_chrootmanager"))
+        .args(["download", "--arch", "amd64", "--profile", "openrc", "--no-gpg"])
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("CHROOTMANAGER_TEST_MODE", home.path())
+        .env("LC_ALL", "C")
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{stdout}\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let cache = home.path().join("cache");
+    assert_eq!(mode(&cache), 0o755);
+    assert_eq!(mode(&cache.join(stage3_name(LATEST))), 0o644);
+}
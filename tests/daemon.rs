@@ -0,0 +1,106 @@
+//! `org.chrootmanager.Manager1` served over a private peer-to-peer connection
+//!
+//! Only the calls that need neither the network nor privileges are made:
+//! the schema of the interface, the listing, and the rejection of names
+//! outside of the chroot base directory.
+
+use chrootmanager::config::{Config, TEST_MODE_ENV};
+use chrootmanager::daemon::{Manager, OBJECT_PATH, SERVICE_NAME};
+use std::fs;
+use tempfile::TempDir;
+use tokio::net::UnixStream;
+use zbus::{connection, fdo, Connection, Guid};
+
+/// Server and client ends of a connection serving a manager of `home`
+async fn serve(home: &TempDir) -> (Connection, Connection) {
+    // The default paths of the configuration must not touch the real home
+    std::env::set_var(TEST_MODE_ENV, home.path());
+    let config = Config {
+        chroot_base_dir: home.path().join("chroots"),
+        stage3_cache_dir: home.path().join("cache"),
+        ..Config::default()
+    };
+
+    let (server_stream, client_stream) = UnixStream::pair().unwrap();
+    let guid = Guid::generate();
+    let server = connection::Builder::unix_stream(server_stream)
+        .server(guid)
+        .unwrap()
+        .p2p()
+        .serve_at(OBJECT_PATH, Manager::new(config))
+        .unwrap()
+        .build();
+    let client = connection::Builder::unix_stream(client_stream).p2p().build();
+    let (server, client) = tokio::try_join!(server, client).unwrap();
+    (server, client)
+}
+
+async fn call<B>(client: &Connection, method: &str, body: &B) -> zbus::Result<zbus::message::Message>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    client
+        .call_method(None::<&str>, OBJECT_PATH, Some(SERVICE_NAME), method, body)
+        .await
+}
+
+#[tokio::test]
+async fn introspection_matches_the_documented_schema() {
+    let home = TempDir::new().unwrap();
+    let (_server, client) = serve(&home).await;
+
+    let reply = client
+        .call_method(None::<&str>, OBJECT_PATH, Some("org.freedesktop.DBus.Introspectable"), "Introspect", &())
+        .await
+        .unwrap();
+    let xml: String = reply.body().deserialize().unwrap();
+    let interface = xml
+        .split("<interface name=\"org.chrootmanager.Manager1\">")
+        .nth(1)
+        .and_then(|rest| rest.split("</interface>").next())
+        .expect("interface missing from the introspection");
+    let compact: String = interface.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    for expected in [
+        r#"<method name="ListChroots"> <arg type="a(sss)" direction="out"/> </method>"#,
+        r#"<method name="CreateChroot"> <arg name="name" type="s" direction="in"/> <arg name="arch" type="s" direction="in"/> <arg name="profile" type="s" direction="in"/> <arg type="s" direction="out"/> </method>"#,
+        r#"<method name="EnterPrepare"> <arg name="name" type="s" direction="in"/> <arg type="s" direction="out"/> </method>"#,
+        r#"<method name="EnterFinish"> <arg name="name" type="s" direction="in"/> </method>"#,
+        r#"<signal name="DownloadProgress"> <arg name="name" type="s"/> <arg name="downloaded" type="t"/> <arg name="total" type="t"/> </signal>"#,
+    ] {
+        assert!(compact.contains(expected), "{expected} not in {compact}");
+    }
+}
+
+#[tokio::test]
+async fn list_chroots_returns_the_chroots_of_the_base_directory() {
+    let home = TempDir::new().unwrap();
+    fs::create_dir_all(home.path().join("chroots/dev/etc")).unwrap();
+    let (_server, client) = serve(&home).await;
+
+    let reply = call(&client, "ListChroots", &()).await.unwrap();
+    let chroots: Vec<(String, String, String)> = reply.body().deserialize().unwrap();
+    let path = home.path().join("chroots/dev").display().to_string();
+    assert_eq!(chroots, vec![("dev".to_string(), String::new(), path)]);
+}
+
+#[tokio::test]
+async fn names_outside_the_base_directory_are_invalid_arguments() {
+    let home = TempDir::new().unwrap();
+    let (_server, client) = serve(&home).await;
+
+    for name in ["..", "a/../../x", ""] {
+        for method in ["EnterPrepare", "EnterFinish"] {
+            let error = call(&client, method, &(name,)).await.unwrap_err();
+            assert!(
+                matches!(fdo::Error::from(error), fdo::Error::InvalidArgs(_)),
+                "{method}({name:?}) was not rejected"
+            );
+        }
+        let error = call(&client, "CreateChroot", &(name, "amd64", "openrc")).await.unwrap_err();
+        assert!(
+            matches!(fdo::Error::from(error), fdo::Error::InvalidArgs(_)),
+            "CreateChroot({name:?}) was not rejected"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Discovery of the architectures and profiles, and its cache
+//!
+//! A local HTTP server stands in for the mirror: it serves a releases page
+//! listing amd64 and arm64, the autobuilds page of amd64 only, and counts
+//! the requests it answers. The binary runs in test mode with a temporary
+//! directory as home, its configuration pointing at that server.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tempfile::TempDir;
+
+const RELEASES: &str = "<a href=\"../\">../</a>\n<a href=\"amd64/\">amd64/</a>\n<a href=\"arm64/\">arm64/</a>\n";
+const AMD64_AUTOBUILDS: &str = "<a href=\"current-stage3-amd64-openrc/\">current-stage3-amd64-openrc/</a>\n\
+<a href=\"current-stage3-amd64-systemd/\">current-stage3-amd64-systemd/</a>\n";
+
+/// Answer one request, counting it
+fn serve(mut stream: TcpStream, requests: &AtomicUsize) {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // Headers, up to the empty line
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+        line.clear();
+    }
+    requests.fetch_add(1, Ordering::SeqCst);
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+    let body = if path.ends_with("/releases/") {
+        Some(RELEASES)
+    } else if path.ends_with("/releases/amd64/autobuilds/") {
+        Some(AMD64_AUTOBUILDS)
+    } else {
+        None
+    };
+    let _ = match body {
+        Some(body) => write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        ),
+        None => write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        ),
+    };
+}
+
+/// Start the mirror, returning its URL and its request count
+fn start_mirror() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counted = Arc::clone(&requests);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let counted = Arc::clone(&counted);
+            thread::spawn(move || serve(stream, &counted));
+        }
+    });
+    (url, requests)
+}
+
+/// Configure the home of a test run to discover from `mirrors`
+fn write_config(home: &Path, mirrors: &[&str]) {
+    let config_dir = home.join(".config/chrootmanager");
+    fs::create_dir_all(&config_dir).unwrap();
+    let mut config = format!(
+        "chroot_base_dir = \"{home}/chroots\"\nstage3_cache_dir = \"{home}/cache\"\n",
+        home = home.display()
+    );
+    for mirror in mirrors {
+        config.push_str(&format!("\n[[mirrors_url]]\nurl = \"{mirror}\"\n"));
+    }
+    fs::write(config_dir.join("config.toml"), config).unwrap();
+}
+
+/// Source kind and architectures of `profiles --format json`
+fn profiles(home: &Path, extra: &[&str]) -> (String, Vec<String>) {
+    let output = Command::new(env!("CARGO_BIN_EXE_chrootmanager"))
+        .args(["profiles", "--format", "json"])
+        .args(extra)
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("CHROOTMANAGER_TEST_MODE", home)
+        .env("LC_ALL", "C")
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{stdout}\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let listing: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let architectures = listing["architectures"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|arch| arch["name"].as_str().unwrap().to_string())
+        .collect();
+    (listing["source"]["kind"].as_str().unwrap().to_string(), architectures)
+}
+
+#[test]
+fn discovered_profiles_are_reused_until_refreshed_or_invalidated() {
+    let (mirror, requests) = start_mirror();
+    let home = TempDir::new().unwrap();
+    write_config(home.path(), &[&mirror]);
+    let cache = home.path().join(".cache/chrootmanager/profiles.json");
+
+    // The arm64 autobuilds page is missing, that architecture is skipped
+    assert_eq!(
+        profiles(home.path(), &[]),
+        ("mirror".to_string(), vec!["amd64".to_string()])
+    );
+    assert!(cache.exists());
+    let crawled = requests.load(Ordering::SeqCst);
+    assert!(crawled > 0);
+
+    assert_eq!(profiles(home.path(), &[]).0, "cache");
+    assert_eq!(requests.load(Ordering::SeqCst), crawled);
+
+    assert_eq!(profiles(home.path(), &["--refresh"]).0, "mirror");
+    assert!(requests.load(Ordering::SeqCst) > crawled);
+
+    // Another mirror list invalidates the cache
+    let (other, _) = start_mirror();
+    write_config(home.path(), &[&mirror, &other]);
+    assert_eq!(profiles(home.path(), &[]).0, "mirror");
+    assert_eq!(profiles(home.path(), &[]).0, "cache");
+
+    // So does its age
+    let mut content: serde_json::Value = serde_json::from_str(&fs::read_to_string(&cache).unwrap()).unwrap();
+    content["discovered_at"] = "2000-01-01T00:00:00Z".into();
+    fs::write(&cache, content.to_string()).unwrap();
+    let before = requests.load(Ordering::SeqCst);
+    assert_eq!(profiles(home.path(), &[]).0, "mirror");
+    assert!(requests.load(Ordering::SeqCst) > before);
+}
@@ -0,0 +1,172 @@
+//! Termination signals sent to the binary during a creation
+//!
+//! A local HTTP server stands in for the mirror: it serves the latest stage3
+//! file, then only the headers of the stage3, so that the download, or the
+//! extraction when streaming, is still waiting for data when the signal
+//! arrives. The binary runs in test mode with a temporary directory as
+//! home, its configuration pointing at that server.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const STAGE3: &str = "stage3-amd64-openrc-20240301T170000Z.tar.xz";
+const STAGE3_SIZE: u64 = 10_000_000;
+
+/// Answer one request, the stage3 never getting past its headers
+fn serve(mut stream: TcpStream) {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // Headers, up to the empty line
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+        line.clear();
+    }
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+    if path.ends_with("/latest-stage3-amd64-openrc.txt") {
+        let body = format!("# Latest stage3\n20240301T170000Z/{STAGE3} {STAGE3_SIZE}\n");
+        let _ = write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+    } else if path.ends_with(&format!("/{STAGE3}")) {
+        let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {STAGE3_SIZE}\r\n\r\n");
+        let _ = stream.flush();
+        thread::sleep(Duration::from_secs(60));
+    } else {
+        let _ = write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+    }
+}
+
+/// Start the mirror, returning its URL
+fn start_mirror() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || serve(stream));
+        }
+    });
+    url
+}
+
+/// Home of a test run, configured to download from `mirror`
+fn home_with_mirror(mirror: &str) -> TempDir {
+    let home = TempDir::new().unwrap();
+    let config_dir = home.path().join(".config/chrootmanager");
+    fs::create_dir_all(&config_dir).unwrap();
+    let config = format!(
+        "chroot_base_dir = \"{home}/chroots\"\nstage3_cache_dir = \"{home}/cache\"\n\n[[mirrors_url]]\nurl = \"{mirror}\"\n",
+        home = home.path().display()
+    );
+    fs::write(config_dir.join("config.toml"), config).unwrap();
+    home
+}
+
+/// Start creating a chroot from the mirror, with `extra` arguments
+fn spawn_create(home: &Path, extra: &[&str]) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_chrootmanager"))
+        .args([
+            "create",
+            "gentoo",
+            "--arch",
+            "amd64",
+            "--profile",
+            "openrc",
+            "--allow-tmpfs",
+            "--ignore-fs-checks",
+        ])
+        .args(extra)
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("CHROOTMANAGER_TEST_MODE", home)
+        .env("LC_ALL", "C")
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+/// Wait until `ready` holds, failing after a while
+fn wait_until(what: &str, ready: impl Fn() -> bool) {
+    let started = Instant::now();
+    while !ready() {
+        assert!(
+            started.elapsed() < Duration::from_secs(60),
+            "timed out waiting for {what}"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Send `signal` to the child and collect its output
+fn interrupt(child: Child, signal: &str) -> Output {
+    let status = Command::new("kill")
+        .args([&format!("-{signal}"), &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    child.wait_with_output().unwrap()
+}
+
+fn partial_download(home: &Path) -> PathBuf {
+    home.join("cache").join(format!("{STAGE3}.part"))
+}
+
+#[test]
+fn sigterm_removes_the_partial_download_and_exits_with_143() {
+    let home = home_with_mirror(&start_mirror());
+    let child = spawn_create(home.path(), &[]);
+    let partial = partial_download(home.path());
+    wait_until("the download", || partial.exists());
+
+    let output = interrupt(child, "TERM");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(128 + 15), "{stdout}");
+    assert!(stdout.contains("Interrupted, cleaning up before exiting"), "{stdout}");
+    assert!(
+        stdout.contains(&format!("Removing the partial download {STAGE3}")),
+        "{stdout}"
+    );
+    assert!(!partial.exists());
+    assert!(!home.path().join(format!("cache/{STAGE3}")).exists());
+}
+
+#[test]
+fn sigint_during_a_streamed_extraction_exits_with_130() {
+    // tar runs through sudo otherwise, which may ask for a password
+    if !nix::unistd::geteuid().is_root() {
+        return;
+    }
+    let home = home_with_mirror(&start_mirror());
+    let child = spawn_create(home.path(), &["--no-cache", "--no-gpg"]);
+    let chroot = home.path().join("chroots/gentoo");
+    wait_until("the extraction", || chroot.exists());
+
+    // The failure of tar, its input closed, gives way to the signal status
+    let output = interrupt(child, "INT");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(128 + 2), "{stdout}");
+    assert!(stdout.contains("Interrupted, cleaning up before exiting"), "{stdout}");
+    assert_eq!(
+        stdout.matches("Removed the partially extracted chroot").count(),
+        1,
+        "{stdout}"
+    );
+    assert!(!chroot.exists());
+}